@@ -0,0 +1,87 @@
+//! Benchmarks of the stats computed for every filled in suggestion, over
+//! tracks the size of short, long and multi-day recordings
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use geo::{Haversine, Length, Point};
+use gpx::{Track, TrackSegment, Waypoint};
+use hikea::trail_stats;
+
+const SIZES: [usize; 3] = [5_000, 50_000, 200_000];
+
+/// A recording of a hike with `len` points a few meters apart, wandering
+/// around like a trail and climbing over hills with GPS noise on top
+fn recording(len: usize) -> Track {
+    let mut rng = fastrand::Rng::with_seed(len as u64);
+    let (mut lon, mut lat) = (-111.65, 40.25);
+    let mut heading = 0.0f64;
+    let mut segment = TrackSegment::new();
+    for i in 0..len {
+        heading += (rng.f64() - 0.5) * 0.3;
+        // Around 3 m between points
+        lon += heading.cos() * 0.000035;
+        lat += heading.sin() * 0.000027;
+        let hills = (i as f64 / 700.0).sin() * 150.0 + (i as f64 / 131.0).sin() * 20.0;
+        let mut waypoint = Waypoint::new(Point::new(lon, lat));
+        waypoint.elevation = Some(2000.0 + hills + (rng.f64() - 0.5) * 4.0);
+        segment.points.push(waypoint);
+    }
+    let mut track = Track::new();
+    track.segments.push(segment);
+    track
+}
+
+fn trail_stats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trail_stats");
+    group.sample_size(10);
+
+    for len in SIZES {
+        let track = recording(len);
+        group.throughput(Throughput::Elements(len as u64));
+
+        group.bench_with_input(BenchmarkId::new("length", len), &track, |b, track| {
+            b.iter_batched(
+                || track.clone(),
+                |mut track| {
+                    trail_stats::trim_trailhead_wander(&mut track);
+                    track.multilinestring().length::<Haversine>()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("approximation", len),
+            &track,
+            |b, track| {
+                b.iter_batched(
+                    || trail_stats::elevation_points(track).unwrap(),
+                    |mut points| trail_stats::approximate_elevation_points(&mut points).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("extrema", len), &track, |b, track| {
+            b.iter_batched(
+                || {
+                    let mut points = trail_stats::elevation_points(track).unwrap();
+                    trail_stats::approximate_elevation_points(&mut points).unwrap();
+                    points
+                },
+                |mut points| trail_stats::find_extrema(&mut points).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("elevation_changes", len),
+            &track,
+            |b, track| b.iter(|| trail_stats::elevation_changes(track).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, trail_stats);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+//! Feeds arbitrary bytes through GPX parsing and the stats pipeline, like an
+//! upload would. Run with `cargo +nightly fuzz run gpx_stats`, adding
+//! `-- -rss_limit_mb=512` to check memory stays bounded.
+#![no_main]
+
+use hikea::trail_stats;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut gpx) = gpx::read(data) else {
+        return;
+    };
+    for track in &mut gpx.tracks {
+        trail_stats::trim_trailhead_wander(track);
+        let _ = trail_stats::elevation_changes(track);
+    }
+});
@@ -0,0 +1,259 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use tracing::instrument;
+
+use crate::{
+    failure::{self, Failure},
+    providers::TrailProvider,
+    Config,
+};
+
+/// Trail information scraped from an AllTrails trail page, used to fill in
+/// the GPX upload form
+#[derive(Debug, Clone)]
+pub struct TrailMetadata {
+    pub id: Option<u64>,
+    pub title: String,
+    pub rating: Option<String>,
+    pub difficulty: Option<String>,
+    pub image: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Where trail links are rewritten to point
+const CANONICAL_ORIGIN: &str = "https://www.alltrails.com";
+
+/// [AllTrails](https://www.alltrails.com), which trail details can also be
+/// scraped from
+pub struct AllTrails;
+
+impl TrailProvider for AllTrails {
+    const NAME: &'static str = "AllTrails";
+
+    fn recognizes(url: &Url) -> bool {
+        is_alltrails(url)
+    }
+
+    fn is_trail(url: &Url) -> bool {
+        trail_path(url).is_some()
+    }
+
+    /// The canonical `https://www.alltrails.com/trail/...` form of a link,
+    /// following short links from the mobile app through their redirects
+    #[instrument(skip(config))]
+    async fn canonical_link(url: Url, config: &Config) -> eyre::Result<String> {
+        if let Some(path) = trail_path(&url) {
+            return Ok(format!("{}/{}", CANONICAL_ORIGIN, path));
+        }
+
+        let mut request = reqwest::Client::new().get(url);
+        if let Some(cookie) = &config.alltrails_cookie {
+            request = request.header("Cookie", cookie);
+        }
+        let response = request
+            .send()
+            .await
+            .wrap_err("Failed to follow AllTrails link")?
+            .error_for_status()
+            .wrap_err("AllTrails link returned an error")?;
+
+        let url = response.url();
+        if !is_alltrails(url) {
+            return Err(
+                Failure::NotATrail("AllTrails link redirected away from AllTrails".into()).into(),
+            );
+        }
+        trail_path(url)
+            .map(|path| format!("{}/{}", CANONICAL_ORIGIN, path))
+            .ok_or_else(|| {
+                Failure::NotATrail("AllTrails link does not lead to a trail".into()).into()
+            })
+    }
+
+    fn exported(gpx: &gpx::Gpx) -> bool {
+        gpx.metadata.as_ref().is_some_and(|metadata| {
+            metadata
+                .links
+                .first()
+                .is_some_and(|link| link.href == "http://www.alltrails.com")
+        })
+    }
+}
+
+/// Whether `url` is on an AllTrails domain, such as `alltrails.com` or one of
+/// its subdomains or localized counterparts
+fn is_alltrails(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let labels = host.split('.').collect::<Vec<_>>();
+    labels.len() >= 2 && labels[labels.len() - 2].eq_ignore_ascii_case("alltrails")
+}
+
+/// The `trail/...` path of `url` without any locale or `explore/` in front of
+/// it, if it's a trail page
+fn trail_path(url: &Url) -> Option<String> {
+    let mut segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .peekable();
+    // Localized pages put the language first, like `de` or `en-gb`
+    if segments.peek().is_some_and(|segment| is_locale(segment)) {
+        segments.next();
+    }
+    if segments.peek() == Some(&"explore") {
+        segments.next();
+    }
+    if segments.next()? != "trail" {
+        return None;
+    }
+    let rest = segments.collect::<Vec<_>>();
+    (!rest.is_empty()).then(|| format!("trail/{}", rest.join("/")))
+}
+
+fn is_locale(segment: &str) -> bool {
+    let mut parts = segment.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+    language.len() == 2
+        && language.bytes().all(|b| b.is_ascii_alphabetic())
+        && region.is_none_or(|region| {
+            region.len() == 2 && region.bytes().all(|b| b.is_ascii_alphabetic())
+        })
+        && parts.next().is_none()
+}
+
+#[instrument(skip(config))]
+pub async fn scrape(link: &str, config: &Config) -> eyre::Result<TrailMetadata> {
+    let mut request = reqwest::Client::new().get(link);
+    if let Some(cookie) = &config.alltrails_cookie {
+        request = request.header("Cookie", cookie);
+    }
+
+    let response = request
+        .send()
+        .await
+        .wrap_err("Failed to request AllTrails trail page")?;
+    if failure::session_expired(response.status(), config) {
+        return Err(Failure::SessionExpired.into());
+    }
+    let html = response
+        .error_for_status()
+        .wrap_err("AllTrails trail page returned an error")?
+        .text()
+        .await
+        .wrap_err("Failed to read AllTrails trail page")?;
+
+    parse_trail_page(&html).wrap_err("Failed to parse AllTrails trail page")
+}
+
+/// Downloads the GPX export of a trail through `alltrails_gpx_url`, exports
+/// require an AllTrails session so `alltrails_cookie` has to be set as well
+#[instrument(skip(config))]
+pub async fn download_gpx(trail_id: u64, config: &Config) -> eyre::Result<gpx::Gpx> {
+    let url = config
+        .alltrails_gpx_url
+        .as_ref()
+        .ok_or_eyre("No `alltrails_gpx_url` is configured")?
+        .replace("{id}", &trail_id.to_string());
+    let cookie = config
+        .alltrails_cookie
+        .as_ref()
+        .ok_or_eyre("No `alltrails_cookie` is configured")?;
+
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("Cookie", cookie)
+        .send()
+        .await
+        .wrap_err("Failed to request AllTrails GPX export")?;
+    if failure::session_expired(response.status(), config) {
+        return Err(Failure::SessionExpired.into());
+    }
+    let bytes = response
+        .error_for_status()
+        .wrap_err("AllTrails GPX export returned an error")?
+        .bytes()
+        .await
+        .wrap_err("Failed to read AllTrails GPX export")?;
+
+    gpx::read(std::io::Cursor::new(bytes)).wrap_err("Failed to read GPX file from AllTrails")
+}
+
+fn parse_trail_page(html: &str) -> eyre::Result<TrailMetadata> {
+    let document = Html::parse_document(html);
+
+    let meta = |property: &str| {
+        let selector = Selector::parse(&format!("meta[property=\"{}\"]", property))
+            .map_err(|e| eyre!("Invalid selector for `{}`: {}", property, e))?;
+        Ok::<_, eyre::Report>(
+            document
+                .select(&selector)
+                .next()
+                .and_then(|e| e.attr("content"))
+                .map(|c| c.trim().to_owned())
+                .filter(|c| !c.is_empty()),
+        )
+    };
+
+    let scripts = Selector::parse(
+        "script[type=\"application/ld+json\"], script#__NEXT_DATA__[type=\"application/json\"]",
+    )
+    .map_err(|e| eyre!("Invalid selector for page data: {}", e))?;
+    let data = document
+        .select(&scripts)
+        .filter_map(|script| serde_json::from_str::<Value>(&script.inner_html()).ok())
+        .collect::<Vec<_>>();
+
+    let title = find_key(&data, &["name"])
+        .and_then(|v| v.as_str().map(str::to_owned))
+        .or(meta("og:title")?)
+        .ok_or_eyre("AllTrails page has no title")?;
+
+    let rating = find_key(&data, &["ratingValue", "avgRating", "avg_rating"]).and_then(|v| {
+        v.as_f64()
+            .or_else(|| v.as_str()?.parse().ok())
+            .map(|r| format!("{:.1}", r))
+    });
+
+    let difficulty = find_key(&data, &["difficultyRating", "difficulty_rating"])
+        .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()))
+        .map(|d| {
+            match d {
+                0..=2 => "Easy",
+                3..=4 => "Moderate",
+                _ => "Hard",
+            }
+            .to_owned()
+        });
+
+    let id = find_key(&data, &["trailId", "trail_id"])
+        .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()));
+
+    Ok(TrailMetadata {
+        id,
+        title,
+        rating,
+        difficulty,
+        image: meta("og:image")?,
+        description: meta("og:description")?,
+    })
+}
+
+/// Depth-first search for the first value stored under any of `keys`
+fn find_key<'a>(values: &'a [Value], keys: &[&str]) -> Option<&'a Value> {
+    fn find<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+        match value {
+            Value::Object(map) => keys
+                .iter()
+                .find_map(|key| map.get(*key).filter(|v| !v.is_null()))
+                .or_else(|| map.values().find_map(|v| find(v, keys))),
+            Value::Array(array) => array.iter().find_map(|v| find(v, keys)),
+            _ => None,
+        }
+    }
+
+    values.iter().find_map(|value| find(value, keys))
+}
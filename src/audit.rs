@@ -0,0 +1,155 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use serenity::all::UserId;
+use tracing::{info, warn};
+
+use crate::scheduler::Job;
+
+/// Who a state changing Discord call is made on behalf of
+#[derive(Debug, Clone, Copy)]
+pub enum Actor<'a> {
+    User(UserId),
+    /// An admin on the web interface, by name
+    Admin(&'a str),
+    Job(Job),
+    /// The bot itself, such as when syncing commands at startup
+    Bot,
+}
+
+impl Display for Actor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Actor::User(user_id) => write!(f, "user {}", user_id),
+            Actor::Admin(name) => write!(f, "admin `{}`", name),
+            Actor::Job(job) => write!(f, "job `{}`", job.name()),
+            Actor::Bot => f.write_str("bot"),
+        }
+    }
+}
+
+/// Kinds of state changing Discord calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    EditMessage,
+    DeleteMessage,
+    SendMessage,
+    React,
+    EditEvent,
+    Followup,
+    SetCommands,
+    DeleteCommand,
+    AddRole,
+}
+
+impl Mutation {
+    pub const ALL: [Mutation; 9] = [
+        Mutation::EditMessage,
+        Mutation::DeleteMessage,
+        Mutation::SendMessage,
+        Mutation::React,
+        Mutation::EditEvent,
+        Mutation::Followup,
+        Mutation::SetCommands,
+        Mutation::DeleteCommand,
+        Mutation::AddRole,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mutation::EditMessage => "edit_message",
+            Mutation::DeleteMessage => "delete_message",
+            Mutation::SendMessage => "send_message",
+            Mutation::React => "react",
+            Mutation::EditEvent => "edit_event",
+            Mutation::Followup => "followup",
+            Mutation::SetCommands => "set_commands",
+            Mutation::DeleteCommand => "delete_command",
+            Mutation::AddRole => "add_role",
+        }
+    }
+}
+
+/// Calls made to Discord since startup, and how many of them failed, by
+/// [`Mutation`]
+#[derive(Default)]
+pub struct Metrics {
+    made: [AtomicU64; Mutation::ALL.len()],
+    failed: [AtomicU64; Mutation::ALL.len()],
+}
+
+impl Metrics {
+    /// Calls made and failed of every kind of mutation
+    pub fn counts(&self) -> impl Iterator<Item = (Mutation, u64, u64)> + '_ {
+        Mutation::ALL.into_iter().map(|mutation| {
+            (
+                mutation,
+                self.made[mutation as usize].load(Ordering::Relaxed),
+                self.failed[mutation as usize].load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+/// Makes the state changing Discord `call`, logging who it's made for, what
+/// it targets and a summary of what it changes, so what the bot did can be
+/// pieced together afterwards
+pub async fn audited<T>(
+    metrics: &Metrics,
+    mutation: Mutation,
+    actor: Actor<'_>,
+    target: impl Display,
+    summary: &str,
+    call: impl Future<Output = serenity::Result<T>>,
+) -> serenity::Result<T> {
+    info!(
+        target: "audit",
+        mutation = mutation.name(),
+        %actor,
+        %target,
+        summary,
+        "Calling Discord"
+    );
+    metrics.made[mutation as usize].fetch_add(1, Ordering::Relaxed);
+
+    let result = call.await;
+    if let Err(e) = &result {
+        metrics.failed[mutation as usize].fetch_add(1, Ordering::Relaxed);
+        warn!(
+            target: "audit",
+            mutation = mutation.name(),
+            %actor,
+            %target,
+            error = %e,
+            "Discord call failed"
+        );
+    }
+    result
+}
+
+/// The fields set in a request builder, with long text cut short and lists
+/// reduced to their length
+pub fn summarize(payload: &impl Serialize) -> String {
+    match serde_json::to_value(payload) {
+        Ok(Value::Object(fields)) => fields
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| match value {
+                Value::String(text) if text.chars().count() > 50 => {
+                    format!("{}=\"{}…\"", key, text.chars().take(50).collect::<String>())
+                }
+                Value::Array(items) => format!("{}=[{}]", key, items.len()),
+                Value::Object(_) => format!("{}={{…}}", key),
+                value => format!("{}={}", key, value),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        Ok(value) => value.to_string(),
+        Err(e) => format!("unserializable payload: {}", e),
+    }
+}
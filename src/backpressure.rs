@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::warn;
+
+use crate::{AppState, Config};
+
+/// Memory a request is expected to take up for each byte of its body, since
+/// GPX files and photos take up far more once parsed or decoded
+const MEMORY_PER_BODY_BYTE: usize = 8;
+
+/// Memory set aside for work on images, such as cropping an event banner or
+/// rendering a route map, in bytes
+pub const IMAGE_MEMORY: usize = 64 * 1024 * 1024;
+
+/// Seconds clients are told to wait before retrying when saturated
+const RETRY_AFTER: u64 = 5;
+
+/// Memory an upload of `body_size` bytes is expected to take up
+pub fn upload_memory(body_size: usize) -> usize {
+    IMAGE_MEMORY + body_size.saturating_mul(MEMORY_PER_BODY_BYTE)
+}
+
+/// Limits how many uploads and image operations run at once, and how much
+/// memory they're expected to take up together, so a burst can't exhaust a
+/// small host. Sized from the config at startup.
+pub struct Limiter {
+    operations: Semaphore,
+    /// In KiB, since semaphores can only hand out so many permits at once
+    memory: Semaphore,
+    memory_budget: usize,
+}
+
+/// Held for as long as an operation runs
+pub struct Permit<'a> {
+    _operation: SemaphorePermit<'a>,
+    _memory: SemaphorePermit<'a>,
+}
+
+impl Limiter {
+    pub fn new(config: &Config) -> Self {
+        let memory_budget = (config.upload_memory_budget_mib * 1024).max(1);
+        Limiter {
+            operations: Semaphore::new(config.max_concurrent_uploads.max(1)),
+            memory: Semaphore::new(memory_budget),
+            memory_budget,
+        }
+    }
+
+    /// A permit for an operation expected to take up `bytes` of memory, or
+    /// `None` if too much is running already. Operations larger than the
+    /// whole budget can still run on their own.
+    pub fn try_acquire(&self, bytes: usize) -> Option<Permit<'_>> {
+        let kib = bytes.div_ceil(1024).clamp(1, self.memory_budget);
+        Some(Permit {
+            _operation: self.operations.try_acquire().ok()?,
+            _memory: self.memory.try_acquire_many(kib as u32).ok()?,
+        })
+    }
+}
+
+/// Turns requests away with `503 Service Unavailable` and `Retry-After` while
+/// the limiter is saturated
+pub async fn limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let body_size = match headers.get(header::CONTENT_LENGTH) {
+        Some(length) => length
+            .to_str()
+            .ok()
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(0),
+        // Chunked bodies could be as large as the body limit allows
+        None if headers.contains_key(header::TRANSFER_ENCODING) => {
+            crate::web_interface::upload_gpx::MAX_UPLOAD_SIZE
+        }
+        None => 0,
+    };
+
+    let Some(_permit) = state.limiter.try_acquire(upload_memory(body_size)) else {
+        warn!(path = %request.uri().path(), "Too many uploads at once, turning request away");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, RETRY_AFTER.to_string())],
+            "Too many uploads are being handled right now, try again in a few seconds",
+        )
+            .into_response();
+    };
+
+    next.run(request).await
+}
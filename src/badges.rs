@@ -0,0 +1,160 @@
+use std::io::Cursor;
+
+use color_eyre::eyre::{self, Context};
+use jsonwebtoken::get_current_timestamp;
+use serde::Deserialize;
+use serenity::all::{CreateAllowedMentions, CreateMessage, Mentionable, MessageId, RoleId, UserId};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    store::Suggestion,
+    units::length_to_meters,
+    AppState, Config,
+};
+
+/// A milestone members earn a badge for, defined in `Config`
+#[derive(Deserialize, Debug)]
+pub struct Badge {
+    /// Shown in the callout, and what earned badges are stored as, so renaming
+    /// a badge awards it again
+    name: String,
+    #[serde(flatten)]
+    milestone: Milestone,
+    /// Granted along with the badge when set
+    role: Option<RoleId>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Milestone {
+    /// Completed hikes the member was interested in
+    Hikes(u64),
+    /// Total elevation gain of those hikes, in `short_units`
+    Gain(f64),
+    /// A hike reaching this elevation, in `short_units`
+    Elevation(f64),
+}
+
+/// The highest elevation of the suggestion's stored GPX file in meters, if it
+/// has one with elevation
+fn highest_point(state: &AppState, suggestion: &Suggestion) -> eyre::Result<Option<f64>> {
+    let Some(gpx) = state.store.suggestion_gpx(suggestion.message_id)? else {
+        return Ok(None);
+    };
+    let gpx = gpx::read(Cursor::new(gpx)).wrap_err("Failed to read stored GPX file")?;
+    Ok(gpx
+        .tracks
+        .iter()
+        .flat_map(|track| &track.segments)
+        .flat_map(|segment| &segment.points)
+        .filter_map(|point| point.elevation)
+        .max_by(f64::total_cmp))
+}
+
+/// Badges `user` has newly reached, given the highest point of the hike that
+/// was just completed
+fn reached<'a>(
+    state: &AppState,
+    config: &'a Config,
+    user: UserId,
+    highest_point: Option<f64>,
+) -> eyre::Result<Vec<&'a Badge>> {
+    let earned = state.store.badges(user)?;
+    let (hikes, gain) = state.store.member_hikes(user)?;
+    let short_units = config.lengths().short_units;
+
+    let mut reached = Vec::new();
+    for badge in &config.badges {
+        if earned.contains(&badge.name) {
+            continue;
+        }
+        let done = match badge.milestone {
+            Milestone::Hikes(count) => hikes >= count,
+            Milestone::Gain(min) => gain >= length_to_meters(min, short_units)?,
+            Milestone::Elevation(min) => {
+                let min = length_to_meters(min, short_units)?;
+                highest_point.is_some_and(|highest| highest >= min)
+            }
+        };
+        if done {
+            reached.push(badge);
+        }
+    }
+    Ok(reached)
+}
+
+/// Awards badges to the members interested in the suggestion posted as
+/// `message_id`, as interest stands in for attendance
+#[instrument(skip(state))]
+pub async fn award(
+    state: &AppState,
+    message_id: MessageId,
+    completed_by: UserId,
+) -> eyre::Result<()> {
+    let config = state.config.load();
+    if config.badges.is_empty() {
+        return Ok(());
+    }
+    let Some(suggestion) = state.store.suggestion(message_id)? else {
+        return Ok(());
+    };
+    let highest_point = highest_point(state, &suggestion)?;
+    let http = state.http.load();
+    let now = get_current_timestamp();
+
+    let mut callouts = Vec::new();
+    let mut mentioned = Vec::new();
+    for user in state
+        .store
+        .interested(message_id)
+        .wrap_err("Failed to load interested members")?
+    {
+        for badge in reached(state, &config, user, highest_point)? {
+            if !state.store.award_badge(user, &badge.name, now)? {
+                continue;
+            }
+            info!(%user, badge = badge.name, "Awarded badge");
+            callouts.push(format!("🏅 {} earned **{}**", user.mention(), badge.name));
+            if !mentioned.contains(&user) {
+                mentioned.push(user);
+            }
+
+            let Some(role) = badge.role else {
+                continue;
+            };
+            let reason = format!("Earned the {} badge", badge.name);
+            if let Err(e) = audited(
+                &state.audit,
+                Mutation::AddRole,
+                Actor::User(completed_by),
+                format!("member {}", user),
+                &format!("role={}", role),
+                http.add_member_role(config.guild_id, user, role, Some(&reason)),
+            )
+            .await
+            {
+                warn!(%user, %role, "Failed to grant badge role: {:?}", e);
+            }
+        }
+    }
+    if callouts.is_empty() {
+        return Ok(());
+    }
+
+    let message = CreateMessage::new()
+        .reference_message((suggestion.channel_id, message_id))
+        .allowed_mentions(CreateAllowedMentions::new().users(mentioned))
+        .content(callouts.join("\n"));
+    audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::User(completed_by),
+        format!("channel {}", suggestion.channel_id),
+        &summarize(&message),
+        suggestion.channel_id.send_message(http.as_ref(), message),
+    )
+    .await
+    .wrap_err("Failed to post badge callout")?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use color_eyre::eyre;
+use geo::{Distance, Haversine};
+use serde::Deserialize;
+
+use crate::{commands::suggest::TrailStats, failure::Failure};
+
+/// Meters long a trail has to be to count as fully long (10 miles)
+const LONG_LENGTH: f64 = 16_000.0;
+/// Meters of elevation gain to count as fully steep (3,000 feet)
+const STEEP_GAIN: f64 = 900.0;
+/// Grade (rise over run) of the steepest section to count as fully steep
+const STEEP_GRADE: f64 = 0.3;
+/// Meters the steepest section is measured over, so noisy points don't count
+const SECTION_LENGTH: f64 = 100.0;
+
+/// How much each part counts toward the beginner friendliness score
+#[derive(Deserialize, Debug)]
+pub struct BeginnerWeights {
+    #[serde(default = "default_weight")]
+    pub length: f64,
+    #[serde(default = "default_weight")]
+    pub gain: f64,
+    #[serde(default = "default_weight")]
+    pub steepest: f64,
+    #[serde(default = "default_weight")]
+    pub exposure: f64,
+    #[serde(default = "default_shade_weight")]
+    pub shade: f64,
+}
+
+impl Default for BeginnerWeights {
+    fn default() -> Self {
+        BeginnerWeights {
+            length: default_weight(),
+            gain: default_weight(),
+            steepest: default_weight(),
+            exposure: default_weight(),
+            shade: default_shade_weight(),
+        }
+    }
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_shade_weight() -> f64 {
+    0.5
+}
+
+/// How much of a trail is shaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shade {
+    None,
+    Partial,
+    Full,
+}
+
+impl Shade {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Shade::None),
+            "partial" => Some(Shade::Partial),
+            "full" => Some(Shade::Full),
+            _ => None,
+        }
+    }
+
+    fn penalty(self) -> f64 {
+        match self {
+            Shade::None => 1.0,
+            Shade::Partial => 0.5,
+            Shade::Full => 0.0,
+        }
+    }
+}
+
+/// What a trail is like that can't be read from its GPX file, as reported by
+/// whoever filled it in
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Conditions {
+    /// Whether the trail has drop-offs or scrambles
+    pub exposed: bool,
+    pub shade: Option<Shade>,
+    /// Whether the trail can be done in a wheelchair or with a stroller
+    pub accessible: Option<bool>,
+}
+
+/// How friendly a trail is to kids and beginners, from 1 to 5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeginnerScore(pub u8);
+
+impl BeginnerScore {
+    pub fn new(stats: TrailStats, conditions: Conditions, weights: &BeginnerWeights) -> Self {
+        let penalties = [
+            ((stats.length / LONG_LENGTH).min(1.0), weights.length),
+            ((stats.gains / STEEP_GAIN).min(1.0), weights.gain),
+            ((stats.steepest / STEEP_GRADE).min(1.0), weights.steepest),
+            (if conditions.exposed { 1.0 } else { 0.0 }, weights.exposure),
+            // Unknown shade counts as partial
+            (conditions.shade.map_or(0.5, Shade::penalty), weights.shade),
+        ];
+        let total = penalties.iter().map(|(_, weight)| weight).sum::<f64>();
+        let penalty = if total > 0.0 {
+            penalties
+                .iter()
+                .map(|(penalty, weight)| penalty * weight)
+                .sum::<f64>()
+                / total
+        } else {
+            0.0
+        };
+        BeginnerScore(5 - (penalty * 4.0).round() as u8)
+    }
+}
+
+impl Display for BeginnerScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let score = self.0.clamp(1, 5) as usize;
+        write!(
+            f,
+            "{}{} ({}/5)",
+            "🟢".repeat(score),
+            "⚪".repeat(5 - score),
+            score
+        )
+    }
+}
+
+/// Grade (rise over run) of the steepest stretch of `track`, up or down,
+/// measured over at least `SECTION_LENGTH`
+pub fn steepest_grade(track: &gpx::Track) -> eyre::Result<f64> {
+    let mut points = Vec::new();
+    let mut distance = 0.0;
+    for segment in &track.segments {
+        for (i, point) in segment.points.iter().enumerate() {
+            if i > 0 {
+                distance += Haversine::distance(segment.points[i - 1].point(), point.point());
+            }
+            points.push((distance, point.elevation.ok_or(Failure::MissingElevation)?));
+        }
+    }
+
+    let mut steepest = 0.0f64;
+    let mut start = 0;
+    for end in 0..points.len() {
+        while start + 1 < end && points[end].0 - points[start + 1].0 >= SECTION_LENGTH {
+            start += 1;
+        }
+        let run = points[end].0 - points[start].0;
+        if run >= SECTION_LENGTH {
+            steepest = steepest.max(((points[end].1 - points[start].1) / run).abs());
+        }
+    }
+    Ok(steepest)
+}
@@ -0,0 +1,36 @@
+use color_eyre::eyre::{self, Context};
+use time::Month;
+
+use crate::AppState;
+
+/// Spelled out for the counts a hiking group is likely to have
+fn count_word(count: u64) -> String {
+    const WORDS: [&str; 10] = [
+        "One", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten",
+    ];
+    match WORDS.get(count.wrapping_sub(1) as usize) {
+        Some(word) => (*word).to_owned(),
+        None => count.to_string(),
+    }
+}
+
+/// A nudge to plan a celebration if any members registered a birthday in
+/// `month`
+pub fn hint(state: &AppState, month: Month) -> eyre::Result<Option<String>> {
+    let count = state
+        .store
+        .birthdays_in(month as u8)
+        .wrap_err("Failed to count birthdays")?;
+    Ok(match count {
+        0 => None,
+        1 => Some(format!(
+            "One member has a birthday in {} — consider a celebratory summit",
+            month
+        )),
+        count => Some(format!(
+            "{} members have birthdays in {} — consider a celebratory summit",
+            count_word(count),
+            month
+        )),
+    })
+}
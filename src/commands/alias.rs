@@ -0,0 +1,194 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateAutocompleteResponse, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    ResolvedOption, ResolvedValue,
+};
+use tracing::{info, instrument};
+
+use crate::{
+    sanitize,
+    trail_names::{normalize, MAX_ALIAS_LENGTH},
+    AppState,
+};
+
+/// Discord caps autocomplete at 25 choices
+const MAX_CHOICES: usize = 25;
+
+/// Longest a choice's name can be, in characters
+const MAX_CHOICE_LENGTH: usize = 100;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("alias")
+        .description("List, add or remove other names a trail goes by")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "trail", "Name of the trail")
+                .required(true)
+                .max_length(100)
+                .set_autocomplete(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "add",
+                "Another name the trail goes by",
+            )
+            .max_length(MAX_ALIAS_LENGTH as u16),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "remove",
+                "A name the trail should no longer go by",
+            )
+            .max_length(MAX_ALIAS_LENGTH as u16),
+        )
+}
+
+/// Names of suggested trails matching what's been typed into a `trail`
+/// option so far
+#[instrument(skip(command, state))]
+pub fn autocomplete(
+    command: &CommandInteraction,
+    state: &AppState,
+) -> eyre::Result<CreateInteractionResponse> {
+    let typed = command
+        .data
+        .autocomplete()
+        .ok_or_eyre("No option is being autocompleted")?
+        .value;
+    let suggestions = state
+        .store
+        .search_suggestions(typed, None, None, MAX_CHOICES)
+        .wrap_err("Failed to search suggestions")?;
+
+    let mut response = CreateAutocompleteResponse::new();
+    for suggestion in suggestions {
+        let title = suggestion
+            .title
+            .chars()
+            .take(MAX_CHOICE_LENGTH)
+            .collect::<String>();
+        response = response.add_string_choice(title.clone(), title);
+    }
+    Ok(CreateInteractionResponse::Autocomplete(response))
+}
+
+#[derive(Debug)]
+pub struct AliasCommand<'a> {
+    trail: &'a str,
+    add: Option<&'a str>,
+    remove: Option<&'a str>,
+}
+
+impl<'a> AliasCommand<'a> {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption<'a>]) -> eyre::Result<Self> {
+        let mut trail = None;
+        let mut add = None;
+        let mut remove = None;
+        for option in options {
+            match (option.name, &option.value) {
+                ("trail", ResolvedValue::String(value)) => trail = Some(*value),
+                ("add", ResolvedValue::String(value)) => add = Some(*value),
+                ("remove", ResolvedValue::String(value)) => remove = Some(*value),
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+        Ok(Self {
+            trail: trail.ok_or_eyre("Expected a `trail` option")?,
+            add,
+            remove,
+        })
+    }
+
+    /// Whether an alias is being added or removed, which only admins can do
+    pub fn changes_aliases(&self) -> bool {
+        self.add.is_some() || self.remove.is_some()
+    }
+
+    /// Lists the trail's aliases, after adding or removing one if asked to
+    #[instrument(skip(command, state))]
+    pub fn respond(
+        &self,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> eyre::Result<CreateInteractionResponse> {
+        let suggestion = crate::trail_names::find(&state.store, self.trail)?
+            .ok_or_else(|| eyre!("No trail suggestions match `{}`", self.trail))?;
+
+        let mut changed = None;
+        if let Some(alias) = self.add.map(str::trim) {
+            let normalized = normalize(alias);
+            if normalized.is_empty() {
+                return Err(eyre!("`{}` has no letters or digits to go by", alias));
+            }
+            if let Some(taken_by) = state.store.add_alias(
+                suggestion.message_id,
+                alias,
+                &normalized,
+                &command.user.name,
+                get_current_timestamp(),
+            )? {
+                let title = state
+                    .store
+                    .suggestion(taken_by)?
+                    .map_or_else(|| taken_by.to_string(), |taken_by| taken_by.title);
+                return Err(eyre!("`{}` is already an alias of {}", alias, title));
+            }
+            info!(
+                admin = command.user.name,
+                alias,
+                title = suggestion.title,
+                "Added trail alias"
+            );
+            changed = Some(format!("Added `{}`", alias));
+        }
+        if let Some(alias) = self.remove {
+            if !state
+                .store
+                .remove_alias(suggestion.message_id, &normalize(alias))?
+            {
+                return Err(eyre!("`{}` is not an alias of {}", alias, suggestion.title));
+            }
+            info!(
+                admin = command.user.name,
+                alias,
+                title = suggestion.title,
+                "Removed trail alias"
+            );
+            changed = Some(format!("Removed `{}`", alias));
+        }
+
+        let aliases = state
+            .store
+            .aliases(suggestion.message_id)
+            .wrap_err("Failed to load trail aliases")?;
+        let mut content = changed.map_or_else(String::new, |changed| format!("{}\n", changed));
+        let title = sanitize::text(&suggestion.title, sanitize::TITLE_LENGTH);
+        if aliases.is_empty() {
+            content.push_str(&format!(
+                "[{}]({}) goes by no other names",
+                title, suggestion.link
+            ));
+        } else {
+            content.push_str(&format!(
+                "[{}]({}) also goes by:\n{}",
+                title,
+                suggestion.link,
+                aliases
+                    .iter()
+                    .map(|alias| format!("- {}", sanitize::text(alias, MAX_ALIAS_LENGTH)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(content),
+        ))
+    }
+}
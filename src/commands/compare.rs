@@ -0,0 +1,314 @@
+use std::{io::Cursor, sync::Arc};
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use geo::{Distance, Haversine, Point};
+use serenity::all::{
+    Attachment, Color, CommandInteraction, CommandOptionType, CreateAttachment, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponseFollowup, ResolvedValue,
+};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use crate::{sanitize, AppState};
+
+/// Meters a planned point can be from the recording and still count as hiked
+const OVERLAP_RADIUS: f64 = 40.0;
+/// Meters per second below which a recording counts as stopped
+const MOVING_SPEED: f64 = 0.3;
+/// Seconds between recorded points past which the recording is assumed to
+/// have been paused, so the gap isn't counted as moving
+const MAX_GAP: f64 = 10.0 * 60.0;
+/// Meters elevation has to turn around by before it counts as a climb, so GPS
+/// noise doesn't add up to a mountain
+const GAIN_HYSTERESIS: f64 = 5.0;
+/// Meters in a mile, as `avg_speed` is in miles per hour
+const MILE: f64 = 1609.344;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("compare")
+        .description("Compare a recording of a hike against the planned route")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Attachment,
+                "recording",
+                "GPX file recorded on the hike",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "trail",
+                "Name of the suggested trail that was hiked",
+            )
+            .required(true)
+            .max_length(100)
+            .set_autocomplete(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "osm",
+            "Point out where the recording strays from trails mapped in OpenStreetMap",
+        ))
+}
+
+/// How a recorded hike went compared to the planned route, lengths in meters
+#[derive(Debug)]
+struct Comparison {
+    /// Fraction of the planned route that was hiked
+    overlap: f64,
+    recorded_length: f64,
+    /// `None` if the recording has no elevation
+    gain: Option<f64>,
+    /// Seconds, `None` if the recording has no timestamps
+    moving_time: Option<f64>,
+    /// Meters covered while moving
+    moving_length: f64,
+}
+
+/// `point` in meters east and north of `origin`, close enough for comparing
+/// points a few dozen meters apart
+pub(crate) fn local(point: Point, origin: Point) -> (f64, f64) {
+    (
+        (point.x() - origin.x()) * 111_320.0 * origin.y().to_radians().cos(),
+        (point.y() - origin.y()) * 110_540.0,
+    )
+}
+
+/// Fraction of `planned` within `OVERLAP_RADIUS` of any of `recorded`
+fn overlap(planned: &[Point], recorded: &[Point]) -> f64 {
+    let Some(&origin) = planned.first() else {
+        return 0.0;
+    };
+    let mut recorded = recorded
+        .iter()
+        .map(|point| local(*point, origin))
+        .collect::<Vec<_>>();
+    recorded.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let hiked = planned
+        .iter()
+        .filter(|point| {
+            let (x, y) = local(**point, origin);
+            let start = recorded.partition_point(|(other_x, _)| *other_x < x - OVERLAP_RADIUS);
+            recorded[start..]
+                .iter()
+                .take_while(|(other_x, _)| *other_x <= x + OVERLAP_RADIUS)
+                .any(|(other_x, other_y)| {
+                    (other_x - x).powi(2) + (other_y - y).powi(2) <= OVERLAP_RADIUS.powi(2)
+                })
+        })
+        .count();
+    hiked as f64 / planned.len() as f64
+}
+
+fn compare(planned: &gpx::Track, recorded: &gpx::Track) -> Comparison {
+    let planned_points = planned
+        .segments
+        .iter()
+        .flat_map(|segment| segment.points.iter().map(|point| point.point()))
+        .collect::<Vec<_>>();
+    let recorded_points = recorded
+        .segments
+        .iter()
+        .flat_map(|segment| segment.points.iter().map(|point| point.point()))
+        .collect::<Vec<_>>();
+
+    let mut recorded_length = 0.0;
+    let mut moving_time = None;
+    let mut moving_length = 0.0;
+    let mut gain = None;
+    for segment in &recorded.segments {
+        let mut low = None;
+        for pair in segment.points.windows(2) {
+            let distance = Haversine::distance(pair[0].point(), pair[1].point());
+            recorded_length += distance;
+
+            if let (Some(start), Some(end)) = (pair[0].time, pair[1].time) {
+                let seconds =
+                    (OffsetDateTime::from(end) - OffsetDateTime::from(start)).as_seconds_f64();
+                let moving = moving_time.get_or_insert(0.0);
+                if seconds > 0.0 && seconds <= MAX_GAP && distance / seconds >= MOVING_SPEED {
+                    *moving += seconds;
+                    moving_length += distance;
+                }
+            }
+
+            if let (Some(previous), Some(elevation)) = (pair[0].elevation, pair[1].elevation) {
+                let low = low.get_or_insert(previous);
+                let gain = gain.get_or_insert(0.0);
+                if elevation < *low {
+                    *low = elevation;
+                } else if elevation - *low >= GAIN_HYSTERESIS {
+                    *gain += elevation - *low;
+                    *low = elevation;
+                }
+            }
+        }
+    }
+
+    Comparison {
+        overlap: overlap(&planned_points, &recorded_points),
+        recorded_length,
+        gain,
+        moving_time,
+        moving_length,
+    }
+}
+
+async fn read_gpx(attachment: &Attachment) -> eyre::Result<gpx::Gpx> {
+    if attachment.size as usize > crate::web_interface::upload_gpx::MAX_UPLOAD_SIZE {
+        return Err(eyre!("Recording is too large"));
+    }
+    let bytes = attachment
+        .download()
+        .await
+        .wrap_err("Failed to download recording")?;
+    gpx::read(Cursor::new(bytes)).wrap_err("Failed to read recording GPX file")
+}
+
+/// Compares the recording attached to the command against the stored GPX file
+/// of the trail suggestion best matching `trail`
+#[instrument(skip_all)]
+pub async fn respond(
+    command: &CommandInteraction,
+    state: Arc<AppState>,
+) -> eyre::Result<CreateInteractionResponseFollowup> {
+    let mut recording = None;
+    let mut trail = None;
+    let mut osm = false;
+    for option in command.data.options() {
+        match (option.name, option.value) {
+            ("recording", ResolvedValue::Attachment(attachment)) => {
+                recording = Some(attachment.clone())
+            }
+            ("trail", ResolvedValue::String(value)) => trail = Some(value.to_owned()),
+            ("osm", ResolvedValue::Boolean(value)) => osm = value,
+            (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+        }
+    }
+    let recording = recording.ok_or_eyre("Expected a `recording` option")?;
+    let trail = trail.ok_or_eyre("Expected a `trail` option")?;
+
+    let suggestion = crate::trail_names::find(&state.store, &trail)?
+        .ok_or_else(|| eyre!("No trail suggestions match `{}`", trail))?;
+    let planned = state
+        .store
+        .suggestion_gpx(suggestion.message_id)?
+        .ok_or_else(|| {
+            eyre!(
+                "`{}` was filled in before GPX files were kept, fill it in again to compare against it",
+                suggestion.title
+            )
+        })?;
+    let planned = gpx::read(Cursor::new(planned)).wrap_err("Failed to read stored GPX file")?;
+
+    let _permit = state
+        .limiter
+        .try_acquire(crate::backpressure::upload_memory(recording.size as usize))
+        .ok_or_eyre("Too many uploads are being handled right now")?;
+    let recorded = read_gpx(&recording).await?;
+
+    let recorded = recorded
+        .tracks
+        .first()
+        .ok_or_eyre("Recording contained no tracks")?;
+    let comparison = compare(
+        planned
+            .tracks
+            .first()
+            .ok_or_eyre("Stored GPX file contained no tracks")?,
+        recorded,
+    );
+
+    let config = state.config.load();
+    let lengths = config.lengths_for(&state.preferences(command.user.id)?);
+    let mut embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title(sanitize::text(
+            &format!("Planned vs actual: {}", suggestion.title),
+            sanitize::TITLE_LENGTH,
+        ))
+        .url(&suggestion.link)
+        .field(
+            "Route followed",
+            format!("{:.0}%", comparison.overlap * 100.0),
+            true,
+        );
+
+    let recorded_length = lengths.long(comparison.recorded_length)?;
+    embed = match suggestion.length {
+        Some(planned) => {
+            let extra = comparison.recorded_length - planned;
+            embed.field(
+                "Distance",
+                format!(
+                    "{} ({}{} vs planned)",
+                    recorded_length,
+                    if extra < 0.0 { "-" } else { "+" },
+                    lengths.long(extra.abs())?
+                ),
+                true,
+            )
+        }
+        None => embed.field("Distance", recorded_length, true),
+    };
+
+    if let Some(gain) = comparison.gain {
+        let mut value = lengths.short(gain)?;
+        if let Some(planned) = suggestion.gain {
+            value.push_str(&format!(" ({} planned)", lengths.short(planned)?));
+        }
+        embed = embed.field("Elevation gain", value, true);
+    }
+
+    if let Some(moving_time) = comparison.moving_time.filter(|seconds| *seconds > 0.0) {
+        let minutes = (moving_time / 60.0).round() as u64;
+        let speed = comparison.moving_length / MILE / (moving_time / 3600.0);
+        embed = embed
+            .field(
+                "Moving time",
+                format!("{}h {:02}m", minutes / 60, minutes % 60),
+                true,
+            )
+            .field(
+                "Moving speed",
+                format!(
+                    "{:.1} mph (estimates use {:.1} mph)",
+                    speed, config.avg_speed
+                ),
+                true,
+            );
+    }
+
+    let mut followup = CreateInteractionResponseFollowup::new();
+    if osm {
+        let divergences = crate::osm_notes::divergences(recorded, &config)
+            .await
+            .wrap_err("Failed to compare recording against OpenStreetMap")?;
+        if divergences.is_empty() {
+            embed = embed.field(
+                "OpenStreetMap",
+                "The recording stays on mapped trails",
+                false,
+            );
+        } else {
+            let off_trail = divergences.iter().map(|divergence| divergence.length).sum();
+            embed = embed.field(
+                "OpenStreetMap",
+                format!(
+                    "{} of the recording strays from mapped trails in {} places, see the attached notes to fix the map",
+                    lengths.long(off_trail)?,
+                    divergences.len()
+                ),
+                false,
+            );
+            followup = followup.add_file(CreateAttachment::bytes(
+                crate::osm_notes::notes(&suggestion.title, &divergences),
+                crate::osm_notes::FILE_NAME,
+            ));
+        }
+    }
+
+    Ok(followup.embed(embed))
+}
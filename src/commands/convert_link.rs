@@ -1,15 +1,35 @@
-use std::{borrow::Cow, ops::Deref, sync::Arc};
+use std::{
+    borrow::Cow,
+    ops::Deref,
+    sync::{Arc, LazyLock},
+};
 
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use regex::Regex;
+use reqwest::Url;
 use serenity::all::{
-    CommandInteraction, CreateCommand, CreateInteractionResponseMessage, ResolvedTarget,
+    CommandInteraction, CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage,
+    Message, ResolvedTarget,
 };
-use tracing::instrument;
+use tracing::{error, instrument, warn};
 
-use crate::AppState;
+use crate::{
+    audit::{audited, Actor, Mutation},
+    failure::Failure,
+    providers::Provider,
+    AppState,
+};
 
 use super::suggest::SuggestionCommand;
 
+/// Largest text attachment searched for a trail link, in bytes
+const MAX_TEXT_ATTACHMENT_SIZE: u32 = 64 * 1024;
+
+/// Links in free text, stopping at whitespace and the brackets links tend to
+/// be wrapped in
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)https?://[^\s<>()\[\]"'|]+"#).unwrap());
+
 pub fn create_command() -> CreateCommand {
     CreateCommand::new("Convert to hiking suggestion").kind(serenity::all::CommandType::Message)
 }
@@ -18,7 +38,7 @@ pub fn create_command() -> CreateCommand {
 pub async fn respond(
     command: &CommandInteraction,
     state: Arc<AppState>,
-) -> eyre::Result<CreateInteractionResponseMessage> {
+) -> eyre::Result<CreateInteractionResponse> {
     let ResolvedTarget::Message(message) = command
         .data
         .target()
@@ -27,23 +47,104 @@ pub async fn respond(
         return Err(eyre!("Command target was not a message"));
     };
 
-    let response = CreateInteractionResponseMessage::new().embed(
-        SuggestionCommand {
-            suggestion_link: Cow::Borrowed(&message.content),
-        }
-        .respond(
-            command,
-            Arc::clone(&state),
-            message.author.display_name().to_owned(),
-        )
-        .await
-        .wrap_err("Failed to create embed to update link message")?,
-    );
+    let (embed, upload_buttons) = SuggestionCommand {
+        suggestion_link: Cow::Owned(find_trail_link(message).await.ok_or(Failure::NotATrail(
+            "Message does not contain a link to a trail".into(),
+        ))?),
+        gpx_file: None,
+    }
+    .respond(command, &state, message.author.display_name().to_owned())
+    .await
+    .wrap_err("Failed to create embed to update link message")?;
+    let mut response = CreateInteractionResponseMessage::new().embed(embed);
+    if let Some(upload_buttons) = upload_buttons {
+        let state = Arc::clone(&state);
+        let command = command.clone();
+        tokio::spawn(async move {
+            if let Err(e) = upload_buttons.attach(&state, &command).await {
+                error!("{:?}", e);
+            }
+        });
+    }
+
+    // Through a user install the bot can't delete the message, so the
+    // suggestion is posted alongside it instead
+    if !super::in_home_guild(command, &state.config.load()) {
+        return Ok(CreateInteractionResponse::Message(response));
+    }
 
-    message
-        .delete(state.http.load().deref())
+    if state.dry_run(format_args!("deleting message {}", message.id)) {
+        response = response.content("Dry run, the original message was not deleted");
+    } else {
+        audited(
+            &state.audit,
+            Mutation::DeleteMessage,
+            Actor::User(command.user.id),
+            format!("message {} in channel {}", message.id, message.channel_id),
+            "converted to a trail suggestion",
+            message.delete(state.http.load().deref()),
+        )
         .await
         .wrap_err("Failed to delete message to convert")?;
+    }
+
+    Ok(CreateInteractionResponse::UpdateMessage(response))
+}
+
+/// The first link to a trail in `message`, looking through its text, then
+/// its embeds, then any text files attached to it
+#[instrument(skip_all)]
+async fn find_trail_link(message: &Message) -> Option<String> {
+    if let Some(link) = trail_links(&message.content).next() {
+        return Some(link);
+    }
+
+    if let Some(link) = message
+        .embeds
+        .iter()
+        .filter_map(|embed| embed.url.as_deref())
+        .flat_map(trail_links)
+        .next()
+    {
+        return Some(link);
+    }
+
+    for attachment in &message.attachments {
+        let is_text = attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("text/"));
+        if !is_text || attachment.size > MAX_TEXT_ATTACHMENT_SIZE {
+            continue;
+        }
+        let bytes = match attachment.download().await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!(
+                    "Failed to download attachment `{}`: {:?}",
+                    attachment.filename, e
+                );
+                continue;
+            }
+        };
+        let link = trail_links(&String::from_utf8_lossy(&bytes)).next();
+        if link.is_some() {
+            return link;
+        }
+    }
+
+    None
+}
 
-    Ok(response)
+/// Links to trails on any of the [`providers`](crate::providers) in `text`, in order
+fn trail_links(text: &str) -> impl Iterator<Item = String> + '_ {
+    LINK.find_iter(text).filter_map(|link| {
+        let link = link
+            .as_str()
+            .trim_end_matches(['.', ',', '!', '?', ':', ';']);
+        let url = Url::parse(link).ok()?;
+        Provider::of_url(&url)
+            .is_some_and(|provider| provider.is_trail(&url))
+            .then(|| link.to_owned())
+    })
 }
@@ -0,0 +1,253 @@
+use std::io::Cursor;
+
+use axum::http::StatusCode;
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{
+    ActionRowComponent, ButtonStyle, ComponentInteraction, CreateActionRow, CreateAllowedMentions,
+    CreateButton, CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, CreateModal, Http, InputTextStyle, Message, MessageId, ModalInteraction,
+};
+use tracing::instrument;
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    beginner::Conditions,
+    error::DiscordError,
+    pace::Activity,
+    store::PendingDetails,
+    web_interface::upload_gpx::{fill_suggestion, UploadForm},
+    AppState, ComponentId, Config,
+};
+
+pub fn button() -> eyre::Result<CreateButton> {
+    Ok(CreateButton::new(
+        serde_json::to_string(&ComponentId::FillDetails)
+            .wrap_err("Failed to serialize component ID")?,
+    )
+    .style(ButtonStyle::Secondary)
+    .label("Fill in details"))
+}
+
+#[instrument(skip_all)]
+pub fn open_modal(
+    component: &ComponentInteraction,
+    config: &Config,
+) -> eyre::Result<CreateInteractionResponse> {
+    let member = component
+        .member
+        .as_ref()
+        .ok_or_eyre("Button was pressed outside of a guild")?;
+    if !member
+        .roles
+        .iter()
+        .any(|role| config.admin_roles.contains(role))
+    {
+        return Err(eyre!("You do not have any admin role"));
+    }
+    // Buttons posted before the gateway was turned off
+    if !config.gateway {
+        return Err(eyre!(
+            "Details can't be filled in while the gateway is off, use the upload page instead"
+        ));
+    }
+
+    let input = |style, label: &str, id: &str, max_length| {
+        CreateActionRow::InputText(
+            CreateInputText::new(style, label, id)
+                .required(true)
+                .max_length(max_length),
+        )
+    };
+
+    Ok(CreateInteractionResponse::Modal(
+        CreateModal::new(
+            serde_json::to_string(&ComponentId::DetailsModal {
+                message_id: component.message.id,
+            })
+            .wrap_err("Failed to serialize modal ID")?,
+            "Trail details",
+        )
+        .components(vec![
+            input(InputTextStyle::Short, "Title", "title", 256),
+            input(InputTextStyle::Short, "Difficulty", "difficulty", 64),
+            input(InputTextStyle::Short, "Rating", "rating", 64),
+            input(InputTextStyle::Short, "Image URL", "image", 2048),
+            input(
+                InputTextStyle::Paragraph,
+                "Description",
+                "description",
+                4000,
+            ),
+        ]),
+    ))
+}
+
+#[instrument(skip(modal, state))]
+pub fn submit(
+    modal: &ModalInteraction,
+    state: &AppState,
+    message_id: MessageId,
+) -> eyre::Result<CreateInteractionResponse> {
+    let field = |id: &str| {
+        modal
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == id => {
+                    input.value.clone()
+                }
+                _ => None,
+            })
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| eyre!("Modal field `{}` was not filled in", id))
+    };
+
+    let details = PendingDetails {
+        message_id,
+        channel_id: modal.channel_id,
+        user_id: modal.user.id,
+        title: field("title")?,
+        difficulty: field("difficulty")?,
+        rating: field("rating")?,
+        image: field("image")?,
+        description: field("description")?,
+        created_at: get_current_timestamp(),
+    };
+    if let Some(filter) = &state.config.load().content_filter {
+        let roles = modal
+            .member
+            .as_ref()
+            .map_or(&[][..], |member| &member.roles[..]);
+        if !filter.overridden_by(roles) {
+            filter.check(&[
+                ("Title", &details.title),
+                ("Difficulty", &details.difficulty),
+                ("Rating", &details.rating),
+                ("Description", &details.description),
+            ])?;
+        }
+    }
+
+    state
+        .store
+        .insert_pending_details(&details)
+        .wrap_err("Failed to store trail details")?;
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content("Details saved! Reply to the suggestion with the GPX file attached to finish"),
+    ))
+}
+
+/// Finishes a suggestion filled in through the details modal once its author
+/// replies to it with a GPX attachment, called from the gateway
+#[instrument(skip_all)]
+pub async fn attach_gpx(state: &AppState, http: &Http, message: &Message) -> eyre::Result<()> {
+    if message.author.bot {
+        return Ok(());
+    }
+    let Some(referenced) = &message.referenced_message else {
+        return Ok(());
+    };
+    let Some(attachment) = message
+        .attachments
+        .iter()
+        .find(|a| a.filename.to_lowercase().ends_with(".gpx"))
+    else {
+        return Ok(());
+    };
+    let Some(details) = state
+        .store
+        .pending_details(referenced.id, message.author.id)
+        .wrap_err("Failed to load pending trail details")?
+    else {
+        return Ok(());
+    };
+
+    let result = async {
+        let link = referenced
+            .embeds
+            .first()
+            .ok_or_eyre("Suggestion message has no embeds")?
+            .url
+            .as_ref()
+            .ok_or_eyre("Suggestion embed has no URL")?;
+
+        let _permit = state
+            .limiter
+            .try_acquire(crate::backpressure::upload_memory(attachment.size as usize))
+            .ok_or_eyre("Too many uploads are being handled right now")?;
+        let gpx_bytes = attachment
+            .download()
+            .await
+            .wrap_err("Failed to download GPX attachment")?;
+
+        let form = UploadForm {
+            title: details.title,
+            difficulty: Some(details.difficulty),
+            rating: details.rating,
+            image: details.image,
+            image_file: None,
+            description: details.description,
+            gpx_file: gpx::read(Cursor::new(gpx_bytes)).wrap_err("Failed to read GPX file")?,
+            ruck_weight: None,
+            activity: Activity::Hike,
+            conditions: Conditions::default(),
+        };
+
+        fill_suggestion(
+            state,
+            referenced.channel_id,
+            referenced.id,
+            link,
+            form,
+            message.author.display_name(),
+            None,
+        )
+        .await
+        .wrap_err("Failed to fill trail suggestion")?;
+
+        state
+            .store
+            .remove_pending_details(referenced.id)
+            .wrap_err("Failed to clear pending trail details")
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            audited(
+                &state.audit,
+                Mutation::React,
+                Actor::User(message.author.id),
+                format!("message {} in channel {}", message.id, message.channel_id),
+                "✅",
+                message.react(http, '✅'),
+            )
+            .await
+            .wrap_err("Failed to react to GPX reply")?;
+        }
+        Err(e) => {
+            let reply = CreateMessage::new()
+                .reference_message(message)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .embed(DiscordError(StatusCode::OK, e).create_embed());
+            audited(
+                &state.audit,
+                Mutation::SendMessage,
+                Actor::User(message.author.id),
+                format!("channel {}", message.channel_id),
+                &summarize(&reply),
+                message.channel_id.send_message(http, reply),
+            )
+            .await
+            .wrap_err("Failed to reply with error to GPX reply")?;
+        }
+    }
+
+    Ok(())
+}
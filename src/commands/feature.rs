@@ -0,0 +1,80 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::instrument;
+
+use crate::{AppState, Feature};
+
+pub fn create_command() -> CreateCommand {
+    let mut feature = CreateCommandOption::new(
+        CommandOptionType::String,
+        "name",
+        "The subsystem to turn on or off",
+    )
+    .required(true);
+    for f in Feature::ALL {
+        feature = feature.add_string_choice(f.name(), f.name());
+    }
+
+    CreateCommand::new("feature")
+        .description("Turn bot subsystems on or off without reloading")
+        .add_option(feature)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether the subsystem should be enabled",
+            )
+            .required(true),
+        )
+}
+
+#[derive(Debug)]
+pub struct FeatureCommand {
+    feature: Feature,
+    enabled: bool,
+}
+
+impl FeatureCommand {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption]) -> eyre::Result<Self> {
+        let mut feature = None;
+        let mut enabled = None;
+        for option in options {
+            match (option.name, &option.value) {
+                ("name", ResolvedValue::String(name)) => {
+                    feature = Some(
+                        Feature::from_name(name)
+                            .ok_or_else(|| eyre!("Unknown feature `{}`", name))?,
+                    )
+                }
+                ("enabled", ResolvedValue::Boolean(value)) => enabled = Some(*value),
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+
+        Ok(Self {
+            feature: feature.ok_or_eyre("No feature was passed")?,
+            enabled: enabled.ok_or_eyre("`enabled` was not passed")?,
+        })
+    }
+
+    #[instrument(skip(state))]
+    pub fn respond(&self, state: &AppState) -> eyre::Result<CreateInteractionResponse> {
+        state
+            .set_feature(self.feature, self.enabled)
+            .wrap_err("Failed to update feature")?;
+
+        Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(format!(
+                    "`{}` is now {}",
+                    self.feature.name(),
+                    if self.enabled { "enabled" } else { "disabled" }
+                )),
+        ))
+    }
+}
@@ -0,0 +1,90 @@
+use color_eyre::eyre::{self, eyre, Context};
+use serenity::all::{
+    Color, CommandInteraction, CommandType, CreateCommand, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedTarget,
+};
+use tracing::instrument;
+
+use crate::{sanitize, AppState};
+
+/// How many of the most recent hikes are listed
+const RECENT_HIKES: usize = 10;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("Show hike history").kind(CommandType::User)
+}
+
+/// The completed hikes the selected member was interested in, as interest is
+/// all that's recorded of who went
+#[instrument(skip_all)]
+pub fn respond(
+    command: &CommandInteraction,
+    state: &AppState,
+) -> eyre::Result<CreateInteractionResponse> {
+    let Some(ResolvedTarget::User(user, _)) = command.data.target() else {
+        return Err(eyre!("Command target was not a user"));
+    };
+    let hikes = state
+        .store
+        .member_history(user.id)
+        .wrap_err("Failed to load hike history")?;
+
+    let config = state.config.load();
+    let lengths = config.lengths_for(&state.preferences(command.user.id)?);
+    let mut embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title(format!("Hike history of {}", user.display_name()))
+        .field("Hikes", hikes.len().to_string(), true)
+        .field(
+            "Distance",
+            lengths.long(hikes.iter().filter_map(|hike| hike.length).sum())?,
+            true,
+        )
+        .field(
+            "Elevation gain",
+            lengths.short(hikes.iter().filter_map(|hike| hike.gain).sum())?,
+            true,
+        );
+
+    if let Some((longest, length)) = hikes
+        .iter()
+        .filter_map(|hike| Some((hike, hike.length?)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    {
+        embed = embed.field(
+            "Longest hike",
+            format!(
+                "[{}]({}), {}",
+                sanitize::text(&longest.title, sanitize::NAME_LENGTH),
+                longest.link,
+                lengths.long(length)?
+            ),
+            false,
+        );
+    }
+
+    if hikes.is_empty() {
+        embed = embed.description("No completed hikes yet");
+    } else {
+        embed = embed.field(
+            "Recent hikes",
+            sanitize::lines(
+                hikes.iter().take(RECENT_HIKES).map(|hike| {
+                    let title = sanitize::text(&hike.title, sanitize::NAME_LENGTH);
+                    match hike.hiked_at {
+                        Some(hiked_at) => format!("<t:{}:d> [{}]({})", hiked_at, title, hike.link),
+                        None => format!("[{}]({})", title, hike.link),
+                    }
+                }),
+                sanitize::FIELD_LENGTH,
+            ),
+            false,
+        );
+    }
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embed(embed),
+    ))
+}
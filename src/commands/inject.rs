@@ -1,15 +1,23 @@
 use std::{ops::Deref, sync::Arc};
 
+use base64::prelude::*;
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
 use emath::{Align2, Pos2, Vec2};
-use magick_rust::MagickWand;
+use magick_rust::{ColorspaceType, CompositeOperator, FilterType, MagickWand};
+use serde_json::Value;
 use serenity::all::{
-    CommandInteraction, CreateAttachment, CreateCommand, CreateInteractionResponseFollowup,
-    EditScheduledEvent, Permissions, ResolvedTarget,
+    ChannelId, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption,
+    CreateInteractionResponseFollowup, EditScheduledEvent, GuildId, Message, MessageId,
+    Permissions, ResolvedTarget, ResolvedValue, ScheduledEventType, UserId,
 };
-use tracing::instrument;
+use tracing::{instrument, warn};
 
-use crate::AppState;
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    AppState, BannerCrop,
+};
+
+use super::suggest::TRAILHEAD_FIELD;
 
 pub fn create_command() -> CreateCommand {
     CreateCommand::new("Inject hike into recent event")
@@ -17,6 +25,58 @@ pub fn create_command() -> CreateCommand {
         .kind(serenity::all::CommandType::Message)
 }
 
+/// `/inject`, for clients where message context menus are hard to find
+pub fn create_slash_command() -> CreateCommand {
+    CreateCommand::new("inject")
+        .description("Inject a filled in trail suggestion into the most recent event")
+        .default_member_permissions(Permissions::MANAGE_EVENTS)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "message_link",
+                "Link to the trail suggestion message",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "celebration",
+                "Tag the hike as a celebration, such as \"Happy birthday Sam!\"",
+            )
+            .max_length(200),
+        )
+}
+
+/// Event description lines pointing out the hike is a celebration
+fn celebration_copy(celebration: &str) -> String {
+    format!("🎉 **{}**\n\n", celebration)
+}
+
+/// Reads the channel and message out of a Discord message link
+fn parse_message_link(link: &str) -> eyre::Result<(GuildId, ChannelId, MessageId)> {
+    let path = [
+        "discord.com",
+        "ptb.discord.com",
+        "canary.discord.com",
+        "discordapp.com",
+    ]
+    .iter()
+    .find_map(|host| link.strip_prefix(&format!("https://{}/channels/", host)))
+    .ok_or_eyre("Not a Discord message link")?;
+
+    let mut ids = path.trim_end_matches('/').split('/').map(str::parse::<u64>);
+    match (ids.next(), ids.next(), ids.next(), ids.next()) {
+        (Some(Ok(guild)), Some(Ok(channel)), Some(Ok(message)), None)
+            if guild != 0 && channel != 0 && message != 0 =>
+        {
+            Ok((guild.into(), channel.into(), message.into()))
+        }
+        _ => Err(eyre!("Not a Discord message link")),
+    }
+}
+
+/// Responds to both the context menu command and `/inject`
 #[instrument(skip_all)]
 pub async fn respond(
     command: &CommandInteraction,
@@ -26,6 +86,203 @@ pub async fn respond(
         .guild_id
         .ok_or_eyre("Command was not sent from a Guild")?;
 
+    if let Some(target) = command.data.target() {
+        let ResolvedTarget::Message(message) = target else {
+            return Err(eyre!("Command target was not a message"));
+        };
+        return inject(guild, message, command.user.id, None, state).await;
+    }
+
+    let mut link = None;
+    let mut celebration = None;
+    for option in command.data.options() {
+        match (option.name, option.value) {
+            ("message_link", ResolvedValue::String(value)) => link = Some(value),
+            ("celebration", ResolvedValue::String(value)) => celebration = Some(value),
+            (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+        }
+    }
+    let link = link.ok_or_eyre("Expected a `message_link` option")?;
+
+    let (link_guild, channel_id, message_id) =
+        parse_message_link(link).wrap_err_with(|| format!("Invalid message link `{}`", link))?;
+    if link_guild != guild {
+        return Err(eyre!("Message link is from a different server"));
+    }
+    let message = state
+        .http
+        .load()
+        .get_message(channel_id, message_id)
+        .await
+        .wrap_err("Failed to get linked message from Discord")?;
+
+    inject(guild, &message, command.user.id, celebration, state).await
+}
+
+/// Pixels along the long side of the photo that crops are scored at
+const SALIENCY_RESOLUTION: f32 = 128.0;
+
+/// Slides a window of `size` over an edge map of the photo and picks where it
+/// covers the most edges, which tend to be the subject rather than sky or
+/// ground. Falls back to the top center when nothing stands out.
+fn smart_crop(wand: &MagickWand, size: Vec2, image: emath::Rect) -> eyre::Result<emath::Rect> {
+    let top = Align2::CENTER_TOP.align_size_within_rect(size, image);
+    let scale = SALIENCY_RESOLUTION / image.width().max(image.height());
+    let (width, height) = (
+        ((image.width() * scale).round() as usize).max(1),
+        ((image.height() * scale).round() as usize).max(1),
+    );
+
+    let edges = wand.clone();
+    edges
+        .resize_image(width, height, FilterType::Triangle)
+        .wrap_err("Failed to shrink image in MagickWand")?;
+    edges
+        .transform_image_colorspace(ColorspaceType::GRAY)
+        .wrap_err("Failed to convert image to grayscale in MagickWand")?;
+    edges
+        .edge_image(1.0)
+        .wrap_err("Failed to find edges in MagickWand")?;
+    let pixels = edges
+        .export_image_pixels(0, 0, width, height, "I")
+        .filter(|pixels| pixels.len() >= width * height)
+        .ok_or_eyre("Failed to export pixels from MagickWand")?;
+
+    // Sums of every rectangle from the top left corner, so each window is
+    // scored in constant time
+    let mut sums = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            sums[(y + 1) * (width + 1) + x + 1] = pixels[y * width + x] as u64
+                + sums[y * (width + 1) + x + 1]
+                + sums[(y + 1) * (width + 1) + x]
+                - sums[y * (width + 1) + x];
+        }
+    }
+    let window = |x: usize, y: usize, w: usize, h: usize| {
+        sums[(y + h) * (width + 1) + x + w] + sums[y * (width + 1) + x]
+            - sums[y * (width + 1) + x + w]
+            - sums[(y + h) * (width + 1) + x]
+    };
+
+    let (window_width, window_height) = (
+        ((size.x * scale).round() as usize).clamp(1, width),
+        ((size.y * scale).round() as usize).clamp(1, height),
+    );
+    let top_x = ((top.min.x * scale).round() as usize).min(width - window_width);
+    let mut best = (top_x, 0, window(top_x, 0, window_width, window_height));
+    for y in 0..=height - window_height {
+        for x in 0..=width - window_width {
+            let score = window(x, y, window_width, window_height);
+            if score > best.2 {
+                best = (x, y, score);
+            }
+        }
+    }
+
+    let min = Pos2::new(
+        (best.0 as f32 / scale).min(image.width() - size.x),
+        (best.1 as f32 / scale).min(image.height() - size.y),
+    );
+    Ok(emath::Rect::from_min_size(min, size))
+}
+
+/// The JSON body of `edit` with `image` as the event's cover. serenity labels
+/// every image it encodes as a PNG, which would mislabel JPEG and WebP
+/// banners, so the data URI is set by hand and the body is sent with
+/// `Http::edit_scheduled_event` instead of `GuildId::edit_scheduled_event`.
+fn with_cover_image(edit: &EditScheduledEvent, image: &[u8], mime: &str) -> eyre::Result<Value> {
+    let mut payload =
+        serde_json::to_value(edit).wrap_err("Failed to serialize scheduled event edit")?;
+    payload["image"] = Value::String(format!(
+        "data:{};base64,{}",
+        mime,
+        BASE64_STANDARD.encode(image)
+    ));
+    Ok(payload)
+}
+
+async fn download_image(url: &str) -> eyre::Result<MagickWand> {
+    let bytes = reqwest::get(url)
+        .await
+        .wrap_err_with(|| format!("Failed to download `{}`", url))?
+        .error_for_status()
+        .wrap_err_with(|| format!("Failed to download `{}`", url))?
+        .bytes()
+        .await
+        .wrap_err_with(|| format!("Failed to get bytes from `{}`", url))?;
+    let wand = MagickWand::new();
+    wand.read_image_blob(bytes)
+        .wrap_err_with(|| format!("Failed to read `{}` in MagickWand", url))?;
+    Ok(wand)
+}
+
+/// Lays the elevation profile attached to the trail suggestion along the
+/// bottom of the cropped `banner`, and the route map in its top right corner
+async fn compose_banner(banner: &MagickWand, message: &Message) -> eyre::Result<()> {
+    let attached = |title: &str| {
+        message
+            .embeds
+            .iter()
+            .find(|embed| embed.title.as_deref() == Some(title))
+            .and_then(|embed| embed.image.as_ref())
+            .map(|image| image.url.clone())
+    };
+    let (width, height) = (banner.get_image_width(), banner.get_image_height());
+
+    if let Some(url) = attached(crate::route_map::PROFILE_TITLE) {
+        let profile = download_image(&url)
+            .await
+            .wrap_err("Failed to download elevation profile")?;
+        let profile_height = height / 5;
+        profile
+            .resize_image(width, profile_height, FilterType::Lanczos)
+            .wrap_err("Failed to resize elevation profile in MagickWand")?;
+        banner
+            .compose_images(
+                &profile,
+                CompositeOperator::Over,
+                false,
+                0,
+                (height - profile_height) as isize,
+            )
+            .wrap_err("Failed to place elevation profile in MagickWand")?;
+    }
+
+    if let Some(url) = attached(crate::route_map::TITLE) {
+        let route_map = download_image(&url)
+            .await
+            .wrap_err("Failed to download route map")?;
+        let map_height = height * 2 / 5;
+        let map_width =
+            route_map.get_image_width() * map_height / route_map.get_image_height().max(1);
+        let margin = height / 25;
+        route_map
+            .resize_image(map_width, map_height, FilterType::Lanczos)
+            .wrap_err("Failed to resize route map in MagickWand")?;
+        banner
+            .compose_images(
+                &route_map,
+                CompositeOperator::Over,
+                false,
+                width.saturating_sub(map_width + margin) as isize,
+                margin as isize,
+            )
+            .wrap_err("Failed to place route map in MagickWand")?;
+    }
+
+    Ok(())
+}
+
+/// Fills the most recently scheduled event in with the trail in `message`,
+/// leading with `celebration` if the hike is tagged as one
+async fn inject(
+    guild: GuildId,
+    message: &Message,
+    user: UserId,
+    celebration: Option<&str>,
+    state: Arc<AppState>,
+) -> eyre::Result<CreateInteractionResponseFollowup> {
     let scheduled_events = guild
         .scheduled_events(state.http.load().deref(), false)
         .await
@@ -36,38 +293,25 @@ pub async fn respond(
         .max_by_key(|event| event.start_time)
         .ok_or_eyre("Most recently scheduled event not found")?;
 
-    let ResolvedTarget::Message(message) = command
-        .data
-        .target()
-        .ok_or_eyre("Could not resolve command target")?
-    else {
-        return Err(eyre!("Command target was not a message"));
-    };
-
     let target_embed = message
         .embeds
-        .get(0)
+        .first()
         .ok_or_eyre("Target message was not an embed")?;
 
-    let embed_image = reqwest::get(
-        target_embed
+    let Some(_permit) = state.limiter.try_acquire(crate::backpressure::IMAGE_MEMORY) else {
+        return Err(eyre!(
+            "Too many images are being worked on right now, try again in a few seconds"
+        ));
+    };
+    let mut wand = download_image(
+        &target_embed
             .image
             .as_ref()
             .ok_or_eyre("Target embed did not have an image")?
-            .url
-            .as_str(),
+            .url,
     )
     .await
-    .wrap_err("Failed to download image linked in target embed")?
-    .error_for_status()
-    .wrap_err("Failed to download image linked in target embed")?
-    .bytes()
-    .await
-    .wrap_err("Failed to get bytes from image linked in embed")?;
-
-    let wand = MagickWand::new();
-    wand.read_image_blob(embed_image)
-        .wrap_err("Failed to downloaded image from target embed")?;
+    .wrap_err("Failed to download image linked in target embed")?;
 
     let image_size = emath::Rect::from_min_size(
         Pos2::ZERO,
@@ -83,7 +327,15 @@ pub async fn respond(
         Vec2::new(image_size.height(), image_size.height() / (5.0 / 2.0))
     };
 
-    let fit = Align2::CENTER_TOP.align_size_within_rect(fit, image_size);
+    let config = state.config.load();
+    let fit = match config.banner_crop {
+        BannerCrop::Top => Align2::CENTER_TOP.align_size_within_rect(fit, image_size),
+        BannerCrop::Center => Align2::CENTER_CENTER.align_size_within_rect(fit, image_size),
+        BannerCrop::Smart => smart_crop(&wand, fit, image_size).unwrap_or_else(|e| {
+            warn!("{:?}", e);
+            Align2::CENTER_TOP.align_size_within_rect(fit, image_size)
+        }),
+    };
 
     wand.crop_image(
         fit.width() as usize,
@@ -93,39 +345,126 @@ pub async fn respond(
     )
     .wrap_err("Failed to crop image in MagickWand")?;
 
+    // The photo alone still makes a banner
+    if let Err(e) = compose_banner(&wand, message).await {
+        warn!("{:?}", e);
+    }
+
+    wand.set_image_compression_quality(config.banner_quality)
+        .wrap_err("Failed to set image quality in MagickWand")?;
+
     let image = wand
-        .write_image_blob("jpeg")
+        .write_image_blob(config.banner_format.magick_format())
         .wrap_err("Failed to write image from MagickWand")?;
 
-    let mut edit_event = EditScheduledEvent::new()
-        .name(
-            target_embed
-                .title
-                .as_ref()
-                .ok_or_eyre("Target embed did not have a title")?,
-        )
-        .image(&CreateAttachment::bytes(image, "trail.jpg"));
-    let mut description = target_embed.description.clone().unwrap_or_default();
+    let mut edit_event = EditScheduledEvent::new().name(
+        target_embed
+            .title
+            .as_ref()
+            .ok_or_eyre("Target embed did not have a title")?,
+    );
+    let mut description = celebration.map(celebration_copy).unwrap_or_default();
+    description.push_str(target_embed.description.as_deref().unwrap_or_default());
+
+    // Only external events have a location, other events keep the trailhead
+    // in their description
+    let trailhead = target_embed
+        .fields
+        .iter()
+        .find(|field| field.name == TRAILHEAD_FIELD)
+        .and_then(|field| field.value.lines().next())
+        .filter(|_| target_event.kind == ScheduledEventType::External);
+    if let Some(trailhead) = trailhead {
+        edit_event = edit_event.location(trailhead);
+    }
 
     description.push_str("\n\n");
 
     for field in &target_embed.fields {
+        if trailhead.is_some() && field.name == TRAILHEAD_FIELD {
+            continue;
+        }
         std::fmt::Write::write_fmt(
             &mut description,
             format_args!("**{}**: {}\n", field.name, field.value),
         )
         .wrap_err("Failed to write to description string for event")?;
     }
+    // Events only have the one image, so the route map is linked instead
+    let route_map = message
+        .embeds
+        .iter()
+        .find(|embed| embed.title.as_deref() == Some(crate::route_map::TITLE))
+        .and_then(|embed| embed.image.as_ref());
+    if let Some(route_map) = route_map {
+        std::fmt::Write::write_fmt(
+            &mut description,
+            format_args!("**{}**: {}\n", crate::route_map::TITLE, route_map.url),
+        )
+        .wrap_err("Failed to write to description string for event")?;
+    }
+    // For map apps that need tiles downloaded before losing signal
+    if let Some((map, gpx)) = crate::web_interface::offline::links(&state, message.id) {
+        std::fmt::Write::write_fmt(
+            &mut description,
+            format_args!("**Offline map**: {}\n**GPX file**: {}\n", map, gpx),
+        )
+        .wrap_err("Failed to write to description string for event")?;
+    }
     description.pop();
 
     edit_event = edit_event.description(description);
 
-    guild
-        .edit_scheduled_event(state.http.load().deref(), target_event.id, edit_event)
-        .await
-        .wrap_err("Failed to edit scheduled event")?;
+    let payload = with_cover_image(&edit_event, &image, config.banner_format.mime())?;
+
+    if state.dry_run(format_args!("editing scheduled event {}", target_event.id)) {
+        return Ok(CreateInteractionResponseFollowup::new()
+            .content(format!("Dry run, `{}` was not updated", target_event.name))
+            .ephemeral(true));
+    }
+
+    audited(
+        &state.audit,
+        Mutation::EditEvent,
+        Actor::User(user),
+        format!("event {} in guild {}", target_event.id, guild),
+        &summarize(&payload),
+        state
+            .http
+            .load()
+            .edit_scheduled_event(guild, target_event.id, &payload, None),
+    )
+    .await
+    .wrap_err("Failed to edit scheduled event")?;
+
+    state
+        .store
+        .set_event(
+            message.id,
+            target_event.id,
+            target_event.start_time.unix_timestamp().max(0) as u64,
+        )
+        .wrap_err("Failed to record scheduled event for suggestion")?;
+
+    // Points out birthdays in the month of the hike, unless it's already
+    // being celebrated
+    let mut content = String::from("Success");
+    if celebration.is_none() {
+        let hint =
+            time::OffsetDateTime::from_unix_timestamp(target_event.start_time.unix_timestamp())
+                .wrap_err("Event starts at an invalid time")
+                .and_then(|start| crate::birthdays::hint(&state, start.month()));
+        match hint {
+            Ok(Some(hint)) => {
+                content.push_str("\n\n");
+                content.push_str(&hint);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("{:?}", e),
+        }
+    }
 
     Ok(CreateInteractionResponseFollowup::new()
-        .content("Success")
+        .content(content)
         .ephemeral(true))
 }
@@ -0,0 +1,80 @@
+use color_eyre::eyre::{self, Context};
+use serenity::all::{
+    Color, CreateCommand, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, Mentionable,
+};
+use tracing::instrument;
+
+use crate::{sanitize, AppState};
+
+const TITLE: &str = "Interested hikers";
+
+/// Room kept for the footer counting the suggestions that didn't fit
+const FOOTER_LENGTH: usize = 32;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("interested").description("Show who is interested in each trail suggestion")
+}
+
+#[instrument(skip_all)]
+pub fn respond(state: &AppState) -> eyre::Result<CreateInteractionResponse> {
+    let config = state.config.load();
+    let suggestions = state
+        .store
+        .suggestions()
+        .wrap_err("Failed to load suggestions")?;
+
+    let mut embed = CreateEmbed::new().color(Color::DARK_GREEN).title(TITLE);
+
+    if suggestions.is_empty() {
+        embed = embed.description("No trail suggestions have been filled in yet");
+    }
+
+    let mut length = TITLE.chars().count() + FOOTER_LENGTH;
+    let mut shown = 0;
+    for suggestion in suggestions.iter().take(sanitize::MAX_FIELDS) {
+        let interested = state
+            .store
+            .interested(suggestion.message_id)
+            .wrap_err("Failed to load interested members")?;
+
+        let mut value = format!(
+            "[{} interested]({})",
+            interested.len(),
+            suggestion
+                .message_id
+                .link(suggestion.channel_id, Some(config.guild_id))
+        );
+        let mentions = interested
+            .iter()
+            .map(|user| user.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !mentions.is_empty() && value.len() + mentions.len() < sanitize::FIELD_LENGTH {
+            value.push('\n');
+            value.push_str(&mentions);
+        }
+
+        let name = sanitize::text(&suggestion.title, sanitize::FIELD_NAME_LENGTH);
+        length += name.chars().count() + value.chars().count();
+        if length > sanitize::EMBED_LENGTH {
+            break;
+        }
+
+        embed = embed.field(name, value, false);
+        shown += 1;
+    }
+
+    if shown < suggestions.len() {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "…and {} more",
+            suggestions.len() - shown
+        )));
+    }
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embed(embed),
+    ))
+}
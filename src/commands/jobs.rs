@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use serenity::all::{
+    Color, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    ResolvedOption, ResolvedValue,
+};
+use tracing::instrument;
+
+use crate::AppState;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("jobs")
+        .description("Inspect and recover background jobs")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show job runs and jobs that failed after retrying",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "retry",
+                "Run a failed job again",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "id",
+                    "ID of the failed job, from `/jobs list`",
+                )
+                .required(true),
+            ),
+        )
+}
+
+#[derive(Debug)]
+pub enum JobsCommand {
+    List,
+    Retry { id: i64 },
+}
+
+impl JobsCommand {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption]) -> eyre::Result<Self> {
+        let option = options.first().ok_or_eyre("No subcommand was passed")?;
+        match (option.name, &option.value) {
+            ("list", ResolvedValue::SubCommand(_)) => Ok(JobsCommand::List),
+            ("retry", ResolvedValue::SubCommand(options)) => match options.first() {
+                Some(ResolvedOption {
+                    name: "id",
+                    value: ResolvedValue::Integer(id),
+                    ..
+                }) => Ok(JobsCommand::Retry { id: *id }),
+                _ => Err(eyre!("Expected an `id` option")),
+            },
+            (name, _) => Err(eyre!("Unexpected subcommand `{}`", name)),
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub fn list(state: &AppState) -> eyre::Result<CreateInteractionResponse> {
+    let stats = state
+        .store
+        .job_stats()
+        .wrap_err("Failed to load job stats")?;
+    let dead_letters = state
+        .store
+        .dead_letters()
+        .wrap_err("Failed to load dead letters")?;
+
+    let mut jobs = CreateEmbed::new().color(Color::DARK_GREEN).title("Jobs");
+    if stats.is_empty() {
+        jobs = jobs.description("No jobs have been scheduled yet");
+    }
+    for job in stats.iter().take(25) {
+        let mut value = format!(
+            "Next run <t:{}:R>\n{} runs, {} failed",
+            job.next_run, job.runs, job.failures
+        );
+        if let (Some(last_run), Some(duration)) = (job.last_run, job.last_duration_ms) {
+            value.push_str(&format!("\nLast ran <t:{}:R> for {}ms", last_run, duration));
+        }
+        jobs = jobs.field(&job.name, value, false);
+    }
+
+    let mut failed = CreateEmbed::new()
+        .color(Color::RED)
+        .title("Failed jobs")
+        .footer(serenity::all::CreateEmbedFooter::new(
+            "Retry one with /jobs retry",
+        ));
+    if dead_letters.is_empty() {
+        failed = failed.description("No jobs have failed after retrying");
+    }
+    for dead_letter in dead_letters.iter().take(25) {
+        // Keep the field under Discord's 1024 character limit
+        let mut error = dead_letter.error.chars().take(900).collect::<String>();
+        if error.len() < dead_letter.error.len() {
+            error.push('…');
+        }
+        failed = failed.field(
+            format!("#{} {}", dead_letter.id, dead_letter.job),
+            format!(
+                "Failed <t:{}:R> after {} attempts\n```\n{}\n```",
+                dead_letter.failed_at, dead_letter.attempts, error
+            ),
+            false,
+        );
+    }
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embeds(vec![jobs, failed]),
+    ))
+}
+
+#[instrument(skip(state))]
+pub async fn retry(
+    state: Arc<AppState>,
+    id: i64,
+) -> eyre::Result<CreateInteractionResponseFollowup> {
+    let job = crate::scheduler::retry(state, id)
+        .await
+        .wrap_err_with(|| format!("Failed to retry job #{}", id))?;
+
+    Ok(CreateInteractionResponseFollowup::new()
+        .ephemeral(true)
+        .content(format!(
+            "`{}` succeeded, removed it from failed jobs",
+            job.name()
+        )))
+}
@@ -0,0 +1,227 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    ButtonStyle, Color, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage, Embed, EmbedField, MessageId,
+};
+use tracing::instrument;
+
+use crate::{AppState, ComponentId, Config};
+
+/// Name of the embed field showing a suggestion's state
+const STATUS_FIELD: &str = "Status";
+
+/// Where a filled in suggestion is in its lifecycle, moved along by admins
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SuggestionState {
+    #[default]
+    Proposed,
+    Approved,
+    Scheduled,
+    Completed,
+    Rejected,
+}
+
+impl SuggestionState {
+    const ALL: [SuggestionState; 5] = [
+        SuggestionState::Proposed,
+        SuggestionState::Approved,
+        SuggestionState::Scheduled,
+        SuggestionState::Completed,
+        SuggestionState::Rejected,
+    ];
+
+    /// Name the state is stored as
+    pub fn name(self) -> &'static str {
+        match self {
+            SuggestionState::Proposed => "proposed",
+            SuggestionState::Approved => "approved",
+            SuggestionState::Scheduled => "scheduled",
+            SuggestionState::Completed => "completed",
+            SuggestionState::Rejected => "rejected",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|state| state.name() == name)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SuggestionState::Proposed => "Proposed",
+            SuggestionState::Approved => "Approved",
+            SuggestionState::Scheduled => "Scheduled",
+            SuggestionState::Completed => "Completed",
+            SuggestionState::Rejected => "Rejected",
+        }
+    }
+
+    /// Label of the button moving a suggestion to this state
+    fn action(self) -> &'static str {
+        match self {
+            SuggestionState::Proposed => "Reopen",
+            SuggestionState::Approved => "Approve",
+            SuggestionState::Scheduled => "Mark scheduled",
+            SuggestionState::Completed => "Mark completed",
+            SuggestionState::Rejected => "Reject",
+        }
+    }
+
+    /// States a suggestion in this state can be moved to
+    fn next(self) -> &'static [SuggestionState] {
+        match self {
+            SuggestionState::Proposed => &[SuggestionState::Approved, SuggestionState::Rejected],
+            SuggestionState::Approved => &[SuggestionState::Scheduled, SuggestionState::Rejected],
+            SuggestionState::Scheduled => &[SuggestionState::Completed, SuggestionState::Rejected],
+            SuggestionState::Completed | SuggestionState::Rejected => &[],
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            SuggestionState::Proposed => Color::DARK_GREEN,
+            SuggestionState::Approved => Color::BLUE,
+            SuggestionState::Scheduled => Color::GOLD,
+            SuggestionState::Completed => Color::DARK_GREY,
+            SuggestionState::Rejected => Color::RED,
+        }
+    }
+}
+
+/// The state of the suggestion posted as `message_id`, if it's been filled in
+pub fn stored_state(
+    state: &AppState,
+    message_id: MessageId,
+) -> eyre::Result<Option<SuggestionState>> {
+    state
+        .store
+        .suggestion_state(message_id)?
+        .map(|name| {
+            SuggestionState::from_name(&name)
+                .ok_or_else(|| eyre!("Unknown suggestion state `{}`", name))
+        })
+        .transpose()
+}
+
+/// Buttons moving a suggestion in `state` along, which run out once it's
+/// completed or rejected, and a link to download its GPX file from `gpx_url`
+pub fn buttons(
+    state: SuggestionState,
+    gpx_url: Option<String>,
+) -> eyre::Result<Vec<CreateActionRow>> {
+    let mut buttons = state
+        .next()
+        .iter()
+        .map(|&to| {
+            serde_json::to_string(&ComponentId::Transition { to })
+                .wrap_err("Failed to serialize component ID")
+                .map(|id| {
+                    CreateButton::new(id).label(to.action()).style(match to {
+                        SuggestionState::Rejected => ButtonStyle::Danger,
+                        _ => ButtonStyle::Primary,
+                    })
+                })
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    if let Some(gpx_url) = gpx_url {
+        buttons.push(CreateButton::new_link(gpx_url).label("Download GPX"));
+    }
+
+    Ok(if buttons.is_empty() {
+        Vec::new()
+    } else {
+        vec![CreateActionRow::Buttons(buttons)]
+    })
+}
+
+/// Shows `state` on a suggestion embed before it's sent
+pub fn style(embed: CreateEmbed, state: SuggestionState) -> CreateEmbed {
+    embed
+        .color(state.color())
+        .field(STATUS_FIELD, state.label(), false)
+}
+
+/// Shows `state` on a suggestion embed that was already sent, in place of the
+/// old state
+fn restyle(mut embed: Embed, state: SuggestionState) -> CreateEmbed {
+    embed.colour = Some(state.color());
+    match embed
+        .fields
+        .iter_mut()
+        .find(|field| field.name == STATUS_FIELD)
+    {
+        Some(field) => field.value = state.label().to_owned(),
+        None => embed
+            .fields
+            .push(EmbedField::new(STATUS_FIELD, state.label(), false)),
+    }
+    CreateEmbed::from(embed)
+}
+
+/// Moves the suggestion the button was pressed on to `to`
+#[instrument(skip(component, state, config))]
+pub fn transition(
+    component: &ComponentInteraction,
+    state: &AppState,
+    config: &Config,
+    to: SuggestionState,
+) -> eyre::Result<CreateInteractionResponse> {
+    let member = component
+        .member
+        .as_ref()
+        .ok_or_eyre("Button was pressed outside of a guild")?;
+    if !member
+        .roles
+        .iter()
+        .any(|role| config.admin_roles.contains(role))
+    {
+        return Err(eyre!("You do not have any admin role"));
+    }
+
+    let message_id = component.message.id;
+    let from =
+        stored_state(state, message_id)?.ok_or_eyre("Suggestion has not been filled in yet")?;
+    if !from.next().contains(&to) {
+        return Err(eyre!(
+            "A suggestion that is {} can't be {}",
+            from.name(),
+            to.name()
+        ));
+    }
+
+    let moved = state
+        .store
+        .transition_suggestion(
+            message_id,
+            from.name(),
+            to.name(),
+            component.user.id,
+            get_current_timestamp(),
+        )
+        .wrap_err("Failed to store suggestion state")?;
+    if !moved {
+        return Err(eyre!(
+            "Someone else changed the suggestion's status at the same time"
+        ));
+    }
+
+    let gpx_url = state
+        .store
+        .has_suggestion_gpx(message_id)?
+        .then(|| crate::web_interface::api::gpx_url(&config.hostname, message_id));
+
+    let mut embeds = component.message.embeds.clone().into_iter();
+    let suggestion = embeds
+        .next()
+        .ok_or_eyre("Suggestion message has no embeds")?;
+    Ok(CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .embeds(
+                std::iter::once(restyle(suggestion, to))
+                    .chain(embeds.map(CreateEmbed::from))
+                    .collect(),
+            )
+            .components(buttons(to, gpx_url)?),
+    ))
+}
@@ -1,19 +1,23 @@
 use std::{
     borrow::Cow,
     num::NonZeroU64,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
 use serde::{Deserialize, Serialize};
 use serenity::all::{
-    Color, CommandOptionType, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed,
-    CreateEmbedAuthor, CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption,
-    ResolvedValue,
+    ButtonStyle, Color, CommandOptionType, CreateActionRow, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateEmbedAuthor, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
 };
-use tracing::instrument;
+use tokio::time::Instant;
+use tracing::{instrument, warn};
 
-use crate::ComponentId;
+use crate::{
+    musicbrainz::{Release, ReleaseQuery},
+    AppState, ComponentId, Config,
+};
 
 pub fn create_command() -> CreateCommand {
     CreateCommand::new("listenbrainz")
@@ -36,7 +40,7 @@ pub struct ListenbrainzCommand<'a> {
 impl<'a> ListenbrainzCommand<'a> {
     #[instrument]
     pub fn from_options(options: &[ResolvedOption<'a>]) -> eyre::Result<Self> {
-        match options.get(0).ok_or_eyre("No arguments were passed")? {
+        match options.first().ok_or_eyre("No arguments were passed")? {
             ResolvedOption {
                 value: ResolvedValue::String(user),
                 ..
@@ -53,6 +57,8 @@ impl<'a> ListenbrainzCommand<'a> {
                 .wrap_err("Failed to get SystemTime unix timestamp")?
                 .as_secs(),
             user: std::borrow::Cow::Borrowed(self.user),
+            before: None,
+            ended_at: None,
         };
 
         Ok(CreateInteractionResponse::Message(
@@ -74,9 +80,22 @@ impl<'a> ListenbrainzCommand<'a> {
     }
 }
 
-#[derive(Serialize)]
+/// Listens shown per page, leaving room for the currently playing track
+/// within Discord's limit of 10 embeds
+const PAGE_SIZE: usize = 9;
+
+/// Most listens ListenBrainz returns in one request
+const MAX_LISTENS: u64 = 1000;
+
+/// Time spent resolving releases on MusicBrainz before responding without
+/// them, since Discord waits 3 seconds for an interaction response
+const RELEASE_LOOKUP_TIME: Duration = Duration::from_millis(1500);
+
+#[derive(Serialize, Debug)]
 pub struct ListenbrainzBody {
-    min_ts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_ts: Option<u64>,
+    count: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -110,20 +129,168 @@ struct AdditionalInfo<'a> {
     // submission_client_version: Cow<'a, str>,
     release_mbid: Option<Cow<'a, str>>,
     // artist_mbids: Vec<Cow<'a, str>>,
-    // recording_mbid: Cow<'a, str>,
-    // duration_ms: u64,
+    recording_mbid: Option<Cow<'a, str>>,
+    duration_ms: Option<u64>,
+    /// Some clients submit the duration in seconds instead
+    duration: Option<u64>,
 }
 
-pub async fn update_message(
-    time: u64,
+impl Listen<'_> {
+    fn release_mbid(&self) -> Option<&str> {
+        self.track_metadata
+            .additional_info
+            .as_ref()
+            .and_then(|ai| ai.release_mbid.as_deref())
+    }
+
+    fn release_query(&self) -> ReleaseQuery {
+        let info = self.track_metadata.additional_info.as_ref();
+        if let Some(mbid) = self.release_mbid() {
+            ReleaseQuery::Release(mbid.to_owned())
+        } else if let Some(mbid) = info.and_then(|ai| ai.recording_mbid.as_deref()) {
+            ReleaseQuery::Recording(mbid.to_owned())
+        } else {
+            ReleaseQuery::Search {
+                artist: self.track_metadata.artist_name.clone().into_owned(),
+                track: self.track_metadata.track_name.clone().into_owned(),
+            }
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        let info = self.track_metadata.additional_info.as_ref()?;
+        info.duration_ms
+            .map(Duration::from_millis)
+            .or(info.duration.map(Duration::from_secs))
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Summary of every track listened to on a ride that has ended
+fn summary_embed(listens: &[Listen], time: u64, ended_at: u64) -> CreateEmbed {
+    let durations = listens
+        .iter()
+        .filter_map(Listen::duration)
+        .collect::<Vec<_>>();
+    let mut listening_time = format_duration(durations.iter().sum());
+    let unknown = listens.len() - durations.len();
+    if unknown > 0 {
+        listening_time.push_str(&format!(" ({} tracks without a duration)", unknown));
+    }
+
+    CreateEmbed::new()
+        .title("Ride playlist")
+        .color(Color::PURPLE)
+        .field("Tracks", listens.len().to_string(), true)
+        .field("Listening time", listening_time, true)
+        .field(
+            "Ride length",
+            format_duration(Duration::from_secs(ended_at.saturating_sub(time))),
+            true,
+        )
+}
+
+/// `release` is what MusicBrainz resolved for the listen, which fills in the
+/// art and link when the listen has no `release_mbid`
+fn listen_embed(
+    listen: Listen,
+    now_playing: bool,
+    release: Option<Release>,
+    config: &Config,
+) -> CreateEmbed {
+    let author = if now_playing {
+        format!("Now playing · {}", listen.track_metadata.artist_name)
+    } else {
+        listen.track_metadata.artist_name.clone().into_owned()
+    };
+
+    let year = release.as_ref().and_then(|release| release.year.as_deref());
+    let description = match (&listen.track_metadata.release_name, year) {
+        (Some(name), Some(year)) => format!("{} ({})", name, year),
+        (Some(name), None) => name.clone().into_owned(),
+        (None, Some(year)) => year.to_owned(),
+        (None, None) => String::new(),
+    };
+
+    let (image, url) = match (listen.release_mbid(), &release) {
+        (Some(mbid), _) => (
+            config
+                .services
+                .cover_art(format!("release/{}/front-500", mbid)),
+            format!("https://listenbrainz.org/album/{}", mbid),
+        ),
+        (None, Some(release)) => (
+            release.cover_art(config),
+            format!("https://listenbrainz.org/album/{}", release.mbid),
+        ),
+        (None, None) => Default::default(),
+    };
+
+    CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(author))
+        .title(listen.track_metadata.track_name)
+        .description(description)
+        .image(image)
+        .url(url)
+        .color(Color::PURPLE)
+}
+
+/// Resolves each release on MusicBrainz, leaving the rest unresolved once
+/// `RELEASE_LOOKUP_TIME` has passed
+async fn resolve_releases(state: &AppState, queries: Vec<ReleaseQuery>) -> Vec<Option<Release>> {
+    let config = state.config.load();
+    let deadline = Instant::now() + RELEASE_LOOKUP_TIME;
+
+    // One at a time, MusicBrainz rate limits bursts of requests
+    let mut releases = Vec::new();
+    for query in queries {
+        releases.push(state.releases.resolve(query, &config, deadline).await);
+    }
+    releases
+}
+
+/// The track `user` is listening to right now, if any
+#[instrument(skip(config))]
+async fn playing_now(user: &str, config: &Config) -> eyre::Result<Option<Listen<'static>>> {
+    let playing_now: ListenbrainzListens = reqwest::Client::new()
+        .get(
+            config
+                .services
+                .listenbrainz(format!("1/user/{}/playing-now", user)),
+        )
+        .send()
+        .await
+        .wrap_err("Failed to obtain ListenBrainz playing now")?
+        .error_for_status()
+        .wrap_err("ListenBrainz playing now returned an error")?
+        .json()
+        .await
+        .wrap_err("Failed to get JSON from ListenBrainz playing now response")?;
+
+    Ok(playing_now.payload.listens.into_iter().next())
+}
+
+#[instrument(skip(config))]
+async fn listens(
     user: &str,
-) -> eyre::Result<CreateInteractionResponseMessage> {
-    let mut listens: ListenbrainzListens = reqwest::Client::new()
-        .get(format!(
-            "https://api.listenbrainz.org/1/user/{}/listens",
-            user
-        ))
-        .query(&ListenbrainzBody { min_ts: time })
+    body: &ListenbrainzBody,
+    config: &Config,
+) -> eyre::Result<Vec<Listen<'static>>> {
+    let listens: ListenbrainzListens = reqwest::Client::new()
+        .get(
+            config
+                .services
+                .listenbrainz(format!("1/user/{}/listens", user)),
+        )
+        .query(body)
         .send()
         .await
         .wrap_err("Failed to obtain ListenBrainz listens")?
@@ -131,47 +298,156 @@ pub async fn update_message(
         .await
         .wrap_err("Failed to get JSON from ListenBrainz listens response")?;
 
-    listens.payload.listens.sort_by_key(|p| {
-        p.listened_at
+    Ok(listens.payload.listens)
+}
+
+/// An embed of the track `user` is listening to right now, or of the last
+/// track they listened to if nothing is playing
+pub async fn latest_embed(state: &AppState, user: &str) -> eyre::Result<Option<CreateEmbed>> {
+    let config = state.config.load();
+    let (listen, now_playing) = match playing_now(user, &config).await? {
+        Some(listen) => (listen, true),
+        None => {
+            let body = ListenbrainzBody {
+                min_ts: None,
+                count: 1,
+            };
+            match listens(user, &body, &config).await?.into_iter().next() {
+                Some(listen) => (listen, false),
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let release = resolve_releases(state, vec![listen.release_query()])
+        .await
+        .pop()
+        .flatten();
+    Ok(Some(listen_embed(listen, now_playing, release, &config)))
+}
+
+/// Shows the page of listens since `time` ending right before `before`, or
+/// the newest page along with the currently playing track. Once the ride has
+/// ended, only listens from before `ended_at` are shown, along with a summary.
+pub async fn update_message(
+    state: &AppState,
+    time: u64,
+    user: &str,
+    before: Option<u64>,
+    ended_at: Option<u64>,
+) -> eyre::Result<CreateInteractionResponseMessage> {
+    let config = state.config.load();
+    let mut listens = listens(
+        user,
+        &ListenbrainzBody {
+            min_ts: Some(time),
+            count: MAX_LISTENS,
+        },
+        &config,
+    )
+    .await?;
+
+    let listened_at = |listen: &Listen| {
+        listen
+            .listened_at
             .unwrap_or_else(|| NonZeroU64::new(u64::MAX).unwrap())
+            .get()
+    };
+
+    if let Some(ended_at) = ended_at {
+        listens.retain(|listen| listened_at(listen) < ended_at);
+    }
+    listens.sort_by_key(listened_at);
+
+    let total = listens.len();
+    let end = before.map_or(total, |before| {
+        listens.partition_point(|listen| listened_at(listen) < before)
     });
+    let start = end.saturating_sub(PAGE_SIZE);
 
-    let embeds = listens
-        .payload
-        .listens
-        .into_iter()
-        .map(|listen| {
-            CreateEmbed::new()
-                .author(CreateEmbedAuthor::new(listen.track_metadata.artist_name))
-                .title(listen.track_metadata.track_name)
-                .description(listen.track_metadata.release_name.unwrap_or_default())
-                .image(
-                    listen
-                        .track_metadata
-                        .additional_info
-                        .as_ref()
-                        .and_then(|ai| {
-                            ai.release_mbid.as_ref().map(|rmbid| {
-                                format!("https://coverartarchive.org/release/{}/front-500", rmbid)
-                            })
-                        })
-                        .unwrap_or_default(),
-                )
-                .url(
-                    listen
-                        .track_metadata
-                        .additional_info
-                        .and_then(|ai| {
-                            ai.release_mbid
-                                .map(|rmbid| format!("https://listenbrainz.org/album/{}", rmbid))
-                        })
-                        .unwrap_or_default(),
-                )
-                .color(Color::PURPLE)
+    // Older pages end at the oldest listen shown, newer pages end right after
+    // the next PAGE_SIZE listens
+    let older = listens.get(start).filter(|_| start > 0).map(listened_at);
+    let newer_end = (end + PAGE_SIZE).min(total);
+    let newer = listens.get(newer_end).map(listened_at);
+
+    // Not being able to see what's playing shouldn't hide the finished listens
+    let playing_now = if end == total && ended_at.is_none() {
+        playing_now(user, &config).await.unwrap_or_else(|e| {
+            warn!("{:?}", e);
+            None
         })
+    } else {
+        None
+    };
+
+    let content = if total == 0 {
+        String::from("No listens yet")
+    } else {
+        format!("Listens {}-{} of {}", start + 1, end, total)
+    };
+
+    let summary = ended_at.map(|ended_at| summary_embed(&listens, time, ended_at));
+
+    let queries = playing_now
+        .iter()
+        .chain(&listens[start..end])
+        .map(Listen::release_query)
+        .collect();
+    let mut releases = resolve_releases(state, queries).await.into_iter();
+
+    let embeds = summary
+        .into_iter()
+        .chain(
+            playing_now
+                .map(|listen| listen_embed(listen, true, releases.next().flatten(), &config)),
+        )
+        .chain(
+            listens
+                .drain(start..end)
+                .map(|listen| listen_embed(listen, false, releases.next().flatten(), &config)),
+        )
         .collect::<Vec<_>>();
 
+    let button = |label: &str, before: Option<u64>, disabled: bool| {
+        let component = ComponentId::Listenbrainz {
+            time,
+            user: Cow::Borrowed(user),
+            before,
+            ended_at,
+        };
+        serde_json::to_string(&component)
+            .wrap_err("Failed to serialize component ID")
+            .map(|id| CreateButton::new(id).label(label).disabled(disabled))
+    };
+
+    let mut buttons = Vec::new();
+    if total > PAGE_SIZE {
+        // Disabled buttons still need unique IDs
+        buttons.push(button("Older", Some(older.unwrap_or(0)), older.is_none())?);
+        buttons.push(button("Newer", newer, end == total)?);
+    }
+    if ended_at.is_none() {
+        let component = ComponentId::EndRide {
+            time,
+            user: Cow::Borrowed(user),
+        };
+        buttons.push(
+            CreateButton::new(
+                serde_json::to_string(&component).wrap_err("Failed to serialize component ID")?,
+            )
+            .style(ButtonStyle::Danger)
+            .label("End ride"),
+        );
+    }
+    let components = if buttons.is_empty() {
+        Vec::new()
+    } else {
+        vec![CreateActionRow::Buttons(buttons)]
+    };
+
     Ok(CreateInteractionResponseMessage::new()
+        .content(content)
         .embeds(embeds)
-        .components(Vec::new()))
+        .components(components))
 }
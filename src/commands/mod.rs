@@ -1,5 +1,233 @@
+pub mod alias;
+pub mod compare;
 pub mod convert_link;
+pub mod details;
+pub mod feature;
+pub mod history;
 pub mod inject;
+pub mod interested;
+pub mod jobs;
+pub mod lifecycle;
 pub mod listenbrainz;
+pub mod nowplaying;
 pub mod ping;
+pub mod records;
+pub mod search;
+pub mod stale_component;
+pub mod stats;
+pub mod status;
 pub mod suggest;
+pub mod trails;
+pub mod vote;
+
+use std::collections::HashSet;
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use serde_json::Value;
+use serenity::all::{
+    AuthorizingIntegrationOwner, Command, CommandInteraction, CreateCommand, InstallationContext,
+    InteractionContext,
+};
+use tracing::{info, instrument};
+
+use crate::{
+    audit::{self, Actor, Mutation},
+    AppState, Config,
+};
+
+pub fn check_admin(command: &CommandInteraction, config: &Config) -> eyre::Result<()> {
+    let member = command
+        .member
+        .as_ref()
+        .ok_or_eyre("Command was executed outside of a guild")?;
+    if member
+        .roles
+        .iter()
+        .any(|role| config.admin_roles.contains(role))
+    {
+        Ok(())
+    } else {
+        Err(eyre!("You do not have any admin role"))
+    }
+}
+
+/// Whether the command was used in the configured guild through the bot's
+/// guild install, rather than through a user install somewhere else
+pub fn in_home_guild(command: &CommandInteraction, config: &Config) -> bool {
+    command.guild_id == Some(config.guild_id)
+        && command
+            .authorizing_integration_owners
+            .0
+            .iter()
+            .any(|owner| matches!(owner, AuthorizingIntegrationOwner::GuildInstall(Some(_))))
+}
+
+/// Lets members install `command` to their account and use it in DMs and
+/// other servers
+fn user_installable(command: CreateCommand) -> CreateCommand {
+    command
+        .integration_types(vec![InstallationContext::Guild, InstallationContext::User])
+        .contexts(vec![
+            InteractionContext::Guild,
+            InteractionContext::BotDm,
+            InteractionContext::PrivateChannel,
+        ])
+}
+
+fn global_commands() -> Vec<CreateCommand> {
+    vec![
+        ping::create_command(),
+        suggest::create_command(),
+        inject::create_command(),
+        inject::create_slash_command(),
+        user_installable(listenbrainz::create_command()),
+        nowplaying::create_command(),
+        user_installable(convert_link::create_command()),
+        interested::create_command(),
+        history::create_command(),
+        trails::create_command(),
+        search::create_command(),
+        alias::create_command(),
+        compare::create_command(),
+        records::create_command(),
+        stats::create_command(),
+        vote::create_command(),
+        feature::create_command(),
+        jobs::create_command(),
+        status::create_command(),
+    ]
+}
+
+fn command_name(command: &CreateCommand) -> eyre::Result<String> {
+    serde_json::to_value(command)
+        .wrap_err("Failed to serialize command")?
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_eyre("Command has no name")
+}
+
+/// [`global_commands`], prefixed if `test_guild` is configured
+fn desired_commands(config: &Config) -> eyre::Result<Vec<CreateCommand>> {
+    let Some(test_guild) = &config.test_guild else {
+        return Ok(global_commands());
+    };
+    global_commands()
+        .into_iter()
+        .map(|command| {
+            let name = command_name(&command)?;
+            Ok(command.name(format!("{}{}", test_guild.command_prefix, name)))
+        })
+        .collect()
+}
+
+/// Names of the commands this instance registers
+pub fn desired_command_names(config: &Config) -> eyre::Result<HashSet<String>> {
+    desired_commands(config)?.iter().map(command_name).collect()
+}
+
+/// Registers the global commands, or the prefixed test guild commands if
+/// `test_guild` is configured, only calling out to Discord to overwrite them
+/// if the registered commands differ from [`global_commands`]
+#[instrument(skip_all)]
+pub async fn sync_commands(state: &AppState) -> eyre::Result<()> {
+    let http = state.http.load();
+    let http = http.as_ref();
+    let config = state.config.load();
+    let desired = desired_commands(&config)?;
+    let registered = match &config.test_guild {
+        Some(test_guild) => test_guild.guild_id.get_commands(http).await,
+        None => Command::get_global_commands(http).await,
+    }
+    .wrap_err("Failed to get registered commands from Discord")?;
+
+    let desired_json = desired
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("Failed to serialize commands")?;
+    let registered_json = registered
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("Failed to serialize registered commands")?;
+
+    let up_to_date = desired_json.len() == registered_json.len()
+        && desired_json.iter().all(|command| {
+            registered_json
+                .iter()
+                .find(|r| r.get("name") == command.get("name"))
+                .is_some_and(|r| json_matches(command, r))
+        });
+
+    if up_to_date {
+        info!("Registered commands are up to date");
+        return Ok(());
+    }
+
+    let summary = format!("{} commands", desired.len());
+    match &config.test_guild {
+        Some(test_guild) => {
+            audit::audited(
+                &state.audit,
+                Mutation::SetCommands,
+                Actor::Bot,
+                format!("guild {}", test_guild.guild_id),
+                &summary,
+                test_guild.guild_id.set_commands(http, desired),
+            )
+            .await
+        }
+        None => {
+            audit::audited(
+                &state.audit,
+                Mutation::SetCommands,
+                Actor::Bot,
+                "global commands",
+                &summary,
+                Command::set_global_commands(http, desired),
+            )
+            .await
+        }
+    }
+    .wrap_err("Failed to set commands on Discord")?;
+    info!("Updated registered commands");
+    Ok(())
+}
+
+/// Whether every field set in `desired` has the same value in `registered`.
+/// Discord leaves out empty fields, so empty values match missing ones.
+fn json_matches(desired: &Value, registered: &Value) -> bool {
+    fn is_empty(value: &Value) -> bool {
+        match value {
+            Value::Null | Value::Bool(false) => true,
+            Value::String(s) => s.is_empty(),
+            Value::Array(array) => array.is_empty(),
+            Value::Object(map) => map.is_empty(),
+            Value::Bool(true) | Value::Number(_) => false,
+        }
+    }
+
+    match (desired, registered) {
+        (Value::Object(desired), Value::Object(registered)) => {
+            desired.iter().all(|(key, value)| {
+                registered
+                    .get(key)
+                    .map_or(is_empty(value), |r| json_matches(value, r))
+            })
+        }
+        (Value::Array(desired), Value::Array(registered)) => {
+            desired.len() == registered.len()
+                && desired
+                    .iter()
+                    .zip(registered)
+                    .all(|(desired, registered)| json_matches(desired, registered))
+        }
+        (Value::Number(desired), Value::Number(registered)) => {
+            desired.as_f64() == registered.as_f64()
+        }
+        (desired, registered) => {
+            desired == registered || (is_empty(desired) && is_empty(registered))
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponseFollowup,
+    ResolvedOption, ResolvedValue,
+};
+use tracing::instrument;
+
+use crate::AppState;
+
+use super::listenbrainz;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("nowplaying")
+        .description("Show what a ListenBrainz user is listening to")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "user",
+            "A username on ListenBrainz, defaults to the car's",
+        ))
+}
+
+#[derive(Debug)]
+pub struct NowplayingCommand {
+    user: Option<String>,
+}
+
+impl NowplayingCommand {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption]) -> eyre::Result<Self> {
+        match options.first() {
+            None => Ok(Self { user: None }),
+            Some(ResolvedOption {
+                name: "user",
+                value: ResolvedValue::String(user),
+                ..
+            }) => Ok(Self {
+                user: Some((*user).to_owned()),
+            }),
+            _ => Err(eyre!("Option passed was not the right type")),
+        }
+    }
+
+    #[instrument(skip(state))]
+    pub async fn respond(
+        &self,
+        state: &AppState,
+    ) -> eyre::Result<CreateInteractionResponseFollowup> {
+        let config = state.config.load();
+        let user = self
+            .user
+            .as_deref()
+            .or(config.default_listenbrainz_user.as_deref())
+            .ok_or_eyre("No user was given and no `default_listenbrainz_user` is configured")?;
+
+        let embed = listenbrainz::latest_embed(state, user)
+            .await
+            .wrap_err_with(|| format!("Failed to get the latest listen of `{}`", user))?;
+
+        Ok(match embed {
+            Some(embed) => CreateInteractionResponseFollowup::new().embed(embed),
+            None => CreateInteractionResponseFollowup::new()
+                .content(format!("`{}` hasn't listened to anything yet", user)),
+        })
+    }
+}
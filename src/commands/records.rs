@@ -0,0 +1,168 @@
+use color_eyre::eyre::{self, eyre, Context};
+use serenity::all::{
+    CommandOptionType, CreateAttachment, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use time::{Date, Month, OffsetDateTime};
+use tracing::instrument;
+
+use crate::{sanitize, store::Suggestion, units::length_to_meters, AppState};
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("records")
+        .description("Export a season's hikes as a table")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "format", "Format of the table")
+                .add_string_choice("CSV", "csv")
+                .add_string_choice("Org mode", "org"),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "year",
+                "Season to export, this year if left out",
+            )
+            .min_int_value(2000)
+            .max_int_value(9999),
+        )
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Csv,
+    Org,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "org" => Some(Format::Org),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Org => "org",
+        }
+    }
+
+    fn cell(self, value: &str) -> String {
+        match self {
+            Format::Csv if value.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            }
+            Format::Csv => value.to_owned(),
+            Format::Org => value.replace('|', "\\vert{}").replace(['\n', '\r'], " "),
+        }
+    }
+
+    fn row(self, cells: &[String]) -> String {
+        let cells = cells.iter().map(|cell| self.cell(cell)).collect::<Vec<_>>();
+        match self {
+            Format::Csv => format!("{}\n", cells.join(",")),
+            Format::Org => format!("| {} |\n", cells.join(" | ")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RecordsCommand {
+    format: Format,
+    year: Option<i32>,
+}
+
+impl RecordsCommand {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption<'_>]) -> eyre::Result<Self> {
+        let mut format = Format::default();
+        let mut year = None;
+        for option in options {
+            match (option.name, &option.value) {
+                ("format", ResolvedValue::String(name)) => {
+                    format = Format::from_name(name)
+                        .ok_or_else(|| eyre!("`format` must be `csv` or `org`, not `{}`", name))?
+                }
+                ("year", ResolvedValue::Integer(value)) => {
+                    year = Some(i32::try_from(*value).map_err(|_| eyre!("`year` is out of range"))?)
+                }
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+        Ok(Self { format, year })
+    }
+
+    /// The season's hikes as a file, with lengths in the server's units so
+    /// every export of the spreadsheet lines up
+    #[instrument(skip(state))]
+    pub fn respond(&self, state: &AppState) -> eyre::Result<CreateInteractionResponse> {
+        let year = self
+            .year
+            .unwrap_or_else(|| OffsetDateTime::now_utc().year());
+        let start_of = |year| {
+            Date::from_calendar_date(year, Month::January, 1)
+                .map(|date| date.midnight().assume_utc().unix_timestamp().max(0) as u64)
+                .wrap_err("Failed to find start of season")
+        };
+        let hikes = state
+            .store
+            .hikes_between(start_of(year)?, start_of(year + 1)?)
+            .wrap_err("Failed to load hikes")?;
+
+        let config = state.config.load();
+        let lengths = config.lengths();
+        let long = length_to_meters(1.0, lengths.long_units)?;
+        let short = length_to_meters(1.0, lengths.short_units)?;
+
+        let mut table = self.format.row(&[
+            String::from("Date"),
+            String::from("Trail"),
+            String::from("Link"),
+            String::from("Difficulty"),
+            format!("Length ({})", lengths.long_units.abbreviation()),
+            format!("Gain ({})", lengths.short_units.abbreviation()),
+            String::from("Interested"),
+        ]);
+        if self.format == Format::Org {
+            table.push_str("|-\n");
+        }
+        for (hike, interested) in &hikes {
+            table.push_str(&self.format.row(&row(hike, *interested, long, short)));
+        }
+
+        Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(format!("{} hikes in {}", hikes.len(), year))
+                .add_file(CreateAttachment::bytes(
+                    table,
+                    format!("hikes-{}.{}", year, self.format.extension()),
+                )),
+        ))
+    }
+}
+
+fn row(hike: &Suggestion, interested: u64, long: f64, short: f64) -> Vec<String> {
+    let date = hike
+        .hiked_at
+        .and_then(|hiked_at| OffsetDateTime::from_unix_timestamp(hiked_at as i64).ok())
+        .map(|time| time.date().to_string())
+        .unwrap_or_default();
+    vec![
+        date,
+        // A file rather than a message, so there's no markdown to escape
+        sanitize::budget(&hike.title, sanitize::TITLE_LENGTH, None),
+        hike.link.clone(),
+        hike.difficulty.clone().unwrap_or_default(),
+        hike.length
+            .map(|length| format!("{:.1}", length / long))
+            .unwrap_or_default(),
+        hike.gain
+            .map(|gain| format!("{:.0}", gain / short))
+            .unwrap_or_default(),
+        interested.to_string(),
+    ]
+}
@@ -0,0 +1,116 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedOption, ResolvedValue, UserId,
+};
+use tracing::instrument;
+
+use crate::AppState;
+
+use super::trails::suggestion_embed;
+
+/// Discord caps messages at 10 embeds
+const MAX_RESULTS: usize = 10;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("search")
+        .description("Search past trail suggestions")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "query",
+                "Words to look for in trail names and descriptions",
+            )
+            .required(true)
+            .max_length(100),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "beginner_friendly",
+                "Only show trails at least this friendly to kids and beginners, from 1 to 5",
+            )
+            .min_int_value(1)
+            .max_int_value(5),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "accessible",
+            "Only show trails that can be done in a wheelchair or with a stroller",
+        ))
+}
+
+#[derive(Debug)]
+pub struct SearchCommand<'a> {
+    query: &'a str,
+    min_beginner_score: Option<u8>,
+    accessible: Option<bool>,
+}
+
+impl<'a> SearchCommand<'a> {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption<'a>]) -> eyre::Result<Self> {
+        let mut query = None;
+        let mut min_beginner_score = None;
+        let mut accessible = None;
+        for option in options {
+            match (option.name, &option.value) {
+                ("query", ResolvedValue::String(value)) => query = Some(*value),
+                ("beginner_friendly", ResolvedValue::Integer(score)) => {
+                    min_beginner_score = Some(
+                        u8::try_from(*score)
+                            .ok()
+                            .filter(|score| (1..=5).contains(score))
+                            .ok_or_else(|| eyre!("`beginner_friendly` must be from 1 to 5"))?,
+                    )
+                }
+                ("accessible", ResolvedValue::Boolean(value)) => accessible = Some(*value),
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+        Ok(Self {
+            query: query.ok_or_eyre("Expected a `query` option")?,
+            min_beginner_score,
+            accessible,
+        })
+    }
+
+    #[instrument(skip(state))]
+    pub fn respond(
+        &self,
+        state: &AppState,
+        user: UserId,
+    ) -> eyre::Result<CreateInteractionResponse> {
+        let config = state.config.load();
+        let preferences = state.preferences(user)?;
+        let now = get_current_timestamp();
+        let suggestions = state
+            .store
+            .search_suggestions(
+                self.query,
+                self.min_beginner_score,
+                self.accessible,
+                MAX_RESULTS,
+            )
+            .wrap_err("Failed to search suggestions")?;
+
+        if suggestions.is_empty() {
+            return Ok(CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("No trail suggestions match `{}`", self.query)),
+            ));
+        }
+
+        let embeds = suggestions
+            .into_iter()
+            .map(|suggestion| suggestion_embed(suggestion, now, config.lengths_for(&preferences)))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!("Trail suggestions matching `{}`", self.query))
+                .embeds(embeds),
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+use std::{ops::Deref, sync::Arc};
+
+use color_eyre::eyre::{self, Context, OptionExt};
+use serenity::all::{
+    ActionRowComponent, ButtonKind, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage, EditMessage,
+};
+use tracing::{error, instrument, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    AppState,
+};
+
+/// Responds to a component whose custom_id isn't a `ComponentId`, which
+/// happens with buttons from older versions of the bot, and disables it
+#[instrument(skip_all)]
+pub fn respond(
+    component: &ComponentInteraction,
+    state: &Arc<AppState>,
+) -> CreateInteractionResponse {
+    warn!(
+        custom_id = component.data.custom_id,
+        "Received a component with an unknown custom_id"
+    );
+
+    let component = component.clone();
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        if let Err(e) = disable(&component, &state).await {
+            error!("{:?}", e);
+        }
+    });
+
+    CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content("This button is from an older version of the bot and no longer works"),
+    )
+}
+
+/// Edits the message to disable the stale button. Rows with anything other
+/// than buttons can't be rebuilt, so those messages are left alone.
+async fn disable(component: &ComponentInteraction, state: &AppState) -> eyre::Result<()> {
+    let mut rows = Vec::new();
+    for row in &component.message.components {
+        let buttons = row
+            .components
+            .iter()
+            .map(|row_component| match row_component {
+                ActionRowComponent::Button(button) => {
+                    let stale = matches!(
+                        &button.data,
+                        ButtonKind::NonLink { custom_id, .. }
+                            if *custom_id == component.data.custom_id
+                    );
+                    Some(CreateButton::from(button.clone()).disabled(button.disabled || stale))
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_eyre("Message with a stale component has components other than buttons")?;
+        rows.push(CreateActionRow::Buttons(buttons));
+    }
+
+    let edit = EditMessage::new().components(rows);
+    let mut message = component.message.deref().clone();
+    audited(
+        &state.audit,
+        Mutation::EditMessage,
+        Actor::User(component.user.id),
+        format!("message {} in channel {}", message.id, message.channel_id),
+        &summarize(&edit),
+        message.edit(state.http.load().deref(), edit),
+    )
+    .await
+    .wrap_err("Failed to disable stale component")
+}
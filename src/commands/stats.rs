@@ -0,0 +1,146 @@
+use color_eyre::eyre::{self, eyre, Context};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{
+    Color, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage, Mentionable, ResolvedOption,
+    ResolvedValue, UserId,
+};
+use time::{Date, Month, OffsetDateTime};
+use tracing::instrument;
+
+use crate::AppState;
+
+/// How many members the leaderboard lists
+const LEADERBOARD_LENGTH: usize = 10;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("stats")
+        .description("Show how far the group has hiked")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "period",
+                "Only count hikes from this month or year",
+            )
+            .add_string_choice("This month", "month")
+            .add_string_choice("This year", "year")
+            .add_string_choice("All time", "all"),
+        )
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Period {
+    Month,
+    Year,
+    #[default]
+    All,
+}
+
+impl Period {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "month" => Some(Period::Month),
+            "year" => Some(Period::Year),
+            "all" => Some(Period::All),
+            _ => None,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Period::Month => "Hiking stats this month",
+            Period::Year => "Hiking stats this year",
+            Period::All => "Hiking stats",
+        }
+    }
+
+    /// Unix timestamp the period starts at
+    fn start(self) -> eyre::Result<u64> {
+        let today = OffsetDateTime::now_utc().date();
+        let start = match self {
+            Period::Month => Date::from_calendar_date(today.year(), today.month(), 1),
+            Period::Year => Date::from_calendar_date(today.year(), Month::January, 1),
+            Period::All => return Ok(0),
+        }
+        .wrap_err("Failed to find start of period")?;
+        Ok(start.midnight().assume_utc().unix_timestamp().max(0) as u64)
+    }
+}
+
+#[derive(Debug)]
+pub struct StatsCommand {
+    period: Period,
+}
+
+impl StatsCommand {
+    #[instrument]
+    pub fn from_options(options: &[ResolvedOption<'_>]) -> eyre::Result<Self> {
+        let mut period = Period::default();
+        for option in options {
+            match (option.name, &option.value) {
+                ("period", ResolvedValue::String(name)) => {
+                    period = Period::from_name(name).ok_or_else(|| {
+                        eyre!("`period` must be `month`, `year`, or `all`, not `{}`", name)
+                    })?
+                }
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+        Ok(Self { period })
+    }
+
+    /// Totals of the hikes that have happened in the period, in the units
+    /// `user` prefers. There's no record of who actually showed up to a hike,
+    /// so the leaderboard goes by who was interested in it.
+    #[instrument(skip(state))]
+    pub fn respond(
+        &self,
+        state: &AppState,
+        user: UserId,
+    ) -> eyre::Result<CreateInteractionResponse> {
+        let from = self.period.start()?;
+        let until = get_current_timestamp();
+        let (hikes, length, gain) = state
+            .store
+            .hike_totals(from, until)
+            .wrap_err("Failed to total hikes")?;
+        let leaderboard = state
+            .store
+            .interested_leaderboard(from, until, LEADERBOARD_LENGTH)
+            .wrap_err("Failed to load leaderboard")?;
+
+        let config = state.config.load();
+        let lengths = config.lengths_for(&state.preferences(user)?);
+        let mut embed = CreateEmbed::new()
+            .color(Color::DARK_GREEN)
+            .title(self.period.title())
+            .field("Hikes", hikes.to_string(), true)
+            .field("Distance", lengths.long(length)?, true)
+            .field("Elevation gain", lengths.short(gain)?, true);
+
+        if !leaderboard.is_empty() {
+            embed = embed.field(
+                "Most hikes joined",
+                leaderboard
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (member, hikes))| {
+                        format!(
+                            "{}. {} - {} hike{}",
+                            i + 1,
+                            member.mention(),
+                            hikes,
+                            if *hikes == 1 { "" } else { "s" }
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                false,
+            );
+        }
+
+        Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new().embed(embed),
+        ))
+    }
+}
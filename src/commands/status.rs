@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use serenity::all::{Color, CreateCommand, CreateEmbed, CreateInteractionResponseFollowup};
+use tracing::instrument;
+
+use crate::{health, AppState};
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("status").description("Check the health of the bot's integrations")
+}
+
+#[instrument(skip_all)]
+pub async fn respond(state: Arc<AppState>) -> eyre::Result<CreateInteractionResponseFollowup> {
+    let probes = health::probes(&state).await;
+    let healthy = probes.iter().all(|probe| probe.result.is_ok());
+
+    let mut embed = CreateEmbed::new()
+        .color(if healthy {
+            Color::DARK_GREEN
+        } else {
+            Color::RED
+        })
+        .title("Integration status");
+    for probe in probes {
+        let value = match probe.result {
+            Ok(latency) => format!("✅ {}ms", latency.as_millis()),
+            Err(e) => format!("❌ {:#}", e).chars().take(1024).collect(),
+        };
+        embed = embed.field(probe.name, value, true);
+    }
+
+    let calls = state
+        .audit
+        .counts()
+        .filter(|(_, made, _)| *made > 0)
+        .map(|(mutation, made, failed)| match failed {
+            0 => format!("`{}`: {}", mutation.name(), made),
+            failed => format!("`{}`: {} ({} failed)", mutation.name(), made, failed),
+        })
+        .collect::<Vec<_>>();
+    embed = embed.field(
+        "Discord calls since startup",
+        if calls.is_empty() {
+            String::from("None")
+        } else {
+            calls.join("\n")
+        },
+        false,
+    );
+
+    Ok(CreateInteractionResponseFollowup::new()
+        .ephemeral(true)
+        .embed(embed))
+}
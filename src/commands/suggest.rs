@@ -1,25 +1,36 @@
-use std::{borrow::Cow, ops::Deref, sync::Arc};
+use std::{borrow::Cow, io::Cursor, ops::Deref};
 
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
-use geo::{Contains, Distance, Haversine, Length, Line, Point};
+use geo::{BoundingRect, Contains, Distance, Haversine, Length, Point};
+use hikea::trail_stats;
 use serenity::{
     all::{
-        Color, CommandInteraction, CommandOptionType, CreateButton, CreateCommandOption,
-        CreateEmbed, CreateEmbedAuthor, EditMessage, ResolvedOption, ResolvedValue,
+        Attachment, Color, CommandInteraction, CommandOptionType, CreateButton,
+        CreateCommandOption, CreateEmbed, CreateEmbedAuthor, EditMessage, Message, ResolvedOption,
+        ResolvedValue,
     },
     builder::CreateCommand,
 };
-use tracing::instrument;
+use tracing::{error, instrument};
 use uom::{
     fmt::DisplayStyle,
-    si::{
-        length::{meter, Units},
-        time::hour,
-        velocity::mile_per_hour,
-    },
+    si::{length::meter, time::hour, velocity::mile_per_hour},
 };
 
-use crate::{web_interface::upload_gpx::UploadForm, AppState};
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    difficulty::Difficulty,
+    failure::Failure,
+    geocode::Place,
+    osrm::Drive,
+    pace::{self, Activity},
+    providers::{self, Provider},
+    route_type::RouteType,
+    sanitize::{self, FIELD_LENGTH},
+    units::Lengths,
+    web_interface::upload_gpx::{fill_suggestion, UploadForm},
+    AppState, Config, Region,
+};
 
 pub fn create_command() -> CreateCommand {
     CreateCommand::new("suggest")
@@ -28,95 +39,104 @@ pub fn create_command() -> CreateCommand {
             CreateCommandOption::new(
                 CommandOptionType::String,
                 "alltrails_link",
-                "Post a link to an AllTrails hike in Utah",
+                "Post a link to an AllTrails hike",
             )
             .required(true),
         )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Attachment,
+            "gpx_file",
+            "AllTrails GPX export of the hike, admins can fill the suggestion in right away",
+        ))
 }
 
-struct ElevationPoint {
-    point: Point,
-    distance: f64,
-    elevation: f64,
-    extremum: bool,
-    survived: bool,
+/// Stats computed from a GPX file, all lengths are in meters
+#[derive(Debug, Clone, Copy)]
+pub struct TrailStats {
+    pub length: f64,
+    pub gains: f64,
+    /// Grade of the steepest section, as rise over run
+    pub steepest: f64,
 }
 
 #[derive(Debug)]
 pub struct SuggestionCommand<'a> {
     pub suggestion_link: Cow<'a, str>,
+    pub gpx_file: Option<Attachment>,
 }
 
 impl<'a> SuggestionCommand<'a> {
     #[instrument]
     pub fn from_options(options: &[ResolvedOption<'a>]) -> Result<Self, eyre::Report> {
-        match options.get(0).ok_or_eyre("No arguments were passed")? {
-            ResolvedOption {
-                value: ResolvedValue::String(suggestion_link),
-                ..
-            } => Ok(SuggestionCommand {
-                suggestion_link: Cow::Borrowed(suggestion_link),
-            }),
-            _ => Err(eyre!("Option passed was not the right type")),
+        let mut suggestion_link = None;
+        let mut gpx_file = None;
+        for option in options {
+            match (option.name, &option.value) {
+                ("alltrails_link", ResolvedValue::String(link)) => {
+                    suggestion_link = Some(Cow::Borrowed(*link))
+                }
+                ("gpx_file", ResolvedValue::Attachment(attachment)) => {
+                    gpx_file = Some((*attachment).clone())
+                }
+                _ => return Err(eyre!("Option passed was not the right type")),
+            }
         }
+        Ok(SuggestionCommand {
+            suggestion_link: suggestion_link.ok_or_eyre("No AllTrails link was passed")?,
+            gpx_file,
+        })
     }
 
+    /// The suggestion embed, and the buttons to add to it once it's posted
     #[instrument(skip(command, state))]
     pub async fn respond(
         mut self,
         command: &CommandInteraction,
-        state: Arc<AppState>,
+        state: &AppState,
         author: String,
-    ) -> Result<CreateEmbed, eyre::Report> {
-        if !self
-            .suggestion_link
-            .starts_with("https://www.alltrails.com")
-        {
-            return Err(eyre!(
-                "Trail suggestion was not from <https://www.alltrails.com>"
-            ));
-        }
+    ) -> Result<(CreateEmbed, Option<UploadButtons>), eyre::Report> {
+        let config = state.config.load();
+        let (provider, link) = providers::canonical_link(&self.suggestion_link, &config).await?;
+        self.suggestion_link = Cow::Owned(link);
 
-        if self
-            .suggestion_link
-            .starts_with("https://www.alltrails.com/explore/")
+        // Only AllTrails links say where the trail is, trails from elsewhere
+        // are checked once their GPX file is uploaded
+        if provider == Provider::AllTrails
+            && !config
+                .allowed_regions
+                .iter()
+                .any(|region| self.suggestion_link.starts_with(&region.alltrails_prefix))
         {
-            self.suggestion_link = Cow::Owned(format!(
-                "https://www.alltrails.com/{}",
-                &self.suggestion_link["https://www.alltrails.com/explore/".len()..]
+            return Err(Failure::OutsideRegions(format!(
+                "Trail suggestion is not in any of the allowed regions: {}",
+                region_names(&config.allowed_regions)
             ))
+            .into());
         }
 
-        if !self
-            .suggestion_link
-            .starts_with("https://www.alltrails.com/trail/us/utah")
-        {
-            return Err(eyre!("Trail suggestion is not in Utah"));
+        if self.gpx_file.is_some() {
+            if !super::in_home_guild(command, &config) {
+                return Err(eyre!("GPX files can only be attached in the home server"));
+            }
+            super::check_admin(command, &config)
+                .wrap_err("Only admins can fill a suggestion in with a GPX file")?;
         }
 
-        let interaction = command.clone();
-
-        tokio::spawn(async move {
-            let http = state.http.load();
-            let mut response = interaction.get_response(http.deref()).await.unwrap();
-            response
-                .edit(
-                    http.deref(),
-                    EditMessage::new().button(
-                        CreateButton::new_link(format!(
-                            "{}/hikea/upload_gpx/{}/{}",
-                            state.config.load().hostname,
-                            response.channel_id.get(),
-                            response.id.get()
-                        ))
-                        .label("Upload AllTrails data for Trail"),
-                    ),
-                )
-                .await
-                .unwrap();
-        });
+        // Trail details can only be filled in from the home guild
+        let upload_buttons = if super::in_home_guild(command, &config) {
+            Some(UploadButtons {
+                provider,
+                link: self.suggestion_link.clone().into_owned(),
+                gpx_file: self.gpx_file.take(),
+                filled_by: author.clone(),
+                // The GPX file for filled in details is replied over the gateway
+                details_button: config.gateway.then(super::details::button).transpose()?,
+            })
+        } else {
+            None
+        };
 
-        Ok(CreateEmbed::new()
+        let embed = CreateEmbed::new()
             .color(Color::DARK_GREEN)
             .title("Trail suggestion!")
             .author(CreateEmbedAuthor::new(author))
@@ -125,64 +145,138 @@ impl<'a> SuggestionCommand<'a> {
                         An admin will take your suggestion and \
                         fill it in with trail information shortly",
             )
-            .url(self.suggestion_link))
+            .url(self.suggestion_link);
+        Ok((embed, upload_buttons))
+    }
+}
+
+/// Buttons for filling a suggestion in, which can only be added once the
+/// suggestion has been posted
+#[derive(Debug)]
+pub struct UploadButtons {
+    provider: Provider,
+    link: String,
+    gpx_file: Option<Attachment>,
+    filled_by: String,
+    details_button: Option<CreateButton>,
+}
+
+impl UploadButtons {
+    /// Adds the buttons to the response to `interaction`, or fills the
+    /// suggestion in right away if a GPX file was attached
+    #[instrument(skip_all)]
+    pub async fn attach(
+        self,
+        state: &AppState,
+        interaction: &CommandInteraction,
+    ) -> eyre::Result<()> {
+        let http = state.http.load();
+        let mut response = interaction
+            .get_response(http.deref())
+            .await
+            .wrap_err("Failed to get the suggestion's response")?;
+        if let Some(gpx_file) = self.gpx_file {
+            // Falls back to the upload buttons
+            match fill_from_attachment(state, &response, &self.link, &gpx_file, &self.filled_by)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) => error!("{:?}", e),
+            }
+        }
+
+        let mut edit = EditMessage::new().button(
+            CreateButton::new_link(format!(
+                "{}/hikea/upload_gpx/{}/{}",
+                state.config.load().hostname,
+                response.channel_id.get(),
+                response.id.get()
+            ))
+            .label(format!("Upload {} data for Trail", self.provider.name())),
+        );
+        if let Some(details_button) = self.details_button {
+            edit = edit.button(details_button);
+        }
+        let target = format!("message {} in channel {}", response.id, response.channel_id);
+        audited(
+            &state.audit,
+            Mutation::EditMessage,
+            Actor::User(interaction.user.id),
+            target,
+            &summarize(&edit),
+            response.edit(http.deref(), edit),
+        )
+        .await
+        .wrap_err("Failed to add upload buttons to the suggestion")?;
+        if let Err(e) = state
+            .store
+            .insert_upload_button(
+                response.id,
+                response.channel_id,
+                jsonwebtoken::get_current_timestamp(),
+            )
+            .wrap_err("Failed to track upload button")
+        {
+            error!("{:?}", e);
+        }
+        Ok(())
     }
 }
 
 #[instrument(skip_all)]
 pub fn embed_from_gpx(
     link: &str,
-    short_units: Units,
-    long_units: Units,
-    avg_speed: f64,
+    trail_page: &str,
+    config: &Config,
     form: UploadForm,
-) -> eyre::Result<CreateEmbed> {
-    let utah_rect = geo::Rect::new(
-        geo::coord! { x: -114.093, y: 42.017 },
-        geo::coord! { x: -108.995, y: 36.933 },
-    );
-
-    let metadata = form
-        .gpx_file
-        .metadata
-        .ok_or_eyre("GPX File has no metadata")?;
-
-    if metadata
-        .links
-        .get(0)
-        .ok_or_eyre("GPX File metadata has no links")?
-        .href
-        != "http://www.alltrails.com"
-    {
-        return Err(eyre!("GPX File did not originate from AllTrails"));
-    }
-
-    if !utah_rect.contains(
-        &metadata
-            .bounds
-            .ok_or_eyre("GPX file did not have boundry metadata")?,
-    ) {
-        return Err(eyre!("Uploaded GPX trail is not in Utah"));
+    drive: Option<Drive>,
+    trailhead_area: Option<Place>,
+) -> eyre::Result<(CreateEmbed, TrailStats)> {
+    let provider = Provider::of(link).ok_or_else(|| {
+        Failure::NotATrail(format!("Suggestion is not from any of {}", providers::names()).into())
+    })?;
+    let lengths = config.lengths();
+    let pace = config.pace();
+    let regions = &config.allowed_regions;
+    if !provider.exported(&form.gpx_file) {
+        return Err(Failure::NotATrail(
+            format!("GPX File did not originate from {}", provider.name()).into(),
+        )
+        .into());
     }
 
     let track = form
         .gpx_file
         .tracks
-        .get(0)
+        .first()
         .ok_or_eyre("GPX file contained no tracks")?;
 
+    // Only AllTrails is known to always export the bounds
+    let bounds = match form.gpx_file.metadata.as_ref().and_then(|m| m.bounds) {
+        Some(bounds) => bounds,
+        None => track
+            .multilinestring()
+            .bounding_rect()
+            .ok_or_eyre("GPX track has no points")?,
+    };
+
+    if !regions.iter().any(|region| region.rect().contains(&bounds)) {
+        return Err(Failure::OutsideRegions(format!(
+            "Uploaded GPX trail is not in any of the allowed regions: {}",
+            region_names(regions)
+        ))
+        .into());
+    }
+
     let line_string = track.multilinestring();
     let length = line_string.length::<Haversine>();
-    let mut gains = 0.0;
-    let mut losses = 0.0;
+    let route_type = RouteType::from_track(&line_string);
     let mut max_altitude = 0.0;
     let mut min_altitude = f64::MAX;
     let mut avg = (0.0, 0);
     for segment in &track.segments {
         for point in segment.points.iter() {
-            let elevation = point
-                .elevation
-                .ok_or_eyre("Waypoint does not contain elevation data")?;
+            let elevation = point.elevation.ok_or(Failure::MissingElevation)?;
             avg.0 += elevation;
             avg.1 += 1;
             if max_altitude < elevation {
@@ -193,439 +287,320 @@ pub fn embed_from_gpx(
             }
         }
     }
-    let elevation_points = vec![ElevationPoint {
-        distance: 0.0,
-        elevation: track
-            .segments
-            .get(0)
-            .ok_or_eyre("GPX track has no segments")?
-            .points
-            .get(0)
-            .ok_or_eyre("GPX segment has no points")?
-            .elevation
-            .ok_or_eyre("Waypoint does not have elevation data")?,
-        extremum: true,
-        survived: false,
-        point: track
-            .segments
-            .get(0)
-            .ok_or_eyre("GPX track has no segments")?
-            .points
-            .get(0)
-            .ok_or_eyre("GPX segment has no points")?
-            .point(),
-    }];
-    let mut elevation_points = track
+    let trailhead = track
         .segments
-        .iter()
-        .flat_map(|s| s.points.windows(2))
-        .try_fold(
-            (elevation_points, 0.0),
-            |(mut points, mut distance), point| {
-                distance += Haversine::distance(point[0].point(), point[1].point());
-                points.push(ElevationPoint {
-                    distance,
-                    elevation: point[1]
-                        .elevation
-                        .ok_or_eyre("Waypoint does not have elevation data")?,
-                    extremum: false,
-                    survived: false,
-                    point: point[1].point(),
-                });
-                Ok::<_, eyre::Report>((points, distance))
-            },
-        )?
-        .0;
-    approximate_elevation_points(&mut elevation_points)
-        .wrap_err("Failed to approximate elevation points")?;
-    elevation_points
-        .last_mut()
-        .ok_or_eyre("Finding elevation points yeilded no results")?
-        .extremum = true;
-    elevation_points
-        .first_mut()
-        .ok_or_eyre("Finding elevation points yeilded no results")?
-        .extremum = true;
-    find_maximum_extremum_between(0, elevation_points.len() - 1, &mut elevation_points)
-        .wrap_err("Failed to find maximum extremum of elevation points")?;
-    let mut prev_elevation_point = elevation_points
         .first()
-        .ok_or_eyre("Finding elevation points yeilded no results")?;
-    for elevation_point in elevation_points.iter().skip(1).filter(|e| e.extremum) {
-        let diff = elevation_point.elevation - prev_elevation_point.elevation;
-        if diff > 0.0 {
-            gains += diff;
-        } else {
-            losses += diff.abs();
-        }
-        prev_elevation_point = elevation_point;
-    }
+        .ok_or_eyre("GPX track has no segments")?
+        .points
+        .first()
+        .ok_or_eyre("GPX segment has no points")?
+        .point();
+    let (gains, losses) =
+        trail_stats::elevation_changes(track).wrap_err("Failed to find elevation changes")?;
 
-    let travel_time = uom::si::f64::Length::new::<meter>(length)
-        / uom::si::f64::Velocity::new::<mile_per_hour>(avg_speed);
+    let (time_field, travel_time) = match form.activity {
+        Activity::Hike => (
+            "Approximate Time to Complete",
+            uom::si::f64::Length::new::<meter>(length)
+                / uom::si::f64::Velocity::new::<mile_per_hour>(pace.avg_speed),
+        ),
+        Activity::TrailRun => (
+            "Approximate Time to Run",
+            uom::si::f64::Length::new::<meter>(length)
+                / uom::si::f64::Velocity::new::<mile_per_hour>(pace.run_speed)
+                * pace::grade_adjustment(track).wrap_err("Failed to adjust pace for grade")?,
+        ),
+    };
 
-    Ok(CreateEmbed::new()
+    let stats = TrailStats {
+        length,
+        gains,
+        steepest: crate::beginner::steepest_grade(track)
+            .wrap_err("Failed to find steepest section")?,
+    };
+    let computed = Difficulty::from_stats(stats);
+    let difficulty_field = match &form.difficulty {
+        Some(difficulty) => format!(
+            "{} (AllTrails)\n{} (computed)",
+            sanitize::text(difficulty, FIELD_LENGTH / 2),
+            computed
+        ),
+        None => format!("{} (computed)", computed),
+    };
+
+    // Everything typed in is cut to fit, so no field or the message as a
+    // whole goes over Discord's limits.
+    let mut embed = CreateEmbed::new()
         .color(Color::DARK_GREEN)
         .url(link)
-        .title(form.title)
-        .description(form.description)
-        .field("Difficulty", form.difficulty, false)
-        .field("Rating", form.rating, false)
+        .title(sanitize::text(&form.title, sanitize::TITLE_LENGTH))
+        .description(sanitize::budget(
+            &sanitize::escape(&form.description),
+            sanitize::DESCRIPTION_BUDGET,
+            Some(trail_page),
+        ))
+        .field("Difficulty", difficulty_field, false)
+        .field("Rating", sanitize::text(&form.rating, FIELD_LENGTH), false)
         .field(
-            "Approximate Time to Complete",
+            time_field,
             format!(
                 "{:.2}",
                 travel_time.into_format_args(hour, DisplayStyle::Abbreviation)
             ),
             false,
-        )
+        );
+
+    // Pandolf only covers walking
+    if let (Activity::Hike, Some(load)) = (form.activity, form.ruck_weight) {
+        let (unloaded, loaded) = pace
+            .ruck(track, load)
+            .wrap_err("Failed to estimate hiking with a ruck")?;
+        // Scaled so it lines up with the time above, which skips wandering
+        // around the trailhead
+        let ruck_time = travel_time * (loaded.hours / unloaded.hours);
+        embed = embed.field(
+            format!("With a {} lb ruck", load),
+            format!(
+                "{:.2}, about {:.0} kcal ({:.0} kcal without)",
+                ruck_time.into_format_args(hour, DisplayStyle::Abbreviation),
+                loaded.calories,
+                unloaded.calories
+            ),
+            false,
+        );
+    }
+
+    embed = embed
         .field(
             "Length",
-            format_length(length, long_units).wrap_err("Failed to format length")?,
+            lengths.long(length).wrap_err("Failed to format length")?,
+            false,
+        )
+        .field(
+            "Route type",
+            route_type.map_or_else(
+                || String::from("Unknown"),
+                |route_type| route_type.to_string(),
+            ),
             false,
         )
         .field(
             "Uphill",
-            format_length(gains, short_units).wrap_err("Failed to format length")?,
+            lengths.short(gains).wrap_err("Failed to format length")?,
             true,
         )
         .field(
             "Downhill",
-            format_length(losses, short_units).wrap_err("Failed to format length")?,
+            lengths.short(losses).wrap_err("Failed to format length")?,
             true,
         )
         .field(
             "Avg. Elevation",
-            format_length(avg.0 / avg.1 as f64, short_units).wrap_err("Failed to format length")?,
+            lengths
+                .short(avg.0 / avg.1 as f64)
+                .wrap_err("Failed to format length")?,
             false,
         )
         .field(
             "Minimum altitude",
-            format_length(min_altitude, short_units).wrap_err("Failed to format length")?,
+            lengths
+                .short(min_altitude)
+                .wrap_err("Failed to format length")?,
             true,
         )
         .field(
             "Maximum altitude",
-            format_length(max_altitude, short_units).wrap_err("Failed to format length")?,
+            lengths
+                .short(max_altitude)
+                .wrap_err("Failed to format length")?,
             true,
         )
-        .image(form.image))
-}
+        .field(TRAILHEAD_FIELD, trailhead_links(trailhead), false)
+        .image(form.image);
 
-#[instrument]
-fn format_length(length: f64, unit: uom::si::length::Units) -> eyre::Result<String> {
-    let length = uom::si::f64::Length::new::<meter>(length);
-    match unit {
-        uom::si::length::Units::yottameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::zettameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::exameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::petameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::terameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::gigameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::megameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::kilometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::hectometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::decameter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::meter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::decimeter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::centimeter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::millimeter(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::micrometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::nanometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::picometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::femtometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::attometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::zeptometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::yoctometer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::angstrom(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::bohr_radius(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::atomic_unit_of_length(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::astronomical_unit(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::chain(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::fathom(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::fermi(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::foot(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::foot_survey(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::inch(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::light_year(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::microinch(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::micron(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::mil(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::mile(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::mile_survey(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::nautical_mile(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::parsec(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::pica_computer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::pica_printers(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::point_computer(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::point_printers(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::rod(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        uom::si::length::Units::yard(u) => Ok(format!(
-            "{:.1}",
-            length.into_format_args(u, DisplayStyle::Abbreviation)
-        )),
-        u => Err(eyre!("Conversion from unit `{:?}` is not implemented", u)),
+    if let Some(area) = trailhead_area {
+        embed = embed.field(
+            "Trailhead area",
+            format!(
+                "{} ({} away)",
+                sanitize::text(&area.name, sanitize::NAME_LENGTH),
+                lengths
+                    .long(Haversine::distance(trailhead, area.point))
+                    .wrap_err("Failed to format length")?
+            ),
+            false,
+        );
     }
-}
 
-// Borrowed from OsmAnd: https://github.com/osmandapp/OsmAnd/blob/0026e71e1be4cd29fb904c5d0735f02cf80d88b6/OsmAnd-shared/src/commonMain/kotlin/net/osmand/shared/gpx/ElevationDiffsCalculator.kt#L20
-// https://github.com/osmandapp/OsmAnd/blob/master/OsmAnd-shared/src/commonMain/kotlin/net/osmand/shared/gpx/ElevationApproximator.kt#L5
-
-fn get_projection_dist(x: f64, y: f64, fromx: f64, fromy: f64, tox: f64, toy: f64) -> f64 {
-    let m_dist = (fromx - tox) * (fromx - tox) + (fromy - toy) * (fromy - toy);
-    // let projection = KMapUtils.scalarMultiplication(fromx, fromy, tox, toy, x, y);
-    let projection = (tox - fromx) * (x - fromx) + (toy - fromy) * (y - fromy);
-    let (prx, pry) = if projection < 0.0 {
-        (fromx, fromy)
-    } else if projection >= m_dist {
-        (tox, toy)
-    } else {
-        (
-            fromx + (tox - fromx) * (projection / m_dist),
-            fromy + (toy - fromy) * (projection / m_dist),
-        )
-    };
-    return ((prx - x) * (prx - x) + (pry - y) * (pry - y)).sqrt();
-}
-
-#[instrument(skip_all)]
-fn approximate_elevation_points(points: &mut Vec<ElevationPoint>) -> eyre::Result<bool> {
-    const SLOPE_THRESHOLD: f64 = 70.0;
-    let mut last_survived = 0;
-    let mut survived_count = 0;
-    for i in 1..points.len() - 1 {
-        let prev_ele = points
-            .get(last_survived)
-            .ok_or_eyre("Point not found")?
-            .elevation;
-        let ele = points.get(i).ok_or_eyre("Point not found")?.elevation;
-        let ele_next = points.get(i + 1).ok_or_eyre("Point not found")?.elevation;
-        if (ele - prev_ele) * (ele_next - ele) > 0.0 {
-            points[i].survived = true;
-            last_survived = i;
-            survived_count += 1;
-        }
+    if let Some(points_of_interest) = points_of_interest(&form.gpx_file.waypoints, track, lengths)
+        .wrap_err("Failed to list points of interest")?
+    {
+        embed = embed.field("Points of interest", points_of_interest, false);
     }
-    points.last_mut().unwrap().survived = true;
-    survived_count += 1;
-    if survived_count < 2 {
-        return Ok(false);
+
+    if let Some(drive) = drive {
+        let minutes = drive.duration.as_secs() / 60;
+        embed = embed.field(
+            "Drive time from home base",
+            format!(
+                "{}h {}m, {} round trip",
+                minutes / 60,
+                minutes % 60,
+                lengths
+                    .long(drive.distance * 2.0)
+                    .wrap_err("Failed to format length")?
+            ),
+            false,
+        );
     }
 
-    last_survived = 0;
-    survived_count = 0;
-    for i in 1..points.len() - 1 {
-        if !points.get(i).ok_or_eyre("Point not found")?.survived {
-            continue;
-        }
+    Ok((embed, stats))
+}
 
-        let ele = points.get(i).ok_or_eyre("Point not found")?.elevation;
-        let prev_ele = points
-            .get(last_survived)
-            .ok_or_eyre("Point not found")?
-            .elevation;
-        let dist = Line::new(points[i].point, points[last_survived].point).length::<Haversine>();
-        let slope = (ele - prev_ele) * 100.0 / dist;
-        if slope.abs() > SLOPE_THRESHOLD {
-            points[i].survived = false;
-            continue;
-        }
-        last_survived = i;
-        survived_count += 1;
-    }
-    if survived_count < 2 {
-        return Ok(false);
+/// Points of interest listed in a suggestion before the rest are counted
+const MAX_POINTS_OF_INTEREST: usize = 8;
+
+/// `waypoints` in the order they're passed along `track`, each with how far
+/// along the trail it is
+fn points_of_interest(
+    waypoints: &[gpx::Waypoint],
+    track: &gpx::Track,
+    lengths: Lengths<'_>,
+) -> eyre::Result<Option<String>> {
+    if waypoints.is_empty() {
+        return Ok(None);
     }
 
-    points[0].survived = true;
-    points.last_mut().unwrap().survived = true;
-    let mut elevation_points = Vec::new();
-    last_survived = 0;
-    for i in 0..points.len() {
-        if !points[i].survived {
-            continue;
+    let mut along = Vec::new();
+    let mut distance = 0.0;
+    for point in track.segments.iter().flat_map(|s| &s.points) {
+        if let Some(&(previous, _)) = along.last() {
+            distance += Haversine::distance(previous, point.point());
         }
-        elevation_points.push(ElevationPoint {
-            distance: if last_survived == 0 {
-                0.0
-            } else {
-                Line::new(points[i].point, points[last_survived].point).length::<Haversine>()
-            },
-            elevation: points[i].elevation,
-            point: points[i].point,
-            extremum: false,
-            survived: true,
-        });
-        last_survived = i;
+        along.push((point.point(), distance));
+    }
+
+    let mut points = waypoints
+        .iter()
+        .map(|waypoint| {
+            let name = waypoint
+                .name
+                .as_deref()
+                .or(waypoint.type_.as_deref())
+                .unwrap_or("Unnamed waypoint");
+            // Waypoints are rarely exactly on the track, so they're placed at
+            // the closest point of it
+            let distance = along
+                .iter()
+                .map(|&(point, distance)| (Haversine::distance(point, waypoint.point()), distance))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map_or(0.0, |(_, distance)| distance);
+            (name, distance)
+        })
+        .collect::<Vec<_>>();
+    points.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut lines = points
+        .iter()
+        .take(MAX_POINTS_OF_INTEREST)
+        .map(|(name, distance)| {
+            Ok(format!(
+                "{} · {}",
+                sanitize::text(name, sanitize::NAME_LENGTH),
+                lengths.long(*distance)?
+            ))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    if points.len() > MAX_POINTS_OF_INTEREST {
+        lines.push(format!(
+            "…and {} more",
+            points.len() - MAX_POINTS_OF_INTEREST
+        ));
     }
-    *points = elevation_points;
-    Ok(true)
+    Ok(Some(lines.join("\n")))
 }
 
-#[instrument(skip(points))]
-fn find_maximum_extremum_between(
-    start: usize,
-    end: usize,
-    points: &mut Vec<ElevationPoint>,
+/// Fills the suggestion in straight from the GPX file attached to `/suggest`,
+/// with the rest of the trail details scraped from AllTrails
+#[instrument(skip(state, message, attachment))]
+async fn fill_from_attachment(
+    state: &AppState,
+    message: &Message,
+    link: &str,
+    attachment: &Attachment,
+    filled_by: &str,
 ) -> eyre::Result<()> {
-    const ELE_THRESHOLD: f64 = 7.0;
-
-    let first_point_dist = points.get(start).ok_or_eyre("Point not found")?.distance;
-    let first_point_ele = points.get(start).ok_or_eyre("Point not found")?.elevation;
-    let end_point_dist = points.get(end).ok_or_eyre("Point not found")?.distance;
-    let end_point_ele = points.get(end).ok_or_eyre("Point not found")?.elevation;
-    let mut max = start;
-    let mut max_diff = ELE_THRESHOLD;
-    for i in start + 1..end {
-        let md = get_projection_dist(
-            points.get(i).ok_or_eyre("Point not found")?.distance,
-            points.get(i).ok_or_eyre("Point not found")?.elevation,
-            first_point_dist,
-            first_point_ele,
-            end_point_dist,
-            end_point_ele,
-        );
-        if md > max_diff {
-            max = i;
-            max_diff = md;
-        }
+    if !state.feature_enabled(crate::Feature::Scraper) {
+        return Err(eyre!("AllTrails scraping is disabled"));
     }
-    if max != start {
-        points[max].extremum = true;
-        find_maximum_extremum_between(start, max, points)?;
-        find_maximum_extremum_between(max, end, points)?;
+    if Provider::of(link) != Some(Provider::AllTrails) {
+        return Err(eyre!("Trail details can only be scraped from AllTrails"));
     }
-    Ok(())
+    let _permit = state
+        .limiter
+        .try_acquire(crate::backpressure::upload_memory(attachment.size as usize))
+        .ok_or_eyre("Too many uploads are being handled right now")?;
+
+    let gpx_bytes = attachment
+        .download()
+        .await
+        .wrap_err("Failed to download GPX attachment")?;
+    let gpx_file = gpx::read(Cursor::new(gpx_bytes)).wrap_err("Failed to read GPX file")?;
+
+    let config = state.config.load();
+    let metadata = crate::alltrails::scrape(link, &config)
+        .await
+        .wrap_err("Failed to scrape AllTrails")?;
+    let form = UploadForm::from_metadata(metadata, gpx_file)
+        .ok_or_eyre("AllTrails is missing some of the trail details")?;
+
+    fill_suggestion(
+        state,
+        message.channel_id,
+        message.id,
+        link,
+        form,
+        filled_by,
+        None,
+    )
+    .await
+    .wrap_err("Failed to fill trail suggestion")
+}
+
+/// The first point of the first track in `gpx`
+pub fn trailhead(gpx: &gpx::Gpx) -> eyre::Result<Point> {
+    Ok(gpx
+        .tracks
+        .first()
+        .ok_or_eyre("GPX file contained no tracks")?
+        .segments
+        .first()
+        .ok_or_eyre("GPX track has no segments")?
+        .points
+        .first()
+        .ok_or_eyre("GPX segment has no points")?
+        .point())
+}
+
+/// Name of the embed field holding the trailhead, the first line of which is
+/// its coordinates
+pub const TRAILHEAD_FIELD: &str = "Trailhead";
+
+/// The trailhead's coordinates followed by directions to it in a few map apps
+fn trailhead_links(trailhead: Point) -> String {
+    let (lat, lon) = (trailhead.y(), trailhead.x());
+    format!(
+        "{lat:.5}, {lon:.5}\n\
+        [Google Maps](https://www.google.com/maps/dir/?api=1&destination={lat:.5},{lon:.5}) · \
+        [Apple Maps](https://maps.apple.com/?daddr={lat:.5},{lon:.5}) · \
+        [OpenStreetMap](https://www.openstreetmap.org/?mlat={lat:.5}&mlon={lon:.5}#map=15/{lat:.5}/{lon:.5})"
+    )
+}
+
+fn region_names(regions: &[Region]) -> String {
+    regions
+        .iter()
+        .map(|region| region.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
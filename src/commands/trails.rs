@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+
+use color_eyre::eyre::{self, eyre, Context};
+use jsonwebtoken::get_current_timestamp;
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    Color, CommandOptionType, CreateActionRow, CreateButton, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateEmbedAuthor, CreateInteractionResponse, CreateInteractionResponseMessage,
+    ResolvedOption, ResolvedValue, UserId,
+};
+use tracing::instrument;
+
+use crate::{
+    beginner::BeginnerScore,
+    sanitize,
+    store::{Suggestion, SuggestionFilter},
+    units::{length_to_meters, Lengths},
+    AppState, ComponentId,
+};
+
+const PAGE_SIZE: usize = 5;
+
+/// Longest length filter, in meters, so it fits in a custom_id
+const MAX_FILTER_LENGTH: f64 = 1_000_000.0;
+
+/// Longest custom_id Discord accepts, in characters
+const MAX_CUSTOM_ID_LENGTH: usize = 100;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("trails")
+        .description("Browse past trail suggestions")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "difficulty",
+                "Only show trails with this difficulty",
+            )
+            .max_length(20),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Number,
+            "min_length",
+            "Only show trails at least this long",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Number,
+            "max_length",
+            "Only show trails at most this long",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "hiked",
+            "Only show trails the group has (or hasn't) hiked",
+        ))
+}
+
+/// Filters passed to `/trails`, kept short since they are stored in the
+/// custom_id of the pagination buttons. Lengths are in whole meters.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TrailsFilter<'a> {
+    #[serde(rename = "d", default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Cow<'a, str>>,
+    #[serde(rename = "n", default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<u32>,
+    #[serde(rename = "x", default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u32>,
+    #[serde(rename = "h", default, skip_serializing_if = "Option::is_none")]
+    pub hiked: Option<bool>,
+}
+
+impl<'a> TrailsFilter<'a> {
+    /// Reads the filters, with lengths given in `long_units`
+    #[instrument]
+    pub fn from_options(
+        options: &[ResolvedOption<'a>],
+        long_units: uom::si::length::Units,
+    ) -> eyre::Result<Self> {
+        let meters = |length| {
+            length_to_meters(length, long_units)
+                .map(|meters: f64| meters.clamp(0.0, MAX_FILTER_LENGTH))
+                .wrap_err("Failed to convert filter length")
+        };
+
+        let mut filter = TrailsFilter::default();
+        for option in options {
+            match (option.name, &option.value) {
+                ("difficulty", ResolvedValue::String(difficulty)) => {
+                    filter.difficulty = Some(Cow::Borrowed(difficulty))
+                }
+                // Rounded outwards, so trails right at the limit still match
+                ("min_length", ResolvedValue::Number(length)) => {
+                    filter.min_length = Some(meters(*length)?.floor() as u32)
+                }
+                ("max_length", ResolvedValue::Number(length)) => {
+                    filter.max_length = Some(meters(*length)?.ceil() as u32)
+                }
+                ("hiked", ResolvedValue::Boolean(hiked)) => filter.hiked = Some(*hiked),
+                (name, _) => return Err(eyre!("Unexpected option `{}`", name)),
+            }
+        }
+        Ok(filter)
+    }
+
+    fn to_store_filter(&self) -> SuggestionFilter {
+        SuggestionFilter {
+            difficulty: self.difficulty.as_ref().map(|d| d.to_string()),
+            min_length: self.min_length.map(f64::from),
+            max_length: self.max_length.map(f64::from),
+            hiked: self.hiked,
+        }
+    }
+}
+
+#[instrument(skip(state))]
+pub fn respond(
+    state: &AppState,
+    filter: TrailsFilter,
+    user: UserId,
+) -> eyre::Result<CreateInteractionResponse> {
+    Ok(CreateInteractionResponse::Message(
+        render_page(state, 0, filter, user).wrap_err("Failed to render first page of trails")?,
+    ))
+}
+
+#[instrument(skip(state))]
+pub fn render_page(
+    state: &AppState,
+    page: usize,
+    filter: TrailsFilter,
+    user: UserId,
+) -> eyre::Result<CreateInteractionResponseMessage> {
+    let config = state.config.load();
+    let lengths = config.lengths_for(&state.preferences(user)?);
+    let now = get_current_timestamp();
+    let (suggestions, total) = state
+        .store
+        .filtered_suggestions(&filter.to_store_filter(), now, page * PAGE_SIZE, PAGE_SIZE)
+        .wrap_err("Failed to load suggestions")?;
+
+    if total == 0 {
+        return Ok(CreateInteractionResponseMessage::new()
+            .content("No trail suggestions match those filters")
+            .embeds(Vec::new())
+            .components(Vec::new()));
+    }
+
+    let pages = total.div_ceil(PAGE_SIZE);
+    let embeds = suggestions
+        .into_iter()
+        .map(|suggestion| suggestion_embed(suggestion, now, lengths))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let button = |label: &str, page: usize, disabled: bool| {
+        let component = ComponentId::Trails {
+            page,
+            filter: TrailsFilter {
+                difficulty: filter.difficulty.clone(),
+                ..filter
+            },
+        };
+        let id = serde_json::to_string(&component).wrap_err("Failed to serialize component ID")?;
+        if id.chars().count() > MAX_CUSTOM_ID_LENGTH {
+            return Err(eyre!(
+                "Those filters are too long to page through, try a shorter difficulty"
+            ));
+        }
+        Ok(CreateButton::new(id).label(label).disabled(disabled))
+    };
+
+    Ok(CreateInteractionResponseMessage::new()
+        .content(format!("Page {} of {} ({} trails)", page + 1, pages, total))
+        .embeds(embeds)
+        .components(vec![CreateActionRow::Buttons(vec![
+            button("Previous", page.saturating_sub(1), page == 0)?,
+            button("Next", page + 1, page + 1 >= pages)?,
+        ])]))
+}
+
+/// Shown above the title of accessible trails, so it's seen first
+pub const ACCESSIBLE: &str = "♿ Wheelchair and stroller accessible";
+
+/// Summary of a stored suggestion, shared with `/search`
+pub fn suggestion_embed(
+    suggestion: Suggestion,
+    now: u64,
+    lengths: Lengths,
+) -> eyre::Result<CreateEmbed> {
+    let hiked = suggestion.hiked(now);
+    let mut embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title(sanitize::text(&suggestion.title, sanitize::TITLE_LENGTH))
+        .url(suggestion.link)
+        .field(
+            "Difficulty",
+            suggestion.difficulty.as_deref().unwrap_or("Unknown"),
+            true,
+        )
+        .field("Hiked", if hiked { "Yes" } else { "No" }, true);
+    if suggestion.accessible == Some(true) {
+        embed = embed.author(CreateEmbedAuthor::new(ACCESSIBLE));
+    }
+    if let Some(score) = suggestion.beginner_score {
+        embed = embed.field("Beginner friendly", BeginnerScore(score).to_string(), true);
+    }
+    if let Some(length) = suggestion.length {
+        embed = embed.field(
+            "Length",
+            lengths.long(length).wrap_err("Failed to format length")?,
+            true,
+        );
+    }
+    if let Some(gain) = suggestion.gain {
+        embed = embed.field(
+            "Uphill",
+            lengths.short(gain).wrap_err("Failed to format length")?,
+            true,
+        );
+    }
+    Ok(embed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_filter_fits_in_custom_id() {
+        let component = ComponentId::Trails {
+            page: 9999,
+            filter: TrailsFilter {
+                difficulty: Some(Cow::Borrowed("Strenuous (class 3+)")),
+                min_length: Some(MAX_FILTER_LENGTH as u32),
+                max_length: Some(MAX_FILTER_LENGTH as u32),
+                hiked: Some(false),
+            },
+        };
+        let id = serde_json::to_string(&component).unwrap();
+        assert!(id.chars().count() <= MAX_CUSTOM_ID_LENGTH, "{}", id);
+    }
+}
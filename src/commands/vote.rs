@@ -0,0 +1,429 @@
+use std::{ops::Deref, sync::Arc};
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{
+    Color, CommandInteraction, CommandOptionType, ComponentInteraction,
+    ComponentInteractionDataKind, CreateActionRow, CreateAllowedMentions, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, CreateMessage, CreateSelectMenu, CreateSelectMenuKind,
+    CreateSelectMenuOption, EditMessage, MessageId, ResolvedValue,
+};
+use tracing::{instrument, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    sanitize,
+    scheduler::Job,
+    store::{Poll, Suggestion},
+    AppState, ComponentId,
+};
+
+/// Most options a select menu can hold
+const MAX_CANDIDATES: usize = 25;
+/// Choices each voter ranks, one select menu each, up to the rows a message
+/// can hold
+const MAX_RANKS: usize = 5;
+/// How long polls stay open unless `hours` is given
+const DEFAULT_HOURS: i64 = 48;
+/// Longest a select menu option's label can be, in characters
+const MAX_LABEL_LENGTH: usize = 100;
+
+pub fn create_command() -> CreateCommand {
+    CreateCommand::new("vote")
+        .description("Start a ranked-choice vote between the proposed trail suggestions")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "hours",
+                "How many hours voting stays open for",
+            )
+            .min_int_value(1)
+            .max_int_value(14 * 24),
+        )
+}
+
+fn ordinal(rank: usize) -> String {
+    let suffix = match rank {
+        1 => "st",
+        2 => "nd",
+        3 => "rd",
+        _ => "th",
+    };
+    format!("{}{}", rank, suffix)
+}
+
+/// One select menu per rank, each listing every candidate
+fn ballot_menus(candidates: &[Suggestion]) -> eyre::Result<Vec<CreateActionRow>> {
+    (1..=candidates.len().min(MAX_RANKS))
+        .map(|rank| {
+            let options = candidates
+                .iter()
+                .map(|candidate| {
+                    CreateSelectMenuOption::new(
+                        sanitize::budget(&candidate.title, MAX_LABEL_LENGTH, None),
+                        candidate.message_id.to_string(),
+                    )
+                })
+                .collect();
+            let id = serde_json::to_string(&ComponentId::Ballot { rank: rank as u8 })
+                .wrap_err("Failed to serialize component ID")?;
+            Ok(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(id, CreateSelectMenuKind::String { options })
+                    .placeholder(format!("{} choice", ordinal(rank))),
+            ))
+        })
+        .collect()
+}
+
+/// Posts a poll of the proposed suggestions in the channel the command was
+/// used in
+#[instrument(skip_all)]
+pub async fn respond(
+    command: &CommandInteraction,
+    state: Arc<AppState>,
+) -> eyre::Result<CreateInteractionResponseFollowup> {
+    let hours = match command.data.options().first().map(|option| &option.value) {
+        Some(ResolvedValue::Integer(hours)) => *hours,
+        Some(_) => return Err(eyre!("Expected `hours` to be an integer")),
+        None => DEFAULT_HOURS,
+    };
+    let deadline = get_current_timestamp() + hours.max(1) as u64 * 60 * 60;
+
+    let candidates = state
+        .store
+        .proposed_suggestions(MAX_CANDIDATES)
+        .wrap_err("Failed to load proposed suggestions")?;
+    if candidates.len() < 2 {
+        return Err(eyre!(
+            "At least two trail suggestions have to be proposed to vote between them"
+        ));
+    }
+
+    let embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title("Vote for the next hike")
+        .description(sanitize::lines(
+            std::iter::once(format!(
+                "Rank as many trails as you like, voting closes <t:{}:R>\n",
+                deadline
+            ))
+            .chain(candidates.iter().map(|candidate| {
+                format!(
+                    "- [{}]({})",
+                    sanitize::text(&candidate.title, sanitize::NAME_LENGTH),
+                    candidate.link
+                )
+            })),
+            sanitize::DESCRIPTION_LENGTH,
+        ));
+    let message = CreateMessage::new()
+        .allowed_mentions(CreateAllowedMentions::new())
+        .embed(embed)
+        .components(ballot_menus(&candidates)?);
+
+    let http = state.http.load();
+    let poll = audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::User(command.user.id),
+        format!("channel {}", command.channel_id),
+        &summarize(&message),
+        command.channel_id.send_message(http.deref(), message),
+    )
+    .await
+    .wrap_err("Failed to post poll")?;
+
+    state
+        .store
+        .insert_poll(
+            poll.id,
+            command.channel_id,
+            deadline,
+            &candidates
+                .iter()
+                .map(|candidate| candidate.message_id)
+                .collect::<Vec<_>>(),
+        )
+        .wrap_err("Failed to store poll")?;
+
+    Ok(CreateInteractionResponseFollowup::new()
+        .ephemeral(true)
+        .content("Poll posted!"))
+}
+
+/// Records the trail a member picked at `rank`, then shows them their ballot
+#[instrument(skip(component, state))]
+pub fn cast(
+    component: &ComponentInteraction,
+    state: &AppState,
+    rank: u8,
+) -> eyre::Result<CreateInteractionResponse> {
+    let poll = state
+        .store
+        .poll(component.message.id)?
+        .ok_or_eyre("This poll was not found")?;
+    if poll.closed || poll.deadline <= get_current_timestamp() {
+        return Err(eyre!("Voting has closed"));
+    }
+
+    let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+        return Err(eyre!("Ballot was not a select menu"));
+    };
+    let choice = values
+        .first()
+        .ok_or_eyre("No trail was picked")?
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(MessageId::new)
+        .ok_or_eyre("Picked trail was not a suggestion")?;
+
+    let candidates = state
+        .store
+        .poll_candidates(poll.message_id)
+        .wrap_err("Failed to load poll candidates")?;
+    if !candidates
+        .iter()
+        .any(|candidate| candidate.message_id == choice)
+    {
+        return Err(eyre!("Picked trail is not on the ballot"));
+    }
+
+    state
+        .store
+        .cast_ballot(poll.message_id, component.user.id, rank, choice)
+        .wrap_err("Failed to store ballot")?;
+    let ballot = state
+        .store
+        .ballots(poll.message_id)
+        .wrap_err("Failed to load ballot")?
+        .remove(&component.user.id)
+        .unwrap_or_default();
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content(format!(
+                "Your ballot:\n{}",
+                ballot
+                    .iter()
+                    .enumerate()
+                    .map(|(i, id)| format!("{}. {}", i + 1, title(&candidates, *id)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+    ))
+}
+
+fn title(candidates: &[Suggestion], id: MessageId) -> String {
+    candidates
+        .iter()
+        .find(|candidate| candidate.message_id == id)
+        .map_or_else(
+            || String::from("Unknown trail"),
+            |candidate| sanitize::text(&candidate.title, sanitize::NAME_LENGTH),
+        )
+}
+
+/// Each round of an instant-runoff, most voted first. Ties are eliminated
+/// from the last listed candidate.
+fn instant_runoff(
+    candidates: &[MessageId],
+    ballots: &[Vec<MessageId>],
+) -> Vec<Vec<(MessageId, usize)>> {
+    let mut remaining = candidates.to_vec();
+    let mut rounds = Vec::new();
+    loop {
+        let mut tally = remaining.iter().map(|&id| (id, 0)).collect::<Vec<_>>();
+        for ballot in ballots {
+            let choice = ballot.iter().find(|id| remaining.contains(id));
+            if let Some(count) = tally.iter_mut().find(|(id, _)| Some(id) == choice) {
+                count.1 += 1;
+            }
+        }
+        tally.sort_by_key(|(_, votes)| std::cmp::Reverse(*votes));
+
+        let counted = tally.iter().map(|(_, votes)| votes).sum::<usize>();
+        let decided = remaining.len() <= 1 || counted == 0 || tally[0].1 * 2 > counted;
+        let eliminated = tally.last().map(|(id, _)| *id);
+        rounds.push(tally);
+        if decided {
+            return rounds;
+        }
+        remaining.retain(|id| Some(*id) != eliminated);
+    }
+}
+
+/// Counts the ballots of a poll, posts the results under it and removes its
+/// menus
+async fn close(state: &AppState, poll: &Poll) -> eyre::Result<()> {
+    let candidates = state
+        .store
+        .poll_candidates(poll.message_id)
+        .wrap_err("Failed to load poll candidates")?;
+    let ballots = state
+        .store
+        .ballots(poll.message_id)
+        .wrap_err("Failed to load ballots")?
+        .into_values()
+        .collect::<Vec<_>>();
+    let rounds = instant_runoff(
+        &candidates
+            .iter()
+            .map(|candidate| candidate.message_id)
+            .collect::<Vec<_>>(),
+        &ballots,
+    );
+
+    let mut embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title("Vote results");
+    match rounds.last().and_then(|round| round.first()) {
+        Some((winner, votes)) if *votes > 0 => {
+            let round_count = rounds.len();
+            // Every round has to fit in the embed together, leaving room for
+            // the winner and the round names
+            let round_length = sanitize::FIELD_LENGTH
+                .min((sanitize::EMBED_LENGTH - sanitize::FIELD_LENGTH) / round_count);
+            for (i, round) in rounds.iter().enumerate() {
+                let eliminated = round
+                    .last()
+                    .filter(|_| i + 1 < round_count)
+                    .map(|(eliminated, _)| {
+                        format!("\n*{} eliminated*", title(&candidates, *eliminated))
+                    })
+                    .unwrap_or_default();
+                let mut value = sanitize::lines(
+                    round
+                        .iter()
+                        .map(|(id, votes)| format!("{}: {}", title(&candidates, *id), votes)),
+                    round_length - eliminated.chars().count(),
+                );
+                value.push_str(&eliminated);
+                embed = embed.field(format!("Round {}", i + 1), value, false);
+            }
+            let winner = candidates
+                .iter()
+                .find(|candidate| candidate.message_id == *winner)
+                .ok_or_eyre("Winner was not a candidate")?;
+            embed = embed.description(format!(
+                "[{}]({}) won with {} ballots cast",
+                sanitize::text(&winner.title, sanitize::TITLE_LENGTH),
+                winner.link,
+                ballots.len()
+            ));
+        }
+        _ => embed = embed.description("Nobody voted"),
+    }
+
+    let http = state.http.load();
+    let edit = EditMessage::new().components(Vec::new());
+    if let Err(e) = audited(
+        &state.audit,
+        Mutation::EditMessage,
+        Actor::Job(Job::ClosePolls),
+        format!("message {} in channel {}", poll.message_id, poll.channel_id),
+        &summarize(&edit),
+        poll.channel_id
+            .edit_message(http.deref(), poll.message_id, edit),
+    )
+    .await
+    {
+        // The results are still worth posting if the poll can't be edited
+        warn!(message_id = %poll.message_id, "Failed to remove poll menus: {:?}", e);
+    }
+
+    let results = CreateMessage::new()
+        .reference_message((poll.channel_id, poll.message_id))
+        .allowed_mentions(CreateAllowedMentions::new())
+        .embed(embed);
+    audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::Job(Job::ClosePolls),
+        format!("channel {}", poll.channel_id),
+        &summarize(&results),
+        poll.channel_id.send_message(http.deref(), results),
+    )
+    .await
+    .wrap_err("Failed to post poll results")?;
+
+    state
+        .store
+        .close_poll(poll.message_id)
+        .wrap_err("Failed to mark poll closed")
+}
+
+/// Closes every poll past its deadline. Polls that fail to close are tried
+/// again on the next run.
+pub async fn close_due_polls(state: &AppState) -> eyre::Result<()> {
+    let polls = state
+        .store
+        .due_polls(get_current_timestamp())
+        .wrap_err("Failed to load due polls")?;
+
+    let mut failed = 0;
+    for poll in &polls {
+        if let Err(e) = close(state, poll).await {
+            warn!(message_id = %poll.message_id, "Failed to close poll: {:?}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!("Failed to close {} polls", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids<const N: usize>() -> [MessageId; N] {
+        std::array::from_fn(|i| MessageId::new(i as u64 + 1))
+    }
+
+    #[test]
+    fn stops_at_a_majority() {
+        let [a, b, c] = ids();
+        let rounds = instant_runoff(&[a, b, c], &[vec![a], vec![a, b], vec![b]]);
+        assert_eq!(rounds, vec![vec![(a, 2), (b, 1), (c, 0)]]);
+    }
+
+    #[test]
+    fn eliminates_the_later_listed_on_ties() {
+        let [a, b, c] = ids();
+        let rounds = instant_runoff(&[a, b, c], &[vec![a], vec![a], vec![b, a], vec![c, b]]);
+        assert_eq!(
+            rounds,
+            vec![
+                vec![(a, 2), (b, 1), (c, 1)],
+                vec![(a, 2), (b, 2)],
+                vec![(a, 3)],
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_counting_exhausted_ballots() {
+        let [a, b, c] = ids();
+        let rounds = instant_runoff(&[a, b, c], &[vec![a], vec![b], vec![c]]);
+        assert_eq!(
+            rounds,
+            vec![
+                vec![(a, 1), (b, 1), (c, 1)],
+                vec![(a, 1), (b, 1)],
+                vec![(a, 1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn nobody_voting_takes_one_round() {
+        let [a, b] = ids();
+        let rounds = instant_runoff(&[a, b], &[]);
+        assert_eq!(rounds, vec![vec![(a, 0), (b, 0)]]);
+    }
+}
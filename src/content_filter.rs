@@ -0,0 +1,67 @@
+use color_eyre::eyre::{self, eyre};
+use serde::Deserialize;
+use serenity::all::RoleId;
+
+/// Words and phrases kept out of suggestions, which end up in public embeds
+#[derive(Deserialize, Debug)]
+pub struct ContentFilterConfig {
+    /// Matched against whole words regardless of case, so "ass" doesn't catch
+    /// "Pass". End one with `*` to match every word starting with it.
+    deny: Vec<String>,
+    /// Members with any of these roles can post text the filter would catch
+    #[serde(default)]
+    override_roles: Vec<RoleId>,
+}
+
+impl ContentFilterConfig {
+    /// Whether a member with `roles` bypasses the filter
+    pub fn overridden_by(&self, roles: &[RoleId]) -> bool {
+        roles.iter().any(|role| self.override_roles.contains(role))
+    }
+
+    /// The first denied word or phrase in `text`
+    fn find<'a>(&'a self, text: &str) -> Option<&'a str> {
+        let text = text.to_lowercase();
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+        self.deny.iter().map(String::as_str).find(|denied| {
+            let denied = denied.to_lowercase();
+            let (denied, prefix) = match denied.strip_suffix('*') {
+                Some(denied) => (denied, true),
+                None => (denied.as_str(), false),
+            };
+            let denied = denied.split_whitespace().collect::<Vec<_>>();
+            !denied.is_empty()
+                && words.windows(denied.len()).any(|window| {
+                    window
+                        .iter()
+                        .zip(&denied)
+                        .enumerate()
+                        .all(|(i, (word, denied_word))| {
+                            if prefix && i == denied.len() - 1 {
+                                word.starts_with(denied_word)
+                            } else {
+                                word == denied_word
+                            }
+                        })
+                })
+        })
+    }
+
+    /// Fails if any of `fields`, named by the first of each pair, contains a
+    /// denied word or phrase
+    pub fn check(&self, fields: &[(&str, &str)]) -> eyre::Result<()> {
+        for (name, text) in fields {
+            if let Some(denied) = self.find(text) {
+                return Err(eyre!(
+                    "`{}` contains `{}`, which isn't allowed in suggestions. Reword it, or ask someone who can override the filter.",
+                    name,
+                    denied
+                ));
+            }
+        }
+        Ok(())
+    }
+}
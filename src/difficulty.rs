@@ -0,0 +1,41 @@
+use std::fmt::Display;
+
+use uom::si::{
+    f64::Length,
+    length::{foot, meter, mile},
+};
+
+use crate::commands::suggest::TrailStats;
+
+/// Shenandoah National Park's hiking difficulty, the square root of the
+/// elevation gain in feet × 2 × the length in miles
+#[derive(Debug, Clone, Copy)]
+pub struct Difficulty {
+    pub score: f64,
+}
+
+impl Difficulty {
+    pub fn from_stats(stats: TrailStats) -> Self {
+        let gain = Length::new::<meter>(stats.gains).get::<foot>();
+        let length = Length::new::<meter>(stats.length).get::<mile>();
+        Difficulty {
+            score: (gain * 2.0 * length).sqrt(),
+        }
+    }
+
+    pub fn rating(self) -> &'static str {
+        match self.score {
+            score if score < 50.0 => "Easiest",
+            score if score < 100.0 => "Moderate",
+            score if score < 150.0 => "Moderately Strenuous",
+            score if score < 200.0 => "Strenuous",
+            _ => "Very Strenuous",
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (score {:.0})", self.rating(), self.score)
+    }
+}
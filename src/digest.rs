@@ -0,0 +1,152 @@
+use color_eyre::eyre::{self, Context};
+use jsonwebtoken::get_current_timestamp;
+use serde::Deserialize;
+use serenity::all::{
+    ChannelId, Color, CreateAllowedMentions, CreateEmbed, CreateMessage, MessageId,
+};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    sanitize,
+    scheduler::Job,
+    store::Suggestion,
+    AppState,
+};
+
+/// Covered by each digest, as it's posted weekly
+const WEEK: u64 = 7 * 24 * 60 * 60;
+
+/// Discord caps embed field values at 1024 characters
+const MAX_FIELD_LENGTH: usize = 1024;
+
+/// Where the weekly digest is posted. The weekday and time it's posted at are
+/// set by the `post_digest` job's schedule.
+#[derive(Deserialize, Debug)]
+pub struct DigestConfig {
+    channel_id: ChannelId,
+}
+
+/// One line per trail, with suggestions of the same trail under different
+/// names counted together
+fn new_suggestions(state: &AppState, suggestions: &[Suggestion]) -> eyre::Result<String> {
+    let mut trails: Vec<(MessageId, &Suggestion, usize)> = Vec::new();
+    for suggestion in suggestions {
+        let trail = crate::trail_names::resolve(&state.store, &suggestion.title)?
+            .map_or(suggestion.message_id, |trail| trail.message_id);
+        match trails.iter_mut().find(|(other, _, _)| *other == trail) {
+            Some((_, _, count)) => *count += 1,
+            None => trails.push((trail, suggestion, 1)),
+        }
+    }
+
+    let guild_id = state.config.load().guild_id;
+    let mut lines = String::new();
+    for (shown, (_, trail, count)) in trails.iter().enumerate() {
+        let mut line = format!(
+            "- [{}]({})",
+            sanitize::text(&trail.title, sanitize::TITLE_LENGTH),
+            trail.message_id.link(trail.channel_id, Some(guild_id))
+        );
+        if *count > 1 {
+            line.push_str(&format!(" (suggested {} times)", count));
+        }
+        line.push('\n');
+        // Leaving room for how many more there are
+        if lines.len() + line.len() > MAX_FIELD_LENGTH - 16 {
+            lines.push_str(&format!("and {} more", trails.len() - shown));
+            break;
+        }
+        lines.push_str(&line);
+    }
+    Ok(lines)
+}
+
+fn upcoming_hike(state: &AppState, hike: &Suggestion, starts_at: u64) -> eyre::Result<String> {
+    let config = state.config.load();
+    let lengths = config.lengths();
+    let interested = state
+        .store
+        .interested(hike.message_id)
+        .wrap_err("Failed to load interested members")?
+        .len();
+    let mut value = format!(
+        "[{}]({}) <t:{}:F> (<t:{}:R>)\n{} interested",
+        sanitize::text(&hike.title, sanitize::TITLE_LENGTH),
+        hike.message_id.link(hike.channel_id, Some(config.guild_id)),
+        starts_at,
+        starts_at,
+        interested
+    );
+    if let Some(length) = hike.length {
+        value.push_str(&format!(" · {}", lengths.long(length)?));
+    }
+    if let Some(gain) = hike.gain {
+        value.push_str(&format!(" · {} gain", lengths.short(gain)?));
+    }
+    Ok(value)
+}
+
+/// Posts a digest of the week's new suggestions and the next hike, so
+/// members can catch up without following every message
+pub async fn post_digest(state: &AppState) -> eyre::Result<()> {
+    let config = state.config.load();
+    let Some(digest) = &config.digest else {
+        return Ok(());
+    };
+    let now = get_current_timestamp();
+    let suggestions = state
+        .store
+        .new_suggestions(now.saturating_sub(WEEK))
+        .wrap_err("Failed to load new suggestions")?;
+    let next_hike = state
+        .store
+        .upcoming_events(now)
+        .wrap_err("Failed to load upcoming events")?
+        .into_iter()
+        .find_map(|hike| Some((hike.hiked_at?, hike)));
+
+    let mut embed = CreateEmbed::new()
+        .color(Color::DARK_GREEN)
+        .title("This week in hiking");
+    embed = if suggestions.is_empty() {
+        embed.field("New suggestions", "None this week", false)
+    } else {
+        embed.field(
+            format!("New suggestions ({})", suggestions.len()),
+            new_suggestions(state, &suggestions)?,
+            false,
+        )
+    };
+    embed = match &next_hike {
+        Some((starts_at, hike)) => {
+            embed.field("Next hike", upcoming_hike(state, hike, *starts_at)?, false)
+        }
+        None => embed.field("Next hike", "Nothing scheduled yet", false),
+    };
+    // Once a month is enough of a nudge
+    let today = OffsetDateTime::now_utc();
+    if today.day() <= 7 {
+        if let Some(hint) = crate::birthdays::hint(state, today.month())? {
+            embed = embed.field("Birthdays", hint, false);
+        }
+    }
+
+    let message = CreateMessage::new()
+        .allowed_mentions(CreateAllowedMentions::new())
+        .embed(embed);
+    let http = state.http.load();
+    audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::Job(Job::PostDigest),
+        format!("channel {}", digest.channel_id),
+        &summarize(&message),
+        digest.channel_id.send_message(http.as_ref(), message),
+    )
+    .await
+    .wrap_err("Failed to post weekly digest")?;
+    info!(new_suggestions = suggestions.len(), "Posted weekly digest");
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use color_eyre::eyre::{self, eyre, Context};
+use gpx::Gpx;
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+use crate::Config;
+
+/// Points looked up from the DEM per GPX file, the rest are interpolated.
+/// Public OpenTopoData instances take at most 100 locations per request.
+const SAMPLES: usize = 100;
+
+#[derive(Deserialize, Debug)]
+struct LookupResponse {
+    results: Vec<LookupResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LookupResult {
+    /// Missing where the dataset has no data
+    elevation: Option<f64>,
+}
+
+/// Looks up the elevation of `[latitude, longitude]` points from the
+/// OpenTopoData or Open-Elevation compatible service at `elevation_url`
+#[instrument(skip_all, fields(points = points.len()))]
+async fn lookup(points: &[[f64; 2]], config: &Config) -> eyre::Result<Vec<Option<f64>>> {
+    let locations = points
+        .iter()
+        .map(|[lat, lon]| format!("{},{}", lat, lon))
+        .collect::<Vec<_>>()
+        .join("|");
+
+    let response: LookupResponse = reqwest::Client::new()
+        .get(&config.elevation_url)
+        .query(&[("locations", locations)])
+        .send()
+        .await
+        .wrap_err("Failed to request elevation data")?
+        .error_for_status()
+        .wrap_err("Elevation service returned an error")?
+        .json()
+        .await
+        .wrap_err("Failed to get JSON from elevation service")?;
+
+    if response.results.len() != points.len() {
+        return Err(eyre!(
+            "Elevation service returned {} results for {} points",
+            response.results.len(),
+            points.len()
+        ));
+    }
+
+    Ok(response
+        .results
+        .into_iter()
+        .map(|result| result.elevation)
+        .collect())
+}
+
+/// Fills in waypoints of `gpx` without elevation, looking up to [`SAMPLES`]
+/// of them up and interpolating the rest
+#[instrument(skip_all)]
+pub async fn backfill(gpx: &mut Gpx, config: &Config) -> eyre::Result<()> {
+    if !config.elevation_backfill {
+        return Ok(());
+    }
+
+    for track in &mut gpx.tracks {
+        let mut waypoints = track
+            .segments
+            .iter_mut()
+            .flat_map(|segment| segment.points.iter_mut())
+            .collect::<Vec<_>>();
+
+        let missing = waypoints
+            .iter()
+            .enumerate()
+            .filter(|(_, waypoint)| waypoint.elevation.is_none())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            continue;
+        }
+
+        let step = missing.len().div_ceil(SAMPLES);
+        let samples = missing.iter().step_by(step).copied().collect::<Vec<_>>();
+        let coords = samples
+            .iter()
+            .map(|&i| {
+                let point = waypoints[i].point();
+                [point.y(), point.x()]
+            })
+            .collect::<Vec<_>>();
+        let elevations = lookup(&coords, config)
+            .await
+            .wrap_err("Failed to look up missing elevation data")?;
+        for (&i, elevation) in samples.iter().zip(elevations) {
+            waypoints[i].elevation = elevation;
+        }
+
+        let known = waypoints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, waypoint)| waypoint.elevation.map(|elevation| (i, elevation)))
+            .collect::<Vec<_>>();
+        if known.is_empty() {
+            return Err(eyre!("Elevation service had no data for the trail"));
+        }
+
+        // Waypoints before the first or after the last known elevation take
+        // the nearest one
+        for &i in &missing {
+            if waypoints[i].elevation.is_some() {
+                continue;
+            }
+            let next = known.partition_point(|&(k, _)| k < i);
+            let elevation = match (
+                next.checked_sub(1).and_then(|previous| known.get(previous)),
+                known.get(next),
+            ) {
+                (Some(&(a, a_elevation)), Some(&(b, b_elevation))) => {
+                    a_elevation + (b_elevation - a_elevation) * (i - a) as f64 / (b - a) as f64
+                }
+                (Some(&(_, elevation)), None) | (None, Some(&(_, elevation))) => elevation,
+                (None, None) => unreachable!(),
+            };
+            waypoints[i].elevation = Some(elevation);
+        }
+
+        info!(
+            missing = missing.len(),
+            looked_up = samples.len(),
+            "Backfilled elevation data"
+        );
+    }
+
+    Ok(())
+}
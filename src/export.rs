@@ -0,0 +1,429 @@
+use std::{collections::HashMap, io::Cursor, path::Path};
+
+use color_eyre::eyre::{self, Context};
+use jsonwebtoken::get_current_timestamp;
+use maud::DOCTYPE;
+use reqwest::header::CONTENT_TYPE;
+use serenity::all::MessageId;
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+
+use crate::{
+    store::{Suggestion, Waypoint},
+    web_interface::{api, calendar, trail, waypoints::kind_label},
+    AppState, Config,
+};
+
+/// Calendar file of a static site
+const CALENDAR: &str = "hikes.ics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Export {
+    /// `hikea export`, the pages and photos
+    Archive,
+    /// `hikea export-site`, which adds route maps, GPX, GeoJSON and a
+    /// calendar so it can be hosted as a static site
+    Site,
+}
+
+/// Writes every stored suggestion to `dir` as a static HTML page with its
+/// image downloaded next to it, along with an `index.html` linking to them,
+/// so the archive doesn't depend on Discord. Returns how many were exported.
+#[instrument(skip(state))]
+pub async fn export(state: &AppState, dir: &Path, export: Export) -> eyre::Result<usize> {
+    let images = dir.join("images");
+    std::fs::create_dir_all(&images)
+        .wrap_err_with(|| format!("Failed to create `{}`", images.display()))?;
+
+    let config = state.config.load();
+    let suggestions = state
+        .store
+        .suggestions()
+        .wrap_err("Failed to load trail suggestions")?;
+
+    if export == Export::Site {
+        // The pages are still worth having without a map to draw on
+        if let Err(e) = copy_map_assets(&config, &dir.join("assets")) {
+            warn!("{:?}", e);
+        }
+        let path = dir.join(CALENDAR);
+        std::fs::write(&path, hikes_calendar(&suggestions))
+            .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+    }
+
+    let mut thumbnails = HashMap::new();
+    for suggestion in &suggestions {
+        // The page is still worth having without its image
+        let image = download_image(state, suggestion, &images)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(message_id = %suggestion.message_id, "{:?}", e);
+                None
+            });
+
+        let waypoints = state
+            .store
+            .waypoints(suggestion.message_id)
+            .wrap_err("Failed to load waypoints")?;
+
+        let route = match export {
+            Export::Archive => None,
+            Export::Site => {
+                if let Some(thumbnail) = write_route_map(state, suggestion.message_id, dir)? {
+                    thumbnails.insert(suggestion.message_id, thumbnail);
+                }
+                write_route(state, suggestion.message_id, &waypoints, dir)?
+            }
+        };
+
+        let path = dir.join(format!("{}.html", suggestion.message_id));
+        std::fs::write(
+            &path,
+            hike_page(
+                suggestion,
+                image.as_deref(),
+                &waypoints,
+                route.as_ref(),
+                &config,
+            )?
+            .into_string(),
+        )
+        .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+        info!(title = suggestion.title, "Exported trail suggestion");
+    }
+
+    let path = dir.join("index.html");
+    let calendar = (export == Export::Site).then_some(CALENDAR);
+    std::fs::write(
+        &path,
+        index_page(&suggestions, &thumbnails, calendar, &config)?.into_string(),
+    )
+    .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+
+    Ok(suggestions.len())
+}
+
+/// Copies the map library the trail pages draw with into `assets`
+fn copy_map_assets(config: &Config, assets: &Path) -> eyre::Result<()> {
+    std::fs::create_dir_all(assets)
+        .wrap_err_with(|| format!("Failed to create `{}`", assets.display()))?;
+    for file in ["leaflet.js", "leaflet.css"] {
+        let from = config.assets_dir.join(file);
+        std::fs::copy(&from, assets.join(file))
+            .wrap_err_with(|| format!("Failed to copy `{}`", from.display()))?;
+    }
+    Ok(())
+}
+
+/// Every scheduled or completed hike as calendar entries
+fn hikes_calendar(suggestions: &[Suggestion]) -> String {
+    calendar::calendar(suggestions.iter().filter_map(|suggestion| {
+        Some(calendar::Entry {
+            uid: format!("{}@hikea", suggestion.message_id),
+            start: suggestion.hiked_at? as i64,
+            end: None,
+            summary: &suggestion.title,
+            description: suggestion.description.as_deref().unwrap_or_default(),
+            location: None,
+            suggestion: Some(suggestion),
+        })
+    }))
+}
+
+/// Writes the route map rendered for the suggestion into `maps`, returning
+/// its path relative to the export
+fn write_route_map(
+    state: &AppState,
+    message_id: MessageId,
+    dir: &Path,
+) -> eyre::Result<Option<String>> {
+    let Some(png) = state
+        .store
+        .route_map(message_id)
+        .wrap_err("Failed to load route map")?
+    else {
+        return Ok(None);
+    };
+    let file = format!("maps/{}.png", message_id);
+    let path = dir.join(&file);
+    std::fs::create_dir_all(dir.join("maps"))
+        .wrap_err_with(|| format!("Failed to create `{}`", dir.join("maps").display()))?;
+    std::fs::write(&path, png).wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(Some(file))
+}
+
+/// Writes the suggestion's stored route as `<message_id>.gpx` and
+/// `<message_id>.geojson`, returning it to be mapped
+fn write_route(
+    state: &AppState,
+    message_id: MessageId,
+    waypoints: &[Waypoint],
+    dir: &Path,
+) -> eyre::Result<Option<gpx::Gpx>> {
+    let Some(bytes) = state
+        .store
+        .suggestion_gpx(message_id)
+        .wrap_err("Failed to load GPX file")?
+    else {
+        return Ok(None);
+    };
+    let gpx = match gpx::read(Cursor::new(&bytes)) {
+        Ok(gpx) => gpx,
+        // The page is still worth having without its route
+        Err(e) => {
+            warn!(%message_id, "Failed to read stored GPX file: {:?}", e);
+            return Ok(None);
+        }
+    };
+
+    let path = dir.join(format!("{}.gpx", message_id));
+    std::fs::write(&path, &bytes)
+        .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+    let path = dir.join(format!("{}.geojson", message_id));
+    std::fs::write(&path, api::route_geojson(&gpx, waypoints).to_string())
+        .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(Some(gpx))
+}
+
+/// Downloads the image of the suggestion's embed into `images`, returning its
+/// path relative to the export
+async fn download_image(
+    state: &AppState,
+    suggestion: &Suggestion,
+    images: &Path,
+) -> eyre::Result<Option<String>> {
+    let message = state
+        .http
+        .load()
+        .get_message(suggestion.channel_id, suggestion.message_id)
+        .await
+        .wrap_err("Failed to get trail suggestion message from Discord")?;
+    let Some(url) = message
+        .embeds
+        .first()
+        .and_then(|embed| embed.image.as_ref())
+        .map(|image| image.url.clone())
+    else {
+        return Ok(None);
+    };
+
+    let response = reqwest::get(&url)
+        .await
+        .wrap_err("Failed to download trail image")?
+        .error_for_status()
+        .wrap_err("Failed to download trail image")?;
+    let extension = match response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+    {
+        Some("image/png") => "png",
+        Some("image/webp") => "webp",
+        Some("image/avif") => "avif",
+        Some("image/gif") => "gif",
+        _ => "jpg",
+    };
+    let bytes = response
+        .bytes()
+        .await
+        .wrap_err("Failed to get bytes of trail image")?;
+
+    let file = format!("{}.{}", suggestion.message_id, extension);
+    let path = images.join(&file);
+    std::fs::write(&path, bytes)
+        .wrap_err_with(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(Some(format!("images/{}", file)))
+}
+
+fn date(timestamp: u64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .map(|time| time.date().to_string())
+        .unwrap_or_default()
+}
+
+fn hike_page(
+    suggestion: &Suggestion,
+    image: Option<&str>,
+    waypoints: &[Waypoint],
+    route: Option<&gpx::Gpx>,
+    config: &Config,
+) -> eyre::Result<maud::Markup> {
+    let length = suggestion
+        .length
+        .map(|length| config.lengths().long(length))
+        .transpose()
+        .wrap_err("Failed to format length")?;
+    let gain = suggestion
+        .gain
+        .map(|gain| config.lengths().short(gain))
+        .transpose()
+        .wrap_err("Failed to format length")?;
+    let hiked_at = suggestion
+        .hiked_at
+        .filter(|_| suggestion.hiked(get_current_timestamp()));
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { (suggestion.title) }
+                @if route.is_some() {
+                    (trail::map_head("assets"))
+                }
+            }
+            body {
+                p { a href="index.html" { "All hikes" } }
+                h1 { (suggestion.title) }
+                @if let Some(image) = image {
+                    img src=(image) alt=(suggestion.title) style="max-width: 100%";
+                }
+                @if let Some(route) = route {
+                    (trail::map(config, &format!("{}.geojson", suggestion.message_id)))
+                    @if let Some(profile) = trail::profile(route) {
+                        h2 { "Elevation profile" }
+                        (profile)
+                    }
+                    p { a href=(format!("{}.gpx", suggestion.message_id)) { "GPX file" } }
+                }
+                dl {
+                    @if let Some(hiked_at) = hiked_at {
+                        dt { "Hiked" }
+                        dd { (date(hiked_at)) }
+                    }
+                    @if let Some(difficulty) = &suggestion.difficulty {
+                        dt { "Difficulty" }
+                        dd { (difficulty) }
+                    }
+                    @if let Some(length) = &length {
+                        dt { "Length" }
+                        dd { (length) }
+                    }
+                    @if let Some(gain) = &gain {
+                        dt { "Uphill" }
+                        dd { (gain) }
+                    }
+                }
+                @if let Some(description) = &suggestion.description {
+                    p style="white-space: pre-wrap" { (description) }
+                }
+                @if !waypoints.is_empty() {
+                    h2 { "Waypoints" }
+                    ul {
+                        @for waypoint in waypoints {
+                            li {
+                                (kind_label(&waypoint.kind)) ": " (waypoint.label) " ("
+                                (format!("{:.5}, {:.5}", waypoint.latitude, waypoint.longitude))
+                                ")"
+                            }
+                        }
+                    }
+                }
+                p { a href=(suggestion.link) { "View on AllTrails" } }
+            }
+        }
+    })
+}
+
+fn index_page(
+    suggestions: &[Suggestion],
+    thumbnails: &HashMap<MessageId, String>,
+    calendar: Option<&str>,
+    config: &Config,
+) -> eyre::Result<maud::Markup> {
+    let now = get_current_timestamp();
+    let (hiked, suggested): (Vec<_>, Vec<_>) = suggestions
+        .iter()
+        .partition(|suggestion| suggestion.hiked(now));
+
+    let list = |suggestions: Vec<&Suggestion>| -> eyre::Result<maud::Markup> {
+        let mut items = Vec::new();
+        for suggestion in suggestions {
+            let length = suggestion
+                .length
+                .map(|length| config.lengths().long(length))
+                .transpose()
+                .wrap_err("Failed to format length")?;
+            items.push(maud::html! {
+                li {
+                    @if let Some(thumbnail) = thumbnails.get(&suggestion.message_id) {
+                        a href=(format!("{}.html", suggestion.message_id)) {
+                            img src=(thumbnail) alt="" width="160" style="display: block";
+                        }
+                    }
+                    a href=(format!("{}.html", suggestion.message_id)) { (suggestion.title) }
+                    @if let Some(hiked_at) = suggestion.hiked_at.filter(|hiked_at| *hiked_at <= now) {
+                        " · " (date(hiked_at))
+                    }
+                    @if let Some(length) = length {
+                        " · " (length)
+                    }
+                }
+            });
+        }
+        Ok(maud::html! {
+            ul {
+                @for item in items {
+                    (item)
+                }
+            }
+        })
+    };
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Hikes" }
+            }
+            body {
+                @if let Some(calendar) = calendar {
+                    p { a href=(calendar) { "Add the hikes to your calendar" } }
+                }
+                h1 { "Hikes we've done" }
+                (list(hiked)?)
+                h1 { "Suggested hikes" }
+                (list(suggested)?)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::Point;
+    use serenity::all::ChannelId;
+
+    use super::*;
+
+    fn suggestion(id: u64, hiked_at: Option<u64>) -> Suggestion {
+        Suggestion {
+            message_id: MessageId::new(id),
+            channel_id: ChannelId::new(1),
+            title: String::from("Lake, Loop"),
+            link: String::from("https://www.alltrails.com/trail/us/utah/lake-loop"),
+            created_at: 0,
+            difficulty: None,
+            length: None,
+            gain: None,
+            hiked_at,
+            description: None,
+            beginner_score: None,
+            accessible: None,
+            trailhead: Some(Point::new(-111.5, 40.25)),
+        }
+    }
+
+    #[test]
+    fn calendars_scheduled_hikes() {
+        let ics = hikes_calendar(&[suggestion(1, None), suggestion(2, Some(1_700_000_000))]);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("UID:2@hikea\r\n"));
+        assert!(ics.contains("DTSTART:20231114T221320Z\r\n"));
+        assert!(ics.contains("SUMMARY:Lake\\, Loop\r\n"));
+        assert!(ics.contains("GEO:40.25000;-111.50000\r\n"));
+    }
+}
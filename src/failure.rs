@@ -0,0 +1,67 @@
+use std::{borrow::Cow, fmt::Display};
+
+use crate::Config;
+
+/// Failures people can do something about themselves, which error embeds
+/// suggest a next step for
+#[derive(Debug)]
+pub enum Failure {
+    /// A link that doesn't lead to a trail on a supported site, or a GPX file
+    /// that didn't come from the site the trail is on, with what was wrong
+    NotATrail(Cow<'static, str>),
+    /// A trail outside every region in `allowed_regions`, with what was
+    /// outside them
+    OutsideRegions(String),
+    /// A GPX track without elevation data
+    MissingElevation,
+    /// AllTrails turned away the session in `alltrails_cookie`
+    SessionExpired,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::NotATrail(message) => f.write_str(message),
+            Failure::OutsideRegions(message) => f.write_str(message),
+            Failure::MissingElevation => f.write_str("Waypoint does not have elevation data"),
+            Failure::SessionExpired => f.write_str("AllTrails turned away the bot's session"),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl Failure {
+    /// What to do about the failure, shown under the error
+    pub fn next_steps(&self) -> &'static str {
+        match self {
+            Failure::NotATrail(_) => {
+                "Find the trail on <https://www.alltrails.com>, Gaia GPS or CalTopo and share \
+                the link to its page, like `https://www.alltrails.com/trail/...`. GPX files \
+                have to be exported from that same page."
+            }
+            Failure::OutsideRegions(_) => {
+                "Pick a trail in one of the allowed regions, or ask an admin to add the region \
+                the trail is in"
+            }
+            Failure::MissingElevation => {
+                "Download the GPX file from the trail's AllTrails page again rather than from a \
+                recording or custom route, which can leave elevation out"
+            }
+            Failure::SessionExpired => {
+                "Ask an admin to log in to AllTrails again and update `alltrails_cookie`, or fill \
+                the suggestion in with the buttons on it in the meantime"
+            }
+        }
+    }
+}
+
+/// Whether a response from AllTrails means the session in `alltrails_cookie`
+/// has expired
+pub fn session_expired(status: reqwest::StatusCode, config: &Config) -> bool {
+    config.alltrails_cookie.is_some()
+        && matches!(
+            status,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        )
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::{self, Context};
+use serenity::{
+    all::{
+        ChannelId, Client, EventHandler, GatewayIntents, Message, MessageId, Reaction,
+        ReactionType, Ready,
+    },
+    async_trait,
+    client::Context as GatewayContext,
+};
+use tracing::{error, info, instrument};
+
+use crate::AppState;
+
+pub const INTERESTED_EMOJI: &str = "⛰️";
+
+pub fn is_interested_emoji(emoji: &ReactionType) -> bool {
+    match emoji {
+        ReactionType::Unicode(name) => {
+            name.trim_end_matches('\u{fe0f}') == INTERESTED_EMOJI.trim_end_matches('\u{fe0f}')
+        }
+        _ => false,
+    }
+}
+
+struct Handler {
+    state: Arc<AppState>,
+}
+
+impl Handler {
+    fn tracked_reaction(&self, reaction: &Reaction) -> eyre::Result<bool> {
+        if !is_interested_emoji(&reaction.emoji)
+            || reaction.member.as_ref().is_some_and(|m| m.user.bot)
+        {
+            return Ok(false);
+        }
+
+        self.state.store.is_suggestion(reaction.message_id)
+    }
+
+    #[instrument(skip(self, ctx))]
+    async fn resync(
+        &self,
+        ctx: &GatewayContext,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> eyre::Result<()> {
+        let mut users = Vec::new();
+        let mut after = None;
+        loop {
+            let page = ctx
+                .http
+                .get_reaction_users(
+                    channel_id,
+                    message_id,
+                    &ReactionType::Unicode(INTERESTED_EMOJI.to_owned()),
+                    100,
+                    after,
+                )
+                .await
+                .wrap_err("Failed to get users that reacted to suggestion")?;
+            after = page.last().map(|u| u.id.get());
+            let done = page.len() < 100;
+            users.extend(page.into_iter().filter(|u| !u.bot).map(|u| u.id));
+            if done {
+                break;
+            }
+        }
+
+        self.state
+            .store
+            .set_interested(message_id, users)
+            .wrap_err("Failed to store interested members")
+    }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: GatewayContext, ready: Ready) {
+        info!(user = ready.user.name, "Connected to Discord gateway");
+
+        let suggestions = match self.state.store.suggestions() {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to load suggestions to resync: {:?}", e);
+                return;
+            }
+        };
+
+        for suggestion in suggestions {
+            if let Err(e) = self
+                .resync(&ctx, suggestion.channel_id, suggestion.message_id)
+                .await
+            {
+                error!("Failed to resync interested members: {:?}", e);
+            }
+        }
+    }
+
+    async fn message(&self, ctx: GatewayContext, message: Message) {
+        if let Err(e) = crate::commands::details::attach_gpx(&self.state, &ctx.http, &message).await
+        {
+            error!("Failed to handle GPX reply: {:?}", e);
+        }
+    }
+
+    async fn reaction_add(&self, _ctx: GatewayContext, reaction: Reaction) {
+        let Some(user_id) = reaction.user_id else {
+            return;
+        };
+
+        let result = self.tracked_reaction(&reaction).and_then(|tracked| {
+            if tracked {
+                self.state
+                    .store
+                    .add_interested(reaction.message_id, user_id)
+            } else {
+                Ok(())
+            }
+        });
+
+        if let Err(e) = result {
+            error!("Failed to handle reaction add: {:?}", e);
+        }
+    }
+
+    async fn reaction_remove(&self, _ctx: GatewayContext, reaction: Reaction) {
+        let Some(user_id) = reaction.user_id else {
+            return;
+        };
+
+        let result = self.tracked_reaction(&reaction).and_then(|tracked| {
+            if tracked {
+                self.state
+                    .store
+                    .remove_interested(reaction.message_id, user_id)
+            } else {
+                Ok(())
+            }
+        });
+
+        if let Err(e) = result {
+            error!("Failed to handle reaction remove: {:?}", e);
+        }
+    }
+
+    async fn reaction_remove_all(
+        &self,
+        _ctx: GatewayContext,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+    ) {
+        let result = self
+            .state
+            .store
+            .is_suggestion(message_id)
+            .and_then(|tracked| {
+                if tracked {
+                    self.state.store.set_interested(message_id, [])
+                } else {
+                    Ok(())
+                }
+            });
+
+        if let Err(e) = result {
+            error!("Failed to handle reaction remove all: {:?}", e);
+        }
+    }
+
+    async fn reaction_remove_emoji(&self, _ctx: GatewayContext, reaction: Reaction) {
+        if !is_interested_emoji(&reaction.emoji) {
+            return;
+        }
+
+        let result = self
+            .state
+            .store
+            .is_suggestion(reaction.message_id)
+            .and_then(|tracked| {
+                if tracked {
+                    self.state.store.set_interested(reaction.message_id, [])
+                } else {
+                    Ok(())
+                }
+            });
+
+        if let Err(e) = result {
+            error!("Failed to handle reaction emoji removal: {:?}", e);
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn run(state: Arc<AppState>) -> eyre::Result<()> {
+    let token = state.config.load().token.clone();
+    let intents = GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(token, intents)
+        .event_handler(Handler { state })
+        .await
+        .wrap_err("Failed to build Discord gateway client")?;
+
+    client
+        .start()
+        .await
+        .wrap_err("Discord gateway client failure")
+}
@@ -0,0 +1,384 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use color_eyre::eyre::{self, Context};
+use geo::Point;
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::{debug, instrument};
+
+use crate::Config;
+
+/// Lookups kept before the cache is cleared
+const CACHE_SIZE: usize = 1024;
+
+/// Places returned by a search
+const SEARCH_LIMIT: usize = 5;
+
+/// A named place found by a geocoder
+#[derive(Debug, Clone)]
+pub struct Place {
+    pub name: String,
+    pub point: Point,
+    /// The OpenStreetMap `wheelchair` tag of the place, if the provider
+    /// returns it
+    pub wheelchair: Option<bool>,
+}
+
+/// A geocoding service
+pub trait Geocode {
+    /// Shortest time between requests the service allows
+    const MIN_INTERVAL: Duration;
+
+    /// Places matching `query`, best match first
+    async fn search(&self, query: &str) -> eyre::Result<Vec<Place>>;
+
+    /// The place at `point`, if there is one
+    async fn reverse(&self, point: Point) -> eyre::Result<Option<Place>>;
+}
+
+/// Which geocoding service lookups go to
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    #[default]
+    Nominatim,
+    Photon,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GeocoderConfig {
+    #[serde(default)]
+    pub provider: Provider,
+    /// Defaults to the public instance of the provider
+    pub url: Option<String>,
+}
+
+impl GeocoderConfig {
+    fn url(&self) -> String {
+        self.url
+            .as_deref()
+            .unwrap_or(match self.provider {
+                Provider::Nominatim => "https://nominatim.openstreetmap.org",
+                Provider::Photon => "https://photon.komoot.io",
+            })
+            .trim_end_matches('/')
+            .to_owned()
+    }
+}
+
+fn user_agent(config: &Config) -> String {
+    format!(
+        "hikea/{} ( {} )",
+        env!("CARGO_PKG_VERSION"),
+        config.hostname
+    )
+}
+
+/// [Nominatim](https://nominatim.org), whose public instance asks for at most
+/// one request a second and a user agent it can contact the owner of
+pub struct Nominatim {
+    url: String,
+    user_agent: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NominatimPlace {
+    display_name: String,
+    lat: String,
+    lon: String,
+    /// Only returned when asked for with `extratags`
+    #[serde(default)]
+    extratags: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum NominatimReverse {
+    Place(NominatimPlace),
+    Error { error: String },
+}
+
+impl NominatimPlace {
+    fn into_place(self) -> eyre::Result<Place> {
+        Ok(Place {
+            point: Point::new(
+                self.lon
+                    .parse()
+                    .wrap_err("Nominatim returned an invalid longitude")?,
+                self.lat
+                    .parse()
+                    .wrap_err("Nominatim returned an invalid latitude")?,
+            ),
+            name: self.display_name,
+            // `limited` is left unknown rather than claiming either way
+            wheelchair: self
+                .extratags
+                .as_ref()
+                .and_then(|tags| tags.get("wheelchair"))
+                .and_then(|wheelchair| match wheelchair.as_str() {
+                    "yes" | "designated" => Some(true),
+                    "no" => Some(false),
+                    _ => None,
+                }),
+        })
+    }
+}
+
+impl Geocode for Nominatim {
+    const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+    #[instrument(skip(self))]
+    async fn search(&self, query: &str) -> eyre::Result<Vec<Place>> {
+        reqwest::Client::new()
+            .get(format!("{}/search", self.url))
+            .query(&[
+                ("q", query),
+                ("format", "jsonv2"),
+                ("limit", &SEARCH_LIMIT.to_string()),
+            ])
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await
+            .wrap_err("Failed to request Nominatim search")?
+            .error_for_status()
+            .wrap_err("Nominatim returned an error")?
+            .json::<Vec<NominatimPlace>>()
+            .await
+            .wrap_err("Failed to get JSON from Nominatim search")?
+            .into_iter()
+            .map(NominatimPlace::into_place)
+            .collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn reverse(&self, point: Point) -> eyre::Result<Option<Place>> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/reverse", self.url))
+            .query(&[
+                ("lat", point.y().to_string()),
+                ("lon", point.x().to_string()),
+                ("format", String::from("jsonv2")),
+                ("extratags", String::from("1")),
+            ])
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await
+            .wrap_err("Failed to request Nominatim reverse lookup")?
+            .error_for_status()
+            .wrap_err("Nominatim returned an error")?
+            .json::<NominatimReverse>()
+            .await
+            .wrap_err("Failed to get JSON from Nominatim reverse lookup")?;
+
+        // Nominatim reports nothing being at the point as an error
+        match response {
+            NominatimReverse::Place(place) => place.into_place().map(Some),
+            NominatimReverse::Error { error } => {
+                debug!(error, "Nominatim found no place");
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// [Photon](https://photon.komoot.io), which answers with GeoJSON features
+pub struct Photon {
+    url: String,
+    user_agent: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PhotonResponse {
+    features: Vec<PhotonFeature>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PhotonFeature {
+    geometry: PhotonGeometry,
+    properties: PhotonProperties,
+}
+
+#[derive(Deserialize, Debug)]
+struct PhotonGeometry {
+    /// `[longitude, latitude]`
+    coordinates: [f64; 2],
+}
+
+#[derive(Deserialize, Debug)]
+struct PhotonProperties {
+    name: Option<String>,
+    city: Option<String>,
+    county: Option<String>,
+    state: Option<String>,
+    country: Option<String>,
+}
+
+impl From<PhotonFeature> for Place {
+    fn from(feature: PhotonFeature) -> Self {
+        let properties = feature.properties;
+        let name = [
+            properties.name,
+            properties.city,
+            properties.county,
+            properties.state,
+            properties.country,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+        let [lon, lat] = feature.geometry.coordinates;
+        Place {
+            name,
+            point: Point::new(lon, lat),
+            wheelchair: None,
+        }
+    }
+}
+
+impl Photon {
+    async fn features(&self, path: &str, query: &[(&str, String)]) -> eyre::Result<Vec<Place>> {
+        Ok(reqwest::Client::new()
+            .get(format!("{}/{}", self.url, path))
+            .query(query)
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await
+            .wrap_err("Failed to request Photon")?
+            .error_for_status()
+            .wrap_err("Photon returned an error")?
+            .json::<PhotonResponse>()
+            .await
+            .wrap_err("Failed to get JSON from Photon")?
+            .features
+            .into_iter()
+            .map(Place::from)
+            .collect())
+    }
+}
+
+impl Geocode for Photon {
+    const MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+    #[instrument(skip(self))]
+    async fn search(&self, query: &str) -> eyre::Result<Vec<Place>> {
+        self.features(
+            "api",
+            &[("q", query.to_owned()), ("limit", SEARCH_LIMIT.to_string())],
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn reverse(&self, point: Point) -> eyre::Result<Option<Place>> {
+        Ok(self
+            .features(
+                "reverse",
+                &[
+                    ("lat", point.y().to_string()),
+                    ("lon", point.x().to_string()),
+                    ("limit", String::from("1")),
+                ],
+            )
+            .await?
+            .into_iter()
+            .next())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Query {
+    Search(String),
+    /// Coordinates in hundred thousandths of a degree, about a meter, so
+    /// nearby points share a lookup
+    Reverse(i64, i64),
+}
+
+/// Geocodes through the provider in `geocoder` in the config, caching lookups
+/// and spacing requests out so public instances don't block the bot. Failed
+/// lookups aren't cached.
+#[derive(Default)]
+pub struct Geocoder {
+    cache: Mutex<HashMap<(Provider, Query), Vec<Place>>>,
+    next_request: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl Geocoder {
+    /// Places matching `query`, or `None` if no geocoder is configured
+    #[allow(dead_code)]
+    pub async fn search(&self, query: &str, config: &Config) -> eyre::Result<Option<Vec<Place>>> {
+        self.lookup(Query::Search(query.to_owned()), config).await
+    }
+
+    /// The place at `point`, or `None` if no geocoder is configured or there
+    /// is nothing there
+    pub async fn reverse(&self, point: Point, config: &Config) -> eyre::Result<Option<Place>> {
+        Ok(self
+            .lookup(
+                Query::Reverse(
+                    (point.x() * 1e5).round() as i64,
+                    (point.y() * 1e5).round() as i64,
+                ),
+                config,
+            )
+            .await?
+            .and_then(|places| places.into_iter().next()))
+    }
+
+    async fn lookup(&self, query: Query, config: &Config) -> eyre::Result<Option<Vec<Place>>> {
+        let Some(geocoder) = &config.geocoder else {
+            return Ok(None);
+        };
+        let key = (geocoder.provider, query);
+        if let Some(places) = self.cache.lock().unwrap().get(&key) {
+            return Ok(Some(places.clone()));
+        }
+
+        let user_agent = user_agent(config);
+        let places = match geocoder.provider {
+            Provider::Nominatim => {
+                let nominatim = Nominatim {
+                    url: geocoder.url(),
+                    user_agent,
+                };
+                self.wait(Nominatim::MIN_INTERVAL).await;
+                run(&nominatim, &key.1).await
+            }
+            Provider::Photon => {
+                let photon = Photon {
+                    url: geocoder.url(),
+                    user_agent,
+                };
+                self.wait(Photon::MIN_INTERVAL).await;
+                run(&photon, &key.1).await
+            }
+        }?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(key, places.clone());
+        Ok(Some(places))
+    }
+
+    /// Waits until `interval` after the previous request
+    async fn wait(&self, interval: Duration) {
+        let mut next_request = self.next_request.lock().await;
+        if let Some(next_request) = *next_request {
+            tokio::time::sleep_until(next_request).await;
+        }
+        *next_request = Some(Instant::now() + interval);
+    }
+}
+
+async fn run(geocoder: &impl Geocode, query: &Query) -> eyre::Result<Vec<Place>> {
+    match query {
+        Query::Search(query) => geocoder.search(query).await,
+        Query::Reverse(lon, lat) => Ok(geocoder
+            .reverse(Point::new(*lon as f64 / 1e5, *lat as f64 / 1e5))
+            .await?
+            .into_iter()
+            .collect()),
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{self, Context};
+use tracing::instrument;
+
+use crate::AppState;
+
+/// How long a single probe may take before it counts as unavailable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result of checking one integration, with its latency if it is available
+pub struct Probe {
+    pub name: &'static str,
+    pub result: eyre::Result<Duration>,
+}
+
+async fn probe<F>(name: &'static str, check: F) -> Probe
+where
+    F: std::future::Future<Output = eyre::Result<()>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(result) => result.map(|()| start.elapsed()),
+        Err(_) => Err(eyre::eyre!("Timed out after {:?}", PROBE_TIMEOUT)),
+    };
+    Probe { name, result }
+}
+
+async fn get(url: &str) -> eyre::Result<()> {
+    reqwest::Client::new()
+        .get(url)
+        .send()
+        .await
+        .wrap_err_with(|| format!("Failed to request `{}`", url))?
+        .error_for_status()
+        .wrap_err_with(|| format!("`{}` returned an error", url))?;
+    Ok(())
+}
+
+/// Checks every integration concurrently
+#[instrument(skip_all)]
+pub async fn probes(state: &AppState) -> Vec<Probe> {
+    let config = state.config.load();
+    let listenbrainz_url = config.services.listenbrainz("1/status/get-dump-info");
+    let alltrails_url = config.services.alltrails("robots.txt");
+    let (discord, database, listenbrainz, alltrails) = tokio::join!(
+        probe("Discord", async {
+            state
+                .http
+                .load()
+                .get_current_user()
+                .await
+                .wrap_err("Failed to get current user from Discord")?;
+            Ok(())
+        }),
+        probe("Database", async {
+            state.store.ping().wrap_err("Failed to query database")
+        }),
+        probe("ListenBrainz", get(&listenbrainz_url)),
+        probe("AllTrails", get(&alltrails_url)),
+    );
+
+    vec![discord, database, listenbrainz, alltrails]
+}
@@ -0,0 +1,4 @@
+//! The parts of hikea that don't need the bot around them, for the
+//! benchmarks and fuzz harnesses
+
+pub mod trail_stats;
@@ -1,24 +1,29 @@
 use std::{
     borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
     net::SocketAddr,
     ops::Deref,
-    sync::{atomic::AtomicU64, Arc},
+    path::PathBuf,
+    sync::{Arc, Mutex},
 };
 
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{DefaultBodyLimit, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
 use error::WithStatusCode;
 use oauth2::{ClientId, ClientSecret, RedirectUrl};
-use serde::{de::Error, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use serenity::{
     all::{
-        CreateInteractionResponse, CreateInteractionResponseFollowup,
+        CreateAutocompleteResponse, CreateInteractionResponse, CreateInteractionResponseFollowup,
         CreateInteractionResponseMessage, Verifier,
     },
     http::{Http, HttpBuilder},
@@ -29,10 +34,45 @@ use tower_http::trace::TraceLayer;
 use tracing::*;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use yaaxum_error as error;
 
+mod alltrails;
+mod audit;
+mod backpressure;
+mod badges;
+mod beginner;
+mod birthdays;
 mod commands;
-mod error;
+mod content_filter;
+mod difficulty;
+mod digest;
+mod elevation;
+mod export;
+mod failure;
+mod gateway;
+mod geocode;
+mod health;
+mod mileage;
+mod musicbrainz;
+mod offline_map;
+mod osm_notes;
+mod osrm;
+mod overpass;
+mod pace;
+mod providers;
+mod radar;
+mod reminders;
+mod route_map;
+mod route_type;
+mod sanitize;
+mod scheduler;
+mod store;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod trail_names;
+mod units;
 mod web_interface;
+mod webhooks;
 
 mod ed25519_serde {
     use serde::de::Error;
@@ -44,42 +84,237 @@ mod ed25519_serde {
         D: Deserializer<'de>,
     {
         let bytes: [u8; 32] = hex::serde::deserialize(deserializer)?;
-        Verifier::try_new(bytes).map_err(|e| D::Error::custom(e))
+        Verifier::try_new(bytes).map_err(D::Error::custom)
     }
 }
 
-mod uom_units {
-    use serde::de::Error;
-    use serde::{Deserialize, Deserializer};
-    use uom::si::length::Units;
+#[derive(Deserialize, Debug)]
+struct Bounds {
+    north: f64,
+    south: f64,
+    east: f64,
+    west: f64,
+}
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Units, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let unit_single = String::deserialize(deserializer)?;
+#[derive(Deserialize, Debug)]
+struct Region {
+    name: String,
+    alltrails_prefix: String,
+    bounds: Bounds,
+}
 
-        for unit in uom::si::length::units() {
-            if unit.singular() == unit_single {
-                return Ok(unit);
-            }
+impl Region {
+    fn rect(&self) -> geo::Rect {
+        geo::Rect::new(
+            geo::coord! { x: self.bounds.west, y: self.bounds.north },
+            geo::coord! { x: self.bounds.east, y: self.bounds.south },
+        )
+    }
+
+    fn utah() -> Vec<Region> {
+        vec![Region {
+            name: String::from("Utah"),
+            alltrails_prefix: String::from("https://www.alltrails.com/trail/us/utah"),
+            bounds: Bounds {
+                north: 42.017,
+                south: 36.933,
+                east: -108.995,
+                west: -114.093,
+            },
+        }]
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    #[default]
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    fn mime(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
         }
+    }
+
+    fn magick_format(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Where the event banner is cut out of the trail photo
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum BannerCrop {
+    #[default]
+    Top,
+    Center,
+    /// Wherever the photo has the most detail
+    Smart,
+}
 
-        let mut units = String::from("[");
+fn default_banner_quality() -> usize {
+    85
+}
+
+fn deserialize_banner_quality<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let quality = usize::deserialize(deserializer)?;
+    if !(1..=100).contains(&quality) {
+        return Err(serde::de::Error::custom(format!(
+            "`banner_quality` must be between 1 and 100, got {}",
+            quality
+        )));
+    }
+    Ok(quality)
+}
+
+fn default_max_concurrent_uploads() -> usize {
+    2
+}
+
+fn default_upload_memory_budget_mib() -> usize {
+    512
+}
+
+fn default_database() -> PathBuf {
+    PathBuf::from("./hikea.sqlite")
+}
+
+fn default_overpass_url() -> String {
+    String::from("https://overpass-api.de/api/interpreter")
+}
+
+fn default_assets_dir() -> PathBuf {
+    PathBuf::from("./assets")
+}
 
-        for unit in uom::si::length::units() {
-            units.push('`');
-            units.push_str(unit.singular());
-            units.push_str("`, ");
+fn default_osrm_url() -> String {
+    String::from("https://router.project-osrm.org")
+}
+
+fn default_elevation_url() -> String {
+    String::from("https://api.opentopodata.org/v1/srtm30m")
+}
+
+/// Base URLs of the services the bot talks to
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ServiceUrls {
+    discord: String,
+    listenbrainz: String,
+    musicbrainz: String,
+    cover_art: String,
+    alltrails: String,
+}
+
+impl Default for ServiceUrls {
+    fn default() -> Self {
+        ServiceUrls {
+            discord: String::from("https://discord.com"),
+            listenbrainz: String::from("https://api.listenbrainz.org"),
+            musicbrainz: String::from("https://musicbrainz.org"),
+            cover_art: String::from("https://coverartarchive.org"),
+            alltrails: String::from("https://www.alltrails.com"),
         }
+    }
+}
 
-        units.push(']');
+fn join_url(base: &str, path: impl Display) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), path)
+}
 
-        return Err(D::Error::invalid_value(
-            serde::de::Unexpected::Str(&unit_single),
-            &units.as_str(),
-        ));
+impl ServiceUrls {
+    fn discord(&self, path: impl Display) -> String {
+        join_url(&self.discord, path)
+    }
+
+    fn listenbrainz(&self, path: impl Display) -> String {
+        join_url(&self.listenbrainz, path)
+    }
+
+    fn musicbrainz(&self, path: impl Display) -> String {
+        join_url(&self.musicbrainz, path)
     }
+
+    fn cover_art(&self, path: impl Display) -> String {
+        join_url(&self.cover_art, path)
+    }
+
+    fn alltrails(&self, path: impl Display) -> String {
+        join_url(&self.alltrails, path)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RouteMapConfig {
+    /// With `{z}`, `{x}` and `{y}` in place of the tile coordinates
+    #[serde(default = "default_tile_url")]
+    tile_url: String,
+    #[serde(default = "default_map_attribution")]
+    attribution: String,
+}
+
+fn default_run_speed() -> f64 {
+    6.0
+}
+
+fn default_reminder_hours() -> Vec<u64> {
+    vec![48, 3]
+}
+
+fn default_tile_url() -> String {
+    String::from("https://tile.openstreetmap.org/{z}/{x}/{y}.png")
+}
+
+fn default_map_attribution() -> String {
+    String::from("© OpenStreetMap contributors")
+}
+
+/// Only used with the `otel` feature
+#[derive(Deserialize, Debug)]
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+struct OtlpConfig {
+    #[serde(default = "default_otlp_endpoint")]
+    endpoint: String,
+    /// From 0 to 1
+    #[serde(default = "default_sample_ratio")]
+    sample_ratio: f64,
+}
+
+fn default_otlp_endpoint() -> String {
+    String::from("http://localhost:4317")
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+/// Registers commands to one guild under a prefix instead of globally
+#[derive(Deserialize, Debug)]
+struct TestGuildConfig {
+    guild_id: GuildId,
+    #[serde(default = "default_command_prefix")]
+    command_prefix: String,
+    #[serde(default = "default_test_interactions_route")]
+    interactions_route: String,
+}
+
+fn default_command_prefix() -> String {
+    String::from("dev-")
+}
+
+fn default_test_interactions_route() -> String {
+    String::from("/hikea/dev/discord")
 }
 
 #[derive(Deserialize)]
@@ -95,59 +330,360 @@ struct Config {
     client_secret: ClientSecret,
     redirect_url: RedirectUrl,
     hostname: String,
-    #[serde(with = "uom_units")]
-    long_units: uom::si::length::Units,
-    #[serde(with = "uom_units")]
-    short_units: uom::si::length::Units,
+    long_units: units::ConfigLength,
+    short_units: units::ConfigLength,
+    /// Decimal places by unit name
+    #[serde(default)]
+    length_precision: HashMap<String, usize>,
+    /// In `long_units`
+    short_units_below: Option<f64>,
     avg_speed: f64,
+    #[serde(default)]
+    ruck: pace::RuckConfig,
+    /// In miles per hour, on flat ground
+    #[serde(default = "default_run_speed")]
+    run_speed: f64,
+    #[serde(default)]
+    beginner_weights: beginner::BeginnerWeights,
+    #[serde(default = "Region::utah")]
+    allowed_regions: Vec<Region>,
+    #[serde(default)]
+    banner_format: ImageFormat,
+    #[serde(
+        default = "default_banner_quality",
+        deserialize_with = "deserialize_banner_quality"
+    )]
+    banner_quality: usize,
+    #[serde(default)]
+    banner_crop: BannerCrop,
+    jwt_key: Option<String>,
+    jwt_key_path: Option<PathBuf>,
+    #[serde(default = "default_database")]
+    database: PathBuf,
+    /// Needs `leaflet.js` and `leaflet.css`
+    #[serde(default = "default_assets_dir")]
+    assets_dir: PathBuf,
+    #[serde(default)]
+    gateway: bool,
+    alltrails_cookie: Option<String>,
+    alltrails_gpx_url: Option<String>,
+    default_listenbrainz_user: Option<String>,
+    #[serde(
+        default = "scheduler::default_jobs",
+        deserialize_with = "scheduler::deserialize_jobs"
+    )]
+    jobs: HashMap<scheduler::Job, scheduler::JobConfig>,
+    /// Hours before each hike, like `[48, 3]`
+    #[serde(default = "default_reminder_hours")]
+    reminder_hours: Vec<u64>,
+    #[serde(default)]
+    badges: Vec<badges::Badge>,
+    content_filter: Option<content_filter::ContentFilterConfig>,
+    #[serde(default)]
+    webhooks: Vec<webhooks::Webhook>,
+    otlp: Option<OtlpConfig>,
+    /// Only log destructive Discord operations
+    #[serde(default)]
+    dry_run: bool,
+    test_guild: Option<TestGuildConfig>,
+    /// `[latitude, longitude]`
+    origin_coords: Option<[f64; 2]>,
+    #[serde(default = "default_osrm_url")]
+    osrm_url: String,
+    #[serde(default)]
+    elevation_backfill: bool,
+    #[serde(default = "default_elevation_url")]
+    elevation_url: String,
+    geocoder: Option<geocode::GeocoderConfig>,
+    #[serde(default)]
+    services: ServiceUrls,
+    route_map: Option<RouteMapConfig>,
+    mileage_chart: Option<mileage::MileageChartConfig>,
+    #[serde(default = "default_overpass_url")]
+    overpass_url: String,
+    digest: Option<digest::DigestConfig>,
+    radar: Option<radar::RadarConfig>,
+    offline_map: Option<offline_map::OfflineMapConfig>,
+    /// Read at startup
+    #[serde(default = "default_max_concurrent_uploads")]
+    max_concurrent_uploads: usize,
+    /// Read at startup
+    #[serde(default = "default_upload_memory_budget_mib")]
+    upload_memory_budget_mib: usize,
 }
 
+/// Keys whose values are never written to the logs
+const SECRET_CONFIG_KEYS: [&str; 5] = [
+    "token",
+    "client_secret",
+    "jwt_key",
+    "alltrails_cookie",
+    "webhooks",
+];
+
 impl Config {
-    fn from_toml() -> Result<Self, toml::de::Error> {
-        let config = toml::from_str::<Config>(
-            &std::fs::read_to_string(
-                std::env::var("CONFIG").unwrap_or_else(|_| String::from("./config.toml")),
-            )
-            .map_err(|e| toml::de::Error::custom(e))?,
-        );
+    /// Reads the config, along with its raw TOML for diffing reloads
+    fn from_toml() -> eyre::Result<(Self, toml::Table)> {
+        let path = std::env::var("CONFIG").unwrap_or_else(|_| String::from("./config.toml"));
+        let source = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read config at `{}`", path))?;
+        let config = toml::from_str::<Config>(&source)
+            .wrap_err_with(|| format!("Failed to parse config at `{}`", path))?;
+        let table = toml::from_str::<toml::Table>(&source)
+            .wrap_err_with(|| format!("Failed to parse config at `{}`", path))?;
         debug!(target: "config",  "Initialized config");
-        config
+        Ok((config, table))
+    }
+
+    fn lengths(&self) -> units::Lengths<'_> {
+        units::Lengths {
+            long_units: self.long_units.0,
+            short_units: self.short_units.0,
+            precision: &self.length_precision,
+            short_units_below: self.short_units_below,
+        }
+    }
+
+    fn pace(&self) -> pace::Pace<'_> {
+        pace::Pace {
+            avg_speed: self.avg_speed,
+            run_speed: self.run_speed,
+            ruck: &self.ruck,
+        }
+    }
+
+    /// Like `lengths`, in the units a member picked on the settings page
+    fn lengths_for(&self, preferences: &store::Preferences) -> units::Lengths<'_> {
+        let unit = |name: &Option<String>, default: units::ConfigLength| {
+            name.as_deref()
+                .and_then(units::length_unit)
+                .unwrap_or(default.0)
+        };
+        units::Lengths {
+            long_units: unit(&preferences.long_units, self.long_units),
+            short_units: unit(&preferences.short_units, self.short_units),
+            ..self.lengths()
+        }
+    }
+
+    fn interactions_route(&self) -> &str {
+        self.test_guild
+            .as_ref()
+            .map_or("/hikea/discord", |test_guild| {
+                &test_guild.interactions_route
+            })
+    }
+
+    /// The name a command was registered under, without the test guild prefix
+    fn command_name<'a>(&self, name: &'a str) -> &'a str {
+        self.test_guild
+            .as_ref()
+            .and_then(|test_guild| name.strip_prefix(test_guild.command_prefix.as_str()))
+            .unwrap_or(name)
+    }
+}
+
+/// Lists every key that was added, removed or changed between two configs,
+/// with nested tables flattened into dotted keys
+fn config_diff(old: &toml::Table, new: &toml::Table, prefix: &str, changes: &mut Vec<String>) {
+    let display = |key: &str, value: &toml::Value| {
+        if SECRET_CONFIG_KEYS.contains(&key) {
+            String::from("<redacted>")
+        } else {
+            value.to_string()
+        }
+    };
+
+    for (key, new_value) in new {
+        let path = format!("{}{}", prefix, key);
+        match (old.get(key), new_value) {
+            (None, _) => changes.push(format!("`{}` added: {}", path, display(key, new_value))),
+            (Some(toml::Value::Table(old_table)), toml::Value::Table(new_table)) => {
+                config_diff(old_table, new_table, &format!("{}.", path), changes)
+            }
+            (Some(old_value), _) if old_value != new_value => changes.push(format!(
+                "`{}` changed: {} -> {}",
+                path,
+                display(key, old_value),
+                display(key, new_value)
+            )),
+            (Some(_), _) => {}
+        }
+    }
+
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        changes.push(format!("`{}{}` removed", prefix, key));
     }
 }
 
 type ConfigSwap = ArcSwap<Config>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    Scraper,
+    Listenbrainz,
+    Suggest,
+    Search,
+    Scheduler,
+}
+
+impl Feature {
+    const ALL: [Feature; 5] = [
+        Feature::Scraper,
+        Feature::Listenbrainz,
+        Feature::Suggest,
+        Feature::Search,
+        Feature::Scheduler,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Feature::Scraper => "scraper",
+            Feature::Listenbrainz => "listenbrainz",
+            Feature::Suggest => "suggest",
+            Feature::Search => "search",
+            Feature::Scheduler => "scheduler",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+}
+
 struct AppState {
     config: ConfigSwap,
+    config_source: ArcSwap<toml::Table>,
     http: ArcSwap<Http>,
     keys: web_interface::Keys,
-    alltrails_message_on: Arc<(AtomicU64, AtomicU64)>,
+    store: store::Store,
+    scraped_trail: ArcSwapOption<(MessageId, alltrails::TrailMetadata)>,
+    disabled_features: ArcSwap<HashSet<Feature>>,
+    releases: musicbrainz::ReleaseCache,
+    geocoder: geocode::Geocoder,
+    pending_uploads: Mutex<HashMap<MessageId, web_interface::upload_gpx::PendingUpload>>,
+    audit: audit::Metrics,
+    limiter: backpressure::Limiter,
+    static_responses: StaticResponses,
 }
 
 impl AppState {
-    pub async fn derive() -> Self {
-        let config = Config::from_toml().unwrap();
+    pub async fn derive(config: Config, config_source: toml::Table) -> Self {
+        let store = store::Store::open(&config.database).unwrap();
+        let limiter = backpressure::Limiter::new(&config);
+        let disabled_features = store
+            .disabled_features()
+            .unwrap()
+            .iter()
+            .filter_map(|name| Feature::from_name(name))
+            .collect();
         AppState {
             http: ArcSwap::new(Arc::new(
                 HttpBuilder::new(config.token.clone())
                     .application_id(config.application_id)
                     .build(),
             )),
+            keys: web_interface::Keys::from_config(&config).unwrap(),
+            store,
             config: ArcSwap::new(Arc::new(config)),
-            keys: web_interface::Keys::new().unwrap(),
-            alltrails_message_on: Arc::new(Default::default()),
+            config_source: ArcSwap::from_pointee(config_source),
+            scraped_trail: ArcSwapOption::empty(),
+            disabled_features: ArcSwap::from_pointee(disabled_features),
+            releases: musicbrainz::ReleaseCache::default(),
+            geocoder: geocode::Geocoder::default(),
+            pending_uploads: Mutex::default(),
+            audit: audit::Metrics::default(),
+            limiter,
+            static_responses: StaticResponses::new().unwrap(),
+        }
+    }
+
+    /// Replaces the values of secret config keys in `text`
+    fn redact_secrets(&self, text: &mut String) {
+        let config_source = self.config_source.load();
+        for key in SECRET_CONFIG_KEYS {
+            if let Some(secret) = config_source.get(key).and_then(|value| value.as_str()) {
+                if !secret.is_empty() && text.contains(secret) {
+                    *text = text.replace(secret, "<redacted>");
+                }
+            }
+        }
+    }
+
+    /// Whether `action` is skipped for `dry_run`, logging it if so
+    pub fn dry_run(&self, action: std::fmt::Arguments) -> bool {
+        let dry_run = self.config.load().dry_run;
+        if dry_run {
+            info!(target: "dry_run", "Dry run, skipping {}", action);
+        }
+        dry_run
+    }
+
+    pub fn feature_enabled(&self, feature: Feature) -> bool {
+        !self.disabled_features.load().contains(&feature)
+    }
+
+    /// Settings `user` picked, or the defaults
+    pub fn preferences(&self, user: UserId) -> eyre::Result<store::Preferences> {
+        Ok(self
+            .store
+            .preferences(user)
+            .wrap_err("Failed to load preferences")?
+            .unwrap_or_default())
+    }
+
+    pub fn check_feature(&self, feature: Feature) -> eyre::Result<()> {
+        if self.feature_enabled(feature) {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "`{}` has been disabled by an admin, try again later",
+                feature.name()
+            ))
         }
     }
 
-    pub async fn refresh(&self) {
-        let config = Arc::new(Config::from_toml().unwrap());
+    /// Persists the new state of `feature`, then swaps it into the cached set
+    pub fn set_feature(&self, feature: Feature, enabled: bool) -> eyre::Result<()> {
+        self.store
+            .set_feature_enabled(feature.name(), enabled)
+            .wrap_err("Failed to store feature state")?;
+
+        let mut disabled = HashSet::clone(&self.disabled_features.load());
+        if enabled {
+            disabled.remove(&feature);
+        } else {
+            disabled.insert(feature);
+        }
+        self.disabled_features.store(Arc::new(disabled));
+        Ok(())
+    }
+
+    /// Reloads the config, keeping the current one if the new one is invalid
+    pub async fn refresh(&self) -> eyre::Result<()> {
+        let (config, config_source) = Config::from_toml()
+            .wrap_err("Failed to reload config, continuing with the previous config")?;
+
+        let mut changes = Vec::new();
+        config_diff(&self.config_source.load(), &config_source, "", &mut changes);
+        if changes.is_empty() {
+            info!(target: "config", "Reloaded config without changes");
+        }
+        for change in changes {
+            info!(target: "config", "Reloaded config, {}", change);
+        }
 
         self.http.store(Arc::new(
             HttpBuilder::new(config.token.clone())
                 .application_id(config.application_id)
                 .build(),
         ));
-        self.config.store(config);
+        self.config.store(Arc::new(config));
+        self.config_source.store(Arc::new(config_source));
+
+        commands::sync_commands(self)
+            .await
+            .wrap_err("Failed to sync commands with Discord after reloading config")
     }
 }
 
@@ -155,61 +691,301 @@ impl AppState {
 async fn main() -> eyre::Result<()> {
     color_eyre::install()?;
     magick_rust::magick_wand_genesis();
-    tracing_subscriber::registry()
+    let (config, config_source) = Config::from_toml().wrap_err("Failed to load config")?;
+    let registry = tracing_subscriber::registry()
         .with(ErrorLayer::default())
         .with(
             EnvFilter::try_from_default_env()
                 .or_else(|_| EnvFilter::try_new("info"))
                 .unwrap(),
         )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-    let state = Arc::new(AppState::derive().await);
-    Command::set_global_commands(
-        state.http.load().as_ref(),
-        vec![
-            commands::ping::create_command(),
-            commands::suggest::create_command(),
-            commands::inject::create_command(),
-            commands::listenbrainz::create_command(),
-            commands::convert_link::create_command(),
-        ],
-    )
-    .await
-    .wrap_err("Failed to set commands on Discord")?;
+        .with(tracing_subscriber::fmt::layer());
+    #[cfg(feature = "otel")]
+    let registry = registry.with(
+        config
+            .otlp
+            .as_ref()
+            .map(telemetry::layer)
+            .transpose()
+            .wrap_err("Failed to set up OpenTelemetry export")?,
+    );
+    registry.init();
+    #[cfg(not(feature = "otel"))]
+    if config.otlp.is_some() {
+        warn!("`otlp` is configured, but hikea was built without the `otel` feature");
+    }
+
+    // `hikea export <directory>` archives the stored hikes instead of serving,
+    // and `hikea export-site <directory>` builds a static site of them
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        let kind = match command.as_str() {
+            "export" => export::Export::Archive,
+            "export-site" => export::Export::Site,
+            _ => {
+                return Err(eyre!(
+                    "Unknown command `{}`, expected `export` or `export-site`",
+                    command
+                ))
+            }
+        };
+        let dir = PathBuf::from(
+            args.next()
+                .ok_or_else(|| eyre!("Usage: hikea {} <directory>", command))?,
+        );
+        let state = AppState::derive(config, config_source).await;
+        let exported = export::export(&state, &dir, kind)
+            .await
+            .wrap_err("Failed to export hikes")?;
+        info!(exported, "Exported hikes to `{}`", dir.display());
+        return Ok(());
+    }
+
+    let state = Arc::new(AppState::derive(config, config_source).await);
+    let state_t = Arc::clone(&state);
+    error::Hooks::new()
+        .redact(move |text| state_t.redact_secrets(text))
+        // Services the bot depends on failing isn't the bot's fault
+        .status_code(|e| {
+            let e = e.downcast_ref::<reqwest::Error>()?;
+            Some(if e.is_timeout() {
+                StatusCode::GATEWAY_TIMEOUT
+            } else {
+                StatusCode::BAD_GATEWAY
+            })
+        })
+        .next_steps(|e| {
+            e.downcast_ref::<failure::Failure>()
+                .map(|failure| Cow::Borrowed(failure.next_steps()))
+        })
+        .problem_type(|e| {
+            e.downcast_ref::<axum::extract::multipart::MultipartError>()
+                .map(|_| Cow::Borrowed("urn:hikea:problem:invalid-form"))
+        })
+        .install()?;
+    commands::sync_commands(&state)
+        .await
+        .wrap_err("Failed to sync commands with Discord")?;
+
+    // Uploads fill in suggestions, which parses GPX files and works on images
+    let uploads = Router::new()
+        .route(
+            "/hikea/upload_gpx/:channel_id/:message_id",
+            get(web_interface::upload_gpx::page),
+        )
+        .route(
+            "/hikea/upload_gpx",
+            post(web_interface::upload_gpx::post).layer(DefaultBodyLimit::max(
+                web_interface::upload_gpx::MAX_UPLOAD_SIZE,
+            )),
+        )
+        .route(
+            "/hikea/upload_gpx/confirm",
+            post(web_interface::upload_gpx::confirm),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::clone(&state),
+            backpressure::limit,
+        ));
 
     let app = Router::new()
-        .route("/hikea/discord", post(discord_interaction))
+        .route(
+            state.config.load().interactions_route(),
+            post(discord_interaction),
+        )
         .route("/hikea/oauth2", get(web_interface::initiate_oauth2))
         .route("/hikea/redirect", get(web_interface::redirect_oauth2))
+        .merge(uploads)
+        .route("/hikea/admin/jobs", get(web_interface::jobs::page))
         .route(
-            "/hikea/upload_gpx/:channel_id/:message_id",
-            get(web_interface::upload_gpx::page),
+            "/hikea/admin/commands",
+            get(web_interface::commands::page).post(web_interface::commands::delete_stale),
+        )
+        .route(
+            "/hikea/admin/waypoints",
+            get(web_interface::waypoints::index),
+        )
+        .route(
+            "/hikea/admin/waypoints/:message_id",
+            get(web_interface::waypoints::page).post(web_interface::waypoints::add),
+        )
+        .route(
+            "/hikea/admin/waypoints/:message_id/delete",
+            post(web_interface::waypoints::remove),
+        )
+        .route("/hikea/admin/aliases", get(web_interface::aliases::index))
+        .route(
+            "/hikea/admin/aliases/:message_id",
+            get(web_interface::aliases::page).post(web_interface::aliases::add),
+        )
+        .route(
+            "/hikea/admin/aliases/:message_id/delete",
+            post(web_interface::aliases::remove),
+        )
+        .route(
+            "/hikea/settings",
+            get(web_interface::settings::page).post(web_interface::settings::save),
+        )
+        .route("/hikea/calendar.ics", get(web_interface::calendar::feed))
+        .route("/hikea/feed.xml", get(web_interface::feed::feed))
+        .route("/hikea/trail/:message_id", get(web_interface::trail::page))
+        .route("/hikea/api/trail/:file", get(web_interface::api::trail))
+        .route("/hikea/assets/:file", get(web_interface::trail::asset))
+        .route(
+            "/hikea/offline/:message_id/map.mbtiles",
+            get(web_interface::offline::map),
         )
-        .route("/hikea/upload_gpx", post(web_interface::upload_gpx::post))
         .route("/hikea", get(web_interface::home_page::page))
+        .layer(axum::middleware::from_fn(error::negotiate))
         .layer(TraceLayer::new_for_http())
         .with_state(Arc::clone(&state));
 
+    if state.config.load().gateway {
+        let state_t = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = gateway::run(state_t).await {
+                error!("{:?}", e);
+            }
+        });
+    }
+
+    tokio::spawn(scheduler::run(Arc::clone(&state)));
+
     let state_t = Arc::clone(&state);
     tokio::spawn(async move {
         let mut stream = tokio::signal::unix::signal(SignalKind::hangup()).unwrap();
         loop {
             stream.recv().await;
-            state_t.refresh().await;
+            if let Err(e) = state_t.refresh().await {
+                error!("{:?}", e);
+            }
         }
     });
     let listener = tokio::net::TcpListener::bind(state.config.load().address)
         .await
         .wrap_err("Failed to bind TCP listener")?;
-    axum::serve(listener, app)
+    let result = axum::serve(listener, app)
         .await
-        .wrap_err("Axum server failure")
+        .wrap_err("Axum server failure");
+
+    #[cfg(feature = "otel")]
+    telemetry::shutdown();
+
+    result
 }
 
 #[derive(Deserialize, Serialize)]
 pub enum ComponentId<'a> {
-    Listenbrainz { time: u64, user: Cow<'a, str> },
+    Listenbrainz {
+        time: u64,
+        user: Cow<'a, str>,
+        /// Only show listens from before this timestamp, for paging
+        #[serde(rename = "b", default, skip_serializing_if = "Option::is_none")]
+        before: Option<u64>,
+        /// When the ride was ended, listens after it are left out
+        #[serde(rename = "e", default, skip_serializing_if = "Option::is_none")]
+        ended_at: Option<u64>,
+    },
+    EndRide {
+        time: u64,
+        user: Cow<'a, str>,
+    },
+    Trails {
+        #[serde(rename = "p")]
+        page: usize,
+        #[serde(rename = "f")]
+        filter: commands::trails::TrailsFilter<'a>,
+    },
+    FillDetails,
+    DetailsModal {
+        message_id: MessageId,
+    },
+    Transition {
+        to: commands::lifecycle::SuggestionState,
+    },
+    Ballot {
+        rank: u8,
+    },
+}
+
+/// Interaction responses that never change, serialized once at startup
+struct StaticResponses {
+    pong: Bytes,
+    ping: Bytes,
+    defer: Bytes,
+    defer_ephemeral: Bytes,
+}
+
+impl StaticResponses {
+    fn new() -> eyre::Result<Self> {
+        let serialize = |response: &CreateInteractionResponse| {
+            serde_json::to_vec(response)
+                .map(Bytes::from)
+                .wrap_err("Failed to serialize static interaction response")
+        };
+        Ok(StaticResponses {
+            pong: serialize(&CreateInteractionResponse::Pong)?,
+            ping: serialize(&commands::ping::respond())?,
+            defer: serialize(&CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new(),
+            ))?,
+            defer_ephemeral: serialize(&CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().ephemeral(true),
+            ))?,
+        })
+    }
+}
+
+/// A reply to an interaction, either built for it or one of the
+/// [`StaticResponses`]
+enum Reply {
+    Built(Box<CreateInteractionResponse>),
+    Static(Bytes),
+}
+
+impl From<CreateInteractionResponse> for Reply {
+    fn from(response: CreateInteractionResponse) -> Self {
+        Reply::Built(Box::new(response))
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        match self {
+            Reply::Built(response) => Json(*response).into_response(),
+            Reply::Static(bytes) => {
+                ([(header::CONTENT_TYPE, "application/json")], bytes).into_response()
+            }
+        }
+    }
+}
+
+/// Sends the response to a deferred `command`, or the error it failed with
+async fn send_followup(
+    state: &AppState,
+    command: &CommandInteraction,
+    response: Result<CreateInteractionResponseFollowup, error::DiscordError>,
+) {
+    let followup = response.unwrap_or_else(|e| {
+        CreateInteractionResponseFollowup::new()
+            .ephemeral(true)
+            .embed(e.create_embed())
+    });
+    let result = audit::audited(
+        &state.audit,
+        audit::Mutation::Followup,
+        audit::Actor::User(command.user.id),
+        format!(
+            "command `{}` in channel {}",
+            command.data.name, command.channel_id
+        ),
+        &audit::summarize(&followup),
+        command.create_followup(state.http.load().deref(), followup),
+    )
+    .await;
+    if let Err(e) = result {
+        error!("Failed to send `{}` followup: {:?}", command.data.name, e);
+    }
 }
 
 #[instrument(skip_all)]
@@ -217,8 +993,9 @@ async fn discord_interaction(
     headers: HeaderMap,
     State(state): State<Arc<AppState>>,
     body: String,
-) -> Result<Json<CreateInteractionResponse>, error::DiscordError> {
+) -> Result<Reply, error::DiscordError> {
     let config = state.config.load();
+    let responses = &state.static_responses;
     let timestamp = headers
         .get("X-Signature-Timestamp")
         .ok_or_eyre("Failed to find `X-Signature-Timestamp` in headers")
@@ -245,16 +1022,18 @@ async fn discord_interaction(
         .with_status_code(StatusCode::BAD_REQUEST)?;
 
     match interaction_body {
-        Interaction::Ping(_) => return Ok(Json(CreateInteractionResponse::Pong)),
-        Interaction::Command(command) => match command.data.name.as_str() {
-            "ping" => Ok(Json(commands::ping::respond())),
+        Interaction::Ping(_) => return Ok(Reply::Static(responses.pong.clone())),
+        Interaction::Command(command) => match config.command_name(&command.data.name) {
+            "ping" => Ok(Reply::Static(responses.ping.clone())),
+            "interested" => Ok(Reply::from(
+                commands::interested::respond(&state)
+                    .wrap_err("Failed to respond to `interested` command")
+                    .interaction_response()?,
+            )),
             "suggest" => {
-                let options = command.data.options();
-                let suggestion_command =
-                    commands::suggest::SuggestionCommand::from_options(&options)
-                        .wrap_err("Failed to initialize `suggest` command")
-                        .interaction_response()?;
-
+                state
+                    .check_feature(Feature::Suggest)
+                    .interaction_response()?;
                 let author = command
                     .member
                     .as_ref()
@@ -262,60 +1041,260 @@ async fn discord_interaction(
                     .interaction_response()?
                     .display_name()
                     .to_owned();
+                let state = Arc::clone(&state);
 
-                Ok(Json(CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().add_embed(
-                        suggestion_command
-                            .respond(&command, Arc::clone(&state), author)
-                            .await
-                            .wrap_err("Failed to respond to `suggest` command")
-                            .interaction_response()?,
-                    ),
-                )))
+                // Following the link to its canonical form can take a while,
+                // so the response is deferred first
+                tokio::spawn(async move {
+                    let options = command.data.options();
+                    let response =
+                        match commands::suggest::SuggestionCommand::from_options(&options)
+                            .wrap_err("Failed to initialize `suggest` command")
+                        {
+                            Ok(suggestion_command) => suggestion_command
+                                .respond(&command, &state, author)
+                                .await
+                                .wrap_err("Failed to respond to `suggest` command"),
+                            Err(e) => Err(e),
+                        };
+                    let (response, upload_buttons) = match response.interaction_response() {
+                        Ok((embed, upload_buttons)) => (
+                            Ok(CreateInteractionResponseFollowup::new().add_embed(embed)),
+                            upload_buttons,
+                        ),
+                        Err(e) => (Err(e), None),
+                    };
+
+                    send_followup(&state, &command, response).await;
+                    if let Some(upload_buttons) = upload_buttons {
+                        if let Err(e) = upload_buttons.attach(&state, &command).await {
+                            error!("{:?}", e);
+                        }
+                    }
+                });
+
+                Ok(Reply::Static(responses.defer.clone()))
+            }
+            "trails" => {
+                let options = command.data.options();
+                let long_units = state
+                    .preferences(command.user.id)
+                    .map(|preferences| state.config.load().lengths_for(&preferences).long_units)
+                    .interaction_response()?;
+                let filter = commands::trails::TrailsFilter::from_options(&options, long_units)
+                    .wrap_err("Failed to initialize `trails` command")
+                    .interaction_response()?;
+
+                Ok(Reply::from(
+                    commands::trails::respond(&state, filter, command.user.id)
+                        .wrap_err("Failed to respond to `trails` command")
+                        .interaction_response()?,
+                ))
+            }
+            "search" => {
+                state
+                    .check_feature(Feature::Search)
+                    .interaction_response()?;
+                let options = command.data.options();
+                let search_command = commands::search::SearchCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `search` command")
+                    .interaction_response()?;
+
+                Ok(Reply::from(
+                    search_command
+                        .respond(&state, command.user.id)
+                        .wrap_err("Failed to respond to `search` command")
+                        .interaction_response()?,
+                ))
+            }
+            "alias" => {
+                let options = command.data.options();
+                let alias_command = commands::alias::AliasCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `alias` command")
+                    .interaction_response()?;
+                if alias_command.changes_aliases() {
+                    commands::check_admin(&command, &config).interaction_response()?;
+                }
+
+                Ok(Reply::from(
+                    alias_command
+                        .respond(&command, &state)
+                        .wrap_err("Failed to respond to `alias` command")
+                        .interaction_response()?,
+                ))
+            }
+            "records" => {
+                let options = command.data.options();
+                let records_command = commands::records::RecordsCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `records` command")
+                    .interaction_response()?;
+
+                Ok(Reply::from(
+                    records_command
+                        .respond(&state)
+                        .wrap_err("Failed to respond to `records` command")
+                        .interaction_response()?,
+                ))
+            }
+            "stats" => {
+                let options = command.data.options();
+                let stats_command = commands::stats::StatsCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `stats` command")
+                    .interaction_response()?;
+
+                Ok(Reply::from(
+                    stats_command
+                        .respond(&state, command.user.id)
+                        .wrap_err("Failed to respond to `stats` command")
+                        .interaction_response()?,
+                ))
             }
             "listenbrainz" => {
+                state
+                    .check_feature(Feature::Listenbrainz)
+                    .interaction_response()?;
                 let options = command.data.options();
                 let listenbrainz_command =
                     commands::listenbrainz::ListenbrainzCommand::from_options(&options)
                         .wrap_err("Failed to initialize `listenbrainz` command")
                         .interaction_response()?;
 
-                Ok(Json(
+                Ok(Reply::from(
                     listenbrainz_command
                         .respond()
                         .wrap_err("Failed to respond to `listenbrainz` command")
                         .interaction_response()?,
                 ))
             }
-            "Inject hike into recent event" => {
+            "nowplaying" => {
+                state
+                    .check_feature(Feature::Listenbrainz)
+                    .interaction_response()?;
+                let options = command.data.options();
+                let nowplaying_command =
+                    commands::nowplaying::NowplayingCommand::from_options(&options)
+                        .wrap_err("Failed to initialize `nowplaying` command")
+                        .interaction_response()?;
                 let state = Arc::clone(&state);
 
                 tokio::spawn(async move {
-                    let response = commands::inject::respond(&command, Arc::clone(&state))
+                    let response = nowplaying_command
+                        .respond(&state)
                         .await
-                        .wrap_err("Failed to respond to `inject_hike` command")
+                        .wrap_err("Failed to respond to `nowplaying` command")
                         .interaction_response();
 
-                    // TODO: Handle these errors
-                    match response {
-                        Ok(r) => command.create_followup(state.http.load().deref(), r).await,
-                        Err(e) => {
-                            command
-                                .create_followup(
-                                    state.http.load().deref(),
-                                    CreateInteractionResponseFollowup::new()
-                                        .ephemeral(true)
-                                        .embed(e.create_embed()),
-                                )
+                    send_followup(&state, &command, response).await;
+                });
+
+                Ok(Reply::Static(responses.defer.clone()))
+            }
+            "feature" => {
+                commands::check_admin(&command, &config).interaction_response()?;
+                let options = command.data.options();
+                let feature_command = commands::feature::FeatureCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `feature` command")
+                    .interaction_response()?;
+
+                Ok(Reply::from(
+                    feature_command
+                        .respond(&state)
+                        .wrap_err("Failed to respond to `feature` command")
+                        .interaction_response()?,
+                ))
+            }
+            "jobs" => {
+                commands::check_admin(&command, &config).interaction_response()?;
+                let options = command.data.options();
+                let jobs_command = commands::jobs::JobsCommand::from_options(&options)
+                    .wrap_err("Failed to initialize `jobs` command")
+                    .interaction_response()?;
+
+                match jobs_command {
+                    commands::jobs::JobsCommand::List => Ok(Reply::from(
+                        commands::jobs::list(&state)
+                            .wrap_err("Failed to respond to `jobs list` command")
+                            .interaction_response()?,
+                    )),
+                    commands::jobs::JobsCommand::Retry { id } => {
+                        let state = Arc::clone(&state);
+
+                        tokio::spawn(async move {
+                            let response = commands::jobs::retry(Arc::clone(&state), id)
                                 .await
-                        }
+                                .wrap_err("Failed to respond to `jobs retry` command")
+                                .interaction_response();
+
+                            send_followup(&state, &command, response).await;
+                        });
+
+                        Ok(Reply::Static(responses.defer_ephemeral.clone()))
                     }
+                }
+            }
+            "status" => {
+                commands::check_admin(&command, &config).interaction_response()?;
+                let state = Arc::clone(&state);
+
+                tokio::spawn(async move {
+                    let response = commands::status::respond(Arc::clone(&state))
+                        .await
+                        .wrap_err("Failed to respond to `status` command")
+                        .interaction_response();
+
+                    send_followup(&state, &command, response).await;
+                });
+
+                Ok(Reply::Static(responses.defer_ephemeral.clone()))
+            }
+            "compare" => {
+                let state = Arc::clone(&state);
+
+                tokio::spawn(async move {
+                    let response = commands::compare::respond(&command, Arc::clone(&state))
+                        .await
+                        .wrap_err("Failed to respond to `compare` command")
+                        .interaction_response();
+
+                    send_followup(&state, &command, response).await;
+                });
+
+                Ok(Reply::Static(responses.defer.clone()))
+            }
+            "vote" => {
+                commands::check_admin(&command, &config).interaction_response()?;
+                let state = Arc::clone(&state);
+
+                tokio::spawn(async move {
+                    let response = commands::vote::respond(&command, Arc::clone(&state))
+                        .await
+                        .wrap_err("Failed to respond to `vote` command")
+                        .interaction_response();
+
+                    send_followup(&state, &command, response).await;
+                });
+
+                Ok(Reply::Static(responses.defer_ephemeral.clone()))
+            }
+            "Inject hike into recent event" | "inject" => {
+                let state = Arc::clone(&state);
+
+                tokio::spawn(async move {
+                    let response = commands::inject::respond(&command, Arc::clone(&state))
+                        .await
+                        .wrap_err("Failed to respond to `inject_hike` command")
+                        .interaction_response();
+
+                    send_followup(&state, &command, response).await;
                 });
 
-                Ok(Json(CreateInteractionResponse::Defer(
-                    CreateInteractionResponseMessage::new().ephemeral(true),
-                )))
+                Ok(Reply::Static(responses.defer_ephemeral.clone()))
             }
+            "Show hike history" => Ok(Reply::from(
+                commands::history::respond(&command, &state)
+                    .wrap_err("Failed to respond to `Show hike history` command")
+                    .interaction_response()?,
+            )),
             "Convert to hiking suggestion" => {
                 let state = Arc::clone(&state);
 
@@ -324,24 +1303,155 @@ async fn discord_interaction(
                     .wrap_err("Failed to respond to `inject_hike` command")
                     .interaction_response()?;
 
-                Ok(Json(CreateInteractionResponse::UpdateMessage(response)))
+                Ok(Reply::from(response))
             }
             name => {
                 return Err(eyre!("Command `{:?}` not implemented", name)).interaction_response()?
             }
         },
         Interaction::Component(component_interaction) => {
-            match serde_json::from_str(&component_interaction.data.custom_id)
-                .wrap_err("Failed to deserialize interaction custom_id")
+            let Ok(component_id) = serde_json::from_str(&component_interaction.data.custom_id)
+            else {
+                return Ok(Reply::from(commands::stale_component::respond(
+                    &component_interaction,
+                    &state,
+                )));
+            };
+
+            match component_id {
+                ComponentId::Listenbrainz {
+                    time,
+                    user,
+                    before,
+                    ended_at,
+                } => {
+                    state
+                        .check_feature(Feature::Listenbrainz)
+                        .interaction_response()?;
+                    Ok(Reply::from(CreateInteractionResponse::UpdateMessage(
+                        commands::listenbrainz::update_message(
+                            &state, time, &user, before, ended_at,
+                        )
+                        .await
+                        .wrap_err("Failed to update listenbrainz message")
+                        .interaction_response()?,
+                    )))
+                }
+                ComponentId::EndRide { time, user } => {
+                    state
+                        .check_feature(Feature::Listenbrainz)
+                        .interaction_response()?;
+                    Ok(Reply::from(CreateInteractionResponse::UpdateMessage(
+                        commands::listenbrainz::update_message(
+                            &state,
+                            time,
+                            &user,
+                            None,
+                            Some(jsonwebtoken::get_current_timestamp()),
+                        )
+                        .await
+                        .wrap_err("Failed to end listenbrainz ride")
+                        .interaction_response()?,
+                    )))
+                }
+                ComponentId::FillDetails => Ok(Reply::from(
+                    commands::details::open_modal(&component_interaction, &state.config.load())
+                        .wrap_err("Failed to open trail details modal")
+                        .interaction_response()?,
+                )),
+                ComponentId::DetailsModal { .. } => {
+                    return Err(eyre!("Modal ID was used as a component")).interaction_response()?
+                }
+                ComponentId::Transition { to } => {
+                    let response = commands::lifecycle::transition(
+                        &component_interaction,
+                        &state,
+                        &state.config.load(),
+                        to,
+                    )
+                    .wrap_err("Failed to change suggestion status")
+                    .interaction_response()?;
+
+                    let event = match to {
+                        commands::lifecycle::SuggestionState::Approved => {
+                            Some(webhooks::Event::Approved)
+                        }
+                        commands::lifecycle::SuggestionState::Scheduled => {
+                            Some(webhooks::Event::Scheduled)
+                        }
+                        _ => None,
+                    };
+                    if let Some(event) = event {
+                        match state.store.suggestion(component_interaction.message.id) {
+                            Ok(Some(suggestion)) => {
+                                tokio::spawn(webhooks::notify(
+                                    state.config.load_full(),
+                                    event,
+                                    suggestion,
+                                    Some(component_interaction.user.id),
+                                ));
+                            }
+                            Ok(None) => {}
+                            Err(e) => error!("Failed to load suggestion for webhooks: {:?}", e),
+                        }
+                    }
+
+                    if to == commands::lifecycle::SuggestionState::Completed {
+                        let state = Arc::clone(&state);
+                        let message_id = component_interaction.message.id;
+                        let completed_by = component_interaction.user.id;
+                        tokio::spawn(async move {
+                            if let Err(e) = badges::award(&state, message_id, completed_by).await {
+                                error!("Failed to award badges: {:?}", e);
+                            }
+                        });
+                    }
+                    Ok(Reply::from(response))
+                }
+                ComponentId::Ballot { rank } => Ok(Reply::from(
+                    commands::vote::cast(&component_interaction, &state, rank)
+                        .wrap_err("Failed to cast ballot")
+                        .interaction_response()?,
+                )),
+                ComponentId::Trails { page, filter } => {
+                    Ok(Reply::from(CreateInteractionResponse::UpdateMessage(
+                        commands::trails::render_page(
+                            &state,
+                            page,
+                            filter,
+                            component_interaction.user.id,
+                        )
+                        .wrap_err("Failed to render page of trails")
+                        .interaction_response()?,
+                    )))
+                }
+            }
+        }
+        Interaction::Autocomplete(command) => match config.command_name(&command.data.name) {
+            "compare" | "alias" => Ok(Reply::from(
+                // Discord only shows choices, so failures can't be shown anyway
+                commands::alias::autocomplete(&command, &state).unwrap_or_else(|e| {
+                    error!("Failed to autocomplete trail: {:?}", e);
+                    CreateInteractionResponse::Autocomplete(CreateAutocompleteResponse::new())
+                }),
+            )),
+            name => {
+                return Err(eyre!("Command `{}` has no autocomplete", name))
+                    .interaction_response()?
+            }
+        },
+        Interaction::Modal(modal) => {
+            match serde_json::from_str(&modal.data.custom_id)
+                .wrap_err("Failed to deserialize modal custom_id")
                 .interaction_response()?
             {
-                ComponentId::Listenbrainz { time, user } => {
-                    Ok(Json(CreateInteractionResponse::UpdateMessage(
-                        commands::listenbrainz::update_message(time, &user)
-                            .await
-                            .wrap_err("Failed to update listenbrainz message")
-                            .interaction_response()?,
-                    )))
+                ComponentId::DetailsModal { message_id } => Ok(Reply::from(
+                    commands::details::submit(&modal, &state, message_id)
+                        .wrap_err("Failed to save trail details")
+                        .interaction_response()?,
+                )),
+                _ => {
+                    return Err(eyre!("Component ID was used as a modal")).interaction_response()?
                 }
             }
         }
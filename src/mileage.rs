@@ -0,0 +1,175 @@
+use color_eyre::eyre::{self, Context, OptionExt};
+use geo::{
+    line_intersection::{line_intersection, LineIntersection},
+    BoundingRect, Distance, Haversine, Intersects, Line, MultiLineString, Point, Rect,
+};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::{store::Waypoint, web_interface::waypoints::kind_label, Config};
+
+/// File name the chart is attached to the suggestion under
+pub const FILE_NAME: &str = "mileage.txt";
+
+/// Roads that count as crossings, leaving out driveways and parking aisles
+const HIGHWAYS: &str =
+    "^(motorway|trunk|primary|secondary|tertiary|unclassified|residential|track)$";
+
+/// Crossings of the same road closer together than this many meters are
+/// counted once, such as where the route meets a road right at a GPX point
+const SAME_CROSSING: f64 = 50.0;
+
+/// Where the mileage chart of long routes looks up road crossings
+#[derive(Deserialize, Debug)]
+pub struct MileageChartConfig {
+    /// Routes at least this many `long_units` long get a chart
+    min_length: f64,
+    /// In place of the server wide `overpass_url`
+    overpass_url: Option<String>,
+}
+
+/// A road from OpenStreetMap, with its bounds so most of the route can be
+/// skipped when looking for where it's crossed
+struct Road {
+    bounds: Rect,
+    lines: Vec<Line>,
+    name: String,
+}
+
+/// Roads in OpenStreetMap around `track`
+async fn roads(
+    track: &MultiLineString,
+    chart: &MileageChartConfig,
+    config: &Config,
+) -> eyre::Result<Vec<Road>> {
+    let bounds = track
+        .bounding_rect()
+        .ok_or_eyre("GPX track has no points")?;
+    let overpass_url = chart
+        .overpass_url
+        .as_deref()
+        .unwrap_or(&config.overpass_url);
+    let ways = crate::overpass::highways(overpass_url, HIGHWAYS, bounds, config).await?;
+
+    Ok(ways
+        .into_iter()
+        .filter_map(|way| {
+            Some(Road {
+                bounds: way.line.bounding_rect()?,
+                lines: way.line.lines().collect(),
+                name: way.name().unwrap_or_else(|| String::from("Unnamed road")),
+            })
+        })
+        .collect())
+}
+
+/// Meters along `track` of each of its points, with gaps between segments not
+/// counted
+fn distances(track: &gpx::Track) -> Vec<(Point, f64)> {
+    let mut points = Vec::new();
+    let mut distance = 0.0;
+    for segment in &track.segments {
+        for (i, point) in segment.points.iter().enumerate() {
+            if i > 0 {
+                distance += Haversine::distance(segment.points[i - 1].point(), point.point());
+            }
+            points.push((point.point(), distance));
+        }
+    }
+    points
+}
+
+/// Meters along the route of the track point closest to `point`
+fn distance_along(points: &[(Point, f64)], point: Point) -> Option<f64> {
+    points
+        .iter()
+        .map(|(track_point, distance)| (Haversine::distance(*track_point, point), *distance))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, distance)| distance)
+}
+
+/// A table of road crossings and `waypoints` with their distance along the
+/// route, or `None` if the route is shorter than `min_length`
+#[instrument(skip_all)]
+pub async fn chart(
+    track: &gpx::Track,
+    title: &str,
+    waypoints: &[Waypoint],
+    chart: &MileageChartConfig,
+    config: &Config,
+) -> eyre::Result<Option<String>> {
+    let lengths = config.lengths();
+    let points = distances(track);
+    let total = points.last().map_or(0.0, |(_, distance)| *distance);
+    let min_length = crate::units::length_to_meters(chart.min_length, lengths.long_units)
+        .wrap_err("Failed to convert minimum mileage chart length")?;
+    if total < min_length {
+        return Ok(None);
+    }
+
+    let roads = roads(&track.multilinestring(), chart, config).await?;
+
+    let mut stops = Vec::new();
+    let mut first = 0;
+    for segment in &track.segments {
+        for (i, pair) in segment.points.windows(2).enumerate() {
+            let line = Line::new(pair[0].point().0, pair[1].point().0);
+            let start = points[first + i].1;
+            for road in &roads {
+                if !road.bounds.intersects(&line.bounding_rect()) {
+                    continue;
+                }
+                for road_line in &road.lines {
+                    let crossing = match line_intersection(line, *road_line) {
+                        Some(LineIntersection::SinglePoint { intersection, .. }) => intersection,
+                        Some(LineIntersection::Collinear { intersection }) => intersection.start,
+                        None => continue,
+                    };
+                    let distance =
+                        start + Haversine::distance(pair[0].point(), Point::from(crossing));
+                    stops.push((distance, format!("Road crossing: {}", road.name)));
+                }
+            }
+        }
+        first += segment.points.len();
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut crossings: Vec<(f64, String)> = Vec::new();
+    for stop in stops {
+        if !crossings
+            .iter()
+            .any(|(distance, name)| *name == stop.1 && stop.0 - distance < SAME_CROSSING)
+        {
+            crossings.push(stop);
+        }
+    }
+
+    let mut stops = crossings;
+    for waypoint in waypoints {
+        if let Some(distance) =
+            distance_along(&points, Point::new(waypoint.longitude, waypoint.latitude))
+        {
+            stops.push((
+                distance,
+                format!("{}: {}", kind_label(&waypoint.kind), waypoint.label),
+            ));
+        }
+    }
+    stops.push((0.0, String::from("Start")));
+    stops.push((total, String::from("End")));
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut table = format!("Mileage chart for {}\n\n", title);
+    table.push_str(&format!("{:>12}  {:>12}  {}\n", "Distance", "Leg", "Stop"));
+    let mut last = 0.0;
+    for (distance, stop) in &stops {
+        table.push_str(&format!(
+            "{:>12}  {:>12}  {}\n",
+            lengths.long(*distance)?,
+            lengths.long(distance - last)?,
+            stop
+        ));
+        last = *distance;
+    }
+    Ok(Some(table))
+}
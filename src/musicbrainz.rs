@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use color_eyre::eyre::{self, Context};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::{instrument, warn};
+
+use crate::Config;
+
+/// Resolved releases kept before the cache is cleared
+const CACHE_SIZE: usize = 1024;
+
+/// How a listen's release is looked up on MusicBrainz, from most to least
+/// specific
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReleaseQuery {
+    Release(String),
+    Recording(String),
+    Search { artist: String, track: String },
+}
+
+/// A release resolved through MusicBrainz
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub mbid: String,
+    pub release_group_mbid: Option<String>,
+    pub year: Option<String>,
+}
+
+impl Release {
+    /// Cover art of the release group, which is more likely to have art than
+    /// any one release in it
+    pub fn cover_art(&self, config: &Config) -> String {
+        match &self.release_group_mbid {
+            Some(mbid) => config
+                .services
+                .cover_art(format!("release-group/{}/front-500", mbid)),
+            None => config
+                .services
+                .cover_art(format!("release/{}/front-500", self.mbid)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRelease {
+    id: String,
+    date: Option<String>,
+    #[serde(rename = "release-group")]
+    release_group: Option<MbReleaseGroup>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbReleaseGroup {
+    id: String,
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRecording {
+    #[serde(default)]
+    releases: Vec<MbRelease>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRecordingSearch {
+    recordings: Vec<MbRecording>,
+}
+
+impl From<MbRelease> for Release {
+    fn from(release: MbRelease) -> Self {
+        let year = release
+            .release_group
+            .as_ref()
+            .and_then(|group| group.first_release_date.as_deref())
+            .or(release.date.as_deref())
+            .filter(|date| date.len() >= 4)
+            .map(|date| date[..4].to_owned());
+        Release {
+            mbid: release.id,
+            release_group_mbid: release.release_group.map(|group| group.id),
+            year,
+        }
+    }
+}
+
+/// The earliest dated release of a recording, so the year is its original
+/// release rather than a compilation
+fn earliest(releases: Vec<MbRelease>) -> Option<Release> {
+    releases
+        .into_iter()
+        .min_by(|a, b| match (&a.date, &b.date) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        })
+        .map(Release::from)
+}
+
+#[instrument(skip(config))]
+async fn lookup(query: &ReleaseQuery, config: &Config) -> eyre::Result<Option<Release>> {
+    let request = match query {
+        ReleaseQuery::Release(mbid) => reqwest::Client::new()
+            .get(
+                config
+                    .services
+                    .musicbrainz(format!("ws/2/release/{}", mbid)),
+            )
+            .query(&[("inc", "release-groups")]),
+        ReleaseQuery::Recording(mbid) => reqwest::Client::new()
+            .get(
+                config
+                    .services
+                    .musicbrainz(format!("ws/2/recording/{}", mbid)),
+            )
+            .query(&[("inc", "releases release-groups")]),
+        ReleaseQuery::Search { artist, track } => reqwest::Client::new()
+            .get(config.services.musicbrainz("ws/2/recording"))
+            .query(&[
+                (
+                    "query",
+                    format!(
+                        "recording:\"{}\" AND artist:\"{}\"",
+                        track.replace('"', ""),
+                        artist.replace('"', "")
+                    )
+                    .as_str(),
+                ),
+                ("limit", "1"),
+            ]),
+    };
+
+    // MusicBrainz asks for a user agent it can contact the owner of
+    let response = request
+        .query(&[("fmt", "json")])
+        .header(
+            "User-Agent",
+            format!(
+                "hikea/{} ( {} )",
+                env!("CARGO_PKG_VERSION"),
+                config.hostname
+            ),
+        )
+        .send()
+        .await
+        .wrap_err("Failed to request MusicBrainz")?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = response
+        .error_for_status()
+        .wrap_err("MusicBrainz returned an error")?;
+
+    match query {
+        ReleaseQuery::Release(_) => Ok(Some(
+            response
+                .json::<MbRelease>()
+                .await
+                .wrap_err("Failed to get JSON from MusicBrainz release")?
+                .into(),
+        )),
+        ReleaseQuery::Recording(_) => Ok(earliest(
+            response
+                .json::<MbRecording>()
+                .await
+                .wrap_err("Failed to get JSON from MusicBrainz recording")?
+                .releases,
+        )),
+        ReleaseQuery::Search { .. } => Ok(response
+            .json::<MbRecordingSearch>()
+            .await
+            .wrap_err("Failed to get JSON from MusicBrainz recording search")?
+            .recordings
+            .into_iter()
+            .next()
+            .and_then(|recording| earliest(recording.releases))),
+    }
+}
+
+/// Releases resolved so far, so paging through listens doesn't look them up
+/// again. Failed lookups aren't cached.
+#[derive(Default)]
+pub struct ReleaseCache {
+    releases: Mutex<HashMap<ReleaseQuery, Option<Release>>>,
+}
+
+impl ReleaseCache {
+    /// Resolves the release for `query`, giving up at `deadline` since
+    /// MusicBrainz shouldn't hold up an interaction response
+    pub async fn resolve(
+        &self,
+        query: ReleaseQuery,
+        config: &Config,
+        deadline: Instant,
+    ) -> Option<Release> {
+        if let Some(release) = self.releases.lock().unwrap().get(&query) {
+            return release.clone();
+        }
+
+        match tokio::time::timeout_at(deadline, lookup(&query, config)).await {
+            Ok(Ok(release)) => {
+                let mut releases = self.releases.lock().unwrap();
+                if releases.len() >= CACHE_SIZE {
+                    releases.clear();
+                }
+                releases.insert(query, release.clone());
+                release
+            }
+            Ok(Err(e)) => {
+                warn!("{:?}", e);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeSet;
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use geo::BoundingRect;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use tracing::{instrument, warn};
+
+use crate::{
+    route_map::{download_tile, project},
+    Config,
+};
+
+const TILE_SIZE: f64 = 256.0;
+
+/// Meters per pixel at the equator at zoom 0
+const EQUATOR_RESOLUTION: f64 = 156_543.033_92;
+
+/// Zoom, column and row of a map tile
+type Tile = (u32, i64, i64);
+
+/// Which map tiles go into the offline map of each route
+#[derive(Deserialize, Debug)]
+pub struct OfflineMapConfig {
+    #[serde(default = "default_zooms")]
+    zooms: Vec<u32>,
+    /// Meters on each side of the route covered by tiles
+    #[serde(default = "default_corridor")]
+    corridor: f64,
+    /// Routes needing more tiles than this are turned away, to go easy on the
+    /// tile server
+    #[serde(default = "default_max_tiles")]
+    max_tiles: usize,
+}
+
+fn default_zooms() -> Vec<u32> {
+    vec![12, 13, 14, 15]
+}
+
+fn default_corridor() -> f64 {
+    500.0
+}
+
+fn default_max_tiles() -> usize {
+    1500
+}
+
+/// Tiles at `zoom` within `corridor` meters of any point of `track`
+fn corridor_tiles(track: &gpx::Track, zoom: u32, corridor: f64, tiles: &mut BTreeSet<Tile>) {
+    let last = 2i64.pow(zoom) - 1;
+    for point in track.segments.iter().flat_map(|segment| &segment.points) {
+        let point = point.point();
+        let (x, y) = project(point.x(), point.y(), zoom);
+        let radius =
+            corridor / (EQUATOR_RESOLUTION * point.y().to_radians().cos() / 2f64.powi(zoom as i32));
+        let tile = |pixel: f64| ((pixel / TILE_SIZE).floor() as i64).clamp(0, last);
+        for tile_x in tile(x - radius)..=tile(x + radius) {
+            for tile_y in tile(y - radius)..=tile(y + radius) {
+                tiles.insert((zoom, tile_x, tile_y));
+            }
+        }
+    }
+}
+
+/// Writes `tiles` into a new MBTiles database at `path`
+fn write_mbtiles(
+    path: &std::path::Path,
+    title: &str,
+    bounds: geo::Rect,
+    zooms: (u32, u32),
+    attribution: &str,
+    tiles: &[(Tile, Vec<u8>)],
+) -> eyre::Result<()> {
+    let mut connection = Connection::open(path).wrap_err("Failed to create MBTiles file")?;
+    let transaction = connection
+        .transaction()
+        .wrap_err("Failed to start MBTiles transaction")?;
+    transaction
+        .execute_batch(
+            "CREATE TABLE metadata (name TEXT, value TEXT);
+            CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+            CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);",
+        )
+        .wrap_err("Failed to create MBTiles tables")?;
+
+    let metadata = [
+        ("name", title.to_owned()),
+        ("format", String::from("png")),
+        ("type", String::from("baselayer")),
+        (
+            "bounds",
+            format!(
+                "{},{},{},{}",
+                bounds.min().x,
+                bounds.min().y,
+                bounds.max().x,
+                bounds.max().y
+            ),
+        ),
+        ("minzoom", zooms.0.to_string()),
+        ("maxzoom", zooms.1.to_string()),
+        ("attribution", attribution.to_owned()),
+    ];
+    for (name, value) in metadata {
+        transaction
+            .execute(
+                "INSERT INTO metadata (name, value) VALUES (?1, ?2)",
+                params![name, value],
+            )
+            .wrap_err("Failed to write MBTiles metadata")?;
+    }
+
+    for ((zoom, x, y), data) in tiles {
+        // MBTiles counts rows from the bottom
+        let row = 2i64.pow(*zoom) - 1 - y;
+        transaction
+            .execute(
+                "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+                params![zoom, x, row, data],
+            )
+            .wrap_err("Failed to write MBTiles tile")?;
+    }
+    transaction
+        .commit()
+        .wrap_err("Failed to commit MBTiles file")
+}
+
+/// An MBTiles file of the map tiles along `track`, for map apps to use
+/// without signal
+#[instrument(skip_all)]
+pub async fn package(
+    track: &gpx::Track,
+    title: &str,
+    offline_map: &OfflineMapConfig,
+    config: &Config,
+) -> eyre::Result<Vec<u8>> {
+    let bounds = track
+        .multilinestring()
+        .bounding_rect()
+        .ok_or_eyre("GPX track has no points")?;
+    let mut wanted = BTreeSet::new();
+    for zoom in &offline_map.zooms {
+        corridor_tiles(track, *zoom, offline_map.corridor, &mut wanted);
+    }
+    if wanted.len() > offline_map.max_tiles {
+        return Err(eyre!(
+            "The route needs {} map tiles, more than the {} an offline map can have",
+            wanted.len(),
+            offline_map.max_tiles
+        ));
+    }
+
+    let (tile_url, attribution) = match &config.route_map {
+        Some(route_map) => (route_map.tile_url.clone(), route_map.attribution.clone()),
+        None => (crate::default_tile_url(), crate::default_map_attribution()),
+    };
+    let client = reqwest::Client::new();
+    let mut tiles = Vec::with_capacity(wanted.len());
+    for (zoom, x, y) in wanted {
+        let url = tile_url
+            .replace("{z}", &zoom.to_string())
+            .replace("{x}", &x.to_string())
+            .replace("{y}", &y.to_string());
+        tiles.push(((zoom, x, y), download_tile(&client, &url, config).await?));
+    }
+
+    let zooms = (
+        offline_map.zooms.iter().copied().min().unwrap_or_default(),
+        offline_map.zooms.iter().copied().max().unwrap_or_default(),
+    );
+    let path = std::env::temp_dir().join(format!("hikea-{}.mbtiles", fastrand::u64(..)));
+    let written = write_mbtiles(&path, title, bounds, zooms, &attribution, &tiles)
+        .and_then(|_| std::fs::read(&path).wrap_err("Failed to read MBTiles file"));
+    if let Err(e) = std::fs::remove_file(&path) {
+        warn!("Failed to remove temporary MBTiles file: {:?}", e);
+    }
+    written
+}
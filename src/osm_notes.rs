@@ -0,0 +1,155 @@
+use color_eyre::eyre::{self, OptionExt};
+use geo::{BoundingRect, Distance, Haversine, Point};
+use tracing::instrument;
+
+use crate::{commands::compare::local, Config};
+
+/// Ways a hike could have followed
+const TRAILS: &str = "^(path|footway|track|bridleway|steps|cycleway|pedestrian)$";
+
+/// Meters a recorded point can be from every mapped trail before it counts as
+/// off of them, past the usual GPS error
+const DIVERGENCE: f64 = 25.0;
+
+/// Meters the recording has to stay off of mapped trails to be worth a
+/// mapper's time
+const MIN_DIVERGENCE_LENGTH: f64 = 100.0;
+
+/// Degrees the area trails are looked up in is grown by past the recording,
+/// so trails just beside its edges are found
+const MARGIN: f64 = 0.002;
+
+/// File name the notes are attached under
+pub const FILE_NAME: &str = "osm-notes.txt";
+
+/// A stretch of the recording away from every trail mapped in OpenStreetMap
+#[derive(Debug)]
+pub struct Divergence {
+    pub start: Point,
+    pub end: Point,
+    /// Meters
+    pub length: f64,
+    /// Southwest and northeast corners
+    bounds: (Point, Point),
+}
+
+/// Meters from `point` to the segment from `start` to `end`, all in local
+/// meters
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    ((point.0 - start.0 - t * dx).powi(2) + (point.1 - start.1 - t * dy).powi(2)).sqrt()
+}
+
+/// Stretches of `recorded` at least `MIN_DIVERGENCE_LENGTH` long that are
+/// more than `DIVERGENCE` from every trail in OpenStreetMap
+#[instrument(skip_all)]
+pub async fn divergences(recorded: &gpx::Track, config: &Config) -> eyre::Result<Vec<Divergence>> {
+    let bounds = recorded
+        .multilinestring()
+        .bounding_rect()
+        .ok_or_eyre("Recording has no points")?;
+    let bounds = geo::Rect::new(
+        (bounds.min().x - MARGIN, bounds.min().y - MARGIN),
+        (bounds.max().x + MARGIN, bounds.max().y + MARGIN),
+    );
+    let origin = Point::from(bounds.center());
+    let trails = crate::overpass::highways(&config.overpass_url, TRAILS, bounds, config)
+        .await?
+        .iter()
+        .flat_map(|way| way.line.lines().collect::<Vec<_>>())
+        .map(|line| {
+            (
+                local(line.start.into(), origin),
+                local(line.end.into(), origin),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let off_trail = |point: Point| {
+        let point = local(point, origin);
+        !trails.iter().any(|(start, end)| {
+            // Cheap check first, most segments are nowhere near
+            point.0 >= start.0.min(end.0) - DIVERGENCE
+                && point.0 <= start.0.max(end.0) + DIVERGENCE
+                && point.1 >= start.1.min(end.1) - DIVERGENCE
+                && point.1 <= start.1.max(end.1) + DIVERGENCE
+                && segment_distance(point, *start, *end) <= DIVERGENCE
+        })
+    };
+
+    let mut divergences = Vec::new();
+    for segment in &recorded.segments {
+        let mut current: Option<Divergence> = None;
+        for (i, point) in segment.points.iter().enumerate() {
+            let point = point.point();
+            if off_trail(point) {
+                let divergence = current.get_or_insert(Divergence {
+                    start: point,
+                    end: point,
+                    length: 0.0,
+                    bounds: (point, point),
+                });
+                if i > 0 {
+                    divergence.length += Haversine::distance(segment.points[i - 1].point(), point);
+                }
+                divergence.end = point;
+                divergence.bounds = (
+                    Point::new(
+                        divergence.bounds.0.x().min(point.x()),
+                        divergence.bounds.0.y().min(point.y()),
+                    ),
+                    Point::new(
+                        divergence.bounds.1.x().max(point.x()),
+                        divergence.bounds.1.y().max(point.y()),
+                    ),
+                );
+            } else if let Some(divergence) = current.take() {
+                divergences.push(divergence);
+            }
+        }
+        divergences.extend(current);
+    }
+    divergences.retain(|divergence| divergence.length >= MIN_DIVERGENCE_LENGTH);
+    Ok(divergences)
+}
+
+/// A note for mappers on each of `divergences`, with links to fix them in
+/// iD and JOSM
+pub fn notes(title: &str, divergences: &[Divergence]) -> String {
+    let mut notes = format!(
+        "Stretches of a recording of {} more than {} m from any trail in OpenStreetMap.\n\
+        Check them against imagery before editing, the recording may have been off trail.\n",
+        title, DIVERGENCE
+    );
+    for (i, divergence) in divergences.iter().enumerate() {
+        let (southwest, northeast) = divergence.bounds;
+        let center = (
+            (southwest.y() + northeast.y()) / 2.0,
+            (southwest.x() + northeast.x()) / 2.0,
+        );
+        notes.push_str(&format!(
+            "\n{}. {:.0} m from {:.5}, {:.5} to {:.5}, {:.5}\n\
+            iD: https://www.openstreetmap.org/edit?editor=id#map=18/{:.5}/{:.5}\n\
+            JOSM: http://127.0.0.1:8111/load_and_zoom?left={:.5}&bottom={:.5}&right={:.5}&top={:.5}\n",
+            i + 1,
+            divergence.length,
+            divergence.start.y(),
+            divergence.start.x(),
+            divergence.end.y(),
+            divergence.end.x(),
+            center.0,
+            center.1,
+            southwest.x(),
+            southwest.y(),
+            northeast.x(),
+            northeast.y()
+        ));
+    }
+    notes
+}
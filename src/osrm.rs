@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use geo::Point;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::{commands::suggest::trailhead, Config};
+
+/// The fastest drive between two points
+#[derive(Debug, Clone, Copy)]
+pub struct Drive {
+    pub duration: Duration,
+    /// One way, in meters
+    pub distance: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct RouteResponse {
+    code: String,
+    message: Option<String>,
+    #[serde(default)]
+    routes: Vec<Route>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Route {
+    /// Seconds
+    duration: f64,
+    /// Meters
+    distance: f64,
+}
+
+/// Asks the OSRM server at `osrm_url` for the fastest drive from `from` to `to`
+#[instrument(skip(config))]
+pub async fn drive(from: Point, to: Point, config: &Config) -> eyre::Result<Drive> {
+    let response: RouteResponse = reqwest::Client::new()
+        .get(format!(
+            "{}/route/v1/driving/{},{};{},{}",
+            config.osrm_url.trim_end_matches('/'),
+            from.x(),
+            from.y(),
+            to.x(),
+            to.y()
+        ))
+        .query(&[("overview", "false")])
+        .send()
+        .await
+        .wrap_err("Failed to request route from OSRM")?
+        .json()
+        .await
+        .wrap_err("Failed to get JSON from OSRM route response")?;
+
+    if response.code != "Ok" {
+        return Err(eyre!(
+            "OSRM could not find a route: {} {}",
+            response.code,
+            response.message.unwrap_or_default()
+        ));
+    }
+
+    let route = response
+        .routes
+        .first()
+        .ok_or_eyre("OSRM returned no routes")?;
+    Ok(Drive {
+        duration: Duration::from_secs_f64(route.duration),
+        distance: route.distance,
+    })
+}
+
+/// The drive from `origin_coords` to the trailhead of `gpx`, if an origin is
+/// configured
+pub async fn drive_to_trailhead(gpx: &gpx::Gpx, config: &Config) -> eyre::Result<Option<Drive>> {
+    let Some([lat, lon]) = config.origin_coords else {
+        return Ok(None);
+    };
+    let trailhead = trailhead(gpx).wrap_err("Failed to find trailhead")?;
+    drive(Point::new(lon, lat), trailhead, config)
+        .await
+        .wrap_err("Failed to find drive time to trailhead")
+        .map(Some)
+}
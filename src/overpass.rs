@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::{self, Context};
+use geo::{Coord, LineString, Rect};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::Config;
+
+#[derive(Deserialize, Debug)]
+struct OverpassResponse {
+    elements: Vec<OverpassWay>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OverpassWay {
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    geometry: Vec<OverpassPoint>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OverpassPoint {
+    lat: f64,
+    lon: f64,
+}
+
+/// A way from OpenStreetMap
+pub struct Way {
+    pub tags: HashMap<String, String>,
+    pub line: LineString,
+}
+
+impl Way {
+    /// The way's name and reference number, whichever it has
+    pub fn name(&self) -> Option<String> {
+        match (self.tags.get("name"), self.tags.get("ref")) {
+            (Some(name), Some(reference)) => Some(format!("{} ({})", name, reference)),
+            (Some(name), None) => Some(name.clone()),
+            (None, Some(reference)) => Some(reference.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Ways in OpenStreetMap within `bounds` whose `highway` tag matches the
+/// `highways` regex, from the Overpass API at `url`
+#[instrument(skip(config))]
+pub async fn highways(
+    url: &str,
+    highways: &str,
+    bounds: Rect,
+    config: &Config,
+) -> eyre::Result<Vec<Way>> {
+    let query = format!(
+        "[out:json][timeout:60];way[\"highway\"~\"{}\"]({},{},{},{});out tags geom;",
+        highways,
+        bounds.min().y,
+        bounds.min().x,
+        bounds.max().y,
+        bounds.max().x
+    );
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .form(&[("data", query)])
+        .header(
+            "User-Agent",
+            format!(
+                "hikea/{} ( {} )",
+                env!("CARGO_PKG_VERSION"),
+                config.hostname
+            ),
+        )
+        .send()
+        .await
+        .wrap_err("Failed to request ways from Overpass")?
+        .error_for_status()
+        .wrap_err("Overpass returned an error")?
+        .json::<OverpassResponse>()
+        .await
+        .wrap_err("Failed to get JSON from Overpass")?;
+
+    Ok(response
+        .elements
+        .into_iter()
+        .map(|way| Way {
+            line: way
+                .geometry
+                .iter()
+                .map(|point| Coord {
+                    x: point.lon,
+                    y: point.lat,
+                })
+                .collect(),
+            tags: way.tags,
+        })
+        .collect())
+}
@@ -0,0 +1,168 @@
+use color_eyre::eyre::{self, eyre};
+use geo::{Distance, Haversine};
+use serde::Deserialize;
+use uom::si::{
+    f64::{Mass, Velocity},
+    mass::{kilogram, pound},
+    velocity::{meter_per_second, mile_per_hour},
+};
+
+use crate::failure::Failure;
+
+/// Joules in a food calorie
+const JOULES_PER_KCAL: f64 = 4184.0;
+
+/// Who ruck estimates are made for
+#[derive(Deserialize, Debug)]
+pub struct RuckConfig {
+    /// Weight of the hiker without a pack, in pounds
+    #[serde(default = "default_body_weight")]
+    pub body_weight: f64,
+    /// Pandolf's terrain factor, from 1.0 on a treadmill to 2.1 in a swamp
+    #[serde(default = "default_terrain_factor")]
+    pub terrain_factor: f64,
+}
+
+impl Default for RuckConfig {
+    fn default() -> Self {
+        RuckConfig {
+            body_weight: default_body_weight(),
+            terrain_factor: default_terrain_factor(),
+        }
+    }
+}
+
+fn default_body_weight() -> f64 {
+    180.0
+}
+
+/// Pandolf's factor for light brush, the closest to a dirt trail
+fn default_terrain_factor() -> f64 {
+    1.2
+}
+
+/// How fast members hike and run, from `avg_speed`, `run_speed` and `ruck` in
+/// the config
+#[derive(Debug, Clone, Copy)]
+pub struct Pace<'a> {
+    /// Miles per hour hiking without a pack
+    pub avg_speed: f64,
+    /// Miles per hour running on flat ground
+    pub run_speed: f64,
+    pub ruck: &'a RuckConfig,
+}
+
+/// What the group is doing on the trail, picked per upload since some of the
+/// group runs the same routes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    #[default]
+    Hike,
+    TrailRun,
+}
+
+impl Activity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hike" => Some(Activity::Hike),
+            "trail_run" => Some(Activity::TrailRun),
+            _ => None,
+        }
+    }
+}
+
+/// Minetti's energy cost of running up a `grade` (rise over run) incline, in
+/// joules per kilogram per meter
+fn running_cost(grade: f64) -> f64 {
+    // Minetti only measured grades this steep
+    let i = grade.clamp(-0.45, 0.45);
+    155.4 * i.powi(5) - 30.4 * i.powi(4) - 43.3 * i.powi(3) + 46.3 * i.powi(2) + 19.5 * i + 3.6
+}
+
+/// How much longer running `track` takes than running as far on flat ground,
+/// from its grade adjusted pace
+pub fn grade_adjustment(track: &gpx::Track) -> eyre::Result<f64> {
+    let mut flat = 0.0;
+    let mut adjusted = 0.0;
+    for points in track.segments.iter().flat_map(|s| s.points.windows(2)) {
+        let distance = Haversine::distance(points[0].point(), points[1].point());
+        if distance == 0.0 {
+            continue;
+        }
+        let rise = points[1].elevation.ok_or(Failure::MissingElevation)?
+            - points[0].elevation.ok_or(Failure::MissingElevation)?;
+        flat += distance;
+        adjusted += distance * running_cost(rise / distance) / running_cost(0.0);
+    }
+    if flat == 0.0 {
+        return Err(eyre!("GPX track doesn't go anywhere"));
+    }
+    Ok(adjusted / flat)
+}
+
+/// Time and energy spent hiking a trail
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Estimate {
+    pub hours: f64,
+    pub calories: f64,
+}
+
+/// Pandolf's metabolic rate in watts of a `body` kg hiker carrying `load` kg
+/// at `speed` m/s up a `grade` percent incline
+fn pandolf(body: f64, load: f64, speed: f64, grade: f64, terrain: f64) -> f64 {
+    1.5 * body
+        + 2.0 * (body + load) * (load / body).powi(2)
+        + terrain * (body + load) * (1.5 * speed.powi(2) + 0.35 * speed * grade)
+}
+
+/// The speed [`pandolf`] gives `rate` watts at, solved from its quadratic
+fn pandolf_speed(rate: f64, body: f64, load: f64, grade: f64, terrain: f64) -> f64 {
+    let a = 1.5 * terrain * (body + load);
+    let b = 0.35 * terrain * (body + load) * grade;
+    let c = 1.5 * body + 2.0 * (body + load) * (load / body).powi(2) - rate;
+    ((b * b - 4.0 * a * c).max(0.0).sqrt() - b) / (2.0 * a)
+}
+
+impl Pace<'_> {
+    /// Estimates for hiking `track` without a pack and with a `load` pound
+    /// one. Descents count as flat, since Pandolf doesn't cover them.
+    pub fn ruck(&self, track: &gpx::Track, load: f64) -> eyre::Result<(Estimate, Estimate)> {
+        let body = Mass::new::<pound>(self.ruck.body_weight).get::<kilogram>();
+        let load_kg = Mass::new::<pound>(load).get::<kilogram>();
+        let speed = Velocity::new::<mile_per_hour>(self.avg_speed).get::<meter_per_second>();
+        let terrain = self.ruck.terrain_factor;
+
+        let mut unloaded = Estimate::default();
+        let mut loaded = Estimate::default();
+        for points in track.segments.iter().flat_map(|s| s.points.windows(2)) {
+            let distance = Haversine::distance(points[0].point(), points[1].point());
+            if distance == 0.0 {
+                continue;
+            }
+            let rise = points[1].elevation.ok_or(Failure::MissingElevation)?
+                - points[0].elevation.ok_or(Failure::MissingElevation)?;
+            let grade = (rise / distance * 100.0).max(0.0);
+
+            let rate = pandolf(body, 0.0, speed, grade, terrain);
+            let seconds = distance / speed;
+            unloaded.hours += seconds / 3600.0;
+            unloaded.calories += rate * seconds / JOULES_PER_KCAL;
+
+            let loaded_speed = pandolf_speed(rate, body, load_kg, grade, terrain);
+            if loaded_speed <= 0.0 {
+                return Err(eyre!(
+                    "A {} lb pack is too heavy to keep going at {} mph",
+                    load,
+                    self.avg_speed
+                ));
+            }
+            let seconds = distance / loaded_speed;
+            loaded.hours += seconds / 3600.0;
+            loaded.calories += rate * seconds / JOULES_PER_KCAL;
+        }
+        if unloaded.hours == 0.0 {
+            return Err(eyre!("GPX track doesn't go anywhere"));
+        }
+        Ok((unloaded, loaded))
+    }
+}
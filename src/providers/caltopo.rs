@@ -0,0 +1,42 @@
+use color_eyre::eyre;
+use reqwest::Url;
+
+use crate::{failure::Failure, Config};
+
+use super::TrailProvider;
+
+/// [CalTopo](https://caltopo.com), whose share links point at a map with the
+/// route drawn on it. SARTopo is the same site under another name.
+pub struct CalTopo;
+
+/// The ID of the map `url` links to, if it does
+fn map_id(url: &Url) -> Option<&str> {
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    if segments.next()? != "m" {
+        return None;
+    }
+    segments.next()
+}
+
+impl TrailProvider for CalTopo {
+    const NAME: &'static str = "CalTopo";
+
+    fn recognizes(url: &Url) -> bool {
+        super::on_domain(url, "caltopo.com") || super::on_domain(url, "sartopo.com")
+    }
+
+    fn is_trail(url: &Url) -> bool {
+        map_id(url).is_some()
+    }
+
+    async fn canonical_link(url: Url, _config: &Config) -> eyre::Result<String> {
+        map_id(&url)
+            .map(|id| format!("https://caltopo.com/m/{}", id))
+            .ok_or_else(|| Failure::NotATrail("CalTopo link does not lead to a map".into()).into())
+    }
+
+    fn exported(gpx: &gpx::Gpx) -> bool {
+        super::exported_by(gpx, "caltopo", "caltopo.com")
+            || super::exported_by(gpx, "sartopo", "sartopo.com")
+    }
+}
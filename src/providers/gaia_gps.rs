@@ -0,0 +1,55 @@
+use color_eyre::eyre;
+use reqwest::Url;
+
+use crate::{failure::Failure, Config};
+
+use super::TrailProvider;
+
+/// [Gaia GPS](https://www.gaiagps.com), whose share links point at public
+/// tracks, routes and hikes
+pub struct GaiaGps;
+
+/// Paths of pages for a single trail, followed by its ID
+const TRAIL_PATHS: [&[&str]; 4] = [
+    &["public"],
+    &["hike"],
+    &["datasummary", "track"],
+    &["datasummary", "route"],
+];
+
+/// The path of `url` up to and including the trail's ID, if it's a trail page
+fn trail_path(url: &Url) -> Option<String> {
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    TRAIL_PATHS.iter().find_map(|prefix| {
+        let id = segments.get(prefix.len())?;
+        (segments.starts_with(prefix) && !id.is_empty())
+            .then(|| format!("{}/{}", prefix.join("/"), id))
+    })
+}
+
+impl TrailProvider for GaiaGps {
+    const NAME: &'static str = "Gaia GPS";
+
+    fn recognizes(url: &Url) -> bool {
+        super::on_domain(url, "gaiagps.com")
+    }
+
+    fn is_trail(url: &Url) -> bool {
+        trail_path(url).is_some()
+    }
+
+    async fn canonical_link(url: Url, _config: &Config) -> eyre::Result<String> {
+        trail_path(&url)
+            .map(|path| format!("https://www.gaiagps.com/{}/", path))
+            .ok_or_else(|| {
+                Failure::NotATrail("Gaia GPS link does not lead to a trail".into()).into()
+            })
+    }
+
+    fn exported(gpx: &gpx::Gpx) -> bool {
+        super::exported_by(gpx, "gaia", "gaiagps.com")
+    }
+}
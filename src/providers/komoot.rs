@@ -0,0 +1,58 @@
+use color_eyre::eyre;
+use reqwest::Url;
+
+use crate::{failure::Failure, Config};
+
+use super::TrailProvider;
+
+/// [Komoot](https://www.komoot.com), whose links point at planned or recorded
+/// tours, optionally behind a language prefix such as `/de-de`
+pub struct Komoot;
+
+/// Kinds of page for a single trail, followed by its ID
+const TRAIL_KINDS: [&str; 2] = ["tour", "smarttour"];
+
+/// The kind and ID of the tour `url` links to, if it does
+fn tour(url: &Url) -> Option<(&str, &str)> {
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    let mut kind = segments.next()?;
+    // Past the language prefix, if there is one
+    if !TRAIL_KINDS.contains(&kind) {
+        kind = segments.next()?;
+    }
+    let id = segments.next()?;
+    (TRAIL_KINDS.contains(&kind) && id.bytes().all(|byte| byte.is_ascii_digit()))
+        .then_some((kind, id))
+}
+
+impl TrailProvider for Komoot {
+    const NAME: &'static str = "Komoot";
+
+    fn recognizes(url: &Url) -> bool {
+        super::on_domain(url, "komoot.com") || super::on_domain(url, "komoot.de")
+    }
+
+    fn is_trail(url: &Url) -> bool {
+        tour(url).is_some()
+    }
+
+    async fn canonical_link(url: Url, _config: &Config) -> eyre::Result<String> {
+        let (kind, id) = tour(&url)
+            .ok_or_else(|| Failure::NotATrail("Komoot link does not lead to a tour".into()))?;
+        // Private tours can only be seen with the token they were shared with
+        Ok(
+            match url.query_pairs().find(|(key, _)| key == "share_token") {
+                Some((_, token)) => format!(
+                    "https://www.komoot.com/{}/{}?share_token={}",
+                    kind, id, token
+                ),
+                None => format!("https://www.komoot.com/{}/{}", kind, id),
+            },
+        )
+    }
+
+    fn exported(gpx: &gpx::Gpx) -> bool {
+        super::exported_by(gpx, "komoot", "komoot.com")
+            || super::exported_by(gpx, "komoot", "komoot.de")
+    }
+}
@@ -0,0 +1,145 @@
+use color_eyre::eyre::{self, Context};
+use reqwest::Url;
+use tracing::instrument;
+
+use crate::{alltrails::AllTrails, failure::Failure, Config};
+
+mod caltopo;
+mod gaia_gps;
+mod komoot;
+mod outdooractive;
+
+pub use caltopo::CalTopo;
+pub use gaia_gps::GaiaGps;
+pub use komoot::Komoot;
+pub use outdooractive::Outdooractive;
+
+/// A site trails can be suggested from
+pub trait TrailProvider {
+    /// Shown to people, such as in errors and button labels
+    const NAME: &'static str;
+
+    /// Whether `url` is on the provider's site
+    fn recognizes(url: &Url) -> bool;
+
+    /// Whether `url` plainly links to a trail, without following it anywhere
+    fn is_trail(url: &Url) -> bool;
+
+    /// The canonical form of a link to a trail on the provider's site
+    async fn canonical_link(url: Url, config: &Config) -> eyre::Result<String>;
+
+    /// Whether `gpx` was exported from the provider
+    fn exported(gpx: &gpx::Gpx) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    AllTrails,
+    GaiaGps,
+    CalTopo,
+    Komoot,
+    Outdooractive,
+}
+
+impl Provider {
+    const ALL: [Provider; 5] = [
+        Provider::AllTrails,
+        Provider::GaiaGps,
+        Provider::CalTopo,
+        Provider::Komoot,
+        Provider::Outdooractive,
+    ];
+
+    /// The provider whose site `link` is on
+    pub fn of(link: &str) -> Option<Self> {
+        Self::of_url(&Url::parse(link.trim()).ok()?)
+    }
+
+    pub fn of_url(url: &Url) -> Option<Self> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        Self::ALL.into_iter().find(|provider| match provider {
+            Provider::AllTrails => AllTrails::recognizes(url),
+            Provider::GaiaGps => GaiaGps::recognizes(url),
+            Provider::CalTopo => CalTopo::recognizes(url),
+            Provider::Komoot => Komoot::recognizes(url),
+            Provider::Outdooractive => Outdooractive::recognizes(url),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::AllTrails => AllTrails::NAME,
+            Provider::GaiaGps => GaiaGps::NAME,
+            Provider::CalTopo => CalTopo::NAME,
+            Provider::Komoot => Komoot::NAME,
+            Provider::Outdooractive => Outdooractive::NAME,
+        }
+    }
+
+    pub fn is_trail(self, url: &Url) -> bool {
+        match self {
+            Provider::AllTrails => AllTrails::is_trail(url),
+            Provider::GaiaGps => GaiaGps::is_trail(url),
+            Provider::CalTopo => CalTopo::is_trail(url),
+            Provider::Komoot => Komoot::is_trail(url),
+            Provider::Outdooractive => Outdooractive::is_trail(url),
+        }
+    }
+
+    pub fn exported(self, gpx: &gpx::Gpx) -> bool {
+        match self {
+            Provider::AllTrails => AllTrails::exported(gpx),
+            Provider::GaiaGps => GaiaGps::exported(gpx),
+            Provider::CalTopo => CalTopo::exported(gpx),
+            Provider::Komoot => Komoot::exported(gpx),
+            Provider::Outdooractive => Outdooractive::exported(gpx),
+        }
+    }
+}
+
+/// Every provider's name, for listing in messages
+pub fn names() -> String {
+    Provider::ALL.map(Provider::name).join(", ")
+}
+
+/// The canonical link to the trail `link` points to, along with the provider
+/// it's on
+#[instrument(skip(config))]
+pub async fn canonical_link(link: &str, config: &Config) -> eyre::Result<(Provider, String)> {
+    let url = Url::parse(link.trim()).wrap_err("Trail suggestion is not a valid link")?;
+    let provider = Provider::of_url(&url).ok_or_else(|| {
+        Failure::NotATrail(format!("Trail suggestion was not from any of {}", names()).into())
+    })?;
+    let link = match provider {
+        Provider::AllTrails => AllTrails::canonical_link(url, config).await,
+        Provider::GaiaGps => GaiaGps::canonical_link(url, config).await,
+        Provider::CalTopo => CalTopo::canonical_link(url, config).await,
+        Provider::Komoot => Komoot::canonical_link(url, config).await,
+        Provider::Outdooractive => Outdooractive::canonical_link(url, config).await,
+    }?;
+    Ok((provider, link))
+}
+
+/// Whether `url` is on `domain` or one of its subdomains
+fn on_domain(url: &Url, domain: &str) -> bool {
+    url.host_str().is_some_and(|host| {
+        host.eq_ignore_ascii_case(domain)
+            || host.to_ascii_lowercase().ends_with(&format!(".{}", domain))
+    })
+}
+
+/// Whether `gpx` names `creator` as the program that created it, or links to
+/// `domain` in its metadata
+fn exported_by(gpx: &gpx::Gpx, creator: &str, domain: &str) -> bool {
+    gpx.creator
+        .as_deref()
+        .is_some_and(|name| name.to_ascii_lowercase().contains(creator))
+        || gpx.metadata.as_ref().is_some_and(|metadata| {
+            metadata
+                .links
+                .iter()
+                .any(|link| Url::parse(&link.href).is_ok_and(|href| on_domain(&href, domain)))
+        })
+}
@@ -0,0 +1,52 @@
+use color_eyre::eyre;
+use reqwest::Url;
+
+use crate::{failure::Failure, Config};
+
+use super::TrailProvider;
+
+/// [Outdooractive](https://www.outdooractive.com), whose route pages end in
+/// the route's ID after its category, region and name, such as
+/// `/en/route/hiking/utah/bell-canyon/12345678/`
+pub struct Outdooractive;
+
+/// The ID of the route `url` links to, if it does
+fn route_id(url: &Url) -> Option<&str> {
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    // Both full route pages and `/en/r/12345678` short links
+    let kind = segments
+        .iter()
+        .position(|segment| matches!(*segment, "route" | "r"))?;
+    let id = segments[kind + 1..]
+        .iter()
+        .rev()
+        .find(|segment| segment.bytes().all(|byte| byte.is_ascii_digit()))?;
+    Some(id)
+}
+
+impl TrailProvider for Outdooractive {
+    const NAME: &'static str = "Outdooractive";
+
+    fn recognizes(url: &Url) -> bool {
+        super::on_domain(url, "outdooractive.com")
+    }
+
+    fn is_trail(url: &Url) -> bool {
+        route_id(url).is_some()
+    }
+
+    async fn canonical_link(url: Url, _config: &Config) -> eyre::Result<String> {
+        route_id(&url)
+            .map(|id| format!("https://www.outdooractive.com/en/r/{}", id))
+            .ok_or_else(|| {
+                Failure::NotATrail("Outdooractive link does not lead to a route".into()).into()
+            })
+    }
+
+    fn exported(gpx: &gpx::Gpx) -> bool {
+        super::exported_by(gpx, "outdooractive", "outdooractive.com")
+    }
+}
@@ -0,0 +1,223 @@
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use magick_rust::{DrawingWand, MagickWand};
+use serde::Deserialize;
+use serenity::all::{CreateAllowedMentions, CreateAttachment, CreateMessage};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    route_map::{draw_tiles, pixel, project},
+    sanitize,
+    scheduler::Job,
+    store::Suggestion,
+    AppState, Config,
+};
+
+/// Size of the snapshot, in pixels
+const WIDTH: usize = 640;
+const HEIGHT: usize = 400;
+
+const BACKGROUND_COLOR: &str = "#f2efe9";
+const TRAILHEAD_COLOR: &str = "#d9480f";
+const TRAILHEAD_RADIUS: f64 = 6.0;
+
+/// RainViewer's universal blue colors, smoothed and with snow shown apart
+/// from rain
+const RADAR_TILE: &str = "/256/{z}/{x}/{y}/2/1_1.png";
+
+const FILE_NAME: &str = "radar.png";
+
+/// Where the radar snapshot posted on the day of each hike comes from
+#[derive(Deserialize, Debug)]
+pub struct RadarConfig {
+    /// Posted once a hike starts within this many hours
+    #[serde(default = "default_hours_before")]
+    hours_before: u64,
+    /// RainViewer only has radar down to zoom 7
+    #[serde(default = "default_zoom")]
+    zoom: u32,
+    #[serde(default = "default_rainviewer_url")]
+    rainviewer_url: String,
+}
+
+fn default_hours_before() -> u64 {
+    4
+}
+
+fn default_zoom() -> u32 {
+    7
+}
+
+fn default_rainviewer_url() -> String {
+    String::from("https://api.rainviewer.com/public/weather-maps.json")
+}
+
+#[derive(Deserialize, Debug)]
+struct WeatherMaps {
+    host: String,
+    radar: RadarFrames,
+}
+
+#[derive(Deserialize, Debug)]
+struct RadarFrames {
+    past: Vec<RadarFrame>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RadarFrame {
+    time: u64,
+    path: String,
+}
+
+/// The latest radar over the map around `trailhead`, as a PNG, along with
+/// when the radar was captured
+#[instrument(skip(radar, config))]
+async fn render(
+    trailhead: geo::Point,
+    radar: &RadarConfig,
+    config: &Config,
+) -> eyre::Result<(Vec<u8>, u64)> {
+    let maps = reqwest::Client::new()
+        .get(&radar.rainviewer_url)
+        .send()
+        .await
+        .wrap_err("Failed to request weather maps from RainViewer")?
+        .error_for_status()
+        .wrap_err("RainViewer returned an error")?
+        .json::<WeatherMaps>()
+        .await
+        .wrap_err("Failed to get JSON from RainViewer")?;
+    let frame = maps
+        .radar
+        .past
+        .last()
+        .ok_or_eyre("RainViewer has no radar frames")?;
+
+    let (center_x, center_y) = project(trailhead.x(), trailhead.y(), radar.zoom);
+    let origin = (
+        center_x - WIDTH as f64 / 2.0,
+        center_y - HEIGHT as f64 / 2.0,
+    );
+
+    let map = MagickWand::new();
+    map.new_image(WIDTH, HEIGHT, &pixel(BACKGROUND_COLOR)?)
+        .wrap_err("Failed to create radar image in MagickWand")?;
+    let (tile_url, attribution) = match &config.route_map {
+        Some(route_map) => (route_map.tile_url.clone(), route_map.attribution.clone()),
+        None => (crate::default_tile_url(), crate::default_map_attribution()),
+    };
+    draw_tiles(&map, &tile_url, radar.zoom, origin, config).await?;
+    let radar_url = format!("{}{}{}", maps.host, frame.path, RADAR_TILE);
+    draw_tiles(&map, &radar_url, radar.zoom, origin, config)
+        .await
+        .wrap_err("Failed to draw radar")?;
+
+    let mut marker = DrawingWand::new();
+    marker.set_fill_color(&pixel(TRAILHEAD_COLOR)?);
+    marker.set_stroke_color(&pixel("#ffffff")?);
+    marker.set_stroke_width(2.0);
+    marker.draw_circle(
+        WIDTH as f64 / 2.0,
+        HEIGHT as f64 / 2.0,
+        WIDTH as f64 / 2.0 + TRAILHEAD_RADIUS,
+        HEIGHT as f64 / 2.0,
+    );
+    map.draw_image(&marker)
+        .wrap_err("Failed to draw trailhead in MagickWand")?;
+
+    let mut text = DrawingWand::new();
+    text.set_fill_color(&pixel("#333333")?);
+    text.set_font_size(11.0);
+    text.draw_annotation(
+        6.0,
+        HEIGHT as f64 - 6.0,
+        &format!("Radar: RainViewer, map: {}", attribution),
+    )
+    .wrap_err("Failed to write radar attribution in MagickWand")?;
+    map.draw_image(&text)
+        .wrap_err("Failed to write radar attribution in MagickWand")?;
+
+    let image = map
+        .write_image_blob("png")
+        .wrap_err("Failed to write radar image from MagickWand")?;
+    Ok((image, frame.time))
+}
+
+async fn post(
+    state: &AppState,
+    suggestion: &Suggestion,
+    trailhead: geo::Point,
+    starts_at: u64,
+    radar: &RadarConfig,
+    config: &Config,
+) -> eyre::Result<()> {
+    let Some(_permit) = state.limiter.try_acquire(crate::backpressure::IMAGE_MEMORY) else {
+        return Err(eyre!("Too many images are being worked on right now"));
+    };
+    let (image, captured_at) = render(trailhead, radar, config).await?;
+
+    let message = CreateMessage::new()
+        .reference_message((suggestion.channel_id, suggestion.message_id))
+        .allowed_mentions(CreateAllowedMentions::new())
+        .content(format!(
+            "🌦️ Radar around the trailhead of **{}** as of <t:{}:t>, the hike starts <t:{}:R>",
+            sanitize::text(&suggestion.title, sanitize::TITLE_LENGTH),
+            captured_at,
+            starts_at
+        ))
+        .add_file(CreateAttachment::bytes(image, FILE_NAME));
+
+    let http = state.http.load();
+    audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::Job(Job::PostRadar),
+        format!("channel {}", suggestion.channel_id),
+        &summarize(&message),
+        suggestion.channel_id.send_message(http.as_ref(), message),
+    )
+    .await
+    .wrap_err("Failed to post radar snapshot")?;
+    info!(title = suggestion.title, "Posted radar snapshot");
+
+    state
+        .store
+        .record_radar(suggestion.message_id, starts_at)
+        .wrap_err("Failed to record radar snapshot")
+}
+
+/// Posts a radar snapshot under each hike starting within `hours_before`.
+/// Snapshots that fail to post are tried again on the next run.
+pub async fn post_due_snapshots(state: &AppState) -> eyre::Result<()> {
+    let config = state.config.load();
+    let Some(radar) = &config.radar else {
+        return Ok(());
+    };
+    let now = get_current_timestamp();
+    let upcoming = state
+        .store
+        .upcoming_events(now)
+        .wrap_err("Failed to load upcoming events")?;
+
+    let mut failed = 0;
+    for suggestion in &upcoming {
+        let (Some(starts_at), Some(trailhead)) = (suggestion.hiked_at, suggestion.trailhead) else {
+            continue;
+        };
+        if starts_at.saturating_sub(radar.hours_before * 60 * 60) > now
+            || state.store.radar_posted(suggestion.message_id, starts_at)?
+        {
+            continue;
+        }
+        if let Err(e) = post(state, suggestion, trailhead, starts_at, radar, &config).await {
+            warn!(message_id = %suggestion.message_id, "Failed to post radar snapshot: {:?}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!("Failed to post {} radar snapshots", failed));
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use color_eyre::eyre::{self, eyre, Context};
+use jsonwebtoken::get_current_timestamp;
+use serenity::all::{CreateAllowedMentions, CreateMessage, Mentionable};
+use tracing::{info, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    sanitize,
+    scheduler::Job,
+    store::Suggestion,
+    AppState,
+};
+
+/// Posts the reminder for the shortest due lead time of the suggestion's
+/// event, then marks every due lead time as sent, so an event scheduled at
+/// short notice doesn't get all of its reminders at once
+async fn remind(
+    state: &AppState,
+    suggestion: &Suggestion,
+    starts_at: u64,
+    now: u64,
+) -> eyre::Result<()> {
+    let sent = state
+        .store
+        .sent_reminders(suggestion.message_id, starts_at)
+        .wrap_err("Failed to load sent reminders")?;
+    let due = state
+        .config
+        .load()
+        .reminder_hours
+        .iter()
+        .copied()
+        .filter(|lead| starts_at.saturating_sub(lead * 60 * 60) <= now && !sent.contains(lead))
+        .collect::<Vec<_>>();
+    let Some(&lead) = due.iter().min() else {
+        return Ok(());
+    };
+
+    // Only members who turned on hike reminders in their settings are pinged
+    let mut pinged = Vec::new();
+    for user in state
+        .store
+        .interested(suggestion.message_id)
+        .wrap_err("Failed to load interested members")?
+    {
+        if state.preferences(user)?.hike_reminders {
+            pinged.push(user);
+        }
+    }
+
+    let mut content = format!(
+        "⏰ **{}** starts <t:{}:R> (<t:{}:F>)",
+        sanitize::text(&suggestion.title, sanitize::TITLE_LENGTH),
+        starts_at,
+        starts_at
+    );
+    if !pinged.is_empty() {
+        content.push_str("\n\n");
+        content.push_str(
+            &pinged
+                .iter()
+                .map(|user| user.mention().to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+    let message = CreateMessage::new()
+        .reference_message((suggestion.channel_id, suggestion.message_id))
+        .allowed_mentions(CreateAllowedMentions::new().users(pinged))
+        .content(content);
+
+    let http = state.http.load();
+    audited(
+        &state.audit,
+        Mutation::SendMessage,
+        Actor::Job(Job::SendReminders),
+        format!("channel {}", suggestion.channel_id),
+        &summarize(&message),
+        suggestion.channel_id.send_message(http.as_ref(), message),
+    )
+    .await
+    .wrap_err("Failed to post reminder")?;
+    info!(title = suggestion.title, lead, "Posted hike reminder");
+
+    state
+        .store
+        .record_reminders(suggestion.message_id, starts_at, &due)
+        .wrap_err("Failed to record reminder")
+}
+
+/// Posts reminders under the suggestions of upcoming hikes at each of the
+/// `reminder_hours` before they start. Reminders that fail to post are tried
+/// again on the next run.
+pub async fn send_due_reminders(state: &AppState) -> eyre::Result<()> {
+    let now = get_current_timestamp();
+    let upcoming = state
+        .store
+        .upcoming_events(now)
+        .wrap_err("Failed to load upcoming events")?;
+
+    let mut failed = 0;
+    for suggestion in &upcoming {
+        let Some(starts_at) = suggestion.hiked_at else {
+            continue;
+        };
+        if let Err(e) = remind(state, suggestion, starts_at, now).await {
+            warn!(message_id = %suggestion.message_id, "Failed to send reminder: {:?}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!("Failed to send {} reminders", failed));
+    }
+    Ok(())
+}
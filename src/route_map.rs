@@ -0,0 +1,251 @@
+use std::f64::consts::PI;
+
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use geo::{BoundingRect, Distance, Haversine, MultiLineString, Point};
+use magick_rust::{CompositeOperator, DrawingWand, MagickWand, PixelWand};
+use tracing::instrument;
+
+use crate::{failure::Failure, Config, RouteMapConfig};
+
+/// Size of the rendered map, in pixels
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 400.0;
+
+/// Space kept between the route and the edge of the map, in pixels
+const PADDING: f64 = 32.0;
+
+const TILE_SIZE: f64 = 256.0;
+
+/// Closest zoom the map is rendered at, so short trails aren't blown up past
+/// what the tiles show
+const MAX_ZOOM: u32 = 17;
+
+/// Shown where there are no tiles, such as past the poles
+const BACKGROUND_COLOR: &str = "#f2efe9";
+const ROUTE_COLOR: &str = "#d9480f";
+const ROUTE_WIDTH: f64 = 4.0;
+
+/// File name the map is attached to the suggestion under
+pub const FILE_NAME: &str = "route.png";
+
+/// Title of the embed showing the map
+pub const TITLE: &str = "Route map";
+
+/// Size of the elevation profile, in pixels
+const PROFILE_WIDTH: usize = 640;
+const PROFILE_HEIGHT: f64 = 120.0;
+
+/// Space kept above the highest point of the profile, in pixels
+const PROFILE_PADDING: f64 = 12.0;
+
+const PROFILE_BACKGROUND: &str = "rgba(0, 0, 0, 0.45)";
+const PROFILE_COLOR: &str = "rgba(255, 255, 255, 0.85)";
+
+pub const PROFILE_FILE_NAME: &str = "elevation.png";
+pub const PROFILE_TITLE: &str = "Elevation profile";
+
+/// Position of `lon`, `lat` in pixels on the Web Mercator map at `zoom`
+pub fn project(lon: f64, lat: f64, zoom: u32) -> (f64, f64) {
+    let size = TILE_SIZE * 2f64.powi(zoom as i32);
+    let lat = lat.to_radians();
+    (
+        (lon + 180.0) / 360.0 * size,
+        (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * size,
+    )
+}
+
+/// Downloads the map tile at `url`
+pub async fn download_tile(
+    client: &reqwest::Client,
+    url: &str,
+    config: &Config,
+) -> eyre::Result<Vec<u8>> {
+    // OpenStreetMap's tile servers turn away requests without a user agent
+    client
+        .get(url)
+        .header(
+            "User-Agent",
+            format!(
+                "hikea/{} ( {} )",
+                env!("CARGO_PKG_VERSION"),
+                config.hostname
+            ),
+        )
+        .send()
+        .await
+        .wrap_err_with(|| format!("Failed to download map tile `{}`", url))?
+        .error_for_status()
+        .wrap_err_with(|| format!("Failed to download map tile `{}`", url))?
+        .bytes()
+        .await
+        .map(Vec::from)
+        .wrap_err("Failed to get bytes of map tile")
+}
+
+/// Lays the map tiles from `tile_url` covering `map` at `zoom` over it, with
+/// the top left corner of `map` at `left`, `top` in pixels on the whole map
+pub async fn draw_tiles(
+    map: &MagickWand,
+    tile_url: &str,
+    zoom: u32,
+    (left, top): (f64, f64),
+    config: &Config,
+) -> eyre::Result<()> {
+    let (width, height) = (map.get_image_width() as f64, map.get_image_height() as f64);
+    let tiles = 2i64.pow(zoom);
+    let client = reqwest::Client::new();
+    for tile_y in (top / TILE_SIZE).floor() as i64..=((top + height) / TILE_SIZE).floor() as i64 {
+        if !(0..tiles).contains(&tile_y) {
+            continue;
+        }
+        for tile_x in
+            (left / TILE_SIZE).floor() as i64..=((left + width) / TILE_SIZE).floor() as i64
+        {
+            let url = tile_url
+                .replace("{z}", &zoom.to_string())
+                .replace("{x}", &tile_x.rem_euclid(tiles).to_string())
+                .replace("{y}", &tile_y.to_string());
+            let bytes = download_tile(&client, &url, config).await?;
+
+            let tile = MagickWand::new();
+            tile.read_image_blob(bytes)
+                .wrap_err("Failed to read map tile in MagickWand")?;
+            map.compose_images(
+                &tile,
+                CompositeOperator::Over,
+                false,
+                (tile_x as f64 * TILE_SIZE - left).round() as isize,
+                (tile_y as f64 * TILE_SIZE - top).round() as isize,
+            )
+            .wrap_err("Failed to place map tile in MagickWand")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders `track` over map tiles from `tile_url`, zoomed in as far as the
+/// whole route fits, as a PNG
+#[instrument(skip_all)]
+pub async fn render(
+    track: &MultiLineString,
+    route_map: &RouteMapConfig,
+    config: &Config,
+) -> eyre::Result<Vec<u8>> {
+    let bounds = track
+        .bounding_rect()
+        .ok_or_eyre("GPX track has no points")?;
+    let zoom = (0..=MAX_ZOOM)
+        .rev()
+        .find(|zoom| {
+            let (west, north) = project(bounds.min().x, bounds.max().y, *zoom);
+            let (east, south) = project(bounds.max().x, bounds.min().y, *zoom);
+            east - west <= WIDTH - PADDING * 2.0 && south - north <= HEIGHT - PADDING * 2.0
+        })
+        .unwrap_or(0);
+
+    let center = bounds.center();
+    let (center_x, center_y) = project(center.x, center.y, zoom);
+    let (left, top) = (center_x - WIDTH / 2.0, center_y - HEIGHT / 2.0);
+
+    let background = pixel(BACKGROUND_COLOR)?;
+    let map = MagickWand::new();
+    map.new_image(WIDTH as usize, HEIGHT as usize, &background)
+        .wrap_err("Failed to create map image in MagickWand")?;
+    draw_tiles(&map, &route_map.tile_url, zoom, (left, top), config).await?;
+
+    let mut route = DrawingWand::new();
+    route.set_stroke_color(&pixel(ROUTE_COLOR)?);
+    route.set_stroke_width(ROUTE_WIDTH);
+    for line in track {
+        for pair in line.0.windows(2) {
+            let (start_x, start_y) = project(pair[0].x, pair[0].y, zoom);
+            let (end_x, end_y) = project(pair[1].x, pair[1].y, zoom);
+            route.draw_line(start_x - left, start_y - top, end_x - left, end_y - top);
+        }
+    }
+    map.draw_image(&route)
+        .wrap_err("Failed to draw route in MagickWand")?;
+
+    let mut attribution = DrawingWand::new();
+    attribution.set_fill_color(&pixel("#333333")?);
+    attribution.set_font_size(11.0);
+    attribution
+        .draw_annotation(6.0, HEIGHT - 6.0, &route_map.attribution)
+        .wrap_err("Failed to write map attribution in MagickWand")?;
+    map.draw_image(&attribution)
+        .wrap_err("Failed to write map attribution in MagickWand")?;
+
+    map.write_image_blob("png")
+        .wrap_err("Failed to write map image from MagickWand")
+}
+
+/// Renders the elevation of `track` against the distance walked as a light
+/// silhouette on a translucent strip, as a PNG meant to be laid over a photo
+#[instrument(skip_all)]
+pub fn render_profile(track: &gpx::Track) -> eyre::Result<Vec<u8>> {
+    let mut profile = Vec::new();
+    let mut walked = 0.0;
+    let mut previous: Option<Point> = None;
+    for waypoint in track.segments.iter().flat_map(|segment| &segment.points) {
+        let point = waypoint.point();
+        if let Some(previous) = previous {
+            walked += Haversine::distance(previous, point);
+        }
+        previous = Some(point);
+        profile.push((walked, waypoint.elevation.ok_or(Failure::MissingElevation)?));
+    }
+    if walked <= 0.0 {
+        return Err(eyre!("GPX track is too short for an elevation profile"));
+    }
+
+    // The highest point walked within each column of pixels
+    let mut columns = vec![None::<f64>; PROFILE_WIDTH];
+    for (distance, elevation) in profile {
+        let column = &mut columns[(distance / walked * (PROFILE_WIDTH - 1) as f64) as usize];
+        *column = Some(column.map_or(elevation, |highest| highest.max(elevation)));
+    }
+    let mut last = None;
+    let columns = columns
+        .into_iter()
+        .map(|column| {
+            last = column.or(last);
+            last
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_eyre("GPX track has no points")?;
+    let lowest = columns.iter().copied().fold(f64::MAX, f64::min);
+    let highest = columns.iter().copied().fold(f64::MIN, f64::max);
+    let range = (highest - lowest).max(1.0);
+
+    let transparent = pixel("none")?;
+    let strip = MagickWand::new();
+    strip
+        .new_image(PROFILE_WIDTH, PROFILE_HEIGHT as usize, &transparent)
+        .wrap_err("Failed to create elevation profile image in MagickWand")?;
+
+    let mut drawing = DrawingWand::new();
+    drawing.set_fill_color(&pixel(PROFILE_BACKGROUND)?);
+    drawing.draw_rectangle(0.0, 0.0, PROFILE_WIDTH as f64, PROFILE_HEIGHT);
+    drawing.set_stroke_color(&pixel(PROFILE_COLOR)?);
+    drawing.set_stroke_width(1.0);
+    for (x, elevation) in columns.into_iter().enumerate() {
+        let height = (elevation - lowest) / range * (PROFILE_HEIGHT - PROFILE_PADDING);
+        drawing.draw_line(x as f64, PROFILE_HEIGHT, x as f64, PROFILE_HEIGHT - height);
+    }
+    strip
+        .draw_image(&drawing)
+        .wrap_err("Failed to draw elevation profile in MagickWand")?;
+
+    strip
+        .write_image_blob("png")
+        .wrap_err("Failed to write elevation profile from MagickWand")
+}
+
+pub fn pixel(color: &str) -> eyre::Result<PixelWand> {
+    let mut pixel = PixelWand::new();
+    pixel
+        .set_color(color)
+        .wrap_err_with(|| format!("Invalid color `{}`", color))?;
+    Ok(pixel)
+}
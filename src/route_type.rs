@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+use geo::{Closest, ClosestPoint, Distance, Haversine, Length, LineString, MultiLineString};
+
+/// Trails ending further than this from where they started don't return to
+/// the trailhead, in meters
+const END_GAP: f64 = 250.0;
+
+/// Points of the return leg within this of the outbound leg retrace it, in
+/// meters
+const RETRACE_DISTANCE: f64 = 30.0;
+
+/// Fraction of the return leg that has to retrace the outbound leg for the
+/// trail to be an out-and-back
+const OUT_AND_BACK_OVERLAP: f64 = 0.6;
+
+/// Points of the return leg checked against the outbound leg
+const SAMPLES: usize = 200;
+
+/// The shape of a trail, which decides whether a shuttle is needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteType {
+    Loop,
+    OutAndBack,
+    PointToPoint,
+}
+
+impl RouteType {
+    /// Splits `track` where half its length has been walked, the turnaround
+    /// of an out-and-back, and checks how much of the way back retraces the
+    /// way out. `None` if the track is too short to tell.
+    pub fn from_track(track: &MultiLineString) -> Option<Self> {
+        let points = track
+            .iter()
+            .flat_map(|line| line.points())
+            .collect::<Vec<_>>();
+        let (first, last) = (*points.first()?, *points.last()?);
+        if Haversine::distance(first, last) > END_GAP {
+            return Some(RouteType::PointToPoint);
+        }
+
+        let half = track.length::<Haversine>() / 2.0;
+        let mut walked = 0.0;
+        let turnaround = points.windows(2).position(|pair| {
+            walked += Haversine::distance(pair[0], pair[1]);
+            walked >= half
+        })? + 1;
+
+        let outbound = LineString::from(points[..=turnaround].to_vec());
+        let back = &points[turnaround..];
+        let mut sampled = 0;
+        let mut retraced = 0;
+        for point in back.iter().step_by(back.len().div_ceil(SAMPLES)) {
+            let closest = match outbound.closest_point(point) {
+                Closest::Intersection(closest) | Closest::SinglePoint(closest) => closest,
+                Closest::Indeterminate => continue,
+            };
+            sampled += 1;
+            if Haversine::distance(*point, closest) <= RETRACE_DISTANCE {
+                retraced += 1;
+            }
+        }
+        if sampled == 0 {
+            return None;
+        }
+
+        if retraced as f64 / sampled as f64 >= OUT_AND_BACK_OVERLAP {
+            Some(RouteType::OutAndBack)
+        } else {
+            Some(RouteType::Loop)
+        }
+    }
+}
+
+impl Display for RouteType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RouteType::Loop => "Loop",
+            RouteType::OutAndBack => "Out-and-back",
+            RouteType::PointToPoint => "Point-to-point",
+        })
+    }
+}
@@ -0,0 +1,175 @@
+// Discord's embed limits, in characters
+pub const TITLE_LENGTH: usize = 256;
+pub const FIELD_NAME_LENGTH: usize = 256;
+pub const FIELD_LENGTH: usize = 1024;
+pub const DESCRIPTION_LENGTH: usize = 4096;
+pub const EMBED_LENGTH: usize = 6000;
+
+/// Fields rather than characters
+pub const MAX_FIELDS: usize = 25;
+
+/// Leaves room for the rest of a suggestion's embeds
+pub const DESCRIPTION_BUDGET: usize = 2048;
+
+/// For names in a list
+pub const NAME_LENGTH: usize = 64;
+
+/// Characters with a meaning in Discord markdown anywhere in a line
+const MARKDOWN: [char; 9] = ['\\', '*', '_', '~', '`', '|', '[', ']', '<'];
+
+/// Characters with a meaning in Discord markdown at the start of a line
+const LINE_MARKDOWN: [char; 3] = ['#', '>', '-'];
+
+/// `text` with markdown escaped and mentions broken up
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            escaped.push('\n');
+        }
+        let trimmed = line.trim_start();
+        escaped.push_str(&line[..line.len() - trimmed.len()]);
+        if trimmed.starts_with(LINE_MARKDOWN) {
+            escaped.push('\\');
+        }
+        for c in trimmed.chars() {
+            if MARKDOWN.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+    }
+    // `<` is escaped already, which covers user, role and channel mentions
+    escaped
+        .replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+}
+
+/// `text` cut down to `max` characters, linking to `read_more` if given
+pub fn budget(text: &str, max: usize, read_more: Option<&str>) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+    let suffix = match read_more {
+        Some(link) => format!("…\n[Read more on the web page]({})", link),
+        None => String::from("…"),
+    };
+    let keep = max.saturating_sub(suffix.chars().count());
+    let mut cut = text.chars().take(keep).collect::<String>();
+    // Ending on a whole word, unless the text has no spaces to end on
+    if let Some(space) = cut.rfind(char::is_whitespace).filter(|space| *space > 0) {
+        cut.truncate(space);
+    }
+    // A trailing backslash from `escape` would escape the ellipsis
+    while cut.ends_with('\\') {
+        cut.pop();
+    }
+    cut.truncate(cut.trim_end().len());
+    cut.push_str(&suffix);
+    cut
+}
+
+/// `text` escaped and cut down to `max` characters
+pub fn text(text: &str, max: usize) -> String {
+    budget(&escape(text), max, None)
+}
+
+/// As many of `lines` as fit in `max` characters, one per line
+pub fn lines(lines: impl IntoIterator<Item = String>, max: usize) -> String {
+    let mut joined = String::new();
+    let mut length = 0;
+    for line in lines {
+        let line_length = line.chars().count() + usize::from(length > 0);
+        if length + line_length > max {
+            break;
+        }
+        if length > 0 {
+            joined.push('\n');
+        }
+        joined.push_str(&line);
+        length += line_length;
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markdown() {
+        assert_eq!(
+            escape("**bold** _x_ ~~y~~"),
+            r"\*\*bold\*\* \_x\_ \~\~y\~\~"
+        );
+        assert_eq!(escape("[link](url)"), r"\[link\](url)");
+        assert_eq!(
+            escape("# Title\n> quote\n  - item\nnot-a-list"),
+            "\\# Title\n\\> quote\n  \\- item\nnot-a-list"
+        );
+    }
+
+    #[test]
+    fn breaks_up_mentions() {
+        assert_eq!(
+            escape("<@123> @everyone @here"),
+            "\\<@123> @\u{200B}everyone @\u{200B}here"
+        );
+    }
+
+    #[test]
+    fn leaves_short_text_alone() {
+        assert_eq!(budget("short", 10, None), "short");
+        assert_eq!(budget("héllo", 5, Some("https://example.com")), "héllo");
+    }
+
+    #[test]
+    fn cuts_on_whole_words() {
+        assert_eq!(budget("one two three", 10, None), "one two…");
+    }
+
+    #[test]
+    fn cuts_multibyte_text_on_characters() {
+        assert_eq!(budget("ééééé ééééé", 8, None), "ééééé…");
+        assert_eq!(budget("🥾🥾🥾🥾🥾", 3, None), "🥾🥾…");
+        for max in 1..12 {
+            for text in [
+                "ééééé ééééé",
+                "🥾🥾🥾🥾🥾🥾🥾🥾🥾🥾🥾🥾",
+                "日本 の 山道 を 歩く",
+            ] {
+                assert!(budget(text, max, None).chars().count() <= max);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_trailing_escapes() {
+        assert_eq!(budget(&escape("ab*cd"), 4, None), "ab…");
+    }
+
+    #[test]
+    fn escapes_before_cutting() {
+        assert_eq!(
+            text("@everyone **go**", 100),
+            "@\u{200B}everyone \\*\\*go\\*\\*"
+        );
+        assert!(text(&"*".repeat(300), TITLE_LENGTH).chars().count() <= TITLE_LENGTH);
+    }
+
+    #[test]
+    fn keeps_whole_lines() {
+        let joined = lines(["one", "two", "three"].map(String::from), 9);
+        assert_eq!(joined, "one\ntwo");
+        assert_eq!(lines(["toolong"].map(String::from), 3), "");
+    }
+
+    #[test]
+    fn links_to_the_rest() {
+        let link = "https://example.com/hikea/trail/1";
+        let cut = budget(&"word ".repeat(100), 100, Some(link));
+        assert!(cut.chars().count() <= 100);
+        assert!(cut.starts_with("word word"));
+        assert!(cut.ends_with(&format!("…\n[Read more on the web page]({})", link)));
+    }
+}
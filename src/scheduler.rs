@@ -0,0 +1,592 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{self, eyre, Context};
+use jsonwebtoken::get_current_timestamp;
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer};
+use serenity::{
+    all::EditMessage,
+    http::{ErrorResponse, HttpError},
+};
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+    audit::{audited, summarize, Actor, Mutation},
+    AppState, Feature,
+};
+
+/// How long details submitted through the details modal wait for a GPX file
+const PENDING_DETAILS_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// How long suggestions keep their upload buttons without being filled in
+const UPLOAD_BUTTON_TTL: u64 = 30 * 24 * 60 * 60;
+
+/// Longest the scheduler sleeps, so config reloads and `/feature` changes
+/// are picked up
+const MAX_SLEEP: u64 = 60;
+
+/// Delay before the first retry of a failed job, doubled for each retry after
+const RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Background jobs, configured under `[jobs.<name>]`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    PrunePendingDetails,
+    ExpireUploadButtons,
+    ClosePolls,
+    SendReminders,
+    PostRadar,
+    PostDigest,
+}
+
+impl Job {
+    const ALL: [Job; 6] = [
+        Job::PrunePendingDetails,
+        Job::ExpireUploadButtons,
+        Job::ClosePolls,
+        Job::SendReminders,
+        Job::PostRadar,
+        Job::PostDigest,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|job| job.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Job::PrunePendingDetails => "prune_pending_details",
+            Job::ExpireUploadButtons => "expire_upload_buttons",
+            Job::ClosePolls => "close_polls",
+            Job::SendReminders => "send_reminders",
+            Job::PostRadar => "post_radar",
+            Job::PostDigest => "post_digest",
+        }
+    }
+
+    async fn run(self, state: Arc<AppState>) -> eyre::Result<()> {
+        match self {
+            Job::PrunePendingDetails => {
+                let pruned = state
+                    .store
+                    .prune_pending_details(
+                        get_current_timestamp().saturating_sub(PENDING_DETAILS_TTL),
+                    )
+                    .wrap_err("Failed to prune pending details")?;
+                debug!(pruned, "Pruned stale pending details");
+                Ok(())
+            }
+            Job::ExpireUploadButtons => expire_upload_buttons(&state).await,
+            Job::ClosePolls => crate::commands::vote::close_due_polls(&state).await,
+            Job::SendReminders => crate::reminders::send_due_reminders(&state).await,
+            Job::PostRadar => crate::radar::post_due_snapshots(&state).await,
+            Job::PostDigest => crate::digest::post_digest(&state).await,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobConfig {
+    schedule: Schedule,
+    /// Up to this many seconds are randomly added to each run
+    jitter: u64,
+    /// Failed runs are retried this many times before being dead-lettered
+    retries: u32,
+    enabled: bool,
+}
+
+/// A `[jobs.<name>]` table, fields that are left out keep the job's default
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct JobOverride {
+    schedule: Option<Schedule>,
+    jitter: Option<u64>,
+    retries: Option<u32>,
+    enabled: Option<bool>,
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+pub fn default_jobs() -> HashMap<Job, JobConfig> {
+    HashMap::from([
+        (
+            Job::PrunePendingDetails,
+            JobConfig {
+                schedule: "0 4 * * *".parse().unwrap(),
+                jitter: 600,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+        (
+            Job::ExpireUploadButtons,
+            JobConfig {
+                schedule: "30 4 * * *".parse().unwrap(),
+                jitter: 600,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+        (
+            Job::ClosePolls,
+            JobConfig {
+                schedule: "*/5 * * * *".parse().unwrap(),
+                jitter: 0,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+        (
+            Job::SendReminders,
+            JobConfig {
+                schedule: "*/5 * * * *".parse().unwrap(),
+                jitter: 0,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+        (
+            Job::PostRadar,
+            JobConfig {
+                schedule: "*/10 * * * *".parse().unwrap(),
+                jitter: 0,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+        (
+            Job::PostDigest,
+            JobConfig {
+                // Mondays, so the week's plans are up front
+                schedule: "0 15 * * 1".parse().unwrap(),
+                jitter: 0,
+                retries: default_retries(),
+                enabled: true,
+            },
+        ),
+    ])
+}
+
+/// Merges the configured `[jobs.<name>]` tables over `default_jobs`, so
+/// configuring one job doesn't turn the others off
+pub fn deserialize_jobs<'de, D>(deserializer: D) -> Result<HashMap<Job, JobConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut jobs = default_jobs();
+    for (job, job_override) in HashMap::<Job, JobOverride>::deserialize(deserializer)? {
+        let default = jobs.remove(&job).ok_or_else(|| {
+            serde::de::Error::custom(format!("Job `{}` has no default configuration", job.name()))
+        })?;
+        jobs.insert(
+            job,
+            JobConfig {
+                schedule: job_override.schedule.unwrap_or(default.schedule),
+                jitter: job_override.jitter.unwrap_or(default.jitter),
+                retries: job_override.retries.unwrap_or(default.retries),
+                enabled: job_override.enabled.unwrap_or(default.enabled),
+            },
+        );
+    }
+    Ok(jobs)
+}
+
+/// Removes the upload buttons from suggestions nobody filled in within
+/// `UPLOAD_BUTTON_TTL`. Messages that fail to update are tried again on the
+/// next run.
+async fn expire_upload_buttons(state: &AppState) -> eyre::Result<()> {
+    let expired = state
+        .store
+        .expired_upload_buttons(get_current_timestamp().saturating_sub(UPLOAD_BUTTON_TTL))
+        .wrap_err("Failed to load expired upload buttons")?;
+
+    let http = state.http.load();
+    let mut failed = 0;
+    for (channel_id, message_id) in expired {
+        let edit = EditMessage::new().components(Vec::new());
+        let result = audited(
+            &state.audit,
+            Mutation::EditMessage,
+            Actor::Job(Job::ExpireUploadButtons),
+            format!("message {} in channel {}", message_id, channel_id),
+            &summarize(&edit),
+            channel_id.edit_message(http.as_ref(), message_id, edit),
+        )
+        .await;
+        match result {
+            // The message was deleted, so there's nothing left to clean up
+            Ok(_)
+            | Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(ErrorResponse {
+                status_code: StatusCode::NOT_FOUND,
+                ..
+            }))) => state
+                .store
+                .remove_upload_button(message_id)
+                .wrap_err("Failed to remove upload button")?,
+            Err(e) => {
+                warn!(%message_id, "Failed to remove upload buttons: {:?}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!(
+            "Failed to remove upload buttons from {} messages",
+            failed
+        ));
+    }
+    Ok(())
+}
+
+/// A cron schedule in UTC, `minute hour day-of-month month day-of-week`.
+/// Fields accept `*`, single values, ranges, lists and `/` steps.
+#[derive(Debug)]
+pub struct Schedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether days of the month or the week were restricted, if both are
+    /// then either matching is enough, like cron
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    fn parse_field(field: &str, min: u64, max: u64) -> eyre::Result<u64> {
+        let mut set = 0;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    step.parse::<u64>()
+                        .wrap_err_with(|| format!("Invalid step in `{}`", part))?,
+                ),
+                None => (part, 1),
+            };
+            if step == 0 {
+                return Err(eyre!("Step in `{}` can't be 0", part));
+            }
+
+            let parse = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .wrap_err_with(|| format!("Invalid value in `{}`", part))
+            };
+            let (start, end) = match range.split_once('-') {
+                _ if range == "*" => (min, max),
+                Some((start, end)) => (parse(start)?, parse(end)?),
+                None if step > 1 => (parse(range)?, max),
+                None => (parse(range)?, parse(range)?),
+            };
+            if start < min || end > max || start > end {
+                return Err(eyre!("`{}` is outside of the range {}-{}", part, min, max));
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                set |= 1 << value;
+            }
+        }
+        Ok(set)
+    }
+
+    fn matches_day(&self, time: OffsetDateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().number_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute after `after`, within the next few years
+    pub fn next_after(&self, after: u64) -> eyre::Result<u64> {
+        let mut time = OffsetDateTime::from_unix_timestamp((after / 60 + 1) as i64 * 60)
+            .wrap_err("Timestamp out of range")?;
+        let limit = time + time::Duration::days(5 * 366);
+
+        while time < limit {
+            if self.months & (1 << u8::from(time.month())) == 0 || !self.matches_day(time) {
+                time = (time + time::Duration::days(1)).replace_time(time::Time::MIDNIGHT);
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = (time + time::Duration::hours(1))
+                    .replace_minute(0)
+                    .wrap_err("Failed to advance schedule")?;
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += time::Duration::minutes(1);
+            } else {
+                return Ok(time.unix_timestamp() as u64);
+            }
+        }
+
+        Err(eyre!("Schedule `{}` never runs", self.source))
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(eyre!("Expected 5 fields in schedule `{}`", s));
+        };
+
+        let mut weekday_set = Self::parse_field(weekdays, 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekday_set & (1 << 7) != 0 {
+            weekday_set |= 1;
+        }
+
+        Ok(Schedule {
+            source: s.to_owned(),
+            minutes: Self::parse_field(minutes, 0, 59)?,
+            hours: Self::parse_field(hours, 0, 23)?,
+            days: Self::parse_field(days, 1, 31)?,
+            months: Self::parse_field(months, 1, 12)?,
+            weekdays: weekday_set,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| serde::de::Error::custom(format!("{:#}", e)))
+    }
+}
+
+/// Runs configured jobs when they are due. Next runs are persisted, so a
+/// run missed while the bot was down happens once on startup.
+#[instrument(skip_all)]
+pub async fn run(state: Arc<AppState>) {
+    let mut running: HashMap<Job, JoinHandle<()>> = HashMap::new();
+
+    loop {
+        // Runs that come due while disabled happen once it's enabled again
+        if !state.feature_enabled(Feature::Scheduler) {
+            tokio::time::sleep(Duration::from_secs(MAX_SLEEP)).await;
+            continue;
+        }
+
+        let now = get_current_timestamp();
+        let mut wake_at = now + MAX_SLEEP;
+
+        let config = state.config.load();
+        for (job, job_config) in config.jobs.iter().filter(|(_, c)| c.enabled) {
+            match poll_job(&state, &mut running, *job, job_config, now) {
+                Ok(next_run) => wake_at = wake_at.min(next_run),
+                Err(e) => error!(job = job.name(), "Failed to schedule job: {:?}", e),
+            }
+        }
+        drop(config);
+
+        tokio::time::sleep(Duration::from_secs(wake_at.saturating_sub(now).max(1))).await;
+    }
+}
+
+/// Starts `job` if it is due, returning when it should next be polled
+fn poll_job(
+    state: &Arc<AppState>,
+    running: &mut HashMap<Job, JoinHandle<()>>,
+    job: Job,
+    job_config: &JobConfig,
+    now: u64,
+) -> eyre::Result<u64> {
+    let name = job.name();
+    let schedule = &job_config.schedule.source;
+    let next_run = |after| {
+        Ok::<_, eyre::Report>(
+            job_config.schedule.next_after(after)? + fastrand::u64(0..=job_config.jitter),
+        )
+    };
+
+    let Some(due) = state
+        .store
+        .job_next_run(name, schedule)
+        .wrap_err("Failed to load next job run")?
+    else {
+        let next = next_run(now)?;
+        state
+            .store
+            .set_job_next_run(name, schedule, next)
+            .wrap_err("Failed to store next job run")?;
+        return Ok(next);
+    };
+
+    if due > now {
+        return Ok(due);
+    }
+
+    let next = next_run(now)?;
+    state
+        .store
+        .set_job_next_run(name, schedule, next)
+        .wrap_err("Failed to store next job run")?;
+
+    if running
+        .get(&job)
+        .is_some_and(|handle| !handle.is_finished())
+    {
+        warn!(
+            job = name,
+            "Skipping job run, the previous run is still going"
+        );
+        return Ok(next);
+    }
+
+    running.insert(
+        job,
+        tokio::spawn(run_job(Arc::clone(state), job, job_config.retries)),
+    );
+    Ok(next)
+}
+
+/// Runs `job` once in its own task, so a panicking job doesn't take the
+/// scheduler down, and records the run
+async fn attempt(state: &Arc<AppState>, job: Job) -> eyre::Result<()> {
+    let started_at = get_current_timestamp();
+    let start = Instant::now();
+
+    let result = tokio::spawn(job.run(Arc::clone(state)))
+        .await
+        .map_err(|e| eyre!("Job panicked: {}", e))
+        .and_then(|result| result);
+    let duration = start.elapsed();
+
+    match &result {
+        Ok(()) => info!(job = job.name(), ?duration, "Job finished"),
+        Err(e) => error!(job = job.name(), ?duration, "Job failed: {:?}", e),
+    }
+
+    if let Err(e) = state
+        .store
+        .record_job_run(
+            job.name(),
+            started_at,
+            duration.as_millis() as u64,
+            result.as_ref().err().map(|e| format!("{:#}", e)).as_deref(),
+        )
+        .wrap_err("Failed to record job run")
+    {
+        error!(job = job.name(), "{:?}", e);
+    }
+
+    result
+}
+
+/// Runs `job`, retrying with backoff and dead-lettering it once it has failed
+/// `retries` more times
+async fn run_job(state: Arc<AppState>, job: Job, retries: u32) {
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempts = 0;
+    let error = loop {
+        attempts += 1;
+        match attempt(&state, job).await {
+            Ok(()) => return,
+            Err(e) if attempts > retries => break e,
+            Err(_) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    };
+
+    match state
+        .store
+        .insert_dead_letter(
+            job.name(),
+            get_current_timestamp(),
+            attempts,
+            &format!("{:#}", error),
+        )
+        .wrap_err("Failed to dead-letter job")
+    {
+        Ok(id) => warn!(job = job.name(), id, "Job exhausted its retries"),
+        Err(e) => error!(job = job.name(), "{:?}", e),
+    }
+}
+
+/// Runs the job of a dead letter again, removing the dead letter if it
+/// succeeds
+#[instrument(skip(state))]
+pub async fn retry(state: Arc<AppState>, id: i64) -> eyre::Result<Job> {
+    let dead_letter = state
+        .store
+        .dead_letter(id)
+        .wrap_err("Failed to load dead letter")?
+        .ok_or_else(|| eyre!("No dead letter with ID `{}`", id))?;
+    let job = Job::from_name(&dead_letter.job)
+        .ok_or_else(|| eyre!("Dead letter is for unknown job `{}`", dead_letter.job))?;
+
+    attempt(&state, job).await.wrap_err("Retried job failed")?;
+
+    state
+        .store
+        .remove_dead_letter(id)
+        .wrap_err("Failed to remove dead letter")?;
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        #[serde(default = "default_jobs", deserialize_with = "deserialize_jobs")]
+        jobs: HashMap<Job, JobConfig>,
+    }
+
+    #[test]
+    fn configured_jobs_merge_over_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [jobs.close_polls]
+            schedule = "0 * * * *"
+
+            [jobs.post_digest]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.jobs.len(), Job::ALL.len());
+        assert_eq!(config.jobs[&Job::ClosePolls].schedule.source, "0 * * * *");
+        assert_eq!(config.jobs[&Job::ClosePolls].retries, default_retries());
+        assert!(!config.jobs[&Job::PostDigest].enabled);
+        assert_eq!(config.jobs[&Job::PostDigest].schedule.source, "0 15 * * 1");
+        assert!(config.jobs[&Job::SendReminders].enabled);
+    }
+
+    #[test]
+    fn missing_jobs_table_uses_defaults() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.jobs.len(), Job::ALL.len());
+        assert!(config.jobs.values().all(|job| job.enabled));
+    }
+
+    #[test]
+    fn unknown_job_fields_are_rejected() {
+        assert!(toml::from_str::<Config>("[jobs.close_polls]\nenable = false").is_err());
+    }
+}
@@ -0,0 +1,1982 @@
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+use color_eyre::eyre::{self, eyre, Context};
+use geo::Point;
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::all::{ChannelId, MessageId, ScheduledEventId, UserId};
+use tracing::{debug, instrument};
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE suggestions (
+        message_id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        link TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE interested (
+        message_id INTEGER NOT NULL REFERENCES suggestions (message_id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL,
+        PRIMARY KEY (message_id, user_id)
+    );",
+    "ALTER TABLE suggestions ADD COLUMN difficulty TEXT;
+    ALTER TABLE suggestions ADD COLUMN length REAL;
+    ALTER TABLE suggestions ADD COLUMN gain REAL;
+    ALTER TABLE suggestions ADD COLUMN event_id INTEGER;
+    ALTER TABLE suggestions ADD COLUMN hiked_at INTEGER;",
+    "CREATE TABLE pending_details (
+        message_id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        difficulty TEXT NOT NULL,
+        rating TEXT NOT NULL,
+        image TEXT NOT NULL,
+        description TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );",
+    "ALTER TABLE suggestions ADD COLUMN description TEXT;
+    CREATE VIRTUAL TABLE suggestions_fts USING fts5 (
+        title,
+        description,
+        content = 'suggestions',
+        content_rowid = 'message_id'
+    );
+    CREATE TRIGGER suggestions_fts_insert AFTER INSERT ON suggestions BEGIN
+        INSERT INTO suggestions_fts (rowid, title, description)
+        VALUES (new.message_id, new.title, new.description);
+    END;
+    CREATE TRIGGER suggestions_fts_delete AFTER DELETE ON suggestions BEGIN
+        INSERT INTO suggestions_fts (suggestions_fts, rowid, title, description)
+        VALUES ('delete', old.message_id, old.title, old.description);
+    END;
+    CREATE TRIGGER suggestions_fts_update AFTER UPDATE ON suggestions BEGIN
+        INSERT INTO suggestions_fts (suggestions_fts, rowid, title, description)
+        VALUES ('delete', old.message_id, old.title, old.description);
+        INSERT INTO suggestions_fts (rowid, title, description)
+        VALUES (new.message_id, new.title, new.description);
+    END;
+    INSERT INTO suggestions_fts (suggestions_fts) VALUES ('rebuild');",
+    "CREATE TABLE disabled_features (name TEXT PRIMARY KEY);",
+    "CREATE TABLE jobs (
+        name TEXT PRIMARY KEY,
+        schedule TEXT NOT NULL,
+        next_run INTEGER NOT NULL,
+        runs INTEGER NOT NULL DEFAULT 0,
+        failures INTEGER NOT NULL DEFAULT 0,
+        last_run INTEGER,
+        last_duration_ms INTEGER,
+        last_error TEXT
+    );",
+    "CREATE TABLE dead_letters (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job TEXT NOT NULL,
+        failed_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL,
+        error TEXT NOT NULL
+    );",
+    "CREATE TABLE upload_buttons (
+        message_id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );",
+    "ALTER TABLE suggestions ADD COLUMN filled_by TEXT;
+    ALTER TABLE suggestions ADD COLUMN filled_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE suggestions ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+    UPDATE suggestions SET filled_at = created_at;",
+    "CREATE TABLE preferences (
+        user_id INTEGER PRIMARY KEY,
+        long_units TEXT,
+        short_units TEXT,
+        time_zone TEXT,
+        email TEXT,
+        hike_reminders INTEGER NOT NULL DEFAULT 0,
+        suggestion_notifications INTEGER NOT NULL DEFAULT 0
+    );",
+    "ALTER TABLE suggestions ADD COLUMN state TEXT NOT NULL DEFAULT 'proposed';
+    CREATE TABLE state_transitions (
+        message_id INTEGER NOT NULL,
+        from_state TEXT NOT NULL,
+        to_state TEXT NOT NULL,
+        user_id INTEGER NOT NULL,
+        at INTEGER NOT NULL
+    );",
+    "ALTER TABLE suggestions ADD COLUMN beginner_score INTEGER;",
+    "CREATE TABLE polls (
+        message_id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        deadline INTEGER NOT NULL,
+        closed INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE poll_candidates (
+        poll_id INTEGER NOT NULL,
+        suggestion_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (poll_id, suggestion_id)
+    );
+    CREATE TABLE ballots (
+        poll_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        rank INTEGER NOT NULL,
+        suggestion_id INTEGER NOT NULL,
+        PRIMARY KEY (poll_id, user_id, rank)
+    );",
+    "ALTER TABLE suggestions ADD COLUMN accessible INTEGER;",
+    "CREATE TABLE waypoints (
+        id INTEGER PRIMARY KEY,
+        message_id INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        label TEXT NOT NULL,
+        latitude REAL NOT NULL,
+        longitude REAL NOT NULL
+    );",
+    "CREATE TABLE reminders (
+        message_id INTEGER NOT NULL,
+        starts_at INTEGER NOT NULL,
+        lead_hours INTEGER NOT NULL,
+        PRIMARY KEY (message_id, starts_at, lead_hours)
+    );",
+    "ALTER TABLE suggestions ADD COLUMN trailhead_latitude REAL;
+    ALTER TABLE suggestions ADD COLUMN trailhead_longitude REAL;",
+    "CREATE TABLE suggestion_gpx (
+        message_id INTEGER PRIMARY KEY,
+        gpx BLOB NOT NULL
+    );",
+    "CREATE TABLE badges (
+        user_id INTEGER NOT NULL,
+        badge TEXT NOT NULL,
+        earned_at INTEGER NOT NULL,
+        PRIMARY KEY (user_id, badge)
+    );",
+    "ALTER TABLE preferences ADD COLUMN birthday_month INTEGER;",
+    "CREATE TABLE radar_snapshots (
+        message_id INTEGER NOT NULL,
+        starts_at INTEGER NOT NULL,
+        PRIMARY KEY (message_id, starts_at)
+    );",
+    "CREATE TABLE offline_maps (
+        message_id INTEGER PRIMARY KEY,
+        mbtiles BLOB NOT NULL
+    );",
+    "CREATE TABLE route_maps (
+        message_id INTEGER PRIMARY KEY,
+        png BLOB NOT NULL
+    );",
+    "CREATE TABLE trail_aliases (
+        normalized TEXT PRIMARY KEY,
+        message_id INTEGER NOT NULL REFERENCES suggestions (message_id) ON DELETE CASCADE,
+        alias TEXT NOT NULL,
+        added_by TEXT NOT NULL,
+        added_at INTEGER NOT NULL
+    );",
+    "CREATE TABLE fill_claims (
+        message_id INTEGER PRIMARY KEY,
+        claimed_by TEXT NOT NULL,
+        claimed_at INTEGER NOT NULL
+    );",
+    // Suggestions used to be created when they were filled in, so their
+    // creation time is taken from the suggestion message's snowflake
+    "UPDATE suggestions SET created_at = ((message_id >> 22) + 1420070400000) / 1000;",
+];
+
+const SUGGESTION_COLUMNS: &str =
+    "message_id, channel_id, title, link, created_at, difficulty, length, gain, hiked_at, description, beginner_score, accessible, trailhead_latitude, trailhead_longitude";
+
+#[derive(Debug)]
+pub struct Suggestion {
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub title: String,
+    pub link: String,
+    pub created_at: u64,
+    pub difficulty: Option<String>,
+    /// In meters
+    pub length: Option<f64>,
+    /// In meters
+    pub gain: Option<f64>,
+    /// Start of the event it was injected into
+    pub hiked_at: Option<u64>,
+    pub description: Option<String>,
+    /// From 1 to 5
+    pub beginner_score: Option<u8>,
+    pub accessible: Option<bool>,
+    pub trailhead: Option<Point>,
+}
+
+impl Suggestion {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Suggestion {
+            message_id: MessageId::new(row.get(0)?),
+            channel_id: ChannelId::new(row.get(1)?),
+            title: row.get(2)?,
+            link: row.get(3)?,
+            created_at: row.get(4)?,
+            difficulty: row.get(5)?,
+            length: row.get(6)?,
+            gain: row.get(7)?,
+            hiked_at: row.get(8)?,
+            description: row.get(9)?,
+            beginner_score: row.get(10)?,
+            accessible: row.get(11)?,
+            trailhead: match (row.get(12)?, row.get(13)?) {
+                (Some(latitude), Some(longitude)) => Some(Point::new(longitude, latitude)),
+                _ => None,
+            },
+        })
+    }
+
+    pub fn hiked(&self, now: u64) -> bool {
+        self.hiked_at.is_some_and(|hiked_at| hiked_at <= now)
+    }
+}
+
+/// Waiting on a GPX file
+#[derive(Debug)]
+pub struct PendingDetails {
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    pub title: String,
+    pub difficulty: String,
+    pub rating: String,
+    pub image: String,
+    pub description: String,
+    pub created_at: u64,
+}
+
+/// Unset values fall back to the config
+#[derive(Debug, Default, Clone)]
+pub struct Preferences {
+    /// Such as `mile`
+    pub long_units: Option<String>,
+    /// Such as `foot`
+    pub short_units: Option<String>,
+    /// Such as `America/Denver`
+    pub time_zone: Option<String>,
+    pub email: Option<String>,
+    pub hike_reminders: bool,
+    pub suggestion_notifications: bool,
+    /// From 1 for January
+    pub birthday_month: Option<u8>,
+}
+
+#[derive(Debug)]
+pub struct Poll {
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub deadline: u64,
+    pub closed: bool,
+}
+
+impl Poll {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Poll {
+            message_id: MessageId::new(row.get(0)?),
+            channel_id: ChannelId::new(row.get(1)?),
+            deadline: row.get(2)?,
+            closed: row.get(3)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Waypoint {
+    pub id: i64,
+    /// Name of a [`crate::web_interface::waypoints::WaypointKind`]
+    pub kind: String,
+    pub label: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Filled {
+    /// `0` if it was never filled in
+    pub revision: u64,
+    /// Missing for older fills
+    pub by: Option<String>,
+    pub at: u64,
+}
+
+#[derive(Debug)]
+pub struct JobStats {
+    pub name: String,
+    pub next_run: u64,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub job: String,
+    pub failed_at: u64,
+    pub attempts: u32,
+    pub error: String,
+}
+
+impl DeadLetter {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(DeadLetter {
+            id: row.get(0)?,
+            job: row.get(1)?,
+            failed_at: row.get(2)?,
+            attempts: row.get(3)?,
+            error: row.get(4)?,
+        })
+    }
+}
+
+/// Lengths are in meters
+#[derive(Debug, Default)]
+pub struct SuggestionFilter {
+    pub difficulty: Option<String>,
+    pub min_length: Option<f64>,
+    pub max_length: Option<f64>,
+    pub hiked: Option<bool>,
+}
+
+pub struct Store {
+    connection: Mutex<Connection>,
+}
+
+impl Store {
+    #[instrument]
+    pub fn open(path: &Path) -> eyre::Result<Self> {
+        let mut connection = Connection::open(path)
+            .wrap_err_with(|| format!("Failed to open database at `{}`", path.display()))?;
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .wrap_err("Failed to enable foreign keys")?;
+        Self::migrate(&mut connection).wrap_err("Failed to migrate database")?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn migrate(connection: &mut Connection) -> eyre::Result<()> {
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .wrap_err("Failed to query database version")?;
+
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start migration transaction")?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            debug!(target: "store", "Applying migration {}", i + 1);
+            transaction
+                .execute_batch(migration)
+                .wrap_err_with(|| format!("Failed to apply migration {}", i + 1))?;
+        }
+        transaction
+            .pragma_update(None, "user_version", MIGRATIONS.len())
+            .wrap_err("Failed to update database version")?;
+        transaction
+            .commit()
+            .wrap_err("Failed to commit migration transaction")
+    }
+
+    #[instrument(skip(self))]
+    pub fn ping(&self) -> eyre::Result<()> {
+        self.connection()?
+            .query_row("SELECT 1", [], |_| Ok(()))
+            .wrap_err("Failed to ping database")
+    }
+
+    fn connection(&self) -> eyre::Result<std::sync::MutexGuard<'_, Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| eyre!("Database connection mutex was poisoned"))
+    }
+
+    /// Returns whether it was stored, which it isn't if it moved past
+    /// `revision`
+    #[instrument(skip(self))]
+    pub fn insert_suggestion(
+        &self,
+        suggestion: &Suggestion,
+        filled_by: &str,
+        filled_at: u64,
+        revision: Option<u64>,
+    ) -> eyre::Result<bool> {
+        let changed = self
+            .connection()?
+            .execute(
+                "INSERT INTO suggestions
+                (message_id, channel_id, title, link, created_at, difficulty, length, gain, description, filled_by, filled_at, beginner_score, accessible, trailhead_latitude, trailhead_longitude)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+                ON CONFLICT (message_id) DO UPDATE SET
+                title = excluded.title,
+                link = excluded.link,
+                difficulty = excluded.difficulty,
+                length = excluded.length,
+                gain = excluded.gain,
+                description = excluded.description,
+                filled_by = excluded.filled_by,
+                filled_at = excluded.filled_at,
+                beginner_score = excluded.beginner_score,
+                accessible = excluded.accessible,
+                trailhead_latitude = excluded.trailhead_latitude,
+                trailhead_longitude = excluded.trailhead_longitude,
+                revision = suggestions.revision + 1
+                WHERE ?16 IS NULL OR suggestions.revision = ?16",
+                params![
+                    suggestion.message_id.get(),
+                    suggestion.channel_id.get(),
+                    suggestion.title,
+                    suggestion.link,
+                    suggestion.created_at,
+                    suggestion.difficulty,
+                    suggestion.length,
+                    suggestion.gain,
+                    suggestion.description,
+                    filled_by,
+                    filled_at,
+                    suggestion.beginner_score,
+                    suggestion.accessible,
+                    suggestion.trailhead.map(|trailhead| trailhead.y()),
+                    suggestion.trailhead.map(|trailhead| trailhead.x()),
+                    revision,
+                ],
+            )
+            .wrap_err("Failed to insert suggestion")?;
+        Ok(changed > 0)
+    }
+
+    /// Returns whether it was claimed. Claims from before `stale_before` are
+    /// taken over.
+    #[instrument(skip(self))]
+    pub fn claim_fill(
+        &self,
+        message_id: MessageId,
+        revision: Option<u64>,
+        claimed_by: &str,
+        now: u64,
+        stale_before: u64,
+    ) -> eyre::Result<bool> {
+        let changed = self
+            .connection()?
+            .execute(
+                "INSERT INTO fill_claims (message_id, claimed_by, claimed_at)
+                SELECT ?1, ?3, ?4
+                WHERE ?2 IS NULL
+                OR ?2 = COALESCE((SELECT revision FROM suggestions WHERE message_id = ?1), 0)
+                ON CONFLICT (message_id) DO UPDATE SET
+                claimed_by = excluded.claimed_by,
+                claimed_at = excluded.claimed_at
+                WHERE fill_claims.claimed_at < ?5",
+                params![message_id.get(), revision, claimed_by, now, stale_before],
+            )
+            .wrap_err("Failed to claim suggestion")?;
+        Ok(changed > 0)
+    }
+
+    #[instrument(skip(self))]
+    pub fn release_fill(&self, message_id: MessageId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "DELETE FROM fill_claims WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to release claim on suggestion")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn filled(&self, message_id: MessageId) -> eyre::Result<Option<Filled>> {
+        self.connection()?
+            .query_row(
+                "SELECT revision, filled_by, filled_at FROM suggestions WHERE message_id = ?1",
+                [message_id.get()],
+                |row| {
+                    Ok(Filled {
+                        revision: row.get(0)?,
+                        by: row.get(1)?,
+                        at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .wrap_err("Failed to look up who filled in suggestion")
+    }
+
+    #[instrument(skip(self))]
+    pub fn suggestion_state(&self, message_id: MessageId) -> eyre::Result<Option<String>> {
+        self.connection()?
+            .query_row(
+                "SELECT state FROM suggestions WHERE message_id = ?1",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("Failed to look up suggestion state")
+    }
+
+    /// Moves a suggestion from `from` to `to` and records who moved it,
+    /// unless it was no longer in `from`
+    #[instrument(skip(self))]
+    pub fn transition_suggestion(
+        &self,
+        message_id: MessageId,
+        from: &str,
+        to: &str,
+        user_id: UserId,
+        at: u64,
+    ) -> eyre::Result<bool> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start transition transaction")?;
+        let changed = transaction
+            .execute(
+                "UPDATE suggestions SET state = ?3 WHERE message_id = ?1 AND state = ?2",
+                params![message_id.get(), from, to],
+            )
+            .wrap_err("Failed to update suggestion state")?;
+        if changed == 0 {
+            return Ok(false);
+        }
+        transaction
+            .execute(
+                "INSERT INTO state_transitions (message_id, from_state, to_state, user_id, at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![message_id.get(), from, to, user_id.get(), at],
+            )
+            .wrap_err("Failed to record suggestion state transition")?;
+        transaction
+            .commit()
+            .wrap_err("Failed to commit suggestion state transition")?;
+        Ok(true)
+    }
+
+    #[instrument(skip(self))]
+    pub fn proposed_suggestions(&self, limit: usize) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE state = 'proposed'
+                ORDER BY created_at DESC LIMIT ?1",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare proposed suggestions query")?;
+        let suggestions = statement
+            .query_map([limit], Suggestion::from_row)
+            .wrap_err("Failed to query proposed suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    #[instrument(skip(self))]
+    pub fn insert_poll(
+        &self,
+        message_id: MessageId,
+        channel_id: ChannelId,
+        deadline: u64,
+        candidates: &[MessageId],
+    ) -> eyre::Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start poll transaction")?;
+        transaction
+            .execute(
+                "INSERT INTO polls (message_id, channel_id, deadline) VALUES (?1, ?2, ?3)",
+                params![message_id.get(), channel_id.get(), deadline],
+            )
+            .wrap_err("Failed to insert poll")?;
+        for (position, candidate) in candidates.iter().enumerate() {
+            transaction
+                .execute(
+                    "INSERT INTO poll_candidates (poll_id, suggestion_id, position)
+                    VALUES (?1, ?2, ?3)",
+                    params![message_id.get(), candidate.get(), position],
+                )
+                .wrap_err("Failed to insert poll candidate")?;
+        }
+        transaction.commit().wrap_err("Failed to commit poll")
+    }
+
+    #[instrument(skip(self))]
+    pub fn poll(&self, message_id: MessageId) -> eyre::Result<Option<Poll>> {
+        self.connection()?
+            .query_row(
+                "SELECT message_id, channel_id, deadline, closed FROM polls WHERE message_id = ?1",
+                [message_id.get()],
+                Poll::from_row,
+            )
+            .optional()
+            .wrap_err("Failed to look up poll")
+    }
+
+    /// Polls still open past their deadline
+    #[instrument(skip(self))]
+    pub fn due_polls(&self, now: u64) -> eyre::Result<Vec<Poll>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT message_id, channel_id, deadline, closed FROM polls
+                WHERE closed = 0 AND deadline <= ?1",
+            )
+            .wrap_err("Failed to prepare due polls query")?;
+        let polls = statement
+            .query_map([now], Poll::from_row)
+            .wrap_err("Failed to query due polls")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read poll")?;
+        Ok(polls)
+    }
+
+    #[instrument(skip(self))]
+    pub fn close_poll(&self, message_id: MessageId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "UPDATE polls SET closed = 1 WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to close poll")?;
+        Ok(())
+    }
+
+    /// Suggestions on the ballot of a poll, in the order they're listed
+    #[instrument(skip(self))]
+    pub fn poll_candidates(&self, poll_id: MessageId) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions
+                JOIN poll_candidates ON poll_candidates.suggestion_id = suggestions.message_id
+                WHERE poll_candidates.poll_id = ?1 ORDER BY poll_candidates.position",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare poll candidates query")?;
+        let suggestions = statement
+            .query_map([poll_id.get()], Suggestion::from_row)
+            .wrap_err("Failed to query poll candidates")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read poll candidate")?;
+        Ok(suggestions)
+    }
+
+    /// Ranks `suggestion_id` at `rank` on a member's ballot, moving it if it
+    /// was ranked somewhere else
+    #[instrument(skip(self))]
+    pub fn cast_ballot(
+        &self,
+        poll_id: MessageId,
+        user_id: UserId,
+        rank: u8,
+        suggestion_id: MessageId,
+    ) -> eyre::Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start ballot transaction")?;
+        transaction
+            .execute(
+                "DELETE FROM ballots WHERE poll_id = ?1 AND user_id = ?2 AND suggestion_id = ?3",
+                params![poll_id.get(), user_id.get(), suggestion_id.get()],
+            )
+            .wrap_err("Failed to clear earlier ranking")?;
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO ballots (poll_id, user_id, rank, suggestion_id)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![poll_id.get(), user_id.get(), rank, suggestion_id.get()],
+            )
+            .wrap_err("Failed to insert ballot")?;
+        transaction.commit().wrap_err("Failed to commit ballot")
+    }
+
+    /// Every ballot cast in a poll, each listing suggestions from the most
+    /// preferred down
+    #[instrument(skip(self))]
+    pub fn ballots(&self, poll_id: MessageId) -> eyre::Result<HashMap<UserId, Vec<MessageId>>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT user_id, suggestion_id FROM ballots WHERE poll_id = ?1
+                ORDER BY user_id, rank",
+            )
+            .wrap_err("Failed to prepare ballots query")?;
+        let mut ballots = HashMap::<UserId, Vec<MessageId>>::new();
+        let rows = statement
+            .query_map([poll_id.get()], |row| {
+                Ok((UserId::new(row.get(0)?), MessageId::new(row.get(1)?)))
+            })
+            .wrap_err("Failed to query ballots")?;
+        for row in rows {
+            let (user_id, suggestion_id) = row.wrap_err("Failed to read ballot")?;
+            ballots.entry(user_id).or_default().push(suggestion_id);
+        }
+        Ok(ballots)
+    }
+
+    #[instrument(skip(self))]
+    pub fn is_suggestion(&self, message_id: MessageId) -> eyre::Result<bool> {
+        self.connection()?
+            .query_row(
+                "SELECT 1 FROM suggestions WHERE message_id = ?1",
+                [message_id.get()],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .wrap_err("Failed to look up suggestion")
+    }
+
+    #[instrument(skip(self))]
+    pub fn suggestion(&self, message_id: MessageId) -> eyre::Result<Option<Suggestion>> {
+        self.connection()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM suggestions WHERE message_id = ?1",
+                    SUGGESTION_COLUMNS
+                ),
+                [message_id.get()],
+                Suggestion::from_row,
+            )
+            .optional()
+            .wrap_err("Failed to look up suggestion")
+    }
+
+    #[instrument(skip(self))]
+    pub fn suggestions(&self) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions ORDER BY created_at DESC",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare suggestions query")?;
+        let suggestions = statement
+            .query_map([], Suggestion::from_row)
+            .wrap_err("Failed to query suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// Suggestions still being voted on, newest first, each with whether its
+    /// GPX file was kept
+    #[instrument(skip(self))]
+    pub fn proposed_with_gpx(&self) -> eyre::Result<Vec<(Suggestion, bool)>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {}, EXISTS (SELECT 1 FROM suggestion_gpx WHERE suggestion_gpx.message_id = suggestions.message_id)
+                FROM suggestions WHERE state = 'proposed' ORDER BY created_at DESC",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare proposed suggestions query")?;
+        let suggestions = statement
+            .query_map([], |row| Ok((Suggestion::from_row(row)?, row.get(14)?)))
+            .wrap_err("Failed to query proposed suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// The most recently hiked completed suggestions
+    #[instrument(skip(self))]
+    pub fn recently_completed(&self, limit: usize) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE state = 'completed'
+                ORDER BY hiked_at DESC LIMIT ?1",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare completed suggestions query")?;
+        let suggestions = statement
+            .query_map([limit], Suggestion::from_row)
+            .wrap_err("Failed to query completed suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// Suggestions filled in since `since` that weren't rejected, oldest first
+    #[instrument(skip(self))]
+    pub fn new_suggestions(&self, since: u64) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE filled_at >= ?1 AND state != 'rejected'
+                ORDER BY filled_at",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare new suggestions query")?;
+        let suggestions = statement
+            .query_map([since], Suggestion::from_row)
+            .wrap_err("Failed to query new suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// The `limit` most recently filled in suggestions that weren't rejected
+    #[instrument(skip(self))]
+    pub fn latest_suggestions(&self, limit: usize) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE state != 'rejected'
+                ORDER BY filled_at DESC LIMIT ?1",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare latest suggestions query")?;
+        let suggestions = statement
+            .query_map([limit], Suggestion::from_row)
+            .wrap_err("Failed to query latest suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// Returns one page of suggestions matching `filter`, along with the
+    /// total amount of suggestions matching it
+    #[instrument(skip(self))]
+    pub fn filtered_suggestions(
+        &self,
+        filter: &SuggestionFilter,
+        now: u64,
+        offset: usize,
+        limit: usize,
+    ) -> eyre::Result<(Vec<Suggestion>, usize)> {
+        const FILTER: &str = "(?1 IS NULL OR difficulty = ?1 COLLATE NOCASE)
+            AND (?2 IS NULL OR length >= ?2)
+            AND (?3 IS NULL OR length <= ?3)
+            AND (?4 IS NULL OR (hiked_at IS NOT NULL AND hiked_at <= ?5) = ?4)";
+
+        let connection = self.connection()?;
+        let filter_params = params![
+            filter.difficulty,
+            filter.min_length,
+            filter.max_length,
+            filter.hiked,
+            now,
+        ];
+
+        let total: usize = connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM suggestions WHERE {}", FILTER),
+                filter_params,
+                |row| row.get(0),
+            )
+            .wrap_err("Failed to count suggestions")?;
+
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE {} ORDER BY created_at DESC LIMIT ?6 OFFSET ?7",
+                SUGGESTION_COLUMNS, FILTER
+            ))
+            .wrap_err("Failed to prepare suggestions query")?;
+        let suggestions = statement
+            .query_map(
+                params![
+                    filter.difficulty,
+                    filter.min_length,
+                    filter.max_length,
+                    filter.hiked,
+                    now,
+                    limit,
+                    offset,
+                ],
+                Suggestion::from_row,
+            )
+            .wrap_err("Failed to query suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+
+        Ok((suggestions, total))
+    }
+
+    /// Suggestions hiked from `from` up to `until`, oldest first, each with how
+    /// many members were interested in it
+    #[instrument(skip(self))]
+    pub fn hikes_between(&self, from: u64, until: u64) -> eyre::Result<Vec<(Suggestion, u64)>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {}, (SELECT COUNT(*) FROM interested WHERE interested.message_id = suggestions.message_id)
+                FROM suggestions WHERE hiked_at >= ?1 AND hiked_at < ?2 ORDER BY hiked_at",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare hikes query")?;
+        let hikes = statement
+            .query_map([from, until], |row| {
+                Ok((Suggestion::from_row(row)?, row.get(14)?))
+            })
+            .wrap_err("Failed to query hikes")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read hike")?;
+        Ok(hikes)
+    }
+
+    /// Completed hikes `user_id` was interested in, most recent first
+    #[instrument(skip(self))]
+    pub fn member_history(&self, user_id: UserId) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE state = 'completed'
+                AND message_id IN (SELECT message_id FROM interested WHERE user_id = ?1)
+                ORDER BY hiked_at DESC",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare hike history query")?;
+        let hikes = statement
+            .query_map([user_id.get()], Suggestion::from_row)
+            .wrap_err("Failed to query hike history")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read hike")?;
+        Ok(hikes)
+    }
+
+    /// How many suggestions were hiked from `from` up to `until`, along with
+    /// their total length and elevation gain in meters
+    #[instrument(skip(self))]
+    pub fn hike_totals(&self, from: u64, until: u64) -> eyre::Result<(u64, f64, f64)> {
+        self.connection()?
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(length), 0), COALESCE(SUM(gain), 0)
+                FROM suggestions WHERE hiked_at >= ?1 AND hiked_at < ?2",
+                [from, until],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .wrap_err("Failed to total hikes")
+    }
+
+    /// Members interested in the most suggestions hiked from `from` up to
+    /// `until`, most first
+    #[instrument(skip(self))]
+    pub fn interested_leaderboard(
+        &self,
+        from: u64,
+        until: u64,
+        limit: usize,
+    ) -> eyre::Result<Vec<(UserId, u64)>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT interested.user_id, COUNT(*) AS hikes FROM interested
+                JOIN suggestions ON suggestions.message_id = interested.message_id
+                WHERE suggestions.hiked_at >= ?1 AND suggestions.hiked_at < ?2
+                GROUP BY interested.user_id ORDER BY hikes DESC LIMIT ?3",
+            )
+            .wrap_err("Failed to prepare leaderboard query")?;
+        let leaderboard = statement
+            .query_map(params![from, until, limit], |row| {
+                Ok((UserId::new(row.get(0)?), row.get(1)?))
+            })
+            .wrap_err("Failed to query leaderboard")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read leaderboard entry")?;
+        Ok(leaderboard)
+    }
+
+    /// Best matches first, with every word of `query` matching as a prefix
+    #[instrument(skip(self))]
+    pub fn search_suggestions(
+        &self,
+        query: &str,
+        min_beginner_score: Option<u8>,
+        accessible: Option<bool>,
+        limit: usize,
+    ) -> eyre::Result<Vec<Suggestion>> {
+        // Only letters, digits and spaces are left, so this can't be parsed as
+        // a LIKE pattern
+        let normalized = crate::trail_names::normalize(query);
+        // Quote each word so user input can't be parsed as FTS5 query syntax
+        let query = query
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let connection = self.connection()?;
+        // Trails known by another name come first, as the query names them
+        // outright
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions
+                WHERE message_id IN
+                (SELECT message_id FROM trail_aliases WHERE normalized LIKE ?1)
+                AND (?3 IS NULL OR beginner_score >= ?3)
+                AND (?4 IS NULL OR (accessible IS 1) = ?4)
+                LIMIT ?2",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare alias search query")?;
+        let mut suggestions = if normalized.is_empty() {
+            Vec::new()
+        } else {
+            statement
+                .query_map(
+                    params![
+                        format!("{}%", normalized),
+                        limit,
+                        min_beginner_score,
+                        accessible
+                    ],
+                    Suggestion::from_row,
+                )
+                .wrap_err("Failed to search trail aliases")?
+                .collect::<Result<Vec<_>, _>>()
+                .wrap_err("Failed to read suggestion")?
+        };
+
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions
+                JOIN (SELECT rowid AS id, rank FROM suggestions_fts WHERE suggestions_fts MATCH ?1)
+                AS matches ON matches.id = suggestions.message_id
+                WHERE (?3 IS NULL OR beginner_score >= ?3)
+                AND (?4 IS NULL OR (accessible IS 1) = ?4)
+                ORDER BY matches.rank LIMIT ?2",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare search query")?;
+        let matches = statement
+            .query_map(
+                params![query, limit, min_beginner_score, accessible],
+                Suggestion::from_row,
+            )
+            .wrap_err("Failed to search suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        for suggestion in matches {
+            if !suggestions
+                .iter()
+                .any(|found| found.message_id == suggestion.message_id)
+            {
+                suggestions.push(suggestion);
+            }
+        }
+        suggestions.truncate(limit);
+        Ok(suggestions)
+    }
+
+    #[instrument(skip(self))]
+    pub fn set_event(
+        &self,
+        message_id: MessageId,
+        event_id: ScheduledEventId,
+        start_time: u64,
+    ) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "UPDATE suggestions SET event_id = ?2, hiked_at = ?3 WHERE message_id = ?1",
+                params![message_id.get(), event_id.get(), start_time],
+            )
+            .wrap_err("Failed to record event for suggestion")?;
+        Ok(())
+    }
+
+    /// The suggestion that was injected into the scheduled event `event_id`
+    #[instrument(skip(self))]
+    pub fn event_suggestion(&self, event_id: ScheduledEventId) -> eyre::Result<Option<Suggestion>> {
+        self.connection()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM suggestions WHERE event_id = ?1 LIMIT 1",
+                    SUGGESTION_COLUMNS
+                ),
+                [event_id.get()],
+                Suggestion::from_row,
+            )
+            .optional()
+            .wrap_err("Failed to look up suggestion of event")
+    }
+
+    /// Suggestions whose scheduled event hasn't started by `now`
+    #[instrument(skip(self))]
+    pub fn upcoming_events(&self, now: u64) -> eyre::Result<Vec<Suggestion>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM suggestions WHERE hiked_at > ?1 ORDER BY hiked_at",
+                SUGGESTION_COLUMNS
+            ))
+            .wrap_err("Failed to prepare upcoming events query")?;
+        let suggestions = statement
+            .query_map([now], Suggestion::from_row)
+            .wrap_err("Failed to query upcoming events")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read suggestion")?;
+        Ok(suggestions)
+    }
+
+    /// Lead times reminders were already posted at for the event of the
+    /// suggestion posted as `message_id` starting at `starts_at`
+    #[instrument(skip(self))]
+    pub fn sent_reminders(&self, message_id: MessageId, starts_at: u64) -> eyre::Result<Vec<u64>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT lead_hours FROM reminders WHERE message_id = ?1 AND starts_at = ?2")
+            .wrap_err("Failed to prepare reminders query")?;
+        let leads = statement
+            .query_map(params![message_id.get(), starts_at], |row| row.get(0))
+            .wrap_err("Failed to query reminders")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read reminder")?;
+        Ok(leads)
+    }
+
+    #[instrument(skip(self))]
+    pub fn record_reminders(
+        &self,
+        message_id: MessageId,
+        starts_at: u64,
+        lead_hours: &[u64],
+    ) -> eyre::Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start transaction")?;
+        for lead in lead_hours {
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO reminders (message_id, starts_at, lead_hours)
+                    VALUES (?1, ?2, ?3)",
+                    params![message_id.get(), starts_at, lead],
+                )
+                .wrap_err("Failed to record reminder")?;
+        }
+        transaction.commit().wrap_err("Failed to commit reminders")
+    }
+
+    /// Whether the radar snapshot for the event of the suggestion posted as
+    /// `message_id` starting at `starts_at` was already posted
+    #[instrument(skip(self))]
+    pub fn radar_posted(&self, message_id: MessageId, starts_at: u64) -> eyre::Result<bool> {
+        self.connection()?
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM radar_snapshots WHERE message_id = ?1 AND starts_at = ?2)",
+                params![message_id.get(), starts_at],
+                |row| row.get(0),
+            )
+            .wrap_err("Failed to look up radar snapshot")
+    }
+
+    #[instrument(skip(self))]
+    pub fn record_radar(&self, message_id: MessageId, starts_at: u64) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR IGNORE INTO radar_snapshots (message_id, starts_at) VALUES (?1, ?2)",
+                params![message_id.get(), starts_at],
+            )
+            .wrap_err("Failed to record radar snapshot")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn add_interested(&self, message_id: MessageId, user_id: UserId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR IGNORE INTO interested (message_id, user_id) VALUES (?1, ?2)",
+                params![message_id.get(), user_id.get()],
+            )
+            .wrap_err("Failed to record interested member")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn remove_interested(&self, message_id: MessageId, user_id: UserId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "DELETE FROM interested WHERE message_id = ?1 AND user_id = ?2",
+                params![message_id.get(), user_id.get()],
+            )
+            .wrap_err("Failed to remove interested member")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self, users))]
+    pub fn set_interested(
+        &self,
+        message_id: MessageId,
+        users: impl IntoIterator<Item = UserId>,
+    ) -> eyre::Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .wrap_err("Failed to start interested transaction")?;
+        transaction
+            .execute(
+                "DELETE FROM interested WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to clear interested members")?;
+        for user_id in users {
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO interested (message_id, user_id) VALUES (?1, ?2)",
+                    params![message_id.get(), user_id.get()],
+                )
+                .wrap_err("Failed to record interested member")?;
+        }
+        transaction
+            .commit()
+            .wrap_err("Failed to commit interested members")
+    }
+
+    #[instrument(skip(self))]
+    pub fn interested(&self, message_id: MessageId) -> eyre::Result<Vec<UserId>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT user_id FROM interested WHERE message_id = ?1")
+            .wrap_err("Failed to prepare interested query")?;
+        let users = statement
+            .query_map([message_id.get()], |row| Ok(UserId::new(row.get(0)?)))
+            .wrap_err("Failed to query interested members")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read interested member")?;
+        Ok(users)
+    }
+
+    /// How many completed hikes `user_id` was interested in, and their total
+    /// elevation gain in meters
+    #[instrument(skip(self))]
+    pub fn member_hikes(&self, user_id: UserId) -> eyre::Result<(u64, f64)> {
+        self.connection()?
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(suggestions.gain), 0) FROM interested
+                JOIN suggestions ON suggestions.message_id = interested.message_id
+                WHERE interested.user_id = ?1 AND suggestions.state = 'completed'",
+                [user_id.get()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .wrap_err("Failed to total member's hikes")
+    }
+
+    #[instrument(skip(self))]
+    pub fn badges(&self, user_id: UserId) -> eyre::Result<Vec<String>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT badge FROM badges WHERE user_id = ?1")
+            .wrap_err("Failed to prepare badges query")?;
+        let badges = statement
+            .query_map([user_id.get()], |row| row.get(0))
+            .wrap_err("Failed to query badges")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read badge")?;
+        Ok(badges)
+    }
+
+    /// Records that `user_id` earned `badge`, returning whether they hadn't
+    /// already
+    #[instrument(skip(self))]
+    pub fn award_badge(&self, user_id: UserId, badge: &str, at: u64) -> eyre::Result<bool> {
+        let changed = self
+            .connection()?
+            .execute(
+                "INSERT OR IGNORE INTO badges (user_id, badge, earned_at) VALUES (?1, ?2, ?3)",
+                params![user_id.get(), badge, at],
+            )
+            .wrap_err("Failed to store badge")?;
+        Ok(changed > 0)
+    }
+
+    #[instrument(skip(self))]
+    pub fn insert_pending_details(&self, details: &PendingDetails) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO pending_details
+                (message_id, channel_id, user_id, title, difficulty, rating, image, description, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    details.message_id.get(),
+                    details.channel_id.get(),
+                    details.user_id.get(),
+                    details.title,
+                    details.difficulty,
+                    details.rating,
+                    details.image,
+                    details.description,
+                    details.created_at,
+                ],
+            )
+            .wrap_err("Failed to insert pending details")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn pending_details(
+        &self,
+        message_id: MessageId,
+        user_id: UserId,
+    ) -> eyre::Result<Option<PendingDetails>> {
+        self.connection()?
+            .query_row(
+                "SELECT message_id, channel_id, user_id, title, difficulty, rating, image, description, created_at
+                FROM pending_details WHERE message_id = ?1 AND user_id = ?2",
+                params![message_id.get(), user_id.get()],
+                |row| {
+                    Ok(PendingDetails {
+                        message_id: MessageId::new(row.get(0)?),
+                        channel_id: ChannelId::new(row.get(1)?),
+                        user_id: UserId::new(row.get(2)?),
+                        title: row.get(3)?,
+                        difficulty: row.get(4)?,
+                        rating: row.get(5)?,
+                        image: row.get(6)?,
+                        description: row.get(7)?,
+                        created_at: row.get(8)?,
+                    })
+                },
+            )
+            .optional()
+            .wrap_err("Failed to look up pending details")
+    }
+
+    #[instrument(skip(self))]
+    pub fn remove_pending_details(&self, message_id: MessageId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "DELETE FROM pending_details WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to remove pending details")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn preferences(&self, user_id: UserId) -> eyre::Result<Option<Preferences>> {
+        self.connection()?
+            .query_row(
+                "SELECT long_units, short_units, time_zone, email, hike_reminders, suggestion_notifications, birthday_month
+                FROM preferences WHERE user_id = ?1",
+                [user_id.get()],
+                |row| {
+                    Ok(Preferences {
+                        long_units: row.get(0)?,
+                        short_units: row.get(1)?,
+                        time_zone: row.get(2)?,
+                        email: row.get(3)?,
+                        hike_reminders: row.get(4)?,
+                        suggestion_notifications: row.get(5)?,
+                        birthday_month: row.get(6)?,
+                    })
+                },
+            )
+            .optional()
+            .wrap_err("Failed to look up preferences")
+    }
+
+    #[instrument(skip(self))]
+    pub fn set_preferences(&self, user_id: UserId, preferences: &Preferences) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO preferences
+                (user_id, long_units, short_units, time_zone, email, hike_reminders, suggestion_notifications, birthday_month)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    user_id.get(),
+                    preferences.long_units,
+                    preferences.short_units,
+                    preferences.time_zone,
+                    preferences.email,
+                    preferences.hike_reminders,
+                    preferences.suggestion_notifications,
+                    preferences.birthday_month,
+                ],
+            )
+            .wrap_err("Failed to save preferences")?;
+        Ok(())
+    }
+
+    /// How many members registered a birthday in `month`, from 1 for January
+    #[instrument(skip(self))]
+    pub fn birthdays_in(&self, month: u8) -> eyre::Result<u64> {
+        self.connection()?
+            .query_row(
+                "SELECT COUNT(*) FROM preferences WHERE birthday_month = ?1",
+                [month],
+                |row| row.get(0),
+            )
+            .wrap_err("Failed to count birthdays")
+    }
+
+    /// Stores the GPX file the suggestion posted as `message_id` was filled in
+    /// with, replacing the one it was filled in with before
+    #[instrument(skip(self, gpx))]
+    pub fn set_suggestion_gpx(&self, message_id: MessageId, gpx: &[u8]) -> eyre::Result<()> {
+        let connection = self.connection()?;
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO suggestion_gpx (message_id, gpx) VALUES (?1, ?2)",
+                params![message_id.get(), gpx],
+            )
+            .wrap_err("Failed to store suggestion GPX file")?;
+        // The offline map of the old route may not cover the new one
+        connection
+            .execute(
+                "DELETE FROM offline_maps WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to remove outdated offline map")?;
+        Ok(())
+    }
+
+    /// The MBTiles offline map generated for the suggestion posted as
+    /// `message_id`, if it's been downloaded since its route was filled in
+    #[instrument(skip(self))]
+    pub fn offline_map(&self, message_id: MessageId) -> eyre::Result<Option<Vec<u8>>> {
+        self.connection()?
+            .query_row(
+                "SELECT mbtiles FROM offline_maps WHERE message_id = ?1",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("Failed to look up offline map")
+    }
+
+    #[instrument(skip(self, mbtiles))]
+    pub fn set_offline_map(&self, message_id: MessageId, mbtiles: &[u8]) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO offline_maps (message_id, mbtiles) VALUES (?1, ?2)",
+                params![message_id.get(), mbtiles],
+            )
+            .wrap_err("Failed to store offline map")?;
+        Ok(())
+    }
+
+    /// The route map rendered when the suggestion posted as `message_id` was
+    /// last filled in
+    #[instrument(skip(self))]
+    pub fn route_map(&self, message_id: MessageId) -> eyre::Result<Option<Vec<u8>>> {
+        self.connection()?
+            .query_row(
+                "SELECT png FROM route_maps WHERE message_id = ?1",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("Failed to look up route map")
+    }
+
+    #[instrument(skip(self))]
+    pub fn has_route_map(&self, message_id: MessageId) -> eyre::Result<bool> {
+        self.connection()?
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM route_maps WHERE message_id = ?1)",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .wrap_err("Failed to look up route map")
+    }
+
+    /// Stores the route map of the suggestion posted as `message_id`, or
+    /// forgets it if none could be rendered this time
+    #[instrument(skip(self, png))]
+    pub fn set_route_map(&self, message_id: MessageId, png: Option<&[u8]>) -> eyre::Result<()> {
+        let connection = self.connection()?;
+        match png {
+            Some(png) => connection.execute(
+                "INSERT OR REPLACE INTO route_maps (message_id, png) VALUES (?1, ?2)",
+                params![message_id.get(), png],
+            ),
+            None => connection.execute(
+                "DELETE FROM route_maps WHERE message_id = ?1",
+                [message_id.get()],
+            ),
+        }
+        .wrap_err("Failed to store route map")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn has_suggestion_gpx(&self, message_id: MessageId) -> eyre::Result<bool> {
+        self.connection()?
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM suggestion_gpx WHERE message_id = ?1)",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .wrap_err("Failed to look up suggestion GPX file")
+    }
+
+    #[instrument(skip(self))]
+    pub fn suggestion_gpx(&self, message_id: MessageId) -> eyre::Result<Option<Vec<u8>>> {
+        self.connection()?
+            .query_row(
+                "SELECT gpx FROM suggestion_gpx WHERE message_id = ?1",
+                [message_id.get()],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("Failed to look up suggestion GPX file")
+    }
+
+    /// Waypoints of the suggestion posted as `message_id`, in the order they
+    /// were added
+    #[instrument(skip(self))]
+    pub fn waypoints(&self, message_id: MessageId) -> eyre::Result<Vec<Waypoint>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT id, kind, label, latitude, longitude FROM waypoints
+                WHERE message_id = ?1 ORDER BY id",
+            )
+            .wrap_err("Failed to prepare waypoints query")?;
+        let waypoints = statement
+            .query_map([message_id.get()], |row| {
+                Ok(Waypoint {
+                    id: row.get(0)?,
+                    kind: row.get(1)?,
+                    label: row.get(2)?,
+                    latitude: row.get(3)?,
+                    longitude: row.get(4)?,
+                })
+            })
+            .wrap_err("Failed to query waypoints")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read waypoint")?;
+        Ok(waypoints)
+    }
+
+    /// Stores `waypoint` with the suggestion posted as `message_id`, ignoring
+    /// its `id`
+    #[instrument(skip(self))]
+    pub fn insert_waypoint(&self, message_id: MessageId, waypoint: &Waypoint) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT INTO waypoints (message_id, kind, label, latitude, longitude)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    message_id.get(),
+                    waypoint.kind,
+                    waypoint.label,
+                    waypoint.latitude,
+                    waypoint.longitude,
+                ],
+            )
+            .wrap_err("Failed to insert waypoint")?;
+        Ok(())
+    }
+
+    /// Removes waypoint `id` from the suggestion posted as `message_id`,
+    /// returning whether it was there
+    #[instrument(skip(self))]
+    pub fn remove_waypoint(&self, message_id: MessageId, id: i64) -> eyre::Result<bool> {
+        let removed = self
+            .connection()?
+            .execute(
+                "DELETE FROM waypoints WHERE message_id = ?1 AND id = ?2",
+                params![message_id.get(), id],
+            )
+            .wrap_err("Failed to remove waypoint")?;
+        Ok(removed > 0)
+    }
+
+    /// The suggestion `normalized` is an alias of
+    #[instrument(skip(self))]
+    pub fn alias_suggestion(&self, normalized: &str) -> eyre::Result<Option<Suggestion>> {
+        self.connection()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM suggestions WHERE message_id =
+                    (SELECT message_id FROM trail_aliases WHERE normalized = ?1)",
+                    SUGGESTION_COLUMNS
+                ),
+                [normalized],
+                Suggestion::from_row,
+            )
+            .optional()
+            .wrap_err("Failed to look up trail alias")
+    }
+
+    /// Other names the suggestion posted as `message_id` goes by
+    #[instrument(skip(self))]
+    pub fn aliases(&self, message_id: MessageId) -> eyre::Result<Vec<String>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT alias FROM trail_aliases WHERE message_id = ?1 ORDER BY alias")
+            .wrap_err("Failed to prepare trail aliases query")?;
+        let aliases = statement
+            .query_map([message_id.get()], |row| row.get(0))
+            .wrap_err("Failed to query trail aliases")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read trail alias")?;
+        Ok(aliases)
+    }
+
+    /// Adds `alias` to the suggestion posted as `message_id`, returning the
+    /// suggestion it already belongs to instead if it's taken
+    #[instrument(skip(self))]
+    pub fn add_alias(
+        &self,
+        message_id: MessageId,
+        alias: &str,
+        normalized: &str,
+        added_by: &str,
+        added_at: u64,
+    ) -> eyre::Result<Option<MessageId>> {
+        let connection = self.connection()?;
+        let added = connection
+            .execute(
+                "INSERT INTO trail_aliases (normalized, message_id, alias, added_by, added_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (normalized) DO UPDATE SET alias = excluded.alias
+                WHERE trail_aliases.message_id = excluded.message_id",
+                params![normalized, message_id.get(), alias, added_by, added_at],
+            )
+            .wrap_err("Failed to add trail alias")?;
+        if added > 0 {
+            return Ok(None);
+        }
+        connection
+            .query_row(
+                "SELECT message_id FROM trail_aliases WHERE normalized = ?1",
+                [normalized],
+                |row| row.get(0).map(MessageId::new),
+            )
+            .optional()
+            .wrap_err("Failed to look up trail alias")
+    }
+
+    /// Removes the alias normalized to `normalized` from the suggestion posted
+    /// as `message_id`, returning whether it was there
+    #[instrument(skip(self))]
+    pub fn remove_alias(&self, message_id: MessageId, normalized: &str) -> eyre::Result<bool> {
+        let removed = self
+            .connection()?
+            .execute(
+                "DELETE FROM trail_aliases WHERE message_id = ?1 AND normalized = ?2",
+                params![message_id.get(), normalized],
+            )
+            .wrap_err("Failed to remove trail alias")?;
+        Ok(removed > 0)
+    }
+
+    #[instrument(skip(self))]
+    pub fn disabled_features(&self) -> eyre::Result<Vec<String>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT name FROM disabled_features")
+            .wrap_err("Failed to prepare disabled features query")?;
+        let features = statement
+            .query_map([], |row| row.get(0))
+            .wrap_err("Failed to query disabled features")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read disabled feature")?;
+        Ok(features)
+    }
+
+    #[instrument(skip(self))]
+    pub fn set_feature_enabled(&self, name: &str, enabled: bool) -> eyre::Result<()> {
+        let sql = if enabled {
+            "DELETE FROM disabled_features WHERE name = ?1"
+        } else {
+            "INSERT OR IGNORE INTO disabled_features (name) VALUES (?1)"
+        };
+        self.connection()?
+            .execute(sql, [name])
+            .wrap_err("Failed to update disabled features")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn prune_pending_details(&self, created_before: u64) -> eyre::Result<usize> {
+        self.connection()?
+            .execute(
+                "DELETE FROM pending_details WHERE created_at < ?1",
+                [created_before],
+            )
+            .wrap_err("Failed to prune pending details")
+    }
+
+    /// Returns the persisted next run of a job, if it was scheduled with
+    /// `schedule`
+    #[instrument(skip(self))]
+    pub fn job_next_run(&self, name: &str, schedule: &str) -> eyre::Result<Option<u64>> {
+        self.connection()?
+            .query_row(
+                "SELECT next_run FROM jobs WHERE name = ?1 AND schedule = ?2",
+                params![name, schedule],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("Failed to look up next job run")
+    }
+
+    #[instrument(skip(self))]
+    pub fn set_job_next_run(&self, name: &str, schedule: &str, next_run: u64) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT INTO jobs (name, schedule, next_run) VALUES (?1, ?2, ?3)
+                ON CONFLICT (name) DO UPDATE SET
+                schedule = excluded.schedule,
+                next_run = excluded.next_run",
+                params![name, schedule, next_run],
+            )
+            .wrap_err("Failed to store next job run")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn record_job_run(
+        &self,
+        name: &str,
+        started_at: u64,
+        duration_ms: u64,
+        error: Option<&str>,
+    ) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "UPDATE jobs SET
+                runs = runs + 1,
+                failures = failures + (?4 IS NOT NULL),
+                last_run = ?2,
+                last_duration_ms = ?3,
+                last_error = ?4
+                WHERE name = ?1",
+                params![name, started_at, duration_ms, error],
+            )
+            .wrap_err("Failed to record job run")?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn job_stats(&self) -> eyre::Result<Vec<JobStats>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT name, next_run, runs, failures, last_run, last_duration_ms, last_error
+                FROM jobs ORDER BY name",
+            )
+            .wrap_err("Failed to prepare job stats query")?;
+        let stats = statement
+            .query_map([], |row| {
+                Ok(JobStats {
+                    name: row.get(0)?,
+                    next_run: row.get(1)?,
+                    runs: row.get(2)?,
+                    failures: row.get(3)?,
+                    last_run: row.get(4)?,
+                    last_duration_ms: row.get(5)?,
+                    last_error: row.get(6)?,
+                })
+            })
+            .wrap_err("Failed to query job stats")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read job stats")?;
+        Ok(stats)
+    }
+
+    #[instrument(skip(self))]
+    pub fn insert_dead_letter(
+        &self,
+        job: &str,
+        failed_at: u64,
+        attempts: u32,
+        error: &str,
+    ) -> eyre::Result<i64> {
+        let connection = self.connection()?;
+        connection
+            .execute(
+                "INSERT INTO dead_letters (job, failed_at, attempts, error)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![job, failed_at, attempts, error],
+            )
+            .wrap_err("Failed to insert dead letter")?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    #[instrument(skip(self))]
+    pub fn dead_letters(&self) -> eyre::Result<Vec<DeadLetter>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT id, job, failed_at, attempts, error FROM dead_letters ORDER BY id DESC",
+            )
+            .wrap_err("Failed to prepare dead letters query")?;
+        let dead_letters = statement
+            .query_map([], DeadLetter::from_row)
+            .wrap_err("Failed to query dead letters")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read dead letter")?;
+        Ok(dead_letters)
+    }
+
+    #[instrument(skip(self))]
+    pub fn dead_letter(&self, id: i64) -> eyre::Result<Option<DeadLetter>> {
+        self.connection()?
+            .query_row(
+                "SELECT id, job, failed_at, attempts, error FROM dead_letters WHERE id = ?1",
+                [id],
+                DeadLetter::from_row,
+            )
+            .optional()
+            .wrap_err("Failed to look up dead letter")
+    }
+
+    #[instrument(skip(self))]
+    pub fn remove_dead_letter(&self, id: i64) -> eyre::Result<()> {
+        self.connection()?
+            .execute("DELETE FROM dead_letters WHERE id = ?1", [id])
+            .wrap_err("Failed to remove dead letter")?;
+        Ok(())
+    }
+
+    /// Tracks a suggestion message that still has its upload buttons
+    #[instrument(skip(self))]
+    pub fn insert_upload_button(
+        &self,
+        message_id: MessageId,
+        channel_id: ChannelId,
+        created_at: u64,
+    ) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO upload_buttons (message_id, channel_id, created_at)
+                VALUES (?1, ?2, ?3)",
+                params![message_id.get(), channel_id.get(), created_at],
+            )
+            .wrap_err("Failed to insert upload button")?;
+        Ok(())
+    }
+
+    /// Suggestions posted with an upload button that haven't been filled in
+    /// yet, oldest first
+    #[instrument(skip(self))]
+    pub fn unfilled_suggestions(&self) -> eyre::Result<Vec<(ChannelId, MessageId, u64)>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT channel_id, message_id, created_at FROM upload_buttons
+                WHERE message_id NOT IN (SELECT message_id FROM suggestions)
+                ORDER BY created_at",
+            )
+            .wrap_err("Failed to prepare unfilled suggestions query")?;
+        let suggestions = statement
+            .query_map([], |row| {
+                Ok((
+                    ChannelId::new(row.get(0)?),
+                    MessageId::new(row.get(1)?),
+                    row.get(2)?,
+                ))
+            })
+            .wrap_err("Failed to query unfilled suggestions")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read unfilled suggestion")?;
+        Ok(suggestions)
+    }
+
+    #[instrument(skip(self))]
+    pub fn remove_upload_button(&self, message_id: MessageId) -> eyre::Result<()> {
+        self.connection()?
+            .execute(
+                "DELETE FROM upload_buttons WHERE message_id = ?1",
+                [message_id.get()],
+            )
+            .wrap_err("Failed to remove upload button")?;
+        Ok(())
+    }
+
+    /// Suggestion messages whose upload buttons were posted before
+    /// `created_before`
+    #[instrument(skip(self))]
+    pub fn expired_upload_buttons(
+        &self,
+        created_before: u64,
+    ) -> eyre::Result<Vec<(ChannelId, MessageId)>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT channel_id, message_id FROM upload_buttons WHERE created_at < ?1")
+            .wrap_err("Failed to prepare expired upload buttons query")?;
+        let buttons = statement
+            .query_map([created_before], |row| {
+                Ok((ChannelId::new(row.get(0)?), MessageId::new(row.get(1)?)))
+            })
+            .wrap_err("Failed to query expired upload buttons")?
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("Failed to read expired upload button")?;
+        Ok(buttons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Store {
+        Store::open(Path::new(":memory:")).unwrap()
+    }
+
+    #[test]
+    fn only_one_fill_is_claimed_at_a_time() {
+        let store = store();
+        let message_id = MessageId::new(1);
+
+        assert!(store.claim_fill(message_id, Some(0), "a", 100, 0).unwrap());
+        assert!(!store.claim_fill(message_id, Some(0), "b", 101, 0).unwrap());
+        assert!(!store.claim_fill(message_id, None, "b", 101, 0).unwrap());
+
+        store.release_fill(message_id).unwrap();
+        assert!(store.claim_fill(message_id, Some(0), "b", 102, 0).unwrap());
+    }
+
+    #[test]
+    fn fill_claims_check_the_revision() {
+        let store = store();
+        assert!(!store
+            .claim_fill(MessageId::new(1), Some(1), "a", 100, 0)
+            .unwrap());
+    }
+
+    #[test]
+    fn stale_fill_claims_are_taken_over() {
+        let store = store();
+        let message_id = MessageId::new(1);
+
+        assert!(store.claim_fill(message_id, Some(0), "a", 100, 0).unwrap());
+        assert!(store
+            .claim_fill(message_id, Some(0), "b", 800, 200)
+            .unwrap());
+    }
+
+    #[test]
+    fn suggestions_keep_when_they_were_made() {
+        let store = store();
+        let suggestion = Suggestion {
+            message_id: MessageId::new(1),
+            channel_id: ChannelId::new(1),
+            title: String::from("Trail"),
+            link: String::from("https://www.alltrails.com/trail/us/utah/trail"),
+            created_at: 100,
+            difficulty: None,
+            length: None,
+            gain: None,
+            hiked_at: None,
+            description: None,
+            beginner_score: None,
+            accessible: None,
+            trailhead: None,
+        };
+        assert!(store
+            .insert_suggestion(&suggestion, "a", 500, None)
+            .unwrap());
+        assert!(store
+            .insert_suggestion(&suggestion, "b", 900, Some(1))
+            .unwrap());
+
+        let filled = store.filled(suggestion.message_id).unwrap().unwrap();
+        assert_eq!((filled.by.as_deref(), filled.at), (Some("b"), 900));
+        let new = store.new_suggestions(800).unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].created_at, 100);
+        assert!(store.new_suggestions(901).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+use color_eyre::eyre::{self, Context};
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    runtime,
+    trace::{Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::OtlpConfig;
+
+/// Exports spans over OTLP/gRPC, sampling root spans by `sample_ratio` and
+/// following the sampling decision of the parent otherwise
+pub fn layer<S>(config: &OtlpConfig) -> eyre::Result<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()
+        .wrap_err("Failed to build OTLP span exporter")?;
+
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            env!("CARGO_PKG_NAME"),
+        )]))
+        .build();
+
+    opentelemetry::global::set_tracer_provider(provider.clone());
+
+    Ok(tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME"))))
+}
+
+/// Flushes spans that haven't been exported yet
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
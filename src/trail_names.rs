@@ -0,0 +1,92 @@
+use color_eyre::eyre::{self, Context};
+use serenity::all::MessageId;
+
+use crate::store::{Store, Suggestion};
+
+/// Longest alias that can be added, in characters
+pub const MAX_ALIAS_LENGTH: usize = 100;
+
+/// Abbreviations expanded so differently written names line up
+const ABBREVIATIONS: [(&str, &str); 8] = [
+    ("mt", "mount"),
+    ("mtn", "mountain"),
+    ("pk", "peak"),
+    ("ft", "fort"),
+    ("lk", "lake"),
+    ("cyn", "canyon"),
+    ("cr", "creek"),
+    ("ck", "creek"),
+];
+
+/// Words sites tack onto trail names that don't tell trails apart
+const FILLER: [&str; 6] = ["the", "trail", "trails", "trailhead", "hike", "th"];
+
+/// `name` lowercased, without punctuation, abbreviations or filler words,
+/// and without the route taken after "via", so "Mount Aire via Elbow Fork"
+/// and "Mt. Aire Trail" are both "mount aire"
+pub fn normalize(name: &str) -> String {
+    let name = name
+        .to_lowercase()
+        .chars()
+        // So "Devil's" doesn't become "devil s"
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>();
+    let mut words = Vec::new();
+    for word in name.split_whitespace() {
+        if word == "via" && !words.is_empty() {
+            break;
+        }
+        if FILLER.contains(&word) {
+            continue;
+        }
+        words.push(
+            ABBREVIATIONS
+                .iter()
+                .find(|(short, _)| *short == word)
+                .map_or(word, |(_, long)| long),
+        );
+    }
+    words.join(" ")
+}
+
+/// The suggestion going by `name`, either as an alias or its own title
+pub fn resolve(store: &Store, name: &str) -> eyre::Result<Option<Suggestion>> {
+    let normalized = normalize(name);
+    if normalized.is_empty() {
+        return Ok(None);
+    }
+    if let Some(suggestion) = store
+        .alias_suggestion(&normalized)
+        .wrap_err("Failed to look up trail alias")?
+    {
+        return Ok(Some(suggestion));
+    }
+    Ok(store
+        .suggestions()
+        .wrap_err("Failed to load suggestions")?
+        .into_iter()
+        .find(|suggestion| normalize(&suggestion.title) == normalized))
+}
+
+/// The suggestion going by `name`, or else the best search match for it
+pub fn find(store: &Store, name: &str) -> eyre::Result<Option<Suggestion>> {
+    if let Some(suggestion) = resolve(store, name)? {
+        return Ok(Some(suggestion));
+    }
+    Ok(store
+        .search_suggestions(name, None, None, 1)
+        .wrap_err("Failed to search suggestions")?
+        .into_iter()
+        .next())
+}
+
+/// Another suggestion going by the same name as `title`, so the same trail
+/// isn't planned twice under different names
+pub fn duplicate_of(
+    store: &Store,
+    message_id: MessageId,
+    title: &str,
+) -> eyre::Result<Option<Suggestion>> {
+    Ok(resolve(store, title)?.filter(|suggestion| suggestion.message_id != message_id))
+}
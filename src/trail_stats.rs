@@ -0,0 +1,339 @@
+//! Length and elevation stats of GPX tracks, kept apart from the bot so the
+//! benchmarks and fuzz harnesses can run them on their own
+
+use color_eyre::eyre::{self, Context, OptionExt};
+use geo::{Distance, Haversine, Length, Line, LineString, Point};
+use gpx::Waypoint;
+use tracing::instrument;
+
+pub struct ElevationPoint {
+    point: Point,
+    distance: f64,
+    elevation: f64,
+    extremum: bool,
+    survived: bool,
+}
+
+/// Total elevation gained and lost along `track`, in meters, ignoring noise
+/// in the recorded elevations
+#[instrument(skip_all)]
+pub fn elevation_changes(track: &gpx::Track) -> eyre::Result<(f64, f64)> {
+    let mut elevation_points = elevation_points(track)?;
+    approximate_elevation_points(&mut elevation_points)
+        .wrap_err("Failed to approximate elevation points")?;
+    find_extrema(&mut elevation_points)?;
+
+    let mut gains = 0.0;
+    let mut losses = 0.0;
+    let mut prev_elevation_point = elevation_points
+        .first()
+        .ok_or_eyre("Finding elevation points yeilded no results")?;
+    for elevation_point in elevation_points.iter().skip(1).filter(|e| e.extremum) {
+        let diff = elevation_point.elevation - prev_elevation_point.elevation;
+        if diff > 0.0 {
+            gains += diff;
+        } else {
+            losses += diff.abs();
+        }
+        prev_elevation_point = elevation_point;
+    }
+    Ok((gains, losses))
+}
+
+/// Every point of `track` with how far along it is
+pub fn elevation_points(track: &gpx::Track) -> eyre::Result<Vec<ElevationPoint>> {
+    let first_waypoint = track
+        .segments
+        .first()
+        .ok_or_eyre("GPX track has no segments")?
+        .points
+        .first()
+        .ok_or_eyre("GPX segment has no points")?;
+    let elevation_points = vec![ElevationPoint {
+        distance: 0.0,
+        elevation: first_waypoint
+            .elevation
+            .ok_or_eyre("GPX point has no elevation")?,
+        extremum: true,
+        survived: false,
+        point: first_waypoint.point(),
+    }];
+    Ok(track
+        .segments
+        .iter()
+        .flat_map(|s| s.points.windows(2))
+        .try_fold(
+            (elevation_points, 0.0),
+            |(mut points, mut distance), point| {
+                distance += Haversine::distance(point[0].point(), point[1].point());
+                points.push(ElevationPoint {
+                    distance,
+                    elevation: point[1]
+                        .elevation
+                        .ok_or_eyre("GPX point has no elevation")?,
+                    extremum: false,
+                    survived: false,
+                    point: point[1].point(),
+                });
+                Ok::<_, eyre::Report>((points, distance))
+            },
+        )?
+        .0)
+}
+
+/// Marks the points where the elevation turns around, along with the first
+/// and last point
+pub fn find_extrema(elevation_points: &mut [ElevationPoint]) -> eyre::Result<()> {
+    elevation_points
+        .last_mut()
+        .ok_or_eyre("Finding elevation points yeilded no results")?
+        .extremum = true;
+    elevation_points
+        .first_mut()
+        .ok_or_eyre("Finding elevation points yeilded no results")?
+        .extremum = true;
+    find_maximum_extremum_between(0, elevation_points.len() - 1, elevation_points)
+        .wrap_err("Failed to find maximum extremum of elevation points")
+}
+
+/// Radius around the first and last recorded point in which points are
+/// considered to be wandering around the trailhead rather than hiking
+const TRAILHEAD_RADIUS: f64 = 25.0;
+/// Distance walked inside [`TRAILHEAD_RADIUS`] before it counts as wandering.
+/// Routes that head straight out or come straight back in, however densely
+/// they're planned, walk about the radius.
+const TRAILHEAD_MIN_WANDER: f64 = 2.0 * TRAILHEAD_RADIUS;
+
+/// Drops the points at either end of `track` spent wandering around the
+/// trailhead, so every stat computed from it agrees
+#[instrument(skip_all)]
+pub fn trim_trailhead_wander(track: &mut gpx::Track) {
+    if let Some(first) = track.segments.first_mut() {
+        let wander = trailhead_wander_len(first.points.iter().map(Waypoint::point));
+        first.points.drain(..wander);
+    }
+    if let Some(last) = track.segments.last_mut() {
+        let wander = trailhead_wander_len(last.points.iter().rev().map(Waypoint::point));
+        let keep = (last.points.len() - wander).max(2);
+        last.points.truncate(keep);
+    }
+}
+
+/// Amount of points following the first point of `points` that are still
+/// within [`TRAILHEAD_RADIUS`] of it, if they wander around enough to trim
+fn trailhead_wander_len(mut points: impl Iterator<Item = Point>) -> usize {
+    let Some(trailhead) = points.next() else {
+        return 0;
+    };
+    let nearby = points
+        .take_while(|point| Haversine::distance(trailhead, *point) <= TRAILHEAD_RADIUS)
+        .collect::<Vec<_>>();
+    let walked = std::iter::once(trailhead)
+        .chain(nearby.iter().copied())
+        .collect::<LineString>()
+        .length::<Haversine>();
+    if walked >= TRAILHEAD_MIN_WANDER {
+        nearby.len()
+    } else {
+        0
+    }
+}
+
+// Borrowed from OsmAnd: https://github.com/osmandapp/OsmAnd/blob/0026e71e1be4cd29fb904c5d0735f02cf80d88b6/OsmAnd-shared/src/commonMain/kotlin/net/osmand/shared/gpx/ElevationDiffsCalculator.kt#L20
+// https://github.com/osmandapp/OsmAnd/blob/master/OsmAnd-shared/src/commonMain/kotlin/net/osmand/shared/gpx/ElevationApproximator.kt#L5
+
+fn get_projection_dist(x: f64, y: f64, fromx: f64, fromy: f64, tox: f64, toy: f64) -> f64 {
+    let m_dist = (fromx - tox) * (fromx - tox) + (fromy - toy) * (fromy - toy);
+    // let projection = KMapUtils.scalarMultiplication(fromx, fromy, tox, toy, x, y);
+    let projection = (tox - fromx) * (x - fromx) + (toy - fromy) * (y - fromy);
+    let (prx, pry) = if projection < 0.0 {
+        (fromx, fromy)
+    } else if projection >= m_dist {
+        (tox, toy)
+    } else {
+        (
+            fromx + (tox - fromx) * (projection / m_dist),
+            fromy + (toy - fromy) * (projection / m_dist),
+        )
+    };
+    ((prx - x) * (prx - x) + (pry - y) * (pry - y)).sqrt()
+}
+
+/// Drops points whose elevation is noise, returning whether enough points
+/// were left to replace `points` with
+#[instrument(skip_all)]
+pub fn approximate_elevation_points(points: &mut Vec<ElevationPoint>) -> eyre::Result<bool> {
+    const SLOPE_THRESHOLD: f64 = 70.0;
+    let mut last_survived = 0;
+    let mut survived_count = 0;
+    for i in 1..points.len() - 1 {
+        let prev_ele = points
+            .get(last_survived)
+            .ok_or_eyre("Point not found")?
+            .elevation;
+        let ele = points.get(i).ok_or_eyre("Point not found")?.elevation;
+        let ele_next = points.get(i + 1).ok_or_eyre("Point not found")?.elevation;
+        if (ele - prev_ele) * (ele_next - ele) > 0.0 {
+            points[i].survived = true;
+            last_survived = i;
+            survived_count += 1;
+        }
+    }
+    points.last_mut().unwrap().survived = true;
+    survived_count += 1;
+    if survived_count < 2 {
+        return Ok(false);
+    }
+
+    last_survived = 0;
+    survived_count = 0;
+    for i in 1..points.len() - 1 {
+        if !points.get(i).ok_or_eyre("Point not found")?.survived {
+            continue;
+        }
+
+        let ele = points.get(i).ok_or_eyre("Point not found")?.elevation;
+        let prev_ele = points
+            .get(last_survived)
+            .ok_or_eyre("Point not found")?
+            .elevation;
+        let dist = Line::new(points[i].point, points[last_survived].point).length::<Haversine>();
+        let slope = (ele - prev_ele) * 100.0 / dist;
+        if slope.abs() > SLOPE_THRESHOLD {
+            points[i].survived = false;
+            continue;
+        }
+        last_survived = i;
+        survived_count += 1;
+    }
+    if survived_count < 2 {
+        return Ok(false);
+    }
+
+    points[0].survived = true;
+    points.last_mut().unwrap().survived = true;
+    let mut elevation_points = Vec::new();
+    last_survived = 0;
+    for i in 0..points.len() {
+        if !points[i].survived {
+            continue;
+        }
+        elevation_points.push(ElevationPoint {
+            distance: if last_survived == 0 {
+                0.0
+            } else {
+                Line::new(points[i].point, points[last_survived].point).length::<Haversine>()
+            },
+            elevation: points[i].elevation,
+            point: points[i].point,
+            extremum: false,
+            survived: true,
+        });
+        last_survived = i;
+    }
+    *points = elevation_points;
+    Ok(true)
+}
+
+/// Marks the point furthest from the line between `start` and `end` as an
+/// extremum, then does the same on either side of it
+#[instrument(skip(points))]
+fn find_maximum_extremum_between(
+    start: usize,
+    end: usize,
+    points: &mut [ElevationPoint],
+) -> eyre::Result<()> {
+    const ELE_THRESHOLD: f64 = 7.0;
+
+    // Not recursive, as uploads can be shaped to overflow the call stack
+    let mut ranges = vec![(start, end)];
+    while let Some((start, end)) = ranges.pop() {
+        let first_point_dist = points.get(start).ok_or_eyre("Point not found")?.distance;
+        let first_point_ele = points.get(start).ok_or_eyre("Point not found")?.elevation;
+        let end_point_dist = points.get(end).ok_or_eyre("Point not found")?.distance;
+        let end_point_ele = points.get(end).ok_or_eyre("Point not found")?.elevation;
+        let mut max = start;
+        let mut max_diff = ELE_THRESHOLD;
+        for i in start + 1..end {
+            let md = get_projection_dist(
+                points.get(i).ok_or_eyre("Point not found")?.distance,
+                points.get(i).ok_or_eyre("Point not found")?.elevation,
+                first_point_dist,
+                first_point_ele,
+                end_point_dist,
+                end_point_ele,
+            );
+            if md > max_diff {
+                max = i;
+                max_diff = md;
+            }
+        }
+        if max != start {
+            points[max].extremum = true;
+            ranges.push((max, end));
+            ranges.push((start, max));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use gpx::{Track, TrackSegment};
+
+    use super::*;
+
+    /// Meters per degree of latitude, close enough near the equator
+    const DEGREE: f64 = 111_195.0;
+
+    fn track(points: impl IntoIterator<Item = (f64, f64)>) -> Track {
+        let mut segment = TrackSegment::new();
+        segment.points = points
+            .into_iter()
+            .map(|(x, y)| Waypoint::new(Point::new(x / DEGREE, y / DEGREE)))
+            .collect();
+        let mut track = Track::new();
+        track.segments.push(segment);
+        track
+    }
+
+    #[test]
+    fn keeps_loops_back_to_the_trailhead() {
+        // A 200 m loop planned every 2 m, ending where it started
+        let loop_route = track((0..=628).map(|i| {
+            let angle = i as f64 / 100.0;
+            (200.0 * (angle.cos() - 1.0), 200.0 * angle.sin())
+        }));
+        let mut trimmed = loop_route.clone();
+        trim_trailhead_wander(&mut trimmed);
+        assert_eq!(trimmed.segments[0].points.len(), 629);
+    }
+
+    #[test]
+    fn keeps_dense_out_and_backs() {
+        let out = (0..=500).map(|i| (0.0, i as f64));
+        let back = (0..500).rev().map(|i| (0.0, i as f64));
+        let mut trimmed = track(out.chain(back));
+        trim_trailhead_wander(&mut trimmed);
+        assert_eq!(trimmed.segments[0].points.len(), 1001);
+    }
+
+    #[test]
+    fn trims_wandering_at_the_trailhead() {
+        // Pacing around the parking lot before and after the hike
+        let pacing = (0..30).map(|i| (0.0, (i % 2) as f64 * 5.0));
+        let hike = (1..=200).map(|i| (0.0, i as f64 * 5.0));
+        let back = (0..200).rev().map(|i| (0.0, i as f64 * 5.0));
+        let pacing_again = (0..30).map(|i| (0.0, (i % 2) as f64 * 5.0));
+        let mut trimmed = track(pacing.chain(hike).chain(back).chain(pacing_again));
+        trim_trailhead_wander(&mut trimmed);
+
+        // Up to the trailhead radius of hiking goes with it at either end
+        let length = trimmed.multilinestring().length::<Haversine>();
+        assert!(
+            (2000.0 - 2.0 * TRAILHEAD_RADIUS..2000.0).contains(&length),
+            "{}",
+            length
+        );
+    }
+}
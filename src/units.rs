@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::{self, eyre};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use uom::si::{
+    f64::Length,
+    length::{self, meter, Units},
+};
+
+/// Decimal places lengths are shown with, unless configured for their unit
+const DEFAULT_PRECISION: usize = 1;
+
+/// Converts `length` in `unit` to meters
+pub fn length_to_meters(length: f64, unit: Units) -> eyre::Result<f64> {
+    macro_rules! to_meters {
+        ($($unit:ident),+ $(,)?) => {
+            match unit {
+                $(Units::$unit(_) => Length::new::<length::$unit>(length).get::<meter>(),)+
+                _ => return Err(eyre!("Unsupported length unit `{}`", unit.singular())),
+            }
+        };
+    }
+
+    Ok(to_meters!(
+        yottameter,
+        zettameter,
+        exameter,
+        petameter,
+        terameter,
+        gigameter,
+        megameter,
+        kilometer,
+        hectometer,
+        decameter,
+        meter,
+        decimeter,
+        centimeter,
+        millimeter,
+        micrometer,
+        nanometer,
+        picometer,
+        femtometer,
+        attometer,
+        zeptometer,
+        yoctometer,
+        angstrom,
+        bohr_radius,
+        atomic_unit_of_length,
+        astronomical_unit,
+        chain,
+        fathom,
+        fermi,
+        foot,
+        foot_survey,
+        inch,
+        light_year,
+        microinch,
+        micron,
+        mil,
+        mile,
+        mile_survey,
+        nautical_mile,
+        parsec,
+        pica_computer,
+        pica_printers,
+        point_computer,
+        point_printers,
+        rod,
+        yard,
+    ))
+}
+
+/// Formats `length` in meters in any `unit`, with `precision` decimal places
+pub fn format_length(length: f64, unit: Units, precision: usize) -> eyre::Result<String> {
+    Ok(format!(
+        "{:.*} {}",
+        precision,
+        length / length_to_meters(1.0, unit)?,
+        unit.abbreviation()
+    ))
+}
+
+/// The length unit with the singular name `name`, such as `mile`
+pub fn length_unit(name: &str) -> Option<Units> {
+    uom::si::length::units().find(|unit| unit.singular() == name)
+}
+
+/// A length unit in the config, written as its singular name such as `mile`
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigLength(pub Units);
+
+impl Serialize for ConfigLength {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.singular())
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigLength {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let unit_single = String::deserialize(deserializer)?;
+
+        if let Some(unit) = length_unit(&unit_single) {
+            return Ok(ConfigLength(unit));
+        }
+
+        let mut units = String::from("[");
+
+        for unit in uom::si::length::units() {
+            units.push('`');
+            units.push_str(unit.singular());
+            units.push_str("`, ");
+        }
+
+        units.push(']');
+
+        Err(D::Error::invalid_value(
+            serde::de::Unexpected::Str(&unit_single),
+            &units.as_str(),
+        ))
+    }
+}
+
+/// How lengths are shown, from `long_units`, `short_units`,
+/// `length_precision` and `short_units_below` in the config
+#[derive(Debug, Clone, Copy)]
+pub struct Lengths<'a> {
+    pub long_units: Units,
+    pub short_units: Units,
+    /// Decimal places by unit name, such as `mile`
+    pub precision: &'a HashMap<String, usize>,
+    /// Long lengths under this many `long_units` are shown in `short_units`
+    pub short_units_below: Option<f64>,
+}
+
+impl Lengths<'_> {
+    fn format(&self, length: f64, unit: Units) -> eyre::Result<String> {
+        let precision = self
+            .precision
+            .get(unit.singular())
+            .copied()
+            .unwrap_or(DEFAULT_PRECISION);
+        format_length(length, unit, precision)
+    }
+
+    /// Formats a distance, such as the length of a trail
+    pub fn long(&self, length: f64) -> eyre::Result<String> {
+        if let Some(below) = self.short_units_below {
+            if length < length_to_meters(below, self.long_units)? {
+                return self.format(length, self.short_units);
+            }
+        }
+        self.format(length, self.long_units)
+    }
+
+    /// Formats a height, such as elevation gain
+    pub fn short(&self, length: f64) -> eyre::Result<String> {
+        self.format(length, self.short_units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_lengths_to_meters() {
+        let mile = length_unit("mile").unwrap();
+        let foot = length_unit("foot").unwrap();
+        assert!((length_to_meters(1.0, mile).unwrap() - 1609.344).abs() < 1e-9);
+        assert!((length_to_meters(10.0, foot).unwrap() - 3.048).abs() < 1e-9);
+        assert_eq!(
+            length_to_meters(2.5, length_unit("meter").unwrap()).unwrap(),
+            2.5
+        );
+    }
+
+    #[test]
+    fn config_lengths_round_trip() {
+        for unit in uom::si::length::units() {
+            let json = serde_json::to_string(&ConfigLength(unit)).unwrap();
+            assert_eq!(json, format!("\"{}\"", unit.singular()));
+            let ConfigLength(parsed) = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.singular(), unit.singular());
+        }
+    }
+
+    #[test]
+    fn config_lengths_reject_unknown_units() {
+        for bad in ["", "miles", "Mile", "mi", " mile", "furlong"] {
+            let json = serde_json::to_string(bad).unwrap();
+            assert!(
+                serde_json::from_str::<ConfigLength>(&json).is_err(),
+                "{}",
+                bad
+            );
+        }
+        assert!(serde_json::from_str::<ConfigLength>("1").is_err());
+    }
+
+    #[test]
+    fn every_unit_converts() {
+        for unit in uom::si::length::units() {
+            assert!(length_to_meters(1.0, unit).is_ok(), "{}", unit.singular());
+        }
+    }
+}
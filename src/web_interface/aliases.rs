@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Redirect,
+    Form,
+};
+use color_eyre::eyre::{eyre, Context, OptionExt};
+use jsonwebtoken::get_current_timestamp;
+use maud::DOCTYPE;
+use serde::Deserialize;
+use serenity::all::MessageId;
+use tracing::{info, instrument};
+
+use crate::{
+    error::WithStatusCode,
+    trail_names::{normalize, MAX_ALIAS_LENGTH},
+    AppState,
+};
+
+/// Every filled in suggestion, linking to its alias editor
+#[instrument(skip_all)]
+pub async fn index(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin("/hikea/admin/aliases")?;
+    let suggestions = state
+        .store
+        .suggestions()
+        .wrap_err("Failed to load trail suggestions")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Trail names" }
+            }
+            body {
+                h1 { "Trail names" }
+                p { "Pick a trail to add the other names AllTrails, OpenStreetMap or the group call it by." }
+                ul {
+                    @for suggestion in &suggestions {
+                        li {
+                            a href=(format!("/hikea/admin/aliases/{}", suggestion.message_id)) {
+                                (suggestion.title)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[instrument(skip(state, claims))]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin(&format!("/hikea/admin/aliases/{}", message_id))?;
+    let suggestion = state
+        .store
+        .suggestion(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or_eyre("Trail suggestion was not found")
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+    let aliases = state
+        .store
+        .aliases(message_id)
+        .wrap_err("Failed to load trail aliases")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Other names for " (suggestion.title) }
+            }
+            body {
+                p { a href="/hikea/admin/aliases" { "All trails" } }
+                h1 { "Other names for " a href=(suggestion.link) { (suggestion.title) } }
+                @if aliases.is_empty() {
+                    p { "This trail goes by no other names yet." }
+                }
+                ul {
+                    @for alias in &aliases {
+                        li {
+                            (alias) " "
+                            form method="post" style="display: inline"
+                                action=(format!("/hikea/admin/aliases/{}/delete", message_id)) {
+                                input type="hidden" name="alias" value=(alias);
+                                input type="submit" value="Remove";
+                            }
+                        }
+                    }
+                }
+                h2 { "Add a name" }
+                form method="post" {
+                    p {
+                        label {
+                            "Name "
+                            input type="text" name="alias" required maxlength=(MAX_ALIAS_LENGTH);
+                        }
+                    }
+                    input type="submit" value="Add";
+                }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AliasForm {
+    alias: String,
+}
+
+#[instrument(skip(state, claims))]
+pub async fn add(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+    Form(form): Form<AliasForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let admin =
+        super::member_name(&claims.require_admin(&format!("/hikea/admin/aliases/{}", message_id))?);
+    let alias = form.alias.trim();
+    let normalized = normalize(alias);
+    if normalized.is_empty() || alias.chars().count() > MAX_ALIAS_LENGTH {
+        return Err(eyre!(
+            "Names must have a letter or digit and be at most {} characters long",
+            MAX_ALIAS_LENGTH
+        ))
+        .with_status_code_html(StatusCode::BAD_REQUEST);
+    }
+    if state
+        .store
+        .suggestion(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .is_none()
+    {
+        return Err(eyre!("Trail suggestion was not found"))
+            .with_status_code_html(StatusCode::NOT_FOUND);
+    }
+
+    if let Some(taken_by) = state
+        .store
+        .add_alias(
+            message_id,
+            alias,
+            &normalized,
+            &admin,
+            get_current_timestamp(),
+        )
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        let title = state
+            .store
+            .suggestion(taken_by)
+            .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+            .map_or_else(|| taken_by.to_string(), |taken_by| taken_by.title);
+        return Err(eyre!(
+            "`{}` is already a name of {}, remove it there first",
+            alias,
+            title
+        ))
+        .with_status_code_html(StatusCode::CONFLICT);
+    }
+    info!(admin, %message_id, alias, "Added trail alias");
+
+    Ok(Redirect::to(&format!(
+        "/hikea/admin/aliases/{}",
+        message_id
+    )))
+}
+
+#[instrument(skip(state, claims))]
+pub async fn remove(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+    Form(form): Form<AliasForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let admin =
+        super::member_name(&claims.require_admin(&format!("/hikea/admin/aliases/{}", message_id))?);
+
+    let removed = state
+        .store
+        .remove_alias(message_id, &normalize(&form.alias))
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(eyre!("Alias was not found")).with_status_code_html(StatusCode::NOT_FOUND);
+    }
+    info!(admin, %message_id, alias = form.alias, "Removed trail alias");
+
+    Ok(Redirect::to(&format!(
+        "/hikea/admin/aliases/{}",
+        message_id
+    )))
+}
@@ -0,0 +1,125 @@
+use std::{io::Cursor, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use color_eyre::eyre::{eyre, Context, OptionExt};
+use serde_json::{json, Value};
+use serenity::all::MessageId;
+use tracing::instrument;
+
+use crate::{error::WithStatusCode, store::Waypoint, AppState};
+
+/// Link to the GPX file of the suggestion posted as `message_id`, for map
+/// apps to load directly
+pub fn gpx_url(hostname: &str, message_id: MessageId) -> String {
+    format!("{}/hikea/api/trail/{}.gpx", hostname, message_id)
+}
+
+/// Link to the route map of the suggestion posted as `message_id`, which only
+/// exists if it was rendered when the suggestion was filled in
+pub fn route_map_url(hostname: &str, message_id: MessageId) -> String {
+    format!("{}/hikea/api/trail/{}.png", hostname, message_id)
+}
+
+/// The route of `gpx` and `waypoints` as a GeoJSON feature collection, with
+/// each waypoint labelled
+pub fn route_geojson(gpx: &gpx::Gpx, waypoints: &[Waypoint]) -> Value {
+    let lines = gpx
+        .tracks
+        .iter()
+        .flat_map(|track| &track.segments)
+        .map(|segment| {
+            segment
+                .points
+                .iter()
+                .map(|point| json!([point.point().x(), point.point().y()]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut features = vec![json!({
+        "type": "Feature",
+        "geometry": { "type": "MultiLineString", "coordinates": lines },
+        "properties": {},
+    })];
+    features.extend(waypoints.iter().map(|waypoint| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [waypoint.longitude, waypoint.latitude] },
+            "properties": {
+                "label": format!("{}: {}", super::waypoints::kind_label(&waypoint.kind), waypoint.label),
+            },
+        })
+    }));
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+/// The stored route of a suggestion as `<message_id>.gpx` or
+/// `<message_id>.geojson`, or its route map as `<message_id>.png`. Map apps
+/// and feed readers can't sign in with Discord, so these are public.
+#[instrument(skip(state))]
+pub async fn trail(
+    State(state): State<Arc<AppState>>,
+    Path(file): Path<String>,
+) -> Result<Response, crate::error::HtmlError> {
+    let (message_id, extension) = file
+        .rsplit_once('.')
+        .and_then(|(id, extension)| Some((id.parse::<MessageId>().ok()?, extension)))
+        .ok_or_else(|| eyre!("`{}` is not a trail file", file))
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+    if extension == "png" {
+        let png = state
+            .store
+            .route_map(message_id)
+            .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or_eyre("This suggestion has no route map")
+            .with_status_code_html(StatusCode::NOT_FOUND)?;
+        return Ok(([(CONTENT_TYPE, "image/png")], png).into_response());
+    }
+    let gpx = state
+        .store
+        .suggestion_gpx(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or_eyre("This suggestion has no GPX file")
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+
+    match extension {
+        "gpx" => Ok((
+            [
+                (CONTENT_TYPE, String::from("application/gpx+xml")),
+                (
+                    CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.gpx\"", message_id),
+                ),
+            ],
+            gpx,
+        )
+            .into_response()),
+        "geojson" => {
+            let gpx = gpx::read(Cursor::new(gpx))
+                .wrap_err("Failed to read stored GPX file")
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+            let waypoints = state
+                .store
+                .waypoints(message_id)
+                .wrap_err("Failed to load waypoints")
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok((
+                [(CONTENT_TYPE, "application/geo+json")],
+                route_geojson(&gpx, &waypoints).to_string(),
+            )
+                .into_response())
+        }
+        _ => Err(eyre!("`{}` is not a trail file", file))
+            .with_status_code_html(StatusCode::NOT_FOUND),
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use color_eyre::eyre::{self, Context};
+use jsonwebtoken::{get_current_timestamp, Validation};
+use serde::{Deserialize, Serialize};
+use serenity::all::{ScheduledEvent, UserId};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use crate::{error::WithStatusCode, store::Suggestion, AppState};
+
+/// Hikes without an end time are assumed to take this long
+const DEFAULT_DURATION: i64 = 4 * 60 * 60;
+
+/// Longest a line of an iCalendar file can be, in bytes
+const MAX_LINE_LENGTH: usize = 75;
+
+/// What a member's calendar feed link is signed with. Calendar apps can't
+/// sign in with Discord, so the link itself is the credential and never
+/// expires, but members who leave the guild lose access.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarClaims {
+    calendar: UserId,
+}
+
+/// The feed link for `user`, to subscribe to from a calendar app
+pub fn feed_url(state: &AppState, user: UserId) -> eyre::Result<String> {
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::EdDSA),
+        &CalendarClaims { calendar: user },
+        &state.keys.encoding,
+    )
+    .wrap_err("Failed to encode calendar claims")?;
+    Ok(format!(
+        "{}/hikea/calendar.ics?token={}",
+        state.config.load().hostname,
+        token
+    ))
+}
+
+/// Escapes `text` for an iCalendar text value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Adds `name:value` to `ics`, folded so no line is longer than
+/// `MAX_LINE_LENGTH`
+fn push_line(ics: &mut String, name: &str, value: &str) {
+    let line = format!("{}:{}", name, value);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// `timestamp` in the iCalendar UTC date-time format
+fn date_time(timestamp: i64) -> String {
+    let time = OffsetDateTime::from_unix_timestamp(timestamp).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+/// A hike on the calendar, from a scheduled event or a stored suggestion
+pub struct Entry<'a> {
+    pub uid: String,
+    pub start: i64,
+    pub end: Option<i64>,
+    pub summary: &'a str,
+    pub description: &'a str,
+    pub location: Option<&'a str>,
+    pub suggestion: Option<&'a Suggestion>,
+}
+
+impl<'a> Entry<'a> {
+    fn from_event(event: &'a ScheduledEvent, suggestion: Option<&'a Suggestion>) -> Self {
+        Entry {
+            uid: format!("{}@hikea", event.id),
+            start: event.start_time.unix_timestamp(),
+            end: event.end_time.map(|end| end.unix_timestamp()),
+            summary: &event.name,
+            description: event.description.as_deref().unwrap_or_default(),
+            location: event
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.location.as_deref()),
+            suggestion,
+        }
+    }
+}
+
+fn push_entry(ics: &mut String, entry: &Entry) {
+    push_line(ics, "BEGIN", "VEVENT");
+    push_line(ics, "UID", &entry.uid);
+    push_line(ics, "DTSTAMP", &date_time(get_current_timestamp() as i64));
+    push_line(ics, "DTSTART", &date_time(entry.start));
+    push_line(
+        ics,
+        "DTEND",
+        &date_time(entry.end.unwrap_or(entry.start + DEFAULT_DURATION)),
+    );
+    push_line(ics, "SUMMARY", &escape(entry.summary));
+
+    let mut description = entry.description.to_owned();
+    if let Some(suggestion) = entry.suggestion {
+        push_line(ics, "URL", &suggestion.link);
+        description = format!("{}\n\n{}", suggestion.link, description);
+    }
+    push_line(ics, "DESCRIPTION", &escape(description.trim()));
+
+    if let Some(location) = entry.location {
+        push_line(ics, "LOCATION", &escape(location));
+    }
+    if let Some(trailhead) = entry.suggestion.and_then(|suggestion| suggestion.trailhead) {
+        push_line(
+            ics,
+            "GEO",
+            &format!("{:.5};{:.5}", trailhead.y(), trailhead.x()),
+        );
+    }
+    push_line(ics, "END", "VEVENT");
+}
+
+/// `entries` as an iCalendar file
+pub fn calendar<'a>(entries: impl IntoIterator<Item = Entry<'a>>) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN", "VCALENDAR");
+    push_line(&mut ics, "VERSION", "2.0");
+    push_line(
+        &mut ics,
+        "PRODID",
+        &format!("-//hikea//hikea {}//EN", env!("CARGO_PKG_VERSION")),
+    );
+    push_line(&mut ics, "X-WR-CALNAME", "Hikes");
+    for entry in entries {
+        push_entry(&mut ics, &entry);
+    }
+    push_line(&mut ics, "END", "VCALENDAR");
+    ics
+}
+
+#[derive(Deserialize)]
+pub struct CalendarQuery {
+    token: String,
+}
+
+/// The guild's scheduled hikes as an iCalendar feed
+#[instrument(skip_all)]
+pub async fn feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, crate::error::HtmlError> {
+    let mut validation = Validation::new(jsonwebtoken::Algorithm::EdDSA);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    let claims =
+        jsonwebtoken::decode::<CalendarClaims>(&query.token, &state.keys.decoding, &validation)
+            .wrap_err("Calendar link is not valid")
+            .with_status_code_html(StatusCode::UNAUTHORIZED)?
+            .claims;
+
+    let config = state.config.load();
+    let http = state.http.load();
+    config
+        .guild_id
+        .member(http.as_ref(), claims.calendar)
+        .await
+        .wrap_err("Calendar link belongs to someone who is no longer a member")
+        .with_status_code_html(StatusCode::FORBIDDEN)?;
+
+    let events = config
+        .guild_id
+        .scheduled_events(http.as_ref(), false)
+        .await
+        .wrap_err("Failed to grab scheduled events for guild")
+        .with_status_code_html(StatusCode::BAD_GATEWAY)?;
+
+    let suggestions = events
+        .iter()
+        .map(|event| state.store.event_suggestion(event.id))
+        .collect::<eyre::Result<Vec<_>>>()
+        .wrap_err("Failed to load suggestion of event")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let ics = calendar(
+        events
+            .iter()
+            .zip(&suggestions)
+            .map(|(event, suggestion)| Entry::from_event(event, suggestion.as_ref())),
+    );
+
+    Ok(([(CONTENT_TYPE, "text/calendar; charset=utf-8")], ics))
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::Redirect, Form};
+use color_eyre::eyre::{self, eyre, Context};
+use maud::DOCTYPE;
+use serde::Deserialize;
+use serenity::all::{Command, CommandId, GuildId, Http};
+use tracing::{info, instrument};
+
+use crate::{
+    audit::{audited, Actor, Mutation},
+    error::WithStatusCode,
+    AppState, Config,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Implemented,
+    /// Registered by this instance's scope, but not implemented by it
+    Stale,
+    /// Registered where this instance doesn't register commands, so it's left
+    /// to whichever instance does
+    Unmanaged,
+}
+
+struct RegisteredCommand {
+    guild: Option<GuildId>,
+    id: CommandId,
+    name: String,
+    status: Status,
+}
+
+/// Every global command and guild command in the home and test guilds
+async fn registered_commands(http: &Http, config: &Config) -> eyre::Result<Vec<RegisteredCommand>> {
+    let implemented = crate::commands::desired_command_names(config)?;
+    let managed = config
+        .test_guild
+        .as_ref()
+        .map(|test_guild| test_guild.guild_id);
+
+    let mut scopes = vec![None, Some(config.guild_id)];
+    if !scopes.contains(&managed) {
+        scopes.push(managed);
+    }
+
+    let mut registered = Vec::new();
+    for guild in scopes {
+        let commands = match guild {
+            Some(guild) => guild.get_commands(http).await,
+            None => Command::get_global_commands(http).await,
+        }
+        .wrap_err("Failed to get registered commands from Discord")?;
+
+        registered.extend(commands.into_iter().map(|command| RegisteredCommand {
+            guild,
+            id: command.id,
+            status: if guild != managed {
+                Status::Unmanaged
+            } else if implemented.contains(&command.name) {
+                Status::Implemented
+            } else {
+                Status::Stale
+            },
+            name: command.name,
+        }));
+    }
+    Ok(registered)
+}
+
+#[instrument(skip_all)]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin("/hikea/admin/commands")?;
+
+    let commands = registered_commands(&state.http.load(), &state.config.load())
+        .await
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let stale = commands
+        .iter()
+        .filter(|command| command.status == Status::Stale)
+        .count();
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Registered commands" }
+            }
+            body {
+                h1 { "Registered commands" }
+                table {
+                    tr {
+                        th { "Scope" }
+                        th { "Name" }
+                        th { "ID" }
+                        th { "Status" }
+                    }
+                    @for command in &commands {
+                        tr style=[(command.status == Status::Stale).then_some("background: #fdd")] {
+                            td {
+                                @match command.guild {
+                                    Some(guild) => { "Guild " (guild) }
+                                    None => { "Global" }
+                                }
+                            }
+                            td { (command.name) }
+                            td { (command.id) }
+                            td {
+                                @match command.status {
+                                    Status::Implemented => "Implemented",
+                                    Status::Stale => "Not implemented",
+                                    Status::Unmanaged => "Registered by another instance",
+                                }
+                            }
+                        }
+                    }
+                }
+                @if stale > 0 {
+                    form method="post" {
+                        label {
+                            input type="checkbox" name="confirm" value="yes" required;
+                            " Delete " (stale) " command(s) this version no longer implements"
+                        }
+                        input type="submit" value="Delete";
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct DeleteForm {
+    confirm: Option<String>,
+}
+
+#[instrument(skip_all)]
+pub async fn delete_stale(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Form(form): Form<DeleteForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let admin = super::member_name(&claims.require_admin("/hikea/admin/commands")?);
+
+    if form.confirm.as_deref() != Some("yes") {
+        return Err(eyre!("Deleting commands has to be confirmed"))
+            .with_status_code_html(StatusCode::BAD_REQUEST);
+    }
+
+    let http = state.http.load();
+    let commands = registered_commands(&http, &state.config.load())
+        .await
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    for command in commands
+        .iter()
+        .filter(|command| command.status == Status::Stale)
+    {
+        let target = match command.guild {
+            Some(guild) => format!("command `{}` in guild {}", command.name, guild),
+            None => format!("global command `{}`", command.name),
+        };
+        let delete = async {
+            match command.guild {
+                Some(guild) => guild.delete_command(http.as_ref(), command.id).await,
+                None => Command::delete_global_command(http.as_ref(), command.id).await,
+            }
+        };
+        audited(
+            &state.audit,
+            Mutation::DeleteCommand,
+            Actor::Admin(&admin),
+            target,
+            "not implemented by this version",
+            delete,
+        )
+        .await
+        .wrap_err_with(|| format!("Failed to delete command `{}`", command.name))
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+        info!(name = command.name, id = %command.id, "Deleted stale command");
+    }
+
+    Ok(Redirect::to("/hikea/admin/commands"))
+}
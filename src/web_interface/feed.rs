@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header::CONTENT_TYPE, http::StatusCode, response::IntoResponse};
+use color_eyre::eyre::{self, Context};
+use maud::html;
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use crate::{error::WithStatusCode, sanitize, store::Suggestion, AppState};
+
+/// How many suggestions the feed lists
+const ENTRIES: usize = 50;
+
+/// Escapes `text` for XML text and attribute values
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// `timestamp` as an RFC 3339 date-time in UTC
+fn date_time(timestamp: u64) -> String {
+    let time =
+        OffsetDateTime::from_unix_timestamp(timestamp as i64).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+/// Difficulty, length and gain of `suggestion`, in the server's units
+fn stats(state: &AppState, suggestion: &Suggestion) -> eyre::Result<String> {
+    let config = state.config.load();
+    let lengths = config.lengths();
+    let mut stats = Vec::new();
+    if let Some(difficulty) = &suggestion.difficulty {
+        stats.push(difficulty.clone());
+    }
+    if let Some(length) = suggestion.length {
+        stats.push(lengths.long(length)?);
+    }
+    if let Some(gain) = suggestion.gain {
+        stats.push(format!("{} gain", lengths.short(gain)?));
+    }
+    Ok(stats.join(" · "))
+}
+
+fn push_entry(atom: &mut String, state: &AppState, suggestion: &Suggestion) -> eyre::Result<()> {
+    let hostname = &state.config.load().hostname;
+    let stats = stats(state, suggestion)?;
+    let route_map = state
+        .store
+        .has_route_map(suggestion.message_id)
+        .wrap_err("Failed to look up route map")?
+        .then(|| super::api::route_map_url(hostname, suggestion.message_id));
+    let content = html! {
+        @if !stats.is_empty() {
+            p { (stats) }
+        }
+        @if let Some(hiked_at) = suggestion.hiked_at {
+            @if let Ok(time) = OffsetDateTime::from_unix_timestamp(hiked_at as i64) {
+                p { "Planned for " (time.date()) }
+            }
+        }
+        @if let Some(description) = &suggestion.description {
+            p { (description) }
+        }
+        @if let Some(route_map) = &route_map {
+            img src=(route_map) alt={ "Route map of " (suggestion.title) };
+        }
+    };
+
+    atom.push_str("<entry>");
+    atom.push_str(&format!(
+        "<id>{}/hikea/suggestions/{}</id>",
+        escape(hostname),
+        suggestion.message_id
+    ));
+    // Feed readers don't render markdown, so titles are only cut to length
+    let title = sanitize::budget(&suggestion.title, sanitize::TITLE_LENGTH, None);
+    atom.push_str(&format!("<title>{}</title>", escape(&title)));
+    atom.push_str(&format!(
+        "<link rel=\"alternate\" href=\"{}\"/>",
+        escape(&suggestion.link)
+    ));
+    if let Some(route_map) = &route_map {
+        atom.push_str(&format!(
+            "<link rel=\"enclosure\" type=\"image/png\" href=\"{}\"/>",
+            escape(route_map)
+        ));
+    }
+    atom.push_str(&format!(
+        "<updated>{}</updated>",
+        date_time(suggestion.created_at)
+    ));
+    atom.push_str(&format!("<summary>{}</summary>", escape(&stats)));
+    atom.push_str(&format!(
+        "<content type=\"html\">{}</content>",
+        escape(&content.into_string())
+    ));
+    atom.push_str("</entry>");
+    Ok(())
+}
+
+/// The latest trail suggestions as an Atom feed, public so family members who
+/// aren't on Discord can follow along
+#[instrument(skip_all)]
+pub async fn feed(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, crate::error::HtmlError> {
+    let suggestions = state
+        .store
+        .latest_suggestions(ENTRIES)
+        .wrap_err("Failed to load suggestions")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let hostname = state.config.load().hostname.clone();
+    let updated = suggestions
+        .iter()
+        .map(|suggestion| suggestion.created_at)
+        .max()
+        .unwrap_or(0);
+
+    let mut atom = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>");
+    atom.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">");
+    atom.push_str(&format!("<id>{}/hikea/feed.xml</id>", escape(&hostname)));
+    atom.push_str("<title>Trail suggestions</title>");
+    atom.push_str(&format!(
+        "<link rel=\"self\" href=\"{}/hikea/feed.xml\"/>",
+        escape(&hostname)
+    ));
+    atom.push_str(&format!("<updated>{}</updated>", date_time(updated)));
+    atom.push_str("<author><name>hikea</name></author>");
+    for suggestion in &suggestions {
+        push_entry(&mut atom, &state, suggestion)
+            .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    atom.push_str("</feed>");
+
+    Ok((
+        [(CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        atom,
+    ))
+}
@@ -1,14 +1,154 @@
-use color_eyre::eyre::eyre;
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode};
+use color_eyre::eyre::{self, eyre, Context};
+use jsonwebtoken::get_current_timestamp;
 use maud::DOCTYPE;
 use serenity::all::PartialMember;
+use time::OffsetDateTime;
 use tracing::instrument;
 
-use crate::error::WithStatusCode;
+use crate::{error::WithStatusCode, AppState};
+
+/// How many completed hikes the dashboard lists
+const RECENT_COMPLETIONS: usize = 10;
+
+/// `timestamp` as a date and time in UTC
+fn date_time(timestamp: u64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .map(|time| {
+            format!(
+                "{} {:02}:{:02} UTC",
+                time.date(),
+                time.hour(),
+                time.minute()
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Suggestions needing attention, upcoming hikes and recent ones, for admins
+fn dashboard(state: &AppState) -> eyre::Result<maud::Markup> {
+    let guild_id = state.config.load().guild_id;
+    let unfilled = state
+        .store
+        .unfilled_suggestions()
+        .wrap_err("Failed to load unfilled suggestions")?;
+    let proposed = state
+        .store
+        .proposed_with_gpx()
+        .wrap_err("Failed to load proposed suggestions")?;
+    let upcoming = state
+        .store
+        .upcoming_events(get_current_timestamp())
+        .wrap_err("Failed to load upcoming events")?;
+    let completed = state
+        .store
+        .recently_completed(RECENT_COMPLETIONS)
+        .wrap_err("Failed to load completed suggestions")?;
+
+    Ok(maud::html! {
+        h2 { "Waiting to be filled in" }
+        @if unfilled.is_empty() {
+            p { "Every suggestion has been filled in." }
+        } @else {
+            table {
+                tr {
+                    th { "Suggested" }
+                    th { "Message" }
+                    th {}
+                }
+                @for (channel_id, message_id, created_at) in &unfilled {
+                    tr {
+                        td { (date_time(*created_at)) }
+                        td {
+                            a href=(message_id.link(*channel_id, Some(guild_id))) { "Open in Discord" }
+                        }
+                        td {
+                            a href=(format!("/hikea/upload_gpx/{}/{}", channel_id, message_id)) {
+                                "Fill in"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { "Proposed" }
+        @if proposed.is_empty() {
+            p { "No suggestions are being voted on." }
+        } @else {
+            table {
+                tr {
+                    th { "Trail" }
+                    th { "GPX file" }
+                    th {}
+                    th {}
+                }
+                @for (suggestion, has_gpx) in &proposed {
+                    tr {
+                        td { a href=(suggestion.link) { (suggestion.title) } }
+                        td {
+                            @if *has_gpx { "Kept" } @else { strong { "Missing" } }
+                        }
+                        td {
+                            @if *has_gpx {
+                                a href=(format!("/hikea/trail/{}", suggestion.message_id)) { "Map" }
+                            }
+                        }
+                        td {
+                            a href=(format!(
+                                "/hikea/upload_gpx/{}/{}",
+                                suggestion.channel_id, suggestion.message_id
+                            )) {
+                                "Fill in again"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { "Upcoming hikes" }
+        @if upcoming.is_empty() {
+            p { "No hikes are scheduled." }
+        } @else {
+            ul {
+                @for suggestion in &upcoming {
+                    li {
+                        (date_time(suggestion.hiked_at.unwrap_or_default())) ": "
+                        a href=(suggestion.link) { (suggestion.title) }
+                    }
+                }
+            }
+        }
+
+        h2 { "Recently completed" }
+        @if completed.is_empty() {
+            p { "No hikes have been completed yet." }
+        } @else {
+            ul {
+                @for suggestion in &completed {
+                    li {
+                        @if let Some(hiked_at) = suggestion.hiked_at {
+                            (date_time(hiked_at)) ": "
+                        }
+                        a href=(suggestion.link) { (suggestion.title) }
+                    }
+                }
+            }
+        }
+    })
+}
 
 #[instrument(skip_all)]
-pub async fn page(claims: super::Claims) -> Result<maud::Markup, crate::error::HtmlError> {
-    let member: PartialMember = match claims {
-        super::Claims::Authenticated { member, .. } => member,
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    let (member, admin): (PartialMember, bool) = match claims {
+        super::Claims::Authenticated { member, .. } => (member, true),
+        super::Claims::Member { member, .. } => (member, false),
         super::Claims::Unauthenticated { .. } => {
             return Err(eyre!("You are not authenticated"))
                 .with_redirect(std::borrow::Cow::Borrowed("/hikea/oauth2?redirect=/hikea"));
@@ -19,6 +159,10 @@ pub async fn page(claims: super::Claims) -> Result<maud::Markup, crate::error::H
         .nick
         .or_else(|| member.user.map(|u| u.name))
         .unwrap_or_default();
+    let dashboard = admin
+        .then(|| dashboard(&state))
+        .transpose()
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let html = maud::html! {
         (DOCTYPE)
@@ -26,10 +170,22 @@ pub async fn page(claims: super::Claims) -> Result<maud::Markup, crate::error::H
             head {
                 meta charset="utf-8";
                 meta name="viewport" content="width=device-width";
-                title { "Test page" }
+                title { "Hikea" }
             }
             body {
                 p { (format_args!("Hi, {}!", user)) }
+                ul {
+                    li { a href="/hikea/settings" { "Settings" } }
+                    @if admin {
+                        li { a href="/hikea/admin/jobs" { "Jobs" } }
+                        li { a href="/hikea/admin/commands" { "Commands" } }
+                        li { a href="/hikea/admin/waypoints" { "Waypoints" } }
+                        li { a href="/hikea/admin/aliases" { "Trail names" } }
+                    }
+                }
+                @if let Some(dashboard) = dashboard {
+                    (dashboard)
+                }
             }
         }
     };
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode};
+use color_eyre::eyre::Context;
+use maud::DOCTYPE;
+use tracing::instrument;
+
+use crate::{error::WithStatusCode, AppState};
+
+#[instrument(skip_all)]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin("/hikea/admin/jobs")?;
+
+    let stats = state
+        .store
+        .job_stats()
+        .wrap_err("Failed to load job stats")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let dead_letters = state
+        .store
+        .dead_letters()
+        .wrap_err("Failed to load dead letters")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Jobs" }
+            }
+            body {
+                h1 { "Jobs" }
+                table {
+                    tr {
+                        th { "Job" }
+                        th { "Next run" }
+                        th { "Runs" }
+                        th { "Failures" }
+                        th { "Last run" }
+                        th { "Last duration" }
+                        th { "Last error" }
+                    }
+                    @for job in &stats {
+                        tr {
+                            td { (job.name) }
+                            td { (job.next_run) }
+                            td { (job.runs) }
+                            td { (job.failures) }
+                            td { @if let Some(last_run) = job.last_run { (last_run) } }
+                            td {
+                                @if let Some(duration) = job.last_duration_ms {
+                                    (duration) "ms"
+                                }
+                            }
+                            td { @if let Some(error) = &job.last_error { pre { (error) } } }
+                        }
+                    }
+                }
+                h2 { "Failed jobs" }
+                p { "Retry a failed job with " code { "/jobs retry" } " in Discord." }
+                table {
+                    tr {
+                        th { "ID" }
+                        th { "Job" }
+                        th { "Failed at" }
+                        th { "Attempts" }
+                        th { "Error" }
+                    }
+                    @for dead_letter in &dead_letters {
+                        tr {
+                            td { (dead_letter.id) }
+                            td { (dead_letter.job) }
+                            td { (dead_letter.failed_at) }
+                            td { (dead_letter.attempts) }
+                            td { pre { (dead_letter.error) } }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -1,4 +1,4 @@
-use std::{sync::Arc, time::Duration};
+use std::{borrow::Cow, io::Write, os::unix::fs::OpenOptionsExt, sync::Arc, time::Duration};
 
 use axum::{
     async_trait,
@@ -7,7 +7,8 @@ use axum::{
     response::Redirect,
 };
 use axum_extra::extract::{cookie::Cookie, CookieJar};
-use color_eyre::eyre::{eyre, Context};
+use base64::prelude::*;
+use color_eyre::eyre::{self, eyre, Context};
 use jsonwebtoken::{get_current_timestamp, DecodingKey, EncodingKey, Validation};
 use oauth2::{
     basic::BasicClient, AuthUrl, AuthorizationCode, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
@@ -16,15 +17,25 @@ use oauth2::{
 use ring::signature::{Ed25519KeyPair, KeyPair};
 use serde::{Deserialize, Serialize};
 use serenity::all::PartialMember;
-use tracing::instrument;
+use tracing::{info, instrument, warn};
 
 use crate::{
-    error::{PropogateRequest, WithStatusCode},
-    AppState,
+    error::{HtmlError, PropogateRequest, WithStatusCode},
+    AppState, Config,
 };
 
+pub mod aliases;
+pub mod api;
+pub mod calendar;
+pub mod commands;
+pub mod feed;
 pub mod home_page;
+pub mod jobs;
+pub mod offline;
+pub mod settings;
+pub mod trail;
 pub mod upload_gpx;
+pub mod waypoints;
 
 pub struct Keys {
     pub encoding: EncodingKey,
@@ -32,11 +43,17 @@ pub struct Keys {
 }
 
 impl Keys {
-    pub fn new() -> Result<Self, ring::error::Unspecified> {
-        let doc = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())?;
-        let encoding_key = EncodingKey::from_ed_der(doc.as_ref());
+    pub fn new() -> eyre::Result<Self> {
+        let doc = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+            .map_err(|_| eyre!("Failed to generate Ed25519 keypair"))?;
+        Self::from_pkcs8(doc.as_ref())
+    }
+
+    pub fn from_pkcs8(doc: &[u8]) -> eyre::Result<Self> {
+        let encoding_key = EncodingKey::from_ed_der(doc);
 
-        let pair = Ed25519KeyPair::from_pkcs8(doc.as_ref())?;
+        let pair = Ed25519KeyPair::from_pkcs8(doc)
+            .map_err(|e| eyre!("Failed to read Ed25519 PKCS8 document: {}", e))?;
         let decoding_key = DecodingKey::from_ed_der(pair.public_key().as_ref());
 
         Ok(Self {
@@ -44,15 +61,52 @@ impl Keys {
             decoding: decoding_key,
         })
     }
+
+    #[instrument(skip_all)]
+    pub fn from_config(config: &Config) -> eyre::Result<Self> {
+        if let Some(key) = &config.jwt_key {
+            let doc = BASE64_STANDARD
+                .decode(key)
+                .wrap_err("`jwt_key` was not valid base64")?;
+            return Self::from_pkcs8(&doc).wrap_err("Failed to load `jwt_key` from config");
+        }
+
+        let Some(path) = &config.jwt_key_path else {
+            warn!("No `jwt_key` or `jwt_key_path` configured, sessions will not survive restarts");
+            return Self::new();
+        };
+
+        match std::fs::read(path) {
+            Ok(doc) => Self::from_pkcs8(&doc)
+                .wrap_err_with(|| format!("Failed to load JWT key from `{}`", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let doc = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+                    .map_err(|_| eyre!("Failed to generate Ed25519 keypair"))?;
+                // Only the bot's user may read the key
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(doc.as_ref()))
+                    .wrap_err_with(|| format!("Failed to write JWT key to `{}`", path.display()))?;
+                info!(path = %path.display(), "Generated new JWT key");
+                Self::from_pkcs8(doc.as_ref())
+            }
+            Err(e) => {
+                Err(e).wrap_err_with(|| format!("Failed to read JWT key from `{}`", path.display()))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "claims")]
 pub enum Claims {
-    Authenticated {
-        member: PartialMember,
-        exp: u64,
-    },
+    /// A member with an admin role
+    Authenticated { member: PartialMember, exp: u64 },
+    /// A member without an admin role, who can only change their own settings
+    Member { member: PartialMember, exp: u64 },
     Unauthenticated {
         csrf_token: CsrfToken,
         pkce_verifier: PkceCodeVerifier,
@@ -61,6 +115,29 @@ pub enum Claims {
     },
 }
 
+impl Claims {
+    /// The admin signed in, sending anyone else to sign in and come back to
+    /// `redirect`
+    pub fn require_admin(self, redirect: &str) -> Result<PartialMember, HtmlError> {
+        match self {
+            Claims::Authenticated { member, .. } => Ok(member),
+            Claims::Member { .. } => Err(eyre!("You do not have any admin role"))
+                .with_status_code_html(StatusCode::FORBIDDEN),
+            Claims::Unauthenticated { .. } => Err(eyre!("You are not authenticated"))
+                .with_redirect(Cow::Owned(format!("/hikea/oauth2?redirect={}", redirect))),
+        }
+    }
+
+    /// The member signed in, admin or not
+    pub fn require_member(self, redirect: &str) -> Result<PartialMember, HtmlError> {
+        match self {
+            Claims::Authenticated { member, .. } | Claims::Member { member, .. } => Ok(member),
+            Claims::Unauthenticated { .. } => Err(eyre!("You are not authenticated"))
+                .with_redirect(Cow::Owned(format!("/hikea/oauth2?redirect={}", redirect))),
+        }
+    }
+}
+
 // #[derive(Debug, Serialize, Deserialize)]
 // pub struct AlltrailsClaims<'a> {
 //     pub link: String,
@@ -138,8 +215,8 @@ pub async fn initiate_oauth2(
     let client = BasicClient::new(
         config.client_id.clone(),
         Some(config.client_secret.clone()),
-        AuthUrl::new("https://discord.com/oauth2/authorize".to_owned()).unwrap(),
-        Some(TokenUrl::new("https://discord.com/api/oauth2/token".to_owned()).unwrap()),
+        AuthUrl::new(config.services.discord("oauth2/authorize")).unwrap(),
+        Some(TokenUrl::new(config.services.discord("api/oauth2/token")).unwrap()),
     )
     .set_redirect_uri(config.redirect_url.clone());
 
@@ -185,8 +262,8 @@ pub async fn redirect_oauth2(
     let client = BasicClient::new(
         config.client_id.clone(),
         Some(config.client_secret.clone()),
-        AuthUrl::new("https://discord.com/oauth2/authorize".to_owned()).unwrap(),
-        Some(TokenUrl::new("https://discord.com/api/oauth2/token".to_owned()).unwrap()),
+        AuthUrl::new(config.services.discord("oauth2/authorize")).unwrap(),
+        Some(TokenUrl::new(config.services.discord("api/oauth2/token")).unwrap()),
     )
     .set_redirect_uri(config.redirect_url.clone());
 
@@ -218,10 +295,11 @@ pub async fn redirect_oauth2(
         .with_status_code_html(StatusCode::UNAUTHORIZED)?;
 
     let member: PartialMember = reqwest::Client::new()
-        .get(format!(
-            "https://discord.com/api/users/@me/guilds/{}/member",
-            config.guild_id
-        ))
+        .get(
+            config
+                .services
+                .discord(format!("api/users/@me/guilds/{}/member", config.guild_id)),
+        )
         .header(
             "Authorization",
             format!("Bearer {}", token_result.access_token().secret()),
@@ -241,36 +319,49 @@ pub async fn redirect_oauth2(
         })
         .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if member
+    let exp = get_current_timestamp()
+        + token_result
+            .expires_in()
+            .unwrap_or_else(|| Duration::from_secs(3600))
+            .as_secs();
+    let claims = if member
         .roles
         .iter()
         .any(|role| config.admin_roles.contains(role))
     {
-        let jar = CookieJar::new().add(Cookie::new(
-            "jwt_session",
-            jsonwebtoken::encode(
-                &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::EdDSA),
-                &Claims::Authenticated {
-                    member,
-                    exp: get_current_timestamp()
-                        + token_result
-                            .expires_in()
-                            .unwrap_or_else(|| Duration::from_secs(3600))
-                            .as_secs(),
-                },
-                &state.keys.encoding,
-            )
-            .wrap_err("Failed to encode JWT Claims")
-            .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?,
-        ));
-        Ok((
-            jar,
-            Redirect::to(redirect_to.as_ref().map(|r| r.as_str()).unwrap_or("/hikea")),
-        ))
+        Claims::Authenticated { member, exp }
     } else {
-        Err(eyre!("You do not have any admin role"))
-            .with_status_code_html(StatusCode::UNAUTHORIZED)?
-    }
+        Claims::Member { member, exp }
+    };
+
+    let jar = CookieJar::new().add(Cookie::new(
+        "jwt_session",
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::EdDSA),
+            &claims,
+            &state.keys.encoding,
+        )
+        .wrap_err("Failed to encode JWT Claims")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?,
+    ));
+    Ok((
+        jar,
+        Redirect::to(redirect_to.as_deref().unwrap_or("/hikea")),
+    ))
+}
+
+/// Name to credit an admin on the web interface with
+pub fn member_name(member: &PartialMember) -> String {
+    member
+        .nick
+        .clone()
+        .or_else(|| {
+            member
+                .user
+                .as_ref()
+                .map(|user| user.display_name().to_owned())
+        })
+        .unwrap_or_else(|| String::from("an unknown admin"))
 }
 
 #[async_trait]
@@ -302,3 +393,65 @@ impl FromRequestParts<Arc<AppState>> for Claims {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member() -> PartialMember {
+        serde_json::from_value(serde_json::json!({
+            "deaf": false,
+            "mute": false,
+            "roles": [],
+            "nick": "Hiker",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn members_are_not_admins() {
+        let claims = Claims::Member {
+            member: member(),
+            exp: 0,
+        };
+        let error = claims.require_admin("/hikea/admin/jobs").unwrap_err();
+        assert_eq!(error.0, StatusCode::FORBIDDEN);
+        assert!(error.2.is_none());
+    }
+
+    #[test]
+    fn members_and_admins_are_members() {
+        let hiker = Claims::Member {
+            member: member(),
+            exp: 0,
+        };
+        let admin = Claims::Authenticated {
+            member: member(),
+            exp: 0,
+        };
+        assert_eq!(
+            hiker
+                .require_member("/hikea/settings")
+                .unwrap()
+                .nick
+                .as_deref(),
+            Some("Hiker")
+        );
+        assert!(admin.require_admin("/hikea/admin/jobs").is_ok());
+    }
+
+    #[test]
+    fn strangers_are_sent_to_sign_in() {
+        let claims = Claims::Unauthenticated {
+            csrf_token: CsrfToken::new(String::from("csrf")),
+            pkce_verifier: PkceCodeVerifier::new(String::from("pkce")),
+            redirect_to: None,
+            exp: 0,
+        };
+        let error = claims.require_member("/hikea/settings").unwrap_err();
+        assert_eq!(
+            error.2.as_deref(),
+            Some("/hikea/oauth2?redirect=/hikea/settings")
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+};
+use color_eyre::eyre::{eyre, Context, OptionExt};
+use serenity::all::MessageId;
+use tracing::{info, instrument};
+
+use crate::{error::WithStatusCode, AppState};
+
+/// Links to the offline map and GPX file of the suggestion posted as
+/// `message_id`, or `None` if offline maps aren't set up
+pub fn links(state: &AppState, message_id: MessageId) -> Option<(String, String)> {
+    let config = state.config.load();
+    config.offline_map.as_ref()?;
+    let base = format!("{}/hikea/offline/{}", config.hostname, message_id);
+    Some((
+        format!("{}/map.mbtiles", base),
+        format!("{}/route.gpx", base),
+    ))
+}
+
+/// The map tiles along the route of the suggestion posted as `message_id` as
+/// an MBTiles file, generated the first time it's downloaded
+#[instrument(skip(state, claims))]
+pub async fn map(
+    State(state): State<Arc<AppState>>,
+    Path(message_id): Path<MessageId>,
+    claims: super::Claims,
+) -> Result<impl IntoResponse, crate::error::HtmlError> {
+    claims.require_member(&format!("/hikea/offline/{}/map.mbtiles", message_id))?;
+    let config = state.config.load();
+    let offline_map = config
+        .offline_map
+        .as_ref()
+        .ok_or_eyre("Offline maps are not set up")
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+
+    let mbtiles = match state
+        .store
+        .offline_map(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        Some(mbtiles) => mbtiles,
+        None => {
+            let suggestion = state
+                .store
+                .suggestion(message_id)
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or_eyre("Suggestion has not been filled in")
+                .with_status_code_html(StatusCode::NOT_FOUND)?;
+            let gpx = state
+                .store
+                .suggestion_gpx(message_id)
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or_eyre("This suggestion has no GPX file")
+                .with_status_code_html(StatusCode::NOT_FOUND)?;
+            let gpx = gpx::read(std::io::Cursor::new(gpx))
+                .wrap_err("Failed to read stored GPX file")
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+            let track = gpx
+                .tracks
+                .first()
+                .ok_or_eyre("Stored GPX file contained no tracks")
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            let Some(_permit) = state.limiter.try_acquire(crate::backpressure::IMAGE_MEMORY) else {
+                return Err(eyre!("Too many maps are being worked on right now"))
+                    .with_status_code_html(StatusCode::SERVICE_UNAVAILABLE);
+            };
+            let mbtiles =
+                crate::offline_map::package(track, &suggestion.title, offline_map, &config)
+                    .await
+                    .wrap_err("Failed to generate offline map")
+                    .with_status_code_html(StatusCode::BAD_GATEWAY)?;
+            state
+                .store
+                .set_offline_map(message_id, &mbtiles)
+                .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+            info!(
+                title = suggestion.title,
+                size = mbtiles.len(),
+                "Generated offline map"
+            );
+            mbtiles
+        }
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/vnd.sqlite3"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"map.mbtiles\""),
+        ],
+        mbtiles,
+    ))
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::Redirect, Form};
+use color_eyre::eyre::{self, eyre, OptionExt};
+use maud::DOCTYPE;
+use serde::Deserialize;
+use serenity::all::UserId;
+use time::Month;
+use tracing::{info, instrument};
+
+use crate::{error::WithStatusCode, store::Preferences, units::length_unit, AppState};
+
+/// The longest address SMTP allows
+const MAX_EMAIL_LENGTH: usize = 254;
+/// Longer than any IANA time zone name
+const MAX_TIME_ZONE_LENGTH: usize = 64;
+
+/// The member signed in, admin or not
+fn signed_in_user(claims: super::Claims) -> Result<UserId, crate::error::HtmlError> {
+    claims
+        .require_member("/hikea/settings")?
+        .user
+        .map(|user| user.id)
+        .ok_or_eyre("Discord did not say which user you are")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn month_options(selected: Option<u8>) -> maud::Markup {
+    maud::html! {
+        option value="" selected[selected.is_none()] { "Not shared" }
+        @for month in 1..=12u8 {
+            @if let Ok(name) = Month::try_from(month) {
+                option value=(month) selected[selected == Some(month)] { (name) }
+            }
+        }
+    }
+}
+
+fn unit_options(selected: Option<&str>) -> maud::Markup {
+    maud::html! {
+        option value="" selected[selected.is_none()] { "Server default" }
+        @for unit in uom::si::length::units() {
+            option value=(unit.singular()) selected[selected == Some(unit.singular())] {
+                (unit.plural())
+            }
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    let user = signed_in_user(claims)?;
+    let preferences = state
+        .preferences(user)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let calendar = super::calendar::feed_url(&state, user)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Settings" }
+            }
+            body {
+                h1 { "Settings" }
+                form method="post" {
+                    p {
+                        label {
+                            "Distances in "
+                            select name="long_units" {
+                                (unit_options(preferences.long_units.as_deref()))
+                            }
+                        }
+                    }
+                    p {
+                        label {
+                            "Heights in "
+                            select name="short_units" {
+                                (unit_options(preferences.short_units.as_deref()))
+                            }
+                        }
+                    }
+                    p {
+                        label {
+                            "Time zone "
+                            input type="text" name="time_zone" placeholder="America/Denver"
+                                maxlength=(MAX_TIME_ZONE_LENGTH)
+                                value=(preferences.time_zone.as_deref().unwrap_or_default());
+                        }
+                    }
+                    p {
+                        label {
+                            "Email "
+                            input type="email" name="email" maxlength=(MAX_EMAIL_LENGTH)
+                                value=(preferences.email.as_deref().unwrap_or_default());
+                        }
+                    }
+                    p {
+                        label {
+                            input type="checkbox" name="hike_reminders" value="yes"
+                                checked[preferences.hike_reminders];
+                            " Remind me about hikes I'm interested in"
+                        }
+                    }
+                    p {
+                        label {
+                            input type="checkbox" name="suggestion_notifications" value="yes"
+                                checked[preferences.suggestion_notifications];
+                            " Notify me about new trail suggestions"
+                        }
+                    }
+                    p {
+                        label {
+                            "Birthday month "
+                            select name="birthday_month" {
+                                (month_options(preferences.birthday_month))
+                            }
+                        }
+                        " Only the month is kept, to suggest celebratory hikes"
+                    }
+                    input type="submit" value="Save";
+                }
+                h2 { "Calendar" }
+                p {
+                    "Subscribe to this link from your calendar app to see scheduled hikes. "
+                    "Keep it to yourself, anyone with it can see them."
+                }
+                p { input type="text" readonly value=(calendar) size="80"; }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct SettingsForm {
+    long_units: String,
+    short_units: String,
+    time_zone: String,
+    email: String,
+    hike_reminders: Option<String>,
+    suggestion_notifications: Option<String>,
+    birthday_month: String,
+}
+
+/// `None` for blank fields, which fall back to the server default
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+impl SettingsForm {
+    fn into_preferences(self) -> eyre::Result<Preferences> {
+        let long_units = non_empty(self.long_units);
+        let short_units = non_empty(self.short_units);
+        for unit in long_units.iter().chain(&short_units) {
+            if length_unit(unit).is_none() {
+                return Err(eyre!("`{}` is not a length unit", unit));
+            }
+        }
+
+        let time_zone = non_empty(self.time_zone);
+        if let Some(time_zone) = &time_zone {
+            if time_zone.len() > MAX_TIME_ZONE_LENGTH
+                || !time_zone
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+            {
+                return Err(eyre!("`{}` is not a time zone name", time_zone));
+            }
+        }
+
+        let email = non_empty(self.email);
+        if let Some(email) = &email {
+            if email.len() > MAX_EMAIL_LENGTH
+                || !email.contains('@')
+                || email.contains(char::is_whitespace)
+            {
+                return Err(eyre!("`{}` is not an email address", email));
+            }
+        }
+
+        let birthday_month = non_empty(self.birthday_month)
+            .map(|month| {
+                month
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|month| (1..=12).contains(month))
+                    .ok_or_else(|| eyre!("`{}` is not a month", month))
+            })
+            .transpose()?;
+
+        Ok(Preferences {
+            long_units,
+            short_units,
+            time_zone,
+            email,
+            hike_reminders: self.hike_reminders.as_deref() == Some("yes"),
+            suggestion_notifications: self.suggestion_notifications.as_deref() == Some("yes"),
+            birthday_month,
+        })
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn save(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Form(form): Form<SettingsForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let user = signed_in_user(claims)?;
+    let preferences = form
+        .into_preferences()
+        .with_status_code_html(StatusCode::BAD_REQUEST)?;
+
+    state
+        .store
+        .set_preferences(user, &preferences)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    info!(%user, "Saved settings");
+
+    Ok(Redirect::to("/hikea/settings"))
+}
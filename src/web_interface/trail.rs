@@ -0,0 +1,191 @@
+use std::{io::Cursor, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use geo::{Distance, Haversine};
+use maud::{PreEscaped, DOCTYPE};
+use serde_json::Value;
+use serenity::all::MessageId;
+use tracing::instrument;
+
+use crate::{error::WithStatusCode, AppState, Config};
+
+/// Size of the elevation profile, in SVG units
+const PROFILE_WIDTH: f64 = 800.0;
+const PROFILE_HEIGHT: f64 = 160.0;
+
+/// Draws the map once the page has loaded, with `TILE_URL`, `ATTRIBUTION` and
+/// `GEOJSON_URL` set before it
+const MAP_SCRIPT: &str = r##"
+const map = L.map("map");
+L.tileLayer(TILE_URL, { attribution: ATTRIBUTION, maxZoom: 17 }).addTo(map);
+fetch(GEOJSON_URL)
+    .then((response) => response.json())
+    .then((geojson) => {
+        const layer = L.geoJSON(geojson, {
+            style: { color: "#d9480f", weight: 4 },
+            onEachFeature: (feature, layer) => {
+                if (feature.properties.label) {
+                    layer.bindPopup(feature.properties.label);
+                }
+            },
+        }).addTo(map);
+        map.fitBounds(layer.getBounds(), { padding: [24, 24] });
+    });
+"##;
+
+fn stored_gpx(state: &AppState, message_id: MessageId) -> eyre::Result<gpx::Gpx> {
+    let gpx = state
+        .store
+        .suggestion_gpx(message_id)?
+        .ok_or_eyre("This suggestion has no GPX file, fill it in again to map it")?;
+    gpx::read(Cursor::new(gpx)).wrap_err("Failed to read stored GPX file")
+}
+
+/// Elevation against distance walked along the first track, as an SVG
+pub fn profile(gpx: &gpx::Gpx) -> Option<maud::Markup> {
+    let mut points = Vec::new();
+    let mut distance = 0.0;
+    for segment in &gpx.tracks.first()?.segments {
+        for (i, point) in segment.points.iter().enumerate() {
+            if i > 0 {
+                distance += Haversine::distance(segment.points[i - 1].point(), point.point());
+            }
+            if let Some(elevation) = point.elevation {
+                points.push((distance, elevation));
+            }
+        }
+    }
+    let low = points
+        .iter()
+        .map(|(_, elevation)| *elevation)
+        .reduce(f64::min)?;
+    let high = points
+        .iter()
+        .map(|(_, elevation)| *elevation)
+        .reduce(f64::max)?;
+    if distance <= 0.0 {
+        return None;
+    }
+
+    let polyline = points
+        .iter()
+        .map(|(distance_along, elevation)| {
+            format!(
+                "{:.1},{:.1}",
+                distance_along / distance * PROFILE_WIDTH,
+                PROFILE_HEIGHT - (elevation - low) / (high - low).max(1.0) * PROFILE_HEIGHT
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(maud::html! {
+        svg viewBox=(format!("0 0 {} {}", PROFILE_WIDTH, PROFILE_HEIGHT))
+            width="100%" preserveAspectRatio="none" style="height: 160px" {
+            polyline points=(polyline) fill="none" stroke="#d9480f" stroke-width="2";
+        }
+    })
+}
+
+/// Links Leaflet from `assets` for [`map`]
+pub fn map_head(assets: &str) -> maud::Markup {
+    maud::html! {
+        link rel="stylesheet" href=(format!("{}/leaflet.css", assets));
+        script src=(format!("{}/leaflet.js", assets)) {}
+    }
+}
+
+/// A map of the route at `geojson_url`
+pub fn map(config: &Config, geojson_url: &str) -> maud::Markup {
+    let (tile_url, attribution) = match &config.route_map {
+        Some(route_map) => (route_map.tile_url.clone(), route_map.attribution.clone()),
+        None => (crate::default_tile_url(), crate::default_map_attribution()),
+    };
+    // Serialized as JSON so they can't break out of the script
+    let variables = format!(
+        "const TILE_URL = {};\nconst ATTRIBUTION = {};\nconst GEOJSON_URL = {};",
+        Value::from(tile_url),
+        Value::from(attribution),
+        Value::from(geojson_url)
+    )
+    .replace("</", "<\\/");
+    maud::html! {
+        div #map style="height: 60vh" {}
+        script { (PreEscaped(variables)) (PreEscaped(MAP_SCRIPT)) }
+    }
+}
+
+/// A map of the route of the suggestion posted as `message_id`, with its
+/// elevation profile below, so it can be looked over before approving it
+#[instrument(skip(state, claims))]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin(&format!("/hikea/trail/{}", message_id))?;
+    let suggestion = state
+        .store
+        .suggestion(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or_eyre("Trail suggestion was not found")
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+    let gpx = stored_gpx(&state, message_id).with_status_code_html(StatusCode::NOT_FOUND)?;
+
+    let config = state.config.load();
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { (suggestion.title) }
+                (map_head("/hikea/assets"))
+            }
+            body {
+                h1 { a href=(suggestion.link) { (suggestion.title) } }
+                (map(&config, &format!("/hikea/api/trail/{}.geojson", message_id)))
+                @if let Some(profile) = profile(&gpx) {
+                    h2 { "Elevation profile" }
+                    (profile)
+                }
+                p {
+                    a href=(format!("/hikea/admin/waypoints/{}", message_id)) { "Edit waypoints" }
+                    " · "
+                    a href=(format!("/hikea/admin/aliases/{}", message_id)) { "Edit other names" }
+                }
+            }
+        }
+    })
+}
+
+/// Serves a file from `assets_dir`, such as the map library, without reaching
+/// outside of it
+#[instrument(skip(state))]
+pub async fn asset(
+    State(state): State<Arc<AppState>>,
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse, crate::error::HtmlError> {
+    if file.starts_with('.') || file.contains(['/', '\\']) {
+        return Err(eyre!("Asset `{}` was not found", file))
+            .with_status_code_html(StatusCode::NOT_FOUND);
+    }
+    let content_type = match file.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("js") => "text/javascript",
+        Some("css") => "text/css",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    };
+    let path = state.config.load().assets_dir.join(&file);
+    let bytes = std::fs::read(&path)
+        .wrap_err_with(|| format!("Asset `{}` was not found", file))
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+
+    Ok(([(CONTENT_TYPE, content_type)], bytes))
+}
@@ -1,40 +1,108 @@
-use std::{
-    io::Cursor,
-    ops::Deref,
-    sync::{atomic::Ordering, Arc},
-};
+use std::{io::Cursor, ops::Deref, sync::Arc};
 
 use axum::{
-    extract::{Multipart, Path, State},
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
-    response::Redirect,
+    response::{IntoResponse, Redirect, Response},
+    Form,
 };
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use color_eyre::eyre::{self, eyre, Context, OptionExt};
 use gpx::Gpx;
+use hikea::trail_stats;
+use jsonwebtoken::get_current_timestamp;
+use magick_rust::MagickWand;
 use maud::DOCTYPE;
-use serenity::all::{ChannelId, Color, CreateEmbed, EditMessage, MessageId};
-use tracing::instrument;
+use serde::Deserialize;
+use serenity::all::{
+    ChannelId, Color, CreateAttachment, CreateEmbed, CreateEmbedAuthor, EditMessage, MessageId,
+};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    alltrails::TrailMetadata,
+    audit::{audited, summarize, Actor, Mutation},
+    beginner::{BeginnerScore, Conditions, Shade},
+    content_filter::ContentFilterConfig,
+    difficulty::Difficulty,
+    error::WithStatusCode,
+    pace::Activity,
+    store::{Filled, Suggestion},
+    AppState,
+};
+
+/// Remembers which suggestion the upload page was opened on and at which
+/// revision, so uploads go to that suggestion and don't silently overwrite
+/// someone else filling it in meanwhile
+const REVISION_COOKIE: &str = "upload_revision";
+
+/// Largest upload form accepted, in bytes, which has room for a photo
+pub const MAX_UPLOAD_SIZE: usize = 32 * 1024 * 1024;
+
+/// Uploaded photos are shrunk to fit within this many pixels on each side
+const MAX_IMAGE_SIZE: usize = 2048;
 
-use crate::{error::WithStatusCode, AppState};
+const IMAGE_QUALITY: usize = 85;
+
+/// File name uploaded photos are attached to the suggestion under
+const IMAGE_FILE_NAME: &str = "trail.jpg";
+
+/// Seconds a claim on filling in a suggestion lasts, in case the bot stops
+/// while holding one
+const FILL_CLAIM_TTL: u64 = 10 * 60;
+
+/// Seconds an upload waits to be confirmed before it's dropped
+const PENDING_UPLOAD_TTL: u64 = 60 * 60;
+
+#[derive(Deserialize, Debug)]
+pub struct PageQuery {
+    /// Revision of an already filled in suggestion to overwrite
+    overwrite: Option<u64>,
+}
 
 #[instrument(skip(state, claims))]
 pub async fn page(
     State(state): State<Arc<AppState>>,
     Path((channel_id, message_id)): Path<(ChannelId, MessageId)>,
+    Query(query): Query<PageQuery>,
     claims: super::Claims,
-) -> Result<Redirect, crate::error::HtmlError> {
-    match claims {
-        super::Claims::Authenticated { .. } => {}
-        super::Claims::Unauthenticated { .. } => {
-            return Err(eyre!("You are not authenticated")).with_redirect(std::borrow::Cow::Owned(
-                format!(
-                    "/hikea/oauth2?redirect=/hikea/upload_gpx/{}/{}",
-                    channel_id.get(),
-                    message_id.get()
-                ),
-            ));
+) -> Result<Response, crate::error::HtmlError> {
+    let member = claims.require_admin(&format!(
+        "/hikea/upload_gpx/{}/{}",
+        channel_id.get(),
+        message_id.get()
+    ))?;
+
+    let filled = state
+        .store
+        .filled(message_id)
+        .wrap_err("Failed to look up trail suggestion")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let revision = match filled {
+        Some(filled) if query.overwrite != Some(filled.revision) => {
+            let overwrite = format!(
+                "/hikea/upload_gpx/{}/{}?overwrite={}",
+                channel_id.get(),
+                message_id.get(),
+                filled.revision
+            );
+            return Ok(confirm_page(
+                &filled,
+                maud::html! {
+                    a href=(overwrite) { "Overwrite" }
+                },
+            )
+            .into_response());
         }
-    }
+        filled => filled.map_or(0, |filled| filled.revision),
+    };
+    let jar = CookieJar::new().add(
+        Cookie::build((
+            REVISION_COOKIE,
+            format!("{}:{}:{}", channel_id.get(), message_id.get(), revision),
+        ))
+        .path("/hikea"),
+    );
 
     let response = state
         .http
@@ -46,7 +114,7 @@ pub async fn page(
 
     let link = response
         .embeds
-        .get(0)
+        .first()
         .ok_or_eyre("No embeds in passed Discord response")
         .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
         .url
@@ -54,155 +122,391 @@ pub async fn page(
         .ok_or_eyre("No URL in passed embed in Discord response")
         .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    state
-        .alltrails_message_on
-        .0
-        .store(channel_id.get(), Ordering::Release);
+    // Only AllTrails pages can be scraped
+    if !state.feature_enabled(crate::Feature::Scraper)
+        || crate::providers::Provider::of(link) != Some(crate::providers::Provider::AllTrails)
+    {
+        state.scraped_trail.store(None);
+        return Ok((jar, Redirect::to(link)).into_response());
+    }
+
+    let config = state.config.load();
+    let metadata = match crate::alltrails::scrape(link, &config).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            warn!(
+                "Failed to scrape AllTrails, falling back to manual upload: {:?}",
+                e
+            );
+            state.scraped_trail.store(None);
+            return Ok((jar, Redirect::to(link)).into_response());
+        }
+    };
+
+    if let Some(trail_id) = metadata.id {
+        match crate::alltrails::download_gpx(trail_id, &config).await {
+            Ok(gpx_file) => {
+                let form = UploadForm::from_metadata(metadata.clone(), gpx_file).filter(|form| {
+                    // Caught text has to be reworded by hand
+                    let checked = config
+                        .content_filter
+                        .as_ref()
+                        .filter(|filter| !filter.overridden_by(&member.roles))
+                        .map(|filter| form.check_content(filter))
+                        .transpose();
+                    if let Err(e) = &checked {
+                        warn!(
+                            "Scraped AllTrails text was caught by the content filter: {:?}",
+                            e
+                        );
+                    }
+                    checked.is_ok()
+                });
+                // Filled in once confirmed, so link previews and reloads
+                // can't change the suggestion
+                if let Some(form) = form {
+                    let upload = PendingUpload {
+                        channel_id,
+                        message_id,
+                        link: link.to_owned(),
+                        form,
+                        at: get_current_timestamp(),
+                    };
+                    let page = scraped_page(&upload, revision);
+                    hold_upload(&state, upload);
+                    // In case it's filled in by hand instead
+                    state
+                        .scraped_trail
+                        .store(Some(Arc::new((message_id, metadata))));
+                    return Ok((jar, page).into_response());
+                }
+            }
+            Err(e) => warn!("Failed to download GPX from AllTrails: {:?}", e),
+        }
+    }
 
     state
-        .alltrails_message_on
-        .1
-        .store(message_id.get(), Ordering::Release);
+        .scraped_trail
+        .store(Some(Arc::new((message_id, metadata))));
 
-    Ok(Redirect::to(&link))
+    Ok((jar, Redirect::to(link)).into_response())
+}
+
+/// The suggestion the upload page was last opened on, and its revision then
+#[derive(Debug, PartialEq, Eq)]
+struct OpenedUpload {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    revision: u64,
+}
+
+fn opened_upload(jar: &CookieJar) -> Option<OpenedUpload> {
+    let cookie = jar.get(REVISION_COOKIE)?;
+    let mut parts = cookie.value().split(':');
+    let opened = OpenedUpload {
+        channel_id: ChannelId::new(parts.next()?.parse().ok().filter(|id| *id != 0)?),
+        message_id: MessageId::new(parts.next()?.parse().ok().filter(|id| *id != 0)?),
+        revision: parts.next()?.parse().ok()?,
+    };
+    parts.next().is_none().then_some(opened)
 }
 
 pub struct UploadForm {
     pub title: String,
-    pub difficulty: String,
+    /// Difficulty reported by AllTrails, the computed difficulty is shown
+    /// without it
+    pub difficulty: Option<String>,
     pub rating: String,
+    /// URL of the photo, which points at `image_file` if one was uploaded
     pub image: String,
+    /// Photo uploaded instead of linked, to be attached to the suggestion
+    pub image_file: Option<Vec<u8>>,
     pub description: String,
     pub gpx_file: Gpx,
+    /// Pounds carried by members training with weighted packs, shown as an
+    /// alternate estimate
+    pub ruck_weight: Option<f64>,
+    pub activity: Activity,
+    pub conditions: Conditions,
 }
 
 impl UploadForm {
-    #[instrument(skip_all)]
-    async fn try_from_multipart(mut multipart: Multipart) -> Result<Self, eyre::Report> {
-        let trail_title = multipart
-            .next_field()
-            .await
-            .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form missing fields")?;
+    /// Fields that have to be filled in
+    const FIELDS: [&'static str; 5] = ["title", "rating", "image", "description", "gpx_file"];
 
-        let trail_title = trail_title
-            .text()
-            .await
-            .wrap_err("Failed to obtain text for multipart field")?;
-
-        if trail_title.is_empty() {
-            return Err(eyre!("Title for trail was not present"));
-        }
-
-        let trail_difficulty = multipart
-            .next_field()
-            .await
-            .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form missing fields")?;
+    /// Builds a form entirely from scraped AllTrails data, if every field
+    /// could be scraped
+    pub fn from_metadata(metadata: TrailMetadata, gpx_file: Gpx) -> Option<Self> {
+        Some(Self {
+            title: metadata.title,
+            difficulty: metadata.difficulty,
+            rating: metadata.rating?,
+            image: metadata.image?,
+            image_file: None,
+            description: metadata.description?,
+            gpx_file,
+            ruck_weight: None,
+            activity: Activity::Hike,
+            conditions: Conditions::default(),
+        })
+    }
 
-        let trail_difficulty = trail_difficulty
-            .text()
-            .await
-            .wrap_err("Failed to obtain text for multipart field")?;
+    /// Fails if text that ends up in the suggestion embed is caught by
+    /// `filter`
+    pub fn check_content(&self, filter: &ContentFilterConfig) -> eyre::Result<()> {
+        filter.check(&[
+            ("title", &self.title),
+            ("difficulty", self.difficulty.as_deref().unwrap_or_default()),
+            ("rating", &self.rating),
+            ("description", &self.description),
+        ])
+    }
 
-        if trail_difficulty.is_empty() {
-            return Err(eyre!("difficulty for trail was not present"));
-        }
+    /// Reads the form, using `defaults` scraped from AllTrails for any text
+    /// field the client left out
+    #[instrument(skip_all)]
+    async fn try_from_multipart(
+        mut multipart: Multipart,
+        defaults: Option<TrailMetadata>,
+    ) -> Result<Self, eyre::Report> {
+        let mut title = None;
+        let mut difficulty = None;
+        let mut rating = None;
+        let mut image = None;
+        let mut image_file = None;
+        let mut description = None;
+        let mut gpx_file = None;
+        let mut ruck_weight = None;
+        let mut activity = Activity::Hike;
+        let mut conditions = Conditions::default();
 
-        let trail_rating = multipart
+        while let Some(field) = multipart
             .next_field()
             .await
             .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form missing fields")?;
+        {
+            let Some(name) = field.name().map(|n| n.to_owned()) else {
+                debug!("Skipping multipart field without a name");
+                continue;
+            };
 
-        let trail_rating = trail_rating
-            .text()
-            .await
-            .wrap_err("Failed to obtain text for multipart field")?;
+            let slot = match name.as_str() {
+                "title" => &mut title,
+                "difficulty" => &mut difficulty,
+                "rating" => &mut rating,
+                "image" => &mut image,
+                "description" => &mut description,
+                "gpx_file" => {
+                    gpx_file = Some(field.bytes().await.wrap_err_with(|| {
+                        format!("Failed to obtain bytes for multipart field `{}`", name)
+                    })?);
+                    continue;
+                }
+                "ruck_weight" => {
+                    let text = field.text().await.wrap_err_with(|| {
+                        format!("Failed to obtain text for multipart field `{}`", name)
+                    })?;
+                    // Left blank when nobody is training with a pack
+                    if !text.is_empty() {
+                        ruck_weight = Some(
+                            text.parse::<f64>()
+                                .ok()
+                                .filter(|weight| weight.is_finite() && *weight > 0.0)
+                                .ok_or_else(|| {
+                                    eyre!("`ruck_weight` must be a positive number of pounds")
+                                })?,
+                        );
+                    }
+                    continue;
+                }
+                "activity" => {
+                    let text = field.text().await.wrap_err_with(|| {
+                        format!("Failed to obtain text for multipart field `{}`", name)
+                    })?;
+                    activity = Activity::from_name(&text).ok_or_else(|| {
+                        eyre!("`activity` must be `hike` or `trail_run`, not `{}`", text)
+                    })?;
+                    continue;
+                }
+                "exposed" => {
+                    let text = field.text().await.wrap_err_with(|| {
+                        format!("Failed to obtain text for multipart field `{}`", name)
+                    })?;
+                    conditions.exposed = match text.as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => return Err(eyre!("`exposed` must be `yes` or `no`, not `{}`", text)),
+                    };
+                    continue;
+                }
+                "accessible" => {
+                    let text = field.text().await.wrap_err_with(|| {
+                        format!("Failed to obtain text for multipart field `{}`", name)
+                    })?;
+                    conditions.accessible = match text.as_str() {
+                        "yes" => Some(true),
+                        "no" => Some(false),
+                        // Left to OpenStreetMap
+                        "" => None,
+                        _ => {
+                            return Err(eyre!(
+                                "`accessible` must be `yes`, `no` or empty, not `{}`",
+                                text
+                            ))
+                        }
+                    };
+                    continue;
+                }
+                "shade" => {
+                    let text = field.text().await.wrap_err_with(|| {
+                        format!("Failed to obtain text for multipart field `{}`", name)
+                    })?;
+                    conditions.shade = Some(Shade::from_name(&text).ok_or_else(|| {
+                        eyre!(
+                            "`shade` must be `none`, `partial` or `full`, not `{}`",
+                            text
+                        )
+                    })?);
+                    continue;
+                }
+                "image_file" => {
+                    let bytes = field.bytes().await.wrap_err_with(|| {
+                        format!("Failed to obtain bytes for multipart field `{}`", name)
+                    })?;
+                    // Browsers send an empty file input when no file is picked
+                    if !bytes.is_empty() {
+                        image_file = Some(bytes);
+                    }
+                    continue;
+                }
+                _ => {
+                    debug!(field = name, "Skipping unknown multipart field");
+                    continue;
+                }
+            };
 
-        if trail_rating.is_empty() {
-            return Err(eyre!("rating for trail was not present"));
-        }
+            let text = field.text().await.wrap_err_with(|| {
+                format!("Failed to obtain text for multipart field `{}`", name)
+            })?;
 
-        let trail_image = multipart
-            .next_field()
-            .await
-            .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form missing fields")?;
+            if text.is_empty() {
+                return Err(eyre!("Multipart field `{}` was present but empty", name));
+            }
 
-        let trail_image = trail_image
-            .text()
-            .await
-            .wrap_err("Failed to obtain text for multipart field")?;
+            *slot = Some(text);
+        }
 
-        if trail_image.is_empty() {
-            return Err(eyre!("image for trail was not present"));
+        let image_file = image_file
+            .map(|bytes| reencode_image(&bytes))
+            .transpose()
+            .wrap_err("Failed to read uploaded image")?;
+        if image_file.is_some() {
+            image = Some(format!("attachment://{}", IMAGE_FILE_NAME));
         }
 
-        let trail_description = multipart
-            .next_field()
-            .await
-            .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form missing fields")?;
+        if let Some(defaults) = defaults {
+            title = title.or(Some(defaults.title));
+            difficulty = difficulty.or(defaults.difficulty);
+            rating = rating.or(defaults.rating);
+            image = image.or(defaults.image);
+            description = description.or(defaults.description);
+        }
 
-        let trail_description = trail_description
-            .text()
-            .await
-            .wrap_err("Failed to obtain text for multipart field")?;
+        let missing = Self::FIELDS
+            .iter()
+            .zip([
+                title.is_none(),
+                rating.is_none(),
+                image.is_none(),
+                description.is_none(),
+                gpx_file.is_none(),
+            ])
+            .filter(|(_, missing)| *missing)
+            .map(|(name, _)| format!("`{}`", name))
+            .collect::<Vec<_>>();
 
-        if trail_description.is_empty() {
-            return Err(eyre!("description for trail was not present"));
+        if !missing.is_empty() {
+            return Err(eyre!(
+                "Multipart form is missing fields: {}",
+                missing.join(", ")
+            ));
         }
 
-        let gpx_file = multipart
-            .next_field()
-            .await
-            .wrap_err("Failed to decode multipart field")?
-            .ok_or_eyre("Multipart form contained no fields")?;
-        let gpx_file_bytes = gpx_file
-            .bytes()
-            .await
-            .wrap_err("Failed to obtain bytes for multipart field")?;
-
         Ok(Self {
-            title: trail_title,
-            difficulty: trail_difficulty,
-            rating: trail_rating,
-            image: trail_image,
-            description: trail_description,
-            gpx_file: gpx::read(Cursor::new(gpx_file_bytes)).wrap_err("Failed to read GPX file")?,
+            title: title.unwrap(),
+            difficulty,
+            rating: rating.unwrap(),
+            image: image.unwrap(),
+            image_file,
+            description: description.unwrap(),
+            gpx_file: gpx::read(Cursor::new(gpx_file.unwrap()))
+                .wrap_err("Failed to read GPX file")?,
+            ruck_weight,
+            activity,
+            conditions,
         })
     }
 }
 
-#[instrument(skip(state, claims))]
+/// Shrinks an uploaded photo and strips its metadata, which can include where
+/// it was taken
+#[instrument(skip_all)]
+fn reencode_image(bytes: &[u8]) -> eyre::Result<Vec<u8>> {
+    let mut wand = MagickWand::new();
+    wand.read_image_blob(bytes)
+        .wrap_err("Failed to read image in MagickWand")?;
+    wand.auto_orient();
+    wand.fit(MAX_IMAGE_SIZE, MAX_IMAGE_SIZE);
+    wand.strip_image()
+        .wrap_err("Failed to strip image metadata in MagickWand")?;
+    wand.set_image_compression_quality(IMAGE_QUALITY)
+        .wrap_err("Failed to set image quality in MagickWand")?;
+    wand.write_image_blob("jpeg")
+        .wrap_err("Failed to write image from MagickWand")
+}
+
+#[instrument(skip(state, claims, jar))]
 pub async fn post(
     State(state): State<Arc<AppState>>,
     claims: super::Claims,
+    jar: CookieJar,
     multipart: Multipart,
 ) -> Result<maud::Markup, crate::error::HtmlError> {
-    let config = state.config.load();
-    let (channel_id, message_id): (ChannelId, MessageId) = (
-        state.alltrails_message_on.0.load(Ordering::Acquire).into(),
-        state.alltrails_message_on.1.load(Ordering::Acquire).into(),
-    );
-    match claims {
-        super::Claims::Authenticated { .. } => {}
-        super::Claims::Unauthenticated { .. } => {
-            return Err(eyre!("You are not authenticated")).with_redirect(std::borrow::Cow::Owned(
-                format!(
-                    "/hikea/oauth2?redirect=/hikea/upload_gpx/{}/{}",
-                    channel_id.get(),
-                    message_id.get()
-                ),
-            ));
-        }
-    }
+    let opened = opened_upload(&jar);
+    let member = claims.require_admin(&match &opened {
+        Some(opened) => format!(
+            "/hikea/upload_gpx/{}/{}",
+            opened.channel_id.get(),
+            opened.message_id.get()
+        ),
+        None => String::from("/hikea"),
+    })?;
+    // Uploads go to the suggestion this browser opened the upload page on,
+    // as other admins may be filling in other suggestions at the same time
+    let OpenedUpload {
+        channel_id,
+        message_id,
+        revision,
+    } = opened
+        .ok_or_eyre("Open the upload page from the trail suggestion before uploading")
+        .with_status_code_html(StatusCode::BAD_REQUEST)?;
+
+    let defaults = state
+        .scraped_trail
+        .load_full()
+        .filter(|scraped| scraped.0 == message_id)
+        .map(|scraped| scraped.1.clone());
 
-    let form = UploadForm::try_from_multipart(multipart)
+    let form = UploadForm::try_from_multipart(multipart, defaults)
         .await
         .wrap_err("Failed to read multipart form")
         .with_status_code_html(StatusCode::BAD_REQUEST)?;
+    if let Some(filter) = &state.config.load().content_filter {
+        if !filter.overridden_by(&member.roles) {
+            form.check_content(filter)
+                .with_status_code_html(StatusCode::BAD_REQUEST)?;
+        }
+    }
 
     let response = state
         .http
@@ -214,7 +518,7 @@ pub async fn post(
 
     let link = response
         .embeds
-        .get(0)
+        .first()
         .ok_or_eyre("No embeds in passed Discord response")
         .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
         .url
@@ -222,36 +526,467 @@ pub async fn post(
         .ok_or_eyre("No URL in passed embed in Discord response")
         .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let embed = crate::commands::suggest::embed_from_gpx(
-        link,
-        config.short_units,
-        config.long_units,
-        config.avg_speed,
+    let upload = PendingUpload {
+        channel_id,
+        message_id,
+        link: link.to_owned(),
         form,
+        at: get_current_timestamp(),
+    };
+    fill_or_confirm(&state, upload, &super::member_name(&member), revision)
+        .await
+        .wrap_err("Failed to fill trail suggestion")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// An upload waiting on confirmation, either to fill the suggestion in from
+/// AllTrails or to overwrite a suggestion someone else filled in
+pub struct PendingUpload {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    link: String,
+    form: UploadForm,
+    /// When the upload was made, to drop it if it's never confirmed
+    at: u64,
+}
+
+/// Holds on to `upload` until it's confirmed, replacing any earlier upload for
+/// the same suggestion
+fn hold_upload(state: &AppState, upload: PendingUpload) {
+    let mut pending = state.pending_uploads.lock().unwrap();
+    let expired = upload.at.saturating_sub(PENDING_UPLOAD_TTL);
+    pending.retain(|_, held| held.at > expired);
+    pending.insert(upload.message_id, upload);
+}
+
+/// Fills in the suggestion if it's still at `revision`, otherwise holds on to
+/// `upload` and asks whether to overwrite it
+async fn fill_or_confirm(
+    state: &AppState,
+    upload: PendingUpload,
+    filled_by: &str,
+    revision: u64,
+) -> eyre::Result<maud::Markup> {
+    let filled = state
+        .store
+        .filled(upload.message_id)
+        .wrap_err("Failed to look up trail suggestion")?
+        .filter(|filled| filled.revision != revision);
+    if let Some(filled) = filled {
+        let page = confirm_page(
+            &filled,
+            maud::html! {
+                form method="post" action="/hikea/upload_gpx/confirm" {
+                    input type="hidden" name="message_id" value=(upload.message_id.get());
+                    input type="hidden" name="revision" value=(filled.revision);
+                    input type="submit" value="Overwrite";
+                }
+            },
+        );
+        hold_upload(state, upload);
+        return Ok(page);
+    }
+
+    fill_suggestion(
+        state,
+        upload.channel_id,
+        upload.message_id,
+        &upload.link,
+        upload.form,
+        filled_by,
+        Some(revision),
     )
-    .wrap_err("Failed to create Discord embed from GPX file")
-    .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
+    Ok(success_page())
+}
 
-    let react_embed = CreateEmbed::new()
-        .color(Color::DARK_GREEN)
-        .title("React with ⛰️ if interested");
+#[derive(Deserialize, Debug)]
+pub struct ConfirmForm {
+    message_id: MessageId,
+    /// Revision of the suggestion the confirmation was shown for
+    revision: u64,
+}
 
-    let http = state.http.load();
-    http.get_message(channel_id, message_id)
+/// Fills in a suggestion with the upload held on to by [`page`] or
+/// [`fill_or_confirm`]
+#[instrument(skip(state, claims))]
+pub async fn confirm(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Form(form): Form<ConfirmForm>,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    let member = claims.require_admin("/hikea")?;
+
+    let upload = state
+        .pending_uploads
+        .lock()
+        .unwrap()
+        .remove(&form.message_id)
+        .ok_or_eyre("The upload is no longer waiting to be confirmed")
+        .with_status_code_html(StatusCode::CONFLICT)?;
+
+    fill_or_confirm(&state, upload, &super::member_name(&member), form.revision)
         .await
-        .wrap_err("Failed to obtain trail request interaction response from Discord")
-        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
-        .edit(
-            http.deref(),
-            EditMessage::new()
-                .embeds(vec![embed, react_embed])
-                .components(Vec::new()),
+        .wrap_err("Failed to fill trail suggestion")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Replaces the suggestion message with the trail information from `form`.
+/// If `revision` is given, the suggestion must not have been filled in since.
+#[instrument(skip(state, form))]
+pub async fn fill_suggestion(
+    state: &AppState,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    link: &str,
+    mut form: UploadForm,
+    filled_by: &str,
+    revision: Option<u64>,
+) -> eyre::Result<()> {
+    let config = state.config.load();
+    let title = form.title.clone();
+    let difficulty = form.difficulty.clone();
+    let description = form.description.clone();
+    let conditions = form.conditions;
+    let image_file = form.image_file.take();
+
+    crate::elevation::backfill(&mut form.gpx_file, &config)
+        .await
+        .wrap_err("Failed to fill in missing elevation data")?;
+    // Trimmed once, so the stats, charts and stored route all agree
+    for track in &mut form.gpx_file.tracks {
+        trail_stats::trim_trailhead_wander(track);
+    }
+    // Kept so recordings of the hike can be compared against it
+    let mut planned = Vec::new();
+    gpx::write(&form.gpx_file, &mut planned).wrap_err("Failed to write GPX file")?;
+
+    // Trails are still worth filling in without a drive time
+    let drive = crate::osrm::drive_to_trailhead(&form.gpx_file, &config)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("{:?}", e);
+            None
+        });
+    let trailhead = crate::commands::suggest::trailhead(&form.gpx_file).ok();
+    let trailhead_area = match trailhead {
+        Some(trailhead) => state
+            .geocoder
+            .reverse(trailhead, &config)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("{:?}", e);
+                None
+            }),
+        None => None,
+    };
+    // Whoever filled the trail in knows it better than OpenStreetMap
+    let accessible = conditions
+        .accessible
+        .or_else(|| trailhead_area.as_ref().and_then(|area| area.wheelchair));
+
+    // Left out if they fail to render
+    let (route_map, profile) = match (&config.route_map, form.gpx_file.tracks.first()) {
+        (Some(route_map), Some(track)) => (
+            crate::route_map::render(&track.multilinestring(), route_map, &config)
+                .await
+                .map(Some)
+                .unwrap_or_else(|e| {
+                    warn!("{:?}", e);
+                    None
+                }),
+            crate::route_map::render_profile(track)
+                .map(Some)
+                .unwrap_or_else(|e| {
+                    warn!("{:?}", e);
+                    None
+                }),
+        ),
+        _ => (None, None),
+    };
+
+    // Left out if it fails to render
+    let mileage_chart = match (&config.mileage_chart, form.gpx_file.tracks.first()) {
+        (Some(mileage_chart), Some(track)) => {
+            let waypoints = state
+                .store
+                .waypoints(message_id)
+                .wrap_err("Failed to load waypoints")?;
+            crate::mileage::chart(track, &title, &waypoints, mileage_chart, &config)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("{:?}", e);
+                    None
+                })
+        }
+        _ => None,
+    };
+
+    let (embed, stats) = crate::commands::suggest::embed_from_gpx(
+        link,
+        &format!("{}/hikea/trail/{}", config.hostname, message_id),
+        &config,
+        form,
+        drive,
+        trailhead_area,
+    )
+    .wrap_err("Failed to create Discord embed from GPX file")?;
+
+    let react_embed = CreateEmbed::new().color(Color::DARK_GREEN).title(format!(
+        "React with {} if interested",
+        crate::gateway::INTERESTED_EMOJI
+    ));
+
+    let beginner_score = BeginnerScore::new(stats, conditions, &config.beginner_weights);
+    let mut embed = embed.field("Beginner friendly", beginner_score.to_string(), false);
+    if accessible == Some(true) {
+        embed = embed.author(CreateEmbedAuthor::new(crate::commands::trails::ACCESSIBLE));
+    }
+    if let Some(duplicate) = crate::trail_names::duplicate_of(&state.store, message_id, &title)? {
+        embed = embed.field(
+            "Suggested before",
+            format!(
+                "[{}]({})",
+                duplicate.title,
+                duplicate
+                    .message_id
+                    .link(duplicate.channel_id, Some(config.guild_id))
+            ),
+            false,
+        );
+    }
+
+    let stored_lifecycle = crate::commands::lifecycle::stored_state(state, message_id)?;
+    let lifecycle = stored_lifecycle.unwrap_or_default();
+    let mut embeds = vec![crate::commands::lifecycle::style(embed, lifecycle)];
+    let mut edit = EditMessage::new().remove_all_attachments();
+    let stored_route_map = route_map.clone();
+    if let Some(image_file) = image_file {
+        edit = edit.new_attachment(CreateAttachment::bytes(image_file, IMAGE_FILE_NAME));
+    }
+    if let Some(route_map) = route_map {
+        embeds.push(
+            CreateEmbed::new()
+                .color(Color::DARK_GREEN)
+                .title(crate::route_map::TITLE)
+                .image(format!("attachment://{}", crate::route_map::FILE_NAME)),
+        );
+        edit = edit.new_attachment(CreateAttachment::bytes(
+            route_map,
+            crate::route_map::FILE_NAME,
+        ));
+    }
+    if let Some(profile) = profile {
+        embeds.push(
+            CreateEmbed::new()
+                .color(Color::DARK_GREEN)
+                .title(crate::route_map::PROFILE_TITLE)
+                .image(format!(
+                    "attachment://{}",
+                    crate::route_map::PROFILE_FILE_NAME
+                )),
+        );
+        edit = edit.new_attachment(CreateAttachment::bytes(
+            profile,
+            crate::route_map::PROFILE_FILE_NAME,
+        ));
+    }
+    if let Some(mileage_chart) = mileage_chart {
+        edit = edit.new_attachment(CreateAttachment::bytes(
+            mileage_chart,
+            crate::mileage::FILE_NAME,
+        ));
+    }
+    embeds.push(react_embed);
+    let edit = edit
+        .embeds(embeds)
+        .components(crate::commands::lifecycle::buttons(
+            lifecycle,
+            Some(crate::web_interface::api::gpx_url(
+                &config.hostname,
+                message_id,
+            )),
+        )?);
+
+    let suggestion = Suggestion {
+        message_id,
+        channel_id,
+        title,
+        link: link.to_owned(),
+        created_at: message_id.created_at().unix_timestamp() as u64,
+        difficulty: Some(
+            difficulty.unwrap_or_else(|| Difficulty::from_stats(stats).rating().to_owned()),
+        ),
+        length: Some(stats.length),
+        gain: Some(stats.gains),
+        hiked_at: None,
+        description: Some(description),
+        beginner_score: Some(beginner_score.0),
+        accessible,
+        trailhead,
+    };
+
+    // Claimed before the message is edited, so two uploads at once can't both
+    // edit it and leave it out of step with the store
+    let now = get_current_timestamp();
+    let claimed = state
+        .store
+        .claim_fill(
+            message_id,
+            revision,
+            filled_by,
+            now,
+            now.saturating_sub(FILL_CLAIM_TTL),
+        )
+        .wrap_err("Failed to claim trail suggestion")?;
+    if !claimed {
+        return Err(eyre!(
+            "Someone else filled in the trail suggestion at the same time"
+        ));
+    }
+    let filled = async {
+        let http = state.http.load();
+        let mut message = http
+            .get_message(channel_id, message_id)
+            .await
+            .wrap_err("Failed to obtain trail request interaction response from Discord")?;
+        audited(
+            &state.audit,
+            Mutation::EditMessage,
+            Actor::Admin(filled_by),
+            format!("message {} in channel {}", message_id, channel_id),
+            &summarize(&edit),
+            message.edit(http.deref(), edit),
         )
         .await
-        .wrap_err("Failed to update embed for trail suggestion on Discord")
-        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .wrap_err("Failed to update embed for trail suggestion on Discord")?;
+        state
+            .store
+            .remove_upload_button(message_id)
+            .wrap_err("Failed to stop tracking upload button")?;
+
+        let stored = state
+            .store
+            .insert_suggestion(&suggestion, filled_by, now, revision)
+            .wrap_err("Failed to store trail suggestion")?;
+        if !stored {
+            return Err(eyre!(
+                "Someone else filled in the trail suggestion at the same time"
+            ));
+        }
+        Ok(())
+    }
+    .await;
+    // Released either way, a failed edit leaves the suggestion as it was
+    if let Err(e) = state.store.release_fill(message_id) {
+        warn!("{:?}", e);
+    }
+    filled?;
+
+    state
+        .store
+        .set_suggestion_gpx(message_id, &planned)
+        .wrap_err("Failed to store GPX file")?;
+    state
+        .store
+        .set_route_map(message_id, stored_route_map.as_deref())
+        .wrap_err("Failed to store route map")?;
+
+    if stored_lifecycle.is_none() {
+        tokio::spawn(crate::webhooks::notify(
+            state.config.load_full(),
+            crate::webhooks::Event::Created,
+            suggestion,
+            None,
+        ));
+    }
+
+    state.scraped_trail.store(None);
+
+    Ok(())
+}
+
+/// Roughly how long ago `at` was
+fn ago(at: u64) -> String {
+    let seconds = get_current_timestamp().saturating_sub(at);
+    let (amount, unit) = match seconds {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        amount,
+        unit,
+        if amount == 1 { "" } else { "s" }
+    )
+}
+
+/// Shows what was scraped from AllTrails, to be confirmed before the
+/// suggestion is filled in with it
+fn scraped_page(upload: &PendingUpload, revision: u64) -> maud::Markup {
+    let form = &upload.form;
+    maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Fill in " (form.title) }
+            }
+            body {
+                h1 { (form.title) }
+                img src=(form.image) alt=(form.title) style="max-width: 100%";
+                dl {
+                    @if let Some(difficulty) = &form.difficulty {
+                        dt { "Difficulty" }
+                        dd { (difficulty) }
+                    }
+                    dt { "Rating" }
+                    dd { (form.rating) }
+                    dt { "Description" }
+                    dd { (form.description) }
+                }
+                form method="post" action="/hikea/upload_gpx/confirm" {
+                    input type="hidden" name="message_id" value=(upload.message_id.get());
+                    input type="hidden" name="revision" value=(revision);
+                    input type="submit" value="Fill in";
+                }
+                p {
+                    a href=(upload.link) { "Fill it in by hand instead" }
+                }
+            }
+        }
+    }
+}
 
-    let html = maud::html! {
+/// Asks whether to overwrite a suggestion that was already filled in, with
+/// `overwrite` to go ahead
+fn confirm_page(filled: &Filled, overwrite: maud::Markup) -> maud::Markup {
+    maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Trail suggestion already filled in" }
+            }
+            body {
+                h1 { "Already filled in" }
+                p {
+                    "This trail suggestion was already filled in by "
+                    (filled.by.as_deref().unwrap_or("someone"))
+                    " " (ago(filled.at)) ". Overwrite it?"
+                }
+                (overwrite)
+            }
+        }
+    }
+}
+
+fn success_page() -> maud::Markup {
+    maud::html! {
         (DOCTYPE)
         html {
             head {
@@ -265,7 +1000,37 @@ pub async fn post(
                 }
             }
         }
-    };
+    }
+}
 
-    Ok(html)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jar(value: &str) -> CookieJar {
+        CookieJar::new().add(Cookie::new(REVISION_COOKIE, value.to_owned()))
+    }
+
+    #[test]
+    fn reads_the_opened_suggestion() {
+        assert_eq!(
+            opened_upload(&jar("12:34:5")),
+            Some(OpenedUpload {
+                channel_id: ChannelId::new(12),
+                message_id: MessageId::new(34),
+                revision: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_cookies_without_a_suggestion() {
+        assert_eq!(opened_upload(&CookieJar::new()), None);
+        // Cookies from before the channel was remembered only name a message,
+        // so uploads with them can't be told apart from another suggestion's
+        assert_eq!(opened_upload(&jar("34:5")), None);
+        assert_eq!(opened_upload(&jar("12:34:5:6")), None);
+        assert_eq!(opened_upload(&jar("0:34:5")), None);
+        assert_eq!(opened_upload(&jar("12:abc:5")), None);
+    }
 }
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Redirect,
+    Form,
+};
+use color_eyre::eyre::{self, eyre, Context, OptionExt};
+use maud::DOCTYPE;
+use serde::Deserialize;
+use serenity::all::MessageId;
+use tracing::{info, instrument};
+
+use crate::{error::WithStatusCode, store::Waypoint, AppState};
+
+/// Longest label a waypoint can have
+const MAX_LABEL_LENGTH: usize = 100;
+
+/// What a waypoint marks along a backpacking route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaypointKind {
+    Camp,
+    Water,
+    Cache,
+}
+
+impl WaypointKind {
+    const ALL: [WaypointKind; 3] = [WaypointKind::Camp, WaypointKind::Water, WaypointKind::Cache];
+
+    /// Name the kind is stored as
+    pub fn name(self) -> &'static str {
+        match self {
+            WaypointKind::Camp => "camp",
+            WaypointKind::Water => "water",
+            WaypointKind::Cache => "cache",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WaypointKind::Camp => "⛺ Campsite",
+            WaypointKind::Water => "💧 Water source",
+            WaypointKind::Cache => "📦 Cache",
+        }
+    }
+}
+
+/// Label of a stored waypoint kind, or the name itself if it's unknown
+pub fn kind_label(name: &str) -> &str {
+    WaypointKind::from_name(name).map_or(name, |kind| kind.label())
+}
+
+/// Every filled in suggestion, linking to its waypoint editor
+#[instrument(skip_all)]
+pub async fn index(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin("/hikea/admin/waypoints")?;
+    let suggestions = state
+        .store
+        .suggestions()
+        .wrap_err("Failed to load trail suggestions")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Waypoints" }
+            }
+            body {
+                h1 { "Waypoints" }
+                p { "Pick a trail to mark its campsites, water sources and caches." }
+                ul {
+                    @for suggestion in &suggestions {
+                        li {
+                            a href=(format!("/hikea/admin/waypoints/{}", suggestion.message_id)) {
+                                (suggestion.title)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[instrument(skip(state, claims))]
+pub async fn page(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+) -> Result<maud::Markup, crate::error::HtmlError> {
+    claims.require_admin(&format!("/hikea/admin/waypoints/{}", message_id))?;
+    let suggestion = state
+        .store
+        .suggestion(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or_eyre("Trail suggestion was not found")
+        .with_status_code_html(StatusCode::NOT_FOUND)?;
+    let waypoints = state
+        .store
+        .waypoints(message_id)
+        .wrap_err("Failed to load waypoints")
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(maud::html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width";
+                title { "Waypoints for " (suggestion.title) }
+            }
+            body {
+                p { a href="/hikea/admin/waypoints" { "All trails" } }
+                h1 { "Waypoints for " a href=(suggestion.link) { (suggestion.title) } }
+                table {
+                    tr {
+                        th { "Kind" }
+                        th { "Label" }
+                        th { "Latitude" }
+                        th { "Longitude" }
+                        th {}
+                    }
+                    @for waypoint in &waypoints {
+                        tr {
+                            td { (kind_label(&waypoint.kind)) }
+                            td { (waypoint.label) }
+                            td { (waypoint.latitude) }
+                            td { (waypoint.longitude) }
+                            td {
+                                form method="post"
+                                    action=(format!("/hikea/admin/waypoints/{}/delete", message_id)) {
+                                    input type="hidden" name="id" value=(waypoint.id);
+                                    input type="submit" value="Remove";
+                                }
+                            }
+                        }
+                    }
+                }
+                h2 { "Add a waypoint" }
+                form method="post" {
+                    p {
+                        label {
+                            "Kind "
+                            select name="kind" {
+                                @for kind in WaypointKind::ALL {
+                                    option value=(kind.name()) { (kind.label()) }
+                                }
+                            }
+                        }
+                    }
+                    p {
+                        label {
+                            "Label "
+                            input type="text" name="label" required maxlength=(MAX_LABEL_LENGTH);
+                        }
+                    }
+                    p {
+                        label {
+                            "Latitude "
+                            input type="number" name="latitude" required step="any" min="-90" max="90";
+                        }
+                    }
+                    p {
+                        label {
+                            "Longitude "
+                            input type="number" name="longitude" required step="any" min="-180" max="180";
+                        }
+                    }
+                    input type="submit" value="Add";
+                }
+            }
+        }
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WaypointForm {
+    kind: String,
+    label: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl WaypointForm {
+    fn into_waypoint(self) -> eyre::Result<Waypoint> {
+        let kind = WaypointKind::from_name(&self.kind)
+            .ok_or_else(|| eyre!("`{}` is not a kind of waypoint", self.kind))?;
+        let label = self.label.trim();
+        if label.is_empty() || label.chars().count() > MAX_LABEL_LENGTH {
+            return Err(eyre!(
+                "Labels must be from 1 to {} characters long",
+                MAX_LABEL_LENGTH
+            ));
+        }
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(eyre!(
+                "`{}, {}` is not a coordinate",
+                self.latitude,
+                self.longitude
+            ));
+        }
+
+        Ok(Waypoint {
+            id: 0,
+            kind: kind.name().to_owned(),
+            label: label.to_owned(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+        })
+    }
+}
+
+#[instrument(skip(state, claims))]
+pub async fn add(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+    Form(form): Form<WaypointForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let admin = super::member_name(
+        &claims.require_admin(&format!("/hikea/admin/waypoints/{}", message_id))?,
+    );
+    let waypoint = form
+        .into_waypoint()
+        .with_status_code_html(StatusCode::BAD_REQUEST)?;
+    if state
+        .store
+        .suggestion(message_id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?
+        .is_none()
+    {
+        return Err(eyre!("Trail suggestion was not found"))
+            .with_status_code_html(StatusCode::NOT_FOUND);
+    }
+
+    state
+        .store
+        .insert_waypoint(message_id, &waypoint)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    info!(admin, %message_id, label = waypoint.label, "Added waypoint");
+
+    Ok(Redirect::to(&format!(
+        "/hikea/admin/waypoints/{}",
+        message_id
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemoveForm {
+    id: i64,
+}
+
+#[instrument(skip(state, claims))]
+pub async fn remove(
+    State(state): State<Arc<AppState>>,
+    claims: super::Claims,
+    Path(message_id): Path<MessageId>,
+    Form(form): Form<RemoveForm>,
+) -> Result<Redirect, crate::error::HtmlError> {
+    let admin = super::member_name(
+        &claims.require_admin(&format!("/hikea/admin/waypoints/{}", message_id))?,
+    );
+
+    let removed = state
+        .store
+        .remove_waypoint(message_id, form.id)
+        .with_status_code_html(StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(eyre!("Waypoint was not found")).with_status_code_html(StatusCode::NOT_FOUND);
+    }
+    info!(admin, %message_id, id = form.id, "Removed waypoint");
+
+    Ok(Redirect::to(&format!(
+        "/hikea/admin/waypoints/{}",
+        message_id
+    )))
+}
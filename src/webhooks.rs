@@ -0,0 +1,162 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::{self, Context};
+use jsonwebtoken::get_current_timestamp;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+use tracing::{instrument, warn};
+
+use crate::{store::Suggestion, Config};
+
+/// How long a webhook has to answer before it's given up on
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that happened to a suggestion that webhooks can be told about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// Filled in for the first time
+    Created,
+    Approved,
+    Scheduled,
+}
+
+impl Event {
+    const ALL: [Event; 3] = [Event::Created, Event::Approved, Event::Scheduled];
+
+    fn name(self) -> &'static str {
+        match self {
+            Event::Created => "created",
+            Event::Approved => "approved",
+            Event::Scheduled => "scheduled",
+        }
+    }
+}
+
+fn all_events() -> Vec<Event> {
+    Event::ALL.to_vec()
+}
+
+/// An endpoint posted to when suggestions change. Its `secret` is never
+/// sent, the payload is signed with it instead.
+#[derive(Deserialize)]
+pub struct Webhook {
+    url: String,
+    secret: String,
+    #[serde(default = "all_events")]
+    events: Vec<Event>,
+}
+
+#[derive(Serialize)]
+struct Trailhead {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// What webhooks are sent about a suggestion, lengths in meters
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: Event,
+    /// Unix timestamp the webhook was sent at, so old deliveries can be told
+    /// apart from new ones
+    sent_at: u64,
+    /// Who caused the event, if it was a member
+    by: Option<UserId>,
+    message_id: String,
+    channel_id: String,
+    title: &'a str,
+    link: &'a str,
+    difficulty: Option<&'a str>,
+    length: Option<f64>,
+    gain: Option<f64>,
+    hiked_at: Option<u64>,
+    trailhead: Option<Trailhead>,
+}
+
+/// Hex encoded HMAC-SHA256 of `body` keyed with `secret`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hex::encode(hmac::sign(&key, body))
+}
+
+#[instrument(skip_all, fields(url = webhook.url))]
+async fn deliver(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    event: Event,
+    body: &[u8],
+    config: &Config,
+) -> eyre::Result<()> {
+    client
+        .post(&webhook.url)
+        .timeout(TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header(
+            "User-Agent",
+            format!(
+                "hikea/{} ( {} )",
+                env!("CARGO_PKG_VERSION"),
+                config.hostname
+            ),
+        )
+        .header("X-Hikea-Event", event.name())
+        .header(
+            "X-Hikea-Signature",
+            format!("sha256={}", sign(&webhook.secret, body)),
+        )
+        .body(body.to_vec())
+        .send()
+        .await
+        .wrap_err("Failed to send webhook")?
+        .error_for_status()
+        .wrap_err("Webhook returned an error")?;
+    Ok(())
+}
+
+/// Posts `event` for `suggestion` to every webhook listening for it. Failed
+/// deliveries are logged, not retried, so a broken integration never holds
+/// up Discord.
+#[instrument(skip(config, suggestion), fields(message_id = %suggestion.message_id))]
+pub async fn notify(config: Arc<Config>, event: Event, suggestion: Suggestion, by: Option<UserId>) {
+    let webhooks = config
+        .webhooks
+        .iter()
+        .filter(|webhook| webhook.events.contains(&event))
+        .collect::<Vec<_>>();
+    if webhooks.is_empty() {
+        return;
+    }
+
+    let payload = Payload {
+        event,
+        sent_at: get_current_timestamp(),
+        by,
+        message_id: suggestion.message_id.to_string(),
+        channel_id: suggestion.channel_id.to_string(),
+        title: &suggestion.title,
+        link: &suggestion.link,
+        difficulty: suggestion.difficulty.as_deref(),
+        length: suggestion.length,
+        gain: suggestion.gain,
+        hiked_at: suggestion.hiked_at,
+        trailhead: suggestion.trailhead.map(|trailhead| Trailhead {
+            latitude: trailhead.y(),
+            longitude: trailhead.x(),
+        }),
+    };
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to serialize webhook payload: {:?}", e);
+            return;
+        }
+    };
+
+    let client = reqwest::Client::new();
+    for webhook in webhooks {
+        if let Err(e) = deliver(&client, webhook, event, &body, &config).await {
+            warn!(url = webhook.url, "Failed to deliver webhook: {:?}", e);
+        }
+    }
+}
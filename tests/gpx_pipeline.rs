@@ -0,0 +1,89 @@
+//! Property tests feeding generated tracks through the stats pipeline, since
+//! uploaded GPX files are attacker controlled. Run with
+//! `cargo test --features fuzz`, and see `fuzz/` for fuzzing the parser too.
+#![cfg(feature = "fuzz")]
+
+use geo::Point;
+use gpx::{Track, TrackSegment, Waypoint};
+use hikea::trail_stats;
+use proptest::prelude::*;
+
+/// Degrees of latitude between points of the generated walks, about 11 m
+const STEP: f64 = 0.0001;
+
+/// A track through `points`, split into `segments` roughly even segments
+fn track(points: Vec<(Point, Option<f64>)>, segments: usize) -> Track {
+    let per_segment = points.len().div_ceil(segments).max(1);
+    let mut track = Track::new();
+    for chunk in points.chunks(per_segment) {
+        let mut segment = TrackSegment::new();
+        segment.points = chunk
+            .iter()
+            .map(|(point, elevation)| {
+                let mut waypoint = Waypoint::new(*point);
+                waypoint.elevation = *elevation;
+                waypoint
+            })
+            .collect();
+        track.segments.push(segment);
+    }
+    track
+}
+
+/// A walk north from the equator with the given elevations
+fn walk(elevations: impl IntoIterator<Item = f64>) -> Track {
+    let points = elevations
+        .into_iter()
+        .enumerate()
+        .map(|(i, elevation)| (Point::new(0.0, i as f64 * STEP), Some(elevation)))
+        .collect();
+    track(points, 1)
+}
+
+fn any_point() -> impl Strategy<Value = (Point, Option<f64>)> {
+    (
+        prop_oneof![-180.0..180.0, any::<f64>()],
+        prop_oneof![-90.0..90.0, any::<f64>()],
+        prop_oneof![
+            Just(None),
+            (-500.0..9000.0).prop_map(Some),
+            any::<f64>().prop_map(Some)
+        ],
+    )
+        .prop_map(|(x, y, elevation)| (Point::new(x, y), elevation))
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_tracks_dont_panic(
+        points in prop::collection::vec(any_point(), 0..500),
+        segments in 1usize..5,
+    ) {
+        let mut track = track(points, segments);
+        trail_stats::trim_trailhead_wander(&mut track);
+        let _ = trail_stats::elevation_changes(&track);
+    }
+
+    #[test]
+    fn elevation_changes_add_up(elevations in prop::collection::vec(-500.0..9000.0, 1..2000)) {
+        let (gains, losses) = trail_stats::elevation_changes(&walk(elevations.clone())).unwrap();
+
+        prop_assert!(gains >= 0.0 && losses >= 0.0);
+        // The first and last point are always kept, so the changes between
+        // them come out to the difference between the two
+        let net = elevations[elevations.len() - 1] - elevations[0];
+        prop_assert!((gains - losses - net).abs() < 1e-6 * (1.0 + gains + losses));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(8))]
+
+    /// Climbs that steepen the whole way split one point at a time when
+    /// looking for extrema, which used to overflow the stack
+    #[test]
+    fn steepening_climbs_finish(len in 2_000usize..5_000, rate in 1.0001..1.001f64) {
+        let track = walk((0..len).map(|i| rate.powi(i as i32)));
+        prop_assert!(trail_stats::elevation_changes(&track).is_ok());
+    }
+}
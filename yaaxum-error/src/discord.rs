@@ -0,0 +1,96 @@
+use std::fmt::{Debug, Display};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use color_eyre::eyre::Report;
+use serenity::all::{
+    Color, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+
+use crate::{next_steps, redact, ErrorResponse};
+
+pub struct DiscordError(pub StatusCode, pub Report);
+
+impl Display for DiscordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().display(self.1.as_ref(), f)
+    }
+}
+
+impl Debug for DiscordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().debug(self.1.as_ref(), f)
+    }
+}
+
+impl ErrorResponse for DiscordError {
+    fn from_report(code: StatusCode, report: Report) -> Self {
+        DiscordError(code, report)
+    }
+}
+
+impl DiscordError {
+    pub fn create_embed(self) -> CreateEmbed {
+        let handler: &color_eyre::Handler = self.1.handler().downcast_ref().unwrap();
+
+        let mut span_trace = Vec::new();
+        let mut span_at = 0;
+
+        handler.span_trace().unwrap().with_spans(|span, fields| {
+            span_trace.push((
+                span_at.to_string(),
+                format!(
+                    "`{}::{}`",
+                    span.module_path().unwrap_or_default(),
+                    span.name()
+                ),
+                false,
+            ));
+            if !fields.is_empty() {
+                span_trace.push((String::from("with"), format!("`{}`", fields), true));
+            }
+            if let Some((file, line)) = span.file().and_then(|f| Some((f, span.line()?))) {
+                span_trace.push((String::from("at"), format!("{}:{}", file, line), true));
+            }
+            span_at += 1;
+            true
+        });
+
+        CreateEmbed::new().title("Error").color(Color::RED).fields(
+            self.1
+                .chain()
+                .enumerate()
+                .map(|(i, e)| (i.to_string(), format!("{}", e), false))
+                .chain(
+                    next_steps(&self.1)
+                        .map(|steps| (String::from("What to do"), steps.into_owned(), false)),
+                )
+                .chain([
+                    (
+                        String::from("Location"),
+                        format!("{}", handler.last_location().unwrap()),
+                        false,
+                    ),
+                    (String::from("Spantrace"), String::new(), false),
+                ])
+                .chain(span_trace)
+                .map(|(name, value, inline)| (name, redact(value), inline)),
+        )
+    }
+
+    pub fn create_interaction_response(self) -> CreateInteractionResponse {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(self.create_embed()),
+        )
+    }
+}
+
+impl IntoResponse for DiscordError {
+    fn into_response(self) -> axum::response::Response {
+        (self.0, Json(self.create_interaction_response())).into_response()
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Display},
+};
+
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    Extension,
+};
+use color_eyre::eyre::Report;
+
+use crate::{problem::Document, redact, ErrorResponse};
+
+pub struct HtmlError(pub StatusCode, pub Report, pub Option<Cow<'static, str>>);
+
+impl Display for HtmlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().display(self.1.as_ref(), f)
+    }
+}
+
+impl Debug for HtmlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().debug(self.1.as_ref(), f)
+    }
+}
+
+impl ErrorResponse for HtmlError {
+    fn from_report(code: StatusCode, report: Report) -> Self {
+        HtmlError(code, report, None)
+    }
+}
+
+impl IntoResponse for HtmlError {
+    fn into_response(self) -> axum::response::Response {
+        if let Some(redirect) = self.2 {
+            (self.0, Redirect::to(&redirect)).into_response()
+        } else {
+            let ansi_string = redact(format!("{:?}", self));
+            let error = ansi_to_html::convert(&ansi_string).unwrap();
+            (
+            self.0,
+            Extension(Document::new(self.0, &self.1)),
+            Html(format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf8\"></head><body><pre><code>{}</code></pre></body></html>",
+                error
+            )),
+        )
+            .into_response()
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub trait PropogateRequest {
+    fn propogate_request_if_err(self, wrap: &'static str) -> Result<reqwest::Response, HtmlError>;
+}
+
+#[cfg(feature = "reqwest")]
+impl PropogateRequest for reqwest::Response {
+    fn propogate_request_if_err(self, wrap: &'static str) -> Result<reqwest::Response, HtmlError> {
+        use color_eyre::eyre::{eyre, Context};
+
+        use crate::WithStatusCode;
+
+        let status = self.status();
+        if status.is_server_error() || status.is_client_error() {
+            return Err(eyre!("Reqwest request encountered an issue: {:?}", status))
+                .wrap_err(wrap)
+                .with_status_code_html(status);
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,192 @@
+//! # yaaxum-error
+//! Yet Another Axum Error Handler
+//!
+//! This crate uses `eyre` to capture the error,
+//! the error is then returned to the browser or
+//! whatever it is, it's then nicely formatted to
+//! a webpage using `ansi_to_html`, a Discord embed,
+//! or a JSON problem details document. Errors
+//! rendered as webpages are swapped for problem
+//! details by [`negotiate`] when the client asks
+//! for JSON.
+//!
+//! [`Hooks`] installed at startup can pick status
+//! codes for errors and redact secrets from
+//! whatever is rendered.
+
+use std::{borrow::Cow, error::Error as StdError, sync::OnceLock};
+
+use axum::http::StatusCode;
+use color_eyre::eyre::{eyre, Report};
+
+#[cfg(feature = "discord")]
+mod discord;
+#[cfg(feature = "html")]
+mod html;
+mod problem;
+
+#[cfg(feature = "discord")]
+pub use discord::DiscordError;
+#[cfg(feature = "html")]
+pub use html::HtmlError;
+#[cfg(all(feature = "html", feature = "reqwest"))]
+pub use html::PropogateRequest;
+pub use problem::{negotiate, ProblemError};
+
+type StatusCodeHook = Box<dyn Fn(&(dyn StdError + 'static)) -> Option<StatusCode> + Send + Sync>;
+type RedactHook = Box<dyn Fn(&mut String) + Send + Sync>;
+type TextHook = Box<dyn Fn(&(dyn StdError + 'static)) -> Option<Cow<'static, str>> + Send + Sync>;
+
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+/// Hooks consulted whenever an error is reported
+#[derive(Default)]
+pub struct Hooks {
+    status_codes: Vec<StatusCodeHook>,
+    redactions: Vec<RedactHook>,
+    problem_types: Vec<TextHook>,
+    next_steps: Vec<TextHook>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Hooks::default()
+    }
+
+    /// Picks the status code for errors that would otherwise be reported as a
+    /// `500 Internal Server Error`, from the first error in the chain `hook`
+    /// returns one for
+    pub fn status_code(
+        mut self,
+        hook: impl Fn(&(dyn StdError + 'static)) -> Option<StatusCode> + Send + Sync + 'static,
+    ) -> Self {
+        self.status_codes.push(Box::new(hook));
+        self
+    }
+
+    /// Edits all text rendered from an error before it's sent, such as to
+    /// remove secrets
+    pub fn redact(mut self, hook: impl Fn(&mut String) + Send + Sync + 'static) -> Self {
+        self.redactions.push(Box::new(hook));
+        self
+    }
+
+    /// Picks the `type` URI of problem details documents from the first error
+    /// in the chain `hook` returns one for, instead of `about:blank`
+    pub fn problem_type(
+        mut self,
+        hook: impl Fn(&(dyn StdError + 'static)) -> Option<Cow<'static, str>> + Send + Sync + 'static,
+    ) -> Self {
+        self.problem_types.push(Box::new(hook));
+        self
+    }
+
+    /// Suggests what to do about errors from the first error in the chain
+    /// `hook` returns a suggestion for, shown alongside the error where there's
+    /// room to
+    pub fn next_steps(
+        mut self,
+        hook: impl Fn(&(dyn StdError + 'static)) -> Option<Cow<'static, str>> + Send + Sync + 'static,
+    ) -> Self {
+        self.next_steps.push(Box::new(hook));
+        self
+    }
+
+    /// Installs the hooks for the rest of the program, which can only be done
+    /// once
+    pub fn install(self) -> Result<(), Report> {
+        HOOKS
+            .set(self)
+            .map_err(|_| eyre!("yaaxum-error hooks were already installed"))
+    }
+}
+
+/// The status code `report` is reported with when it was meant to be `code`
+pub fn status_code(report: &Report, code: StatusCode) -> StatusCode {
+    let Some(hooks) = HOOKS.get() else {
+        return code;
+    };
+    if code != StatusCode::INTERNAL_SERVER_ERROR {
+        return code;
+    }
+    report
+        .chain()
+        .find_map(|e| hooks.status_codes.iter().find_map(|hook| hook(e)))
+        .unwrap_or(code)
+}
+
+/// The `type` URI of the problem details document for `report`
+pub fn problem_type(report: &Report) -> Cow<'static, str> {
+    HOOKS
+        .get()
+        .and_then(|hooks| {
+            report
+                .chain()
+                .find_map(|e| hooks.problem_types.iter().find_map(|hook| hook(e)))
+        })
+        .unwrap_or(Cow::Borrowed("about:blank"))
+}
+
+/// What to do about `report`, if a hook has a suggestion
+pub fn next_steps(report: &Report) -> Option<Cow<'static, str>> {
+    let hooks = HOOKS.get()?;
+    report
+        .chain()
+        .find_map(|e| hooks.next_steps.iter().find_map(|hook| hook(e)))
+}
+
+/// `text` with every redaction hook applied
+pub fn redact(mut text: String) -> String {
+    if let Some(hooks) = HOOKS.get() {
+        for hook in &hooks.redactions {
+            hook(&mut text);
+        }
+    }
+    text
+}
+
+/// An error response rendered a particular way
+pub trait ErrorResponse: Sized {
+    fn from_report(code: StatusCode, report: Report) -> Self;
+}
+
+pub trait WithStatusCode<T> {
+    /// Reports the error as `E`, with the status code a hook picks if `code`
+    /// is a `500 Internal Server Error`
+    fn with_status<E: ErrorResponse>(self, code: StatusCode) -> Result<T, E>;
+    #[cfg(feature = "html")]
+    fn with_status_code_html(self, code: StatusCode) -> Result<T, HtmlError>;
+    #[cfg(feature = "html")]
+    fn with_redirect(self, redirect: std::borrow::Cow<'static, str>) -> Result<T, HtmlError>;
+    #[cfg(feature = "discord")]
+    fn with_status_code(self, code: StatusCode) -> Result<T, DiscordError>;
+    #[cfg(feature = "discord")]
+    fn interaction_response(self) -> Result<T, DiscordError>;
+}
+
+impl<T> WithStatusCode<T> for std::result::Result<T, Report> {
+    fn with_status<E: ErrorResponse>(self, code: StatusCode) -> Result<T, E> {
+        self.map_err(|e| E::from_report(status_code(&e, code), e))
+    }
+
+    #[cfg(feature = "html")]
+    fn with_status_code_html(self, code: StatusCode) -> Result<T, HtmlError> {
+        self.with_status(code)
+    }
+
+    #[cfg(feature = "html")]
+    fn with_redirect(self, redirect: std::borrow::Cow<'static, str>) -> Result<T, HtmlError> {
+        self.map_err(|e| HtmlError(StatusCode::SEE_OTHER, e, Some(redirect)))
+    }
+
+    #[cfg(feature = "discord")]
+    fn with_status_code(self, code: StatusCode) -> Result<T, DiscordError> {
+        self.with_status(code)
+    }
+
+    /// Discord only shows the response if it comes with `200 OK`
+    #[cfg(feature = "discord")]
+    fn interaction_response(self) -> Result<T, DiscordError> {
+        self.map_err(|e| DiscordError(StatusCode::OK, e))
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::{Debug, Display};
+
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use color_eyre::eyre::Report;
+use serde_json::json;
+
+use crate::{problem_type, redact, ErrorResponse};
+
+const CONTENT_TYPE: &str = "application/problem+json";
+
+/// Renders the error as an [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807)
+/// problem details document, with the errors that caused it under `causes`
+pub struct ProblemError(pub StatusCode, pub Report);
+
+impl Display for ProblemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().display(self.1.as_ref(), f)
+    }
+}
+
+impl Debug for ProblemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.handler().debug(self.1.as_ref(), f)
+    }
+}
+
+impl ErrorResponse for ProblemError {
+    fn from_report(code: StatusCode, report: Report) -> Self {
+        ProblemError(code, report)
+    }
+}
+
+impl IntoResponse for ProblemError {
+    fn into_response(self) -> axum::response::Response {
+        (
+            self.0,
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            document(self.0, &self.1),
+        )
+            .into_response()
+    }
+}
+
+/// The problem details document for `report`, kept in the extensions of
+/// responses rendered some other way so [`negotiate`] can swap it in
+#[derive(Clone)]
+pub(crate) struct Document(String);
+
+pub(crate) fn document(code: StatusCode, report: &Report) -> String {
+    let mut chain = report.chain().map(|e| redact(e.to_string()));
+    json!({
+        "type": problem_type(report),
+        "title": code.canonical_reason().unwrap_or("Unknown Error"),
+        "status": code.as_u16(),
+        "detail": chain.next(),
+        "causes": chain.collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+#[cfg(feature = "html")]
+impl Document {
+    pub(crate) fn new(code: StatusCode, report: &Report) -> Self {
+        Document(document(code, report))
+    }
+}
+
+/// Middleware that answers clients asking for JSON with a problem details
+/// document in place of an error rendered as a webpage
+pub async fn negotiate(request: Request, next: Next) -> Response {
+    let wants_json = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json") || accept.contains(CONTENT_TYPE));
+
+    let mut response = next.run(request).await;
+    match response.extensions_mut().remove::<Document>() {
+        Some(Document(document)) if wants_json => (
+            response.status(),
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            document,
+        )
+            .into_response(),
+        _ => response,
+    }
+}